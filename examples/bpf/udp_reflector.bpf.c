@@ -0,0 +1,66 @@
+// SPDX-License-Identifier: GPL-2.0
+//
+// Redirects UDP packets for the port set in `config` to the AF_XDP
+// socket bound to the queue they arrived on, passing everything else
+// up the stack. Used by `examples/udp_reflector.rs`.
+//
+// Build with:
+//
+//     clang -O2 -g -target bpf -c udp_reflector.bpf.c -o udp_reflector.bpf.o
+
+#include <linux/bpf.h>
+#include <linux/if_ether.h>
+#include <linux/in.h>
+#include <linux/ip.h>
+#include <linux/udp.h>
+#include <bpf/bpf_endian.h>
+#include <bpf/bpf_helpers.h>
+
+struct {
+	__uint(type, BPF_MAP_TYPE_XSKMAP);
+	__uint(max_entries, 64);
+	__type(key, __u32);
+	__type(value, __u32);
+} xsks_map SEC(".maps");
+
+// Entry 0 holds the UDP port to redirect, in host byte order.
+struct {
+	__uint(type, BPF_MAP_TYPE_ARRAY);
+	__uint(max_entries, 1);
+	__type(key, __u32);
+	__type(value, __u16);
+} config SEC(".maps");
+
+SEC("xdp")
+int udp_reflector(struct xdp_md *ctx)
+{
+	void *data = (void *)(long)ctx->data;
+	void *data_end = (void *)(long)ctx->data_end;
+	struct ethhdr *eth = data;
+	struct iphdr *ip;
+	struct udphdr *udp;
+	__u32 key = 0;
+	__u16 *port;
+
+	if ((void *)(eth + 1) > data_end || eth->h_proto != bpf_htons(ETH_P_IP))
+		return XDP_PASS;
+
+	ip = (void *)(eth + 1);
+
+	if ((void *)(ip + 1) > data_end || ip->ihl < 5 || ip->protocol != IPPROTO_UDP)
+		return XDP_PASS;
+
+	udp = (void *)ip + ip->ihl * 4;
+
+	if ((void *)(udp + 1) > data_end)
+		return XDP_PASS;
+
+	port = bpf_map_lookup_elem(&config, &key);
+
+	if (!port || udp->dest != bpf_htons(*port))
+		return XDP_PASS;
+
+	return bpf_redirect_map(&xsks_map, ctx->rx_queue_index, XDP_PASS);
+}
+
+char _license[] SEC("license") = "GPL";
@@ -5,7 +5,6 @@ use std::{
     fmt::Debug,
     io::Write,
     iter,
-    net::Ipv4Addr,
     num::NonZeroU32,
     sync::atomic::{AtomicBool, Ordering},
     thread,
@@ -17,9 +16,7 @@ use xsk_rs::{
     config::{BindFlags, FrameSize, Interface, QueueSize, SocketConfig, UmemConfig},
     CompQueue, FillQueue, FrameDesc, RxQueue, Socket, TxQueue, Umem,
 };
-
-mod setup;
-use setup::{util, veth_setup, LinkIpAddr, PacketGenerator, VethDevConfig};
+use xsk_rs_helpers::{util, veth_setup, PacketGenerator, VethDevConfig};
 
 // Reqd for the multithreaded case to signal when all packets have
 // been sent
@@ -159,8 +156,6 @@ fn dev1_to_dev2_single_thread(
     let (mut xsk_tx, pkt_gen) = tx;
     let (mut xsk_rx, _) = rx;
 
-    let rx_cfg = config.receiver;
-
     let tx_umem = &xsk_tx.umem;
 
     let tx_descs = &mut xsk_tx.descs;
@@ -176,13 +171,7 @@ fn dev1_to_dev2_single_thread(
     });
 
     // Populate receiver fill queue
-    let frames_filled = unsafe {
-        xsk_rx
-            .fq
-            .produce(&rx_descs[..rx_cfg.fq_size.get() as usize])
-    };
-
-    assert_eq!(frames_filled, rx_cfg.fq_size.get() as usize);
+    let frames_filled = unsafe { xsk_rx.fq.fill_from(&mut rx_descs.clone()) };
 
     log::debug!("frames added to receiver fill queue: {}", frames_filled);
 
@@ -366,8 +355,6 @@ fn dev1_to_dev2_multithreaded(
     tx: (Xsk, PacketGenerator),
     rx: (Xsk, PacketGenerator),
 ) {
-    let rx_cfg = config.receiver;
-
     let payload_size = config.payload_size;
     let max_batch_size = config.max_batch_size;
     let num_frames_to_send = config.num_packets_to_send;
@@ -391,13 +378,7 @@ fn dev1_to_dev2_multithreaded(
         let rx_frames = &mut xsk_rx.descs;
 
         // Populate receiver fill queue
-        let frames_filled = unsafe {
-            xsk_rx
-                .fq
-                .produce(&rx_frames[..rx_cfg.fq_size.get() as usize])
-        };
-
-        assert_eq!(frames_filled, rx_cfg.fq_size.get() as usize);
+        let frames_filled = unsafe { xsk_rx.fq.fill_from(&mut rx_frames.clone()) };
 
         log::debug!("frames added to receiver fill queue: {}", frames_filled);
 
@@ -682,17 +663,7 @@ fn main() {
 
     let config = Opt::from_args().into();
 
-    let dev1_config = VethDevConfig {
-        if_name: "xsk_test_dev1".into(),
-        addr: [0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a],
-        ip_addr: LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 1), 24),
-    };
-
-    let dev2_config = VethDevConfig {
-        if_name: "xsk_test_dev2".into(),
-        addr: [0x4a, 0xf1, 0x30, 0xeb, 0x0d, 0x31],
-        ip_addr: LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 1), 24),
-    };
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
 
     // We'll keep track of ctrl+c events but not let them kill the process
     // immediately as we may need to clean up the veth pair.
@@ -704,9 +675,12 @@ fn main() {
 
     let example_handle = thread::spawn(move || {
         let res = runtime.block_on(veth_setup::run_with_veth_pair(
+            move |dev1, dev2| {
+                let (dev1_pkt_gen, dev2_pkt_gen) = util::packet_generator_pair(&dev1, &dev2);
+                run_example(config, (dev1, dev1_pkt_gen), (dev2, dev2_pkt_gen))
+            },
             dev1_config,
             dev2_config,
-            move |dev1, dev2| run_example(config, dev1, dev2),
         ));
 
         let _ = complete_tx.send(());
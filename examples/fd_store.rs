@@ -0,0 +1,177 @@
+//! Receives packets on an AF_XDP socket which stays bound across
+//! restarts, by keeping the socket and its UMEM's memory in systemd's
+//! file descriptor store.
+//!
+//! Meant to be run as a service, for example:
+//!
+//! ```text
+//! [Service]
+//! ExecStart=/path/to/fd_store xsk_dev1 0
+//! FileDescriptorStoreMax=2
+//! FileDescriptorStorePreserve=yes
+//! Restart=always
+//! ```
+//!
+//! On first start the UMEM and socket are created and both stored.
+//! After a restart they're reassembled from the stored descriptors
+//! instead, so the socket's queue keeps receiving throughout.
+//!
+//! The process never drops its queues, since deleting a socket has
+//! libxdp remove it from the `XSKMAP` and, if it was the last one,
+//! detach its XDP program. A service which exits cleanly should
+//! instead manage the program itself, see `xsk_rs::xdp`.
+use std::{
+    convert::TryInto,
+    env,
+    num::NonZeroU32,
+    os::unix::prelude::{AsRawFd, OwnedFd},
+};
+use xsk_rs::{
+    config::{Interface, SocketConfig, UmemConfig},
+    systemd, CompQueue, FillQueue, FrameDesc, RxQueue, Socket, TxQueue, Umem, UmemBacking,
+};
+
+const UMEM_FD_NAME: &str = "xsk-umem";
+const SOCKET_FD_NAME: &str = "xsk-socket";
+
+type Queues = (TxQueue, RxQueue, FillQueue, CompQueue);
+
+/// Create a new UMEM and socket, storing their descriptors.
+fn create(
+    if_name: &Interface,
+    queue_id: u32,
+    frame_count: NonZeroU32,
+) -> (Queues, Umem, Vec<FrameDesc>) {
+    let (umem, descs) = Umem::new_with_backing(
+        UmemConfig::default(),
+        frame_count,
+        false,
+        UmemBacking::Memfd,
+    )
+    .expect("failed to create UMEM");
+
+    let (tx_q, rx_q, fq_and_cq) =
+        unsafe { Socket::new(SocketConfig::default(), &umem, if_name, queue_id) }
+            .expect("failed to create socket");
+
+    let (fq, cq) = fq_and_cq.expect("missing fill queue and comp queue");
+
+    let umem_fd = umem.backing_fd().expect("UMEM backed by a memfd");
+
+    // The socket's UMEM was registered through it, so its descriptor
+    // also gives access to the fill and completion rings.
+    systemd::store_fd(UMEM_FD_NAME, umem_fd).expect("failed to store UMEM fd");
+    systemd::store_fd(SOCKET_FD_NAME, rx_q.fd().as_raw_fd()).expect("failed to store socket fd");
+
+    ((tx_q, rx_q, fq, cq), umem, descs)
+}
+
+/// Reassemble the UMEM and socket from their stored descriptors.
+fn reassemble(
+    umem_fd: OwnedFd,
+    socket_fd: OwnedFd,
+    if_name: &Interface,
+    queue_id: u32,
+    frame_count: NonZeroU32,
+) -> (Queues, Umem) {
+    let (umem, _descs) = unsafe { Umem::from_parts(UmemConfig::default(), frame_count, umem_fd) }
+        .expect("failed to reassemble UMEM");
+
+    let socket =
+        unsafe { Socket::from_parts(socket_fd, &umem, if_name, queue_id, SocketConfig::default()) }
+            .expect("failed to reassemble socket");
+
+    let queues = unsafe {
+        (
+            TxQueue::from_raw(&socket).expect("failed to map tx ring"),
+            RxQueue::from_raw(&socket).expect("failed to map rx ring"),
+            FillQueue::from_raw(&socket).expect("failed to map fill ring"),
+            CompQueue::from_raw(&socket).expect("failed to map comp ring"),
+        )
+    };
+
+    (queues, umem)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+
+    let if_name: Interface = args
+        .next()
+        .expect("usage: fd_store <if_name> [queue_id]")
+        .parse()
+        .expect("invalid interface name");
+
+    let queue_id: u32 = args
+        .next()
+        .map_or(0, |id| id.parse().expect("invalid queue id"));
+
+    // Exactly enough frames to fill the fill ring, so every frame is
+    // always either on the fill ring, the rx ring, or about to be
+    // returned to the fill ring.
+    let frame_count: NonZeroU32 = UmemConfig::default()
+        .fill_queue_size()
+        .get()
+        .try_into()
+        .unwrap();
+
+    let mut stored = systemd::listen_fds().expect("failed to read passed fds");
+
+    let mut take = |name| {
+        let idx = stored.iter().position(|(n, _)| n == name)?;
+        Some(stored.swap_remove(idx).1)
+    };
+
+    let ((_tx_q, mut rx_q, mut fq, _cq), umem, mut descs) =
+        match (take(UMEM_FD_NAME), take(SOCKET_FD_NAME)) {
+            (Some(umem_fd), Some(socket_fd)) => {
+                println!("reassembling socket from stored descriptors");
+
+                let (queues, umem) =
+                    reassemble(umem_fd, socket_fd, &if_name, queue_id, frame_count);
+
+                // Frames on the fill ring when the last run ended are
+                // still there, so start with none in hand.
+                (
+                    queues,
+                    umem,
+                    vec![FrameDesc::default(); frame_count.get() as usize],
+                )
+            }
+            _ => {
+                println!("creating new socket");
+
+                let (mut queues, umem, descs) = create(&if_name, queue_id, frame_count);
+
+                assert_eq!(unsafe { queues.2.produce(&descs) }, descs.len());
+
+                let descs = vec![FrameDesc::default(); descs.len()];
+
+                (queues, umem, descs)
+            }
+        };
+
+    // Anything else passed in isn't ours, so is closed here.
+    drop(stored);
+
+    let mut received = 0u64;
+
+    loop {
+        let cnt = unsafe { rx_q.poll_and_consume(&mut descs, 100) }.expect("poll failed");
+
+        for desc in descs.iter().take(cnt) {
+            let pkt = unsafe { umem.data(desc) };
+
+            println!("received {} byte packet", pkt.len());
+        }
+
+        received += cnt as u64;
+
+        if cnt > 0 {
+            println!("{} packets received in total", received);
+
+            // Hand the frames straight back to the kernel.
+            while unsafe { fq.produce(&descs[..cnt]) } != cnt {}
+        }
+    }
+}
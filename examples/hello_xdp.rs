@@ -1,13 +1,10 @@
-use std::{convert::TryInto, io::Write, net::Ipv4Addr, thread};
+use std::{convert::TryInto, io::Write, thread};
 use tokio::runtime::Runtime;
 use xsk_rs::{
     config::{SocketConfig, UmemConfig},
     Socket, Umem,
 };
-
-#[allow(dead_code)]
-mod setup;
-use setup::{util, veth_setup, LinkIpAddr, PacketGenerator, VethDevConfig, ETHERNET_PACKET};
+use xsk_rs_helpers::{util, veth_setup, PacketGenerator, VethDevConfig, ETHERNET_PACKET};
 
 fn hello_xdp(dev1: (VethDevConfig, PacketGenerator), dev2: (VethDevConfig, PacketGenerator)) {
     // Create a UMEM for dev1.
@@ -85,17 +82,7 @@ fn hello_xdp(dev1: (VethDevConfig, PacketGenerator), dev2: (VethDevConfig, Packe
 }
 
 fn main() {
-    let dev1_config = VethDevConfig {
-        if_name: "xsk_test_dev1".into(),
-        addr: [0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a],
-        ip_addr: LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 1), 24),
-    };
-
-    let dev2_config = VethDevConfig {
-        if_name: "xsk_test_dev2".into(),
-        addr: [0x4a, 0xf1, 0x30, 0xeb, 0x0d, 0x31],
-        ip_addr: LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 1), 24),
-    };
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
 
     // We'll keep track of ctrl+c events but not let them kill the process
     // immediately as we may need to clean up the veth pair.
@@ -107,9 +94,12 @@ fn main() {
 
     let example_handle = thread::spawn(move || {
         let res = runtime.block_on(veth_setup::run_with_veth_pair(
+            |dev1, dev2| {
+                let (dev1_pkt_gen, dev2_pkt_gen) = util::packet_generator_pair(&dev1, &dev2);
+                hello_xdp((dev1, dev1_pkt_gen), (dev2, dev2_pkt_gen))
+            },
             dev1_config,
             dev2_config,
-            hello_xdp,
         ));
 
         let _ = complete_tx.send(());
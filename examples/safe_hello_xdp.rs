@@ -0,0 +1,103 @@
+//! Same as `hello_xdp.rs`, but without any `unsafe`, using only the
+//! `xsk_rs::safe` subset of the crate.
+#![forbid(unsafe_code)]
+
+use std::{convert::TryInto, io::Write, thread};
+use tokio::runtime::Runtime;
+use xsk_rs::safe::{
+    config::{SocketConfig, UmemConfig},
+    OwnedSocket, Socket,
+};
+use xsk_rs_helpers::{util, veth_setup, PacketGenerator, VethDevConfig, ETHERNET_PACKET};
+
+fn bind(if_name: &str) -> OwnedSocket {
+    // Create a UMEM with 32 frames and bind an AF_XDP socket using it
+    // to queue 0 of `if_name`.
+    Socket::new_owned(
+        SocketConfig::default(),
+        UmemConfig::default(),
+        32.try_into().unwrap(),
+        &if_name.parse().unwrap(),
+        0,
+    )
+    .expect("failed to create socket")
+}
+
+fn hello_xdp(dev1: (VethDevConfig, PacketGenerator), dev2: (VethDevConfig, PacketGenerator)) {
+    let mut dev1_xsk = bind(dev1.0.if_name());
+    let mut dev2_xsk = bind(dev2.0.if_name());
+
+    // 1. Add frames to dev2's fill queue so we are ready to receive
+    // some packets.
+    dev2_xsk.fq.produce_frames(&mut dev2_xsk.frames);
+
+    // 2. Write to one of dev1's frames.
+    let mut tx_frames = dev1_xsk.frames.split_off(dev1_xsk.frames.len() - 1);
+
+    tx_frames[0]
+        .data_mut()
+        .cursor()
+        .write_all(&ETHERNET_PACKET)
+        .expect("failed writing packet to frame");
+
+    // 3. Submit the frame to the kernel for transmission.
+    println!("sending packet");
+
+    dev1_xsk.tx_q.produce_frames(&mut tx_frames);
+    dev1_xsk.tx_q.wakeup().unwrap();
+
+    // 4. Read on dev2.
+    let mut rx_frames = Vec::new();
+
+    if dev2_xsk.rx_q.poll(100).unwrap() {
+        dev2_xsk.rx_q.consume_frames(&mut rx_frames, 32);
+    }
+
+    // 5. Confirm that one of the packets we received matches what we expect.
+    for frame in rx_frames.iter() {
+        if frame.data().contents() == &ETHERNET_PACKET {
+            println!("received packet!");
+            return;
+        }
+    }
+
+    panic!("no matching packets received")
+}
+
+fn main() {
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
+
+    // We'll keep track of ctrl+c events but not let them kill the process
+    // immediately as we may need to clean up the veth pair.
+    let ctrl_c_events = util::ctrl_channel().unwrap();
+
+    let (complete_tx, complete_rx) = crossbeam_channel::bounded(1);
+
+    let runtime = Runtime::new().unwrap();
+
+    let example_handle = thread::spawn(move || {
+        let res = runtime.block_on(veth_setup::run_with_veth_pair(
+            |dev1, dev2| {
+                let (dev1_pkt_gen, dev2_pkt_gen) = util::packet_generator_pair(&dev1, &dev2);
+                hello_xdp((dev1, dev1_pkt_gen), (dev2, dev2_pkt_gen))
+            },
+            dev1_config,
+            dev2_config,
+        ));
+
+        let _ = complete_tx.send(());
+
+        res
+    });
+
+    // Wait for either the example to finish or for a ctrl+c event to occur.
+    crossbeam_channel::select! {
+        recv(complete_rx) -> _ => {
+        },
+        recv(ctrl_c_events) -> _ => {
+            println!("SIGINT received");
+        }
+    }
+
+    example_handle.join().unwrap().unwrap();
+}
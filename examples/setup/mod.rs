@@ -1,12 +0,0 @@
-pub mod util;
-pub use util::PacketGenerator;
-
-pub mod veth_setup;
-pub use veth_setup::{LinkIpAddr, VethDevConfig};
-
-#[allow(dead_code)]
-pub const ETHERNET_PACKET: [u8; 42] = [
-    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a, 0x08, 0x06, 0x00, 0x01,
-    0x08, 0x00, 0x06, 0x04, 0x00, 0x01, 0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a, 0xc0, 0xa8, 0x45, 0x01,
-    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0xa8, 0x45, 0xfe,
-];
@@ -0,0 +1,251 @@
+//! Reflects UDP packets sent to a given port back to their sender,
+//! tying together most of the crate:
+//!
+//! - A custom XDP program, `examples/bpf/udp_reflector.bpf.c`, loaded
+//!   with `xsk_rs::xdp`, picks out the packets to reflect and passes
+//!   everything else up the stack. The port is set through one of its
+//!   maps.
+//! - The socket is optionally bound with busy polling enabled.
+//! - Frames are managed by a `FrameAllocator`, which keeps the fill
+//!   ring topped up and takes frames back off the completion ring.
+//! - Packets are received and sent with the async queue methods, on a
+//!   tokio runtime.
+//! - The socket's statistics are exported periodically, in the
+//!   Prometheus text format, through a `SocketRegistry`.
+//!
+//! Requires the `async` feature. Build the XDP program first, then,
+//! for example:
+//!
+//! ```text
+//! cargo run --example udp_reflector --features async -- eth0 --port 5353
+//! ```
+use std::{convert::TryInto, path::PathBuf, time::Duration};
+use structopt::StructOpt;
+use xsk_rs::{
+    checksum,
+    config::{Interface, LibxdpFlags, SocketConfig, UmemConfig, XdpFlags},
+    stats::{SocketRegistry, SocketStats},
+    umem::frame::FrameDesc,
+    xdp::ProgramSelector,
+    FrameAllocator, Socket, Umem, XdpProgram,
+};
+
+const ETH_HDR_LEN: usize = 14;
+const ETH_P_IP: u16 = 0x0800;
+const IPPROTO_UDP: u8 = 17;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "udp_reflector")]
+struct Opt {
+    /// Interface to bind to
+    if_name: String,
+
+    /// Queue to bind to
+    #[structopt(short, long, default_value = "0")]
+    queue_id: u32,
+
+    /// UDP port to reflect packets sent to
+    #[structopt(short, long, default_value = "53")]
+    port: u16,
+
+    /// Compiled XDP program
+    #[structopt(long, default_value = "examples/bpf/udp_reflector.bpf.o")]
+    prog: PathBuf,
+
+    /// Busy poll timeout in microseconds, zero to disable
+    #[structopt(long, default_value = "0")]
+    busy_poll_us: u64,
+
+    /// Number of frames in the UMEM
+    #[structopt(long, default_value = "4096")]
+    frame_count: u32,
+
+    /// Seconds between statistics exports
+    #[structopt(long, default_value = "10")]
+    stats_interval: u64,
+}
+
+/// Counters kept by the reflector itself, exported alongside the
+/// socket's statistics.
+#[derive(Debug, Default)]
+struct Counters {
+    reflected: u64,
+    invalid: u64,
+}
+
+/// Turn the UDP packet in `pkt` around in place, swapping its source
+/// and destination addresses and ports. Returns `false` if it isn't a
+/// well formed UDP over IPv4 packet.
+///
+/// Swapping leaves the sums the IPv4 and UDP checksums are taken over
+/// unchanged, so neither needs recalculating.
+fn reflect(pkt: &mut [u8]) -> bool {
+    if !checksum::validate(pkt).is_valid() {
+        return false;
+    }
+
+    if pkt.len() < ETH_HDR_LEN + 20 || u16::from_be_bytes([pkt[12], pkt[13]]) != ETH_P_IP {
+        return false;
+    }
+
+    let ip_hdr_len = (pkt[ETH_HDR_LEN] & 0x0f) as usize * 4;
+    let udp = ETH_HDR_LEN + ip_hdr_len;
+
+    if pkt[ETH_HDR_LEN + 9] != IPPROTO_UDP || pkt.len() < udp + 8 {
+        return false;
+    }
+
+    let (dst_mac, src_mac) = pkt[..12].split_at_mut(6);
+    dst_mac.swap_with_slice(src_mac);
+
+    let addrs = ETH_HDR_LEN + 12;
+    let (src_ip, dst_ip) = pkt[addrs..addrs + 8].split_at_mut(4);
+    src_ip.swap_with_slice(dst_ip);
+
+    let (src_port, dst_port) = pkt[udp..udp + 4].split_at_mut(2);
+    src_port.swap_with_slice(dst_port);
+
+    true
+}
+
+fn export(stats: &[SocketStats], counters: &Counters) {
+    for s in stats {
+        let labels = format!(
+            "if_name={:?},queue_id=\"{}\",cookie=\"{}\"",
+            s.if_name().as_cstr(),
+            s.queue_id(),
+            s.cookie()
+        );
+
+        let st = s.stats();
+
+        let counts = [
+            ("xsk_rx_dropped", st.rx_dropped()),
+            ("xsk_rx_invalid_descs", st.rx_invalid_descs()),
+            ("xsk_tx_invalid_descs", st.tx_invalid_descs()),
+            ("xsk_rx_ring_full", st.rx_ring_full()),
+            (
+                "xsk_rx_fill_ring_empty_descs",
+                st.rx_fill_ring_empty_descs(),
+            ),
+            ("xsk_tx_ring_empty_descs", st.tx_ring_empty_descs()),
+        ];
+
+        for (name, count) in counts.iter() {
+            println!("{}{{{}}} {}", name, labels, count);
+        }
+    }
+
+    println!("udp_reflector_reflected {}", counters.reflected);
+    println!("udp_reflector_invalid {}", counters.invalid);
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let opt = Opt::from_args();
+
+    let if_name: Interface = opt.if_name.parse().expect("invalid interface name");
+
+    // Load and attach the filter before binding, so the socket can be
+    // registered in its `XSKMAP`.
+    let mut prog = XdpProgram::open(&opt.prog, ProgramSelector::Section("xdp"))
+        .expect("failed to open XDP program");
+
+    prog.attach(&if_name, XdpFlags::empty())
+        .expect("failed to attach XDP program");
+
+    prog.map::<u32, u16>("config")
+        .and_then(|config| config.update(&0, &opt.port))
+        .expect("failed to set port");
+
+    let mut config = SocketConfig::builder();
+
+    config.libxdp_flags(LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD);
+
+    if opt.busy_poll_us > 0 {
+        config.busy_poll(Duration::from_micros(opt.busy_poll_us), 64);
+    }
+
+    let (umem, descs) = Umem::new(
+        UmemConfig::default(),
+        opt.frame_count.try_into().expect("frame count is zero"),
+        false,
+    )
+    .expect("failed to create UMEM");
+
+    let (mut tx_q, mut rx_q, fq_and_cq) =
+        unsafe { Socket::new(config.build(), &umem, &if_name, opt.queue_id) }
+            .expect("failed to create socket");
+
+    let (mut fq, mut cq) = fq_and_cq.expect("missing fill queue and comp queue");
+
+    prog.xsk_map("xsks_map")
+        .and_then(|map| map.insert(&rx_q))
+        .expect("failed to register socket in XSKMAP");
+
+    let alloc = unsafe { FrameAllocator::new(&umem, descs) };
+
+    let fq_size = umem.config().fill_queue_size().get() as usize;
+
+    alloc.fill(&mut fq, fq_size);
+
+    let mut registry = SocketRegistry::new();
+    registry.register_rx_queue(&rx_q);
+
+    let mut counters = Counters::default();
+
+    let mut stats_interval = tokio::time::interval(Duration::from_secs(opt.stats_interval));
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    let mut rx_descs = vec![FrameDesc::default(); 64];
+    let mut tx_descs = Vec::with_capacity(rx_descs.len());
+
+    println!("reflecting UDP packets to port {}", opt.port);
+
+    loop {
+        tokio::select! {
+            received = unsafe { rx_q.recv_async(&mut rx_descs) } => {
+                let cnt = received.expect("failed to receive");
+
+                for desc in rx_descs[..cnt].iter_mut() {
+                    // SAFETY: the frame has just come off the rx ring,
+                    // so belongs to us until handed back.
+                    let reflected = reflect(unsafe { umem.data_mut(desc) }.contents_mut());
+
+                    if reflected {
+                        tx_descs.push(*desc);
+                    } else {
+                        counters.invalid += 1;
+                        unsafe { alloc.free(std::slice::from_ref(desc)) };
+                    }
+                }
+
+                if !tx_descs.is_empty() {
+                    let sent = unsafe { tx_q.send_async(&tx_descs) }
+                        .await
+                        .expect("failed to send");
+
+                    counters.reflected += sent as u64;
+                    tx_descs.clear();
+                }
+
+                alloc.reclaim(&mut cq);
+                alloc.fill(&mut fq, fq_size);
+            }
+            _ = stats_interval.tick() => {
+                let stats = registry.gather_all().expect("failed to gather statistics");
+
+                export(&stats, &counters);
+            }
+            _ = &mut ctrl_c => break,
+        }
+    }
+
+    println!("reflected {} packets", counters.reflected);
+
+    // Detach the program explicitly rather than on drop, so failures
+    // are reported.
+    prog.detach().expect("failed to detach XDP program");
+}
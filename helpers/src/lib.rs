@@ -1,10 +1,17 @@
-mod util;
+//! Shared setup for the `xsk-rs` tests and examples: veth pair
+//! management, socket and [`Umem`] construction, and packet
+//! generation.
+//!
+//! Creating veth pairs requires root privileges, or at least
+//! `CAP_NET_ADMIN`.
+
+pub mod util;
 pub use util::PacketGenerator;
 
 pub mod veth_setup;
-pub use veth_setup::{LinkIpAddr, VethDevConfig};
+pub use veth_setup::VethDevConfig;
 
-use std::{net::Ipv4Addr, num::NonZeroU32};
+use std::num::NonZeroU32;
 use xsk_rs::{
     config::{Interface, SocketConfig, UmemConfig},
     socket::{RxQueue, Socket, TxQueue},
@@ -36,14 +43,14 @@ pub struct XskConfig {
 pub fn default_veth_dev_configs() -> (VethDevConfig, VethDevConfig) {
     let dev1_config = VethDevConfig::new(
         "xsk_test_dev1".into(),
-        Some([0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a]),
-        Some(LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 1), 24)),
+        Some("f6:e0:f6:c9:60:0a".parse().unwrap()),
+        Some("192.168.69.1/24".parse().unwrap()),
     );
 
     let dev2_config = VethDevConfig::new(
         "xsk_test_dev2".into(),
-        Some([0x4a, 0xf1, 0x30, 0xeb, 0x0d, 0x31]),
-        Some(LinkIpAddr::new(Ipv4Addr::new(192, 168, 69, 2), 24)),
+        Some("4a:f1:30:eb:0d:31".parse().unwrap()),
+        Some("192.168.69.2/24".parse().unwrap()),
     );
 
     (dev1_config, dev2_config)
@@ -106,8 +113,7 @@ where
             0,
         );
 
-        let dev1_pkt_gen = PacketGenerator::new(dev1_config, dev2_config);
-        let dev2_pkt_gen = dev1_pkt_gen.clone().into_swapped();
+        let (dev1_pkt_gen, dev2_pkt_gen) = util::packet_generator_pair(&dev1_config, &dev2_config);
 
         test((xsk1, dev1_pkt_gen), (xsk2, dev2_pkt_gen))
     };
@@ -150,8 +156,7 @@ pub async fn run_test_with_dev_configs<F>(
             0,
         );
 
-        let dev1_pkt_gen = PacketGenerator::new(dev1_config, dev2_config);
-        let dev2_pkt_gen = dev1_pkt_gen.clone().into_swapped();
+        let (dev1_pkt_gen, dev2_pkt_gen) = util::packet_generator_pair(&dev1_config, &dev2_config);
 
         test((xsk1, dev1_pkt_gen), (xsk2, dev2_pkt_gen))
     };
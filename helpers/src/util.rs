@@ -1,17 +1,8 @@
-use crossbeam_channel::{self, Receiver};
+use crossbeam_channel::Receiver;
 use etherparse::{PacketBuilder, WriteError};
 
 use super::veth_setup::VethDevConfig;
 
-pub fn ctrl_channel() -> Result<Receiver<()>, ctrlc::Error> {
-    let (tx, rx) = crossbeam_channel::bounded(1);
-    ctrlc::set_handler(move || {
-        let _ = tx.send(());
-    })?;
-
-    Ok(rx)
-}
-
 #[derive(Debug, Clone)]
 pub struct PacketGenerator {
     src: VethDevConfig,
@@ -31,13 +22,13 @@ impl PacketGenerator {
         payload_len: usize,
     ) -> Result<Vec<u8>, WriteError> {
         let builder = PacketBuilder::ethernet2(
-            self.src.addr(), // src mac
-            self.dst.addr(), // dst mac
+            self.src.addr().unwrap().octets(), // src mac
+            self.dst.addr().unwrap().octets(), // dst mac
         )
         .ipv4(
-            self.src.ip_addr().octets(), // src ip
-            self.dst.ip_addr().octets(), // dst ip
-            20,                          // time to live
+            self.src.ip_addr().unwrap().octets(), // src ip
+            self.dst.ip_addr().unwrap().octets(), // dst ip
+            20,                                   // time to live
         )
         .udp(src_port, dst_port);
 
@@ -62,3 +53,26 @@ impl PacketGenerator {
 fn generate_random_bytes(len: usize) -> Vec<u8> {
     (0..len).map(|_| rand::random::<u8>()).collect()
 }
+
+/// A pair of packet generators, the first sending from `dev1` to
+/// `dev2` and the second in the opposite direction.
+pub fn packet_generator_pair(
+    dev1: &VethDevConfig,
+    dev2: &VethDevConfig,
+) -> (PacketGenerator, PacketGenerator) {
+    let pkt_gen = PacketGenerator::new(dev1.clone(), dev2.clone());
+    (pkt_gen.clone(), pkt_gen.into_swapped())
+}
+
+/// A channel that receives a message each time ctrl+c is pressed,
+/// rather than the process being killed. Lets examples clean up
+/// their veth pair before exiting.
+pub fn ctrl_channel() -> Result<Receiver<()>, ctrlc::Error> {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+
+    ctrlc::set_handler(move || {
+        let _ = tx.send(());
+    })?;
+
+    Ok(rx)
+}
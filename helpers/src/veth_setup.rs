@@ -1,7 +1,8 @@
 use futures::stream::TryStreamExt;
 use rtnetlink::Handle;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 use tokio::{runtime, task};
+use xsk_rs::net::{Ipv4Net, MacAddr};
 
 #[derive(Debug, Clone, Copy)]
 pub enum LinkStatus {
@@ -31,25 +32,21 @@ impl VethDev {
         })
     }
 
-    async fn set_addr(&self, addr: Vec<u8>) -> anyhow::Result<()> {
+    async fn set_addr(&self, addr: MacAddr) -> anyhow::Result<()> {
         self.handle
             .link()
             .set(self.index)
-            .address(addr)
+            .address(addr.octets().into())
             .execute()
             .await?;
 
         Ok(())
     }
 
-    async fn set_ip_addr(&self, ip_addr: LinkIpAddr) -> anyhow::Result<()> {
+    async fn set_ip_addr(&self, ip_addr: Ipv4Net) -> anyhow::Result<()> {
         self.handle
             .address()
-            .add(
-                self.index,
-                IpAddr::V4(ip_addr.addr.clone()),
-                ip_addr.prefix_len,
-            )
+            .add(self.index, IpAddr::V4(ip_addr.addr()), ip_addr.prefix_len())
             .execute()
             .await?;
 
@@ -94,31 +91,15 @@ impl Drop for VethPair {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct LinkIpAddr {
-    addr: Ipv4Addr,
-    prefix_len: u8,
-}
-
-impl LinkIpAddr {
-    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Self {
-        LinkIpAddr { addr, prefix_len }
-    }
-
-    pub fn octets(&self) -> [u8; 4] {
-        self.addr.octets()
-    }
-}
-
 #[derive(Clone, Debug)]
 pub struct VethDevConfig {
     if_name: String,
-    addr: Option<[u8; 6]>,
-    ip_addr: Option<LinkIpAddr>,
+    addr: Option<MacAddr>,
+    ip_addr: Option<Ipv4Net>,
 }
 
 impl VethDevConfig {
-    pub fn new(if_name: String, addr: Option<[u8; 6]>, ip_addr: Option<LinkIpAddr>) -> Self {
+    pub fn new(if_name: String, addr: Option<MacAddr>, ip_addr: Option<Ipv4Net>) -> Self {
         Self {
             if_name,
             addr,
@@ -130,11 +111,11 @@ impl VethDevConfig {
         &self.if_name
     }
 
-    pub fn addr(&self) -> Option<[u8; 6]> {
+    pub fn addr(&self) -> Option<MacAddr> {
         self.addr
     }
 
-    pub fn ip_addr(&self) -> Option<LinkIpAddr> {
+    pub fn ip_addr(&self) -> Option<Ipv4Net> {
         self.ip_addr
     }
 }
@@ -201,7 +182,7 @@ pub async fn build_veth_pair(
         (&veth_pair.dev2, dev2_config),
     ] {
         if let Some(addr) = c.addr {
-            d.set_addr(addr.into()).await?;
+            d.set_addr(addr).await?;
         }
         if let Some(ip_addr) = c.ip_addr {
             d.set_ip_addr(ip_addr).await?;
@@ -211,13 +192,16 @@ pub async fn build_veth_pair(
     Ok(veth_pair)
 }
 
-pub async fn run_with_veth_pair<F>(
+/// Create a veth pair from the provided configs, bring it up and run
+/// `f` on a blocking thread. The pair is torn down once `f` returns.
+pub async fn run_with_veth_pair<F, T>(
     f: F,
     dev1_config: VethDevConfig,
     dev2_config: VethDevConfig,
-) -> anyhow::Result<()>
+) -> anyhow::Result<T>
 where
-    F: FnOnce(VethDevConfig, VethDevConfig) + Send + 'static,
+    F: FnOnce(VethDevConfig, VethDevConfig) -> T + Send + 'static,
+    T: Send + 'static,
 {
     let veth_pair = build_veth_pair(&dev1_config, &dev2_config).await.unwrap();
 
@@ -0,0 +1,299 @@
+//! `xsk-top`, a live view of the AF_XDP sockets of a running
+//! process.
+//!
+//! ```text
+//! xsk-top [-i <interval ms>] <pid> <fd>...
+//! ```
+//!
+//! Each socket file descriptor is duplicated from the target process
+//! with `pidfd_getfd`, which requires ptrace permission over it (e.g.
+//! `CAP_SYS_PTRACE`). Ring occupancy and packet rates are read from
+//! the socket's rings, mapped read-only, and drop counters from the
+//! `XDP_STATISTICS` socket option.
+//!
+//! The fill and completion rings can only be mapped through the
+//! socket which registered the UMEM, so are shown as `-` for sockets
+//! sharing another's UMEM.
+//!
+//! Requires the `xsk-top` feature.
+
+use libxdp_sys::{
+    XDP_PGOFF_RX_RING, XDP_PGOFF_TX_RING, XDP_UMEM_PGOFF_COMPLETION_RING, XDP_UMEM_PGOFF_FILL_RING,
+};
+use std::{
+    env, io,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    process, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use xsk_rs::socket::{Fd, RingOffsets, XdpStatistics};
+
+const USAGE: &str = "usage: xsk-top [-i <interval ms>] <pid> <fd>...";
+
+/// Duplicate `fd` from process `pid` into this one.
+fn dup_from_process(pid: libc::pid_t, fd: RawFd) -> io::Result<OwnedFd> {
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+
+    if pidfd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `pidfd_open` succeeded so the fd is open and ours.
+    let pidfd = unsafe { OwnedFd::from_raw_fd(pidfd as RawFd) };
+
+    let dup = unsafe { libc::syscall(libc::SYS_pidfd_getfd, pidfd.as_raw_fd(), fd, 0) };
+
+    if dup < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: as above.
+    Ok(unsafe { OwnedFd::from_raw_fd(dup as RawFd) })
+}
+
+/// The first page of one of a socket's rings, mapped read-only. Only
+/// the producer and consumer indices are read, which always lie
+/// within it.
+struct RingView {
+    addr: *mut libc::c_void,
+    len: usize,
+    offsets: RingOffsets,
+}
+
+impl RingView {
+    fn map(fd: RawFd, pgoff: u64, offsets: RingOffsets) -> io::Result<Self> {
+        let len = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+        if offsets.producer() as usize + 4 > len || offsets.consumer() as usize + 4 > len {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "ring indices lie beyond the first page",
+            ));
+        }
+
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                pgoff as libc::off_t,
+            )
+        };
+
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { addr, len, offsets })
+    }
+
+    fn index(&self, offset: u64) -> u32 {
+        // SAFETY: the offset was checked to lie within the mapping
+        // when created, and the kernel keeps the indices aligned.
+        unsafe {
+            let ptr = (self.addr as *const u8).add(offset as usize) as *const AtomicU32;
+            (*ptr).load(Ordering::Acquire)
+        }
+    }
+
+    fn producer(&self) -> u32 {
+        self.index(self.offsets.producer())
+    }
+
+    fn consumer(&self) -> u32 {
+        self.index(self.offsets.consumer())
+    }
+
+    fn occupancy(&self) -> u32 {
+        self.producer().wrapping_sub(self.consumer())
+    }
+}
+
+impl Drop for RingView {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr, self.len) };
+    }
+}
+
+struct Sample {
+    at: Instant,
+    rx_prod: Option<u32>,
+    tx_cons: Option<u32>,
+}
+
+struct Target {
+    remote_fd: RawFd,
+    // `fd` borrows from `_owned`, so must be dropped first.
+    fd: Fd,
+    rx: Option<RingView>,
+    tx: Option<RingView>,
+    fill: Option<RingView>,
+    comp: Option<RingView>,
+    prev: Option<Sample>,
+    _owned: OwnedFd,
+}
+
+impl Target {
+    fn attach(pid: libc::pid_t, remote_fd: RawFd) -> io::Result<Self> {
+        let owned = dup_from_process(pid, remote_fd)?;
+        let raw = owned.as_raw_fd();
+
+        // SAFETY: `owned` is kept alongside `fd` and outlives it.
+        let fd = unsafe { Fd::borrow_raw(raw) };
+
+        let off = fd.mmap_offsets()?;
+
+        Ok(Self {
+            remote_fd,
+            rx: RingView::map(raw, XDP_PGOFF_RX_RING as u64, *off.rx()).ok(),
+            tx: RingView::map(raw, XDP_PGOFF_TX_RING as u64, *off.tx()).ok(),
+            fill: RingView::map(raw, XDP_UMEM_PGOFF_FILL_RING, *off.fill()).ok(),
+            comp: RingView::map(raw, XDP_UMEM_PGOFF_COMPLETION_RING, *off.comp()).ok(),
+            fd,
+            prev: None,
+            _owned: owned,
+        })
+    }
+
+    fn row(&mut self) -> String {
+        let now = Sample {
+            at: Instant::now(),
+            rx_prod: self.rx.as_ref().map(RingView::producer),
+            tx_cons: self.tx.as_ref().map(RingView::consumer),
+        };
+
+        let rate = |prev: Option<u32>, cur: Option<u32>, secs: f64| match (prev, cur) {
+            (Some(p), Some(c)) if secs > 0.0 => format!("{:.0}", c.wrapping_sub(p) as f64 / secs),
+            _ => "-".into(),
+        };
+
+        let (rx_pps, tx_pps) = match &self.prev {
+            Some(prev) => {
+                let secs = now.at.duration_since(prev.at).as_secs_f64();
+                (
+                    rate(prev.rx_prod, now.rx_prod, secs),
+                    rate(prev.tx_cons, now.tx_cons, secs),
+                )
+            }
+            None => ("-".into(), "-".into()),
+        };
+
+        self.prev = Some(now);
+
+        let occ = |ring: &Option<RingView>| {
+            ring.as_ref()
+                .map_or_else(|| "-".into(), |r| r.occupancy().to_string())
+        };
+
+        let stats = match self.fd.xdp_statistics() {
+            Ok(stats) => stats_columns(&stats),
+            Err(e) => format!("{}", e),
+        };
+
+        format!(
+            "{:>6} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10} {}",
+            self.remote_fd,
+            occ(&self.rx),
+            occ(&self.tx),
+            occ(&self.fill),
+            occ(&self.comp),
+            rx_pps,
+            tx_pps,
+            stats
+        )
+    }
+}
+
+fn stats_columns(stats: &XdpStatistics) -> String {
+    format!(
+        "{:>10} {:>10} {:>10} {:>10} {:>10}",
+        stats.rx_dropped(),
+        stats.rx_ring_full(),
+        stats.rx_fill_ring_empty_descs(),
+        stats.rx_invalid_descs(),
+        stats.tx_invalid_descs(),
+    )
+}
+
+fn parse_args() -> Result<(Duration, libc::pid_t, Vec<RawFd>), String> {
+    let mut args = env::args().skip(1).peekable();
+
+    let mut interval = Duration::from_millis(1000);
+
+    if args.peek().map(String::as_str) == Some("-i") {
+        args.next();
+
+        let ms = args
+            .next()
+            .and_then(|s| s.parse().ok())
+            .ok_or("invalid interval")?;
+
+        interval = Duration::from_millis(ms);
+    }
+
+    let pid = args
+        .next()
+        .and_then(|s| s.parse().ok())
+        .ok_or("invalid or missing pid")?;
+
+    let fds = args
+        .map(|s| s.parse().map_err(|_| format!("invalid fd: {}", s)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if fds.is_empty() {
+        return Err("no fds given".into());
+    }
+
+    Ok((interval, pid, fds))
+}
+
+fn main() {
+    let (interval, pid, fds) = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let mut targets = fds
+        .into_iter()
+        .map(|fd| {
+            Target::attach(pid, fd).unwrap_or_else(|e| {
+                eprintln!("failed to attach to fd {} of pid {}: {}", fd, pid, e);
+                process::exit(1);
+            })
+        })
+        .collect::<Vec<_>>();
+
+    loop {
+        let rows = targets.iter_mut().map(Target::row).collect::<Vec<_>>();
+
+        // Clear the screen and move the cursor to the top left.
+        print!("\x1b[2J\x1b[H");
+
+        println!("pid {} (every {:?})\n", pid, interval);
+        println!(
+            "{:>6} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "FD",
+            "RX_OCC",
+            "TX_OCC",
+            "FQ_OCC",
+            "CQ_OCC",
+            "RX_PPS",
+            "TX_PPS",
+            "RX_DROP",
+            "RX_FULL",
+            "FQ_EMPTY",
+            "RX_INVAL",
+            "TX_INVAL"
+        );
+
+        for row in rows {
+            println!("{}", row);
+        }
+
+        thread::sleep(interval);
+    }
+}
@@ -0,0 +1,224 @@
+//! Probing what an interface and its driver support, so that a
+//! socket's flags can be chosen up front rather than by trying them
+//! and seeing what fails on bind.
+//!
+//! ```no_run
+//! use xsk_rs::{caps::Capabilities, config::SocketConfig};
+//!
+//! let if_name = "eth0".parse().unwrap();
+//! let caps = Capabilities::query(&if_name).unwrap();
+//!
+//! let config = SocketConfig::builder()
+//!     .bind_flags(caps.bind_flags())
+//!     .xdp_flags(caps.xdp_flags())
+//!     .build();
+//! ```
+
+use libc::{AF_INET, IFNAMSIZ, SOCK_CLOEXEC, SOCK_DGRAM};
+use std::{
+    io,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use crate::{
+    config::{BindFlags, Interface, XdpFlags},
+    socket::{Channels, XdpFeatures},
+};
+
+/// Layout of `struct ifreq` as used by `SIOCGIFMTU`, where only the
+/// mtu member of the union is of interest.
+#[repr(C)]
+struct IfReqMtu {
+    name: [libc::c_char; IFNAMSIZ],
+    mtu: libc::c_int,
+    // Pads the union out to its full size.
+    _pad: [u8; 20],
+}
+
+/// The MTU `if_name` is currently configured with, as reported by the
+/// `SIOCGIFMTU` ioctl.
+pub fn mtu(if_name: &Interface) -> io::Result<u32> {
+    let name = if_name.as_cstr().to_bytes_with_nul();
+
+    if name.len() > IFNAMSIZ {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "interface name exceeds IFNAMSIZ",
+        ));
+    }
+
+    let mut req = IfReqMtu {
+        name: [0; IFNAMSIZ],
+        mtu: 0,
+        _pad: [0; 20],
+    };
+
+    for (dst, src) in req.name.iter_mut().zip(name) {
+        *dst = *src as libc::c_char;
+    }
+
+    let fd = unsafe { libc::socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `socket` succeeded so the fd is open and owned by us
+    // alone.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let err = unsafe { libc::ioctl(fd.as_raw_fd(), libc::SIOCGIFMTU as _, &mut req) };
+
+    if err < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(req.mtu as u32)
+}
+
+/// What an interface and its driver support.
+///
+/// Anything the kernel or driver can't report is left as [`None`],
+/// for example the XDP features before linux 6.3, or the channels of
+/// a device without any such as the loopback device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    mtu: u32,
+    xdp_features: Option<XdpFeatures>,
+    channels: Option<Channels>,
+}
+
+impl Capabilities {
+    /// Probe the capabilities of `if_name`. Fails only if the
+    /// interface can't be queried at all, for example because it
+    /// doesn't exist.
+    pub fn query(if_name: &Interface) -> io::Result<Self> {
+        Ok(Self {
+            mtu: mtu(if_name)?,
+            xdp_features: XdpFeatures::query(if_name).ok(),
+            channels: Channels::query(if_name).ok(),
+        })
+    }
+
+    /// The interface's current MTU.
+    #[inline]
+    pub fn mtu(&self) -> u32 {
+        self.mtu
+    }
+
+    /// The XDP features of the driver, if known.
+    #[inline]
+    pub fn xdp_features(&self) -> Option<XdpFeatures> {
+        self.xdp_features
+    }
+
+    /// Whether the driver supports native XDP with redirects, so can
+    /// be attached to with
+    /// [`XDP_FLAGS_DRV_MODE`](XdpFlags::XDP_FLAGS_DRV_MODE), if
+    /// known.
+    #[inline]
+    pub fn native_xdp(&self) -> Option<bool> {
+        self.xdp_features
+            .map(|f| f.contains(XdpFeatures::BASIC | XdpFeatures::REDIRECT))
+    }
+
+    /// Whether the driver supports AF_XDP zero-copy, so can be bound
+    /// to with [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY), if known.
+    #[inline]
+    pub fn zero_copy(&self) -> Option<bool> {
+        self.xdp_features
+            .map(|f| f.contains(XdpFeatures::XSK_ZEROCOPY))
+    }
+
+    /// The interface's channels, if known.
+    #[inline]
+    pub fn channels(&self) -> Option<Channels> {
+        self.channels
+    }
+
+    /// The number of queue ids a socket can currently be bound to, if
+    /// known.
+    #[inline]
+    pub fn queue_count(&self) -> Option<u32> {
+        self.channels.map(|c| c.queue_count())
+    }
+
+    /// An upper bound on the number of queue ids the driver supports,
+    /// if known. See [`Channels::max_queue_count`].
+    #[inline]
+    pub fn max_queue_count(&self) -> Option<u32> {
+        self.channels.map(|c| c.max_queue_count())
+    }
+
+    /// The bind flags to use for the fastest mode the driver is known
+    /// to support: [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY) if it
+    /// supports zero-copy, [`XDP_COPY`](BindFlags::XDP_COPY) if it
+    /// doesn't, and neither if unknown, leaving the kernel to try
+    /// zero-copy first and fall back to copy mode.
+    pub fn bind_flags(&self) -> BindFlags {
+        match self.zero_copy() {
+            Some(true) => BindFlags::XDP_ZEROCOPY,
+            Some(false) => BindFlags::XDP_COPY,
+            None => BindFlags::empty(),
+        }
+    }
+
+    /// The XDP flags to use for the fastest attach mode the driver is
+    /// known to support:
+    /// [`XDP_FLAGS_DRV_MODE`](XdpFlags::XDP_FLAGS_DRV_MODE) if it
+    /// supports native XDP,
+    /// [`XDP_FLAGS_SKB_MODE`](XdpFlags::XDP_FLAGS_SKB_MODE) if it
+    /// doesn't, and neither if unknown, leaving the kernel to choose.
+    pub fn xdp_flags(&self) -> XdpFlags {
+        match self.native_xdp() {
+            Some(true) => XdpFlags::XDP_FLAGS_DRV_MODE,
+            Some(false) => XdpFlags::XDP_FLAGS_SKB_MODE,
+            None => XdpFlags::empty(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ifreq_mtu_has_same_size_as_ifreq() {
+        assert_eq!(std::mem::size_of::<IfReqMtu>(), 40);
+    }
+
+    #[test]
+    fn flags_follow_the_known_features() {
+        let caps = |xdp_features| Capabilities {
+            mtu: 1500,
+            xdp_features,
+            channels: None,
+        };
+
+        let full = caps(Some(
+            XdpFeatures::BASIC | XdpFeatures::REDIRECT | XdpFeatures::XSK_ZEROCOPY,
+        ));
+
+        assert_eq!(full.bind_flags().bits(), BindFlags::XDP_ZEROCOPY.bits());
+        assert_eq!(full.xdp_flags().bits(), XdpFlags::XDP_FLAGS_DRV_MODE.bits());
+
+        let generic = caps(Some(XdpFeatures::empty()));
+
+        assert_eq!(generic.bind_flags().bits(), BindFlags::XDP_COPY.bits());
+        assert_eq!(
+            generic.xdp_flags().bits(),
+            XdpFlags::XDP_FLAGS_SKB_MODE.bits()
+        );
+
+        let unknown = caps(None);
+
+        assert_eq!(unknown.zero_copy(), None);
+        assert_eq!(unknown.bind_flags().bits(), BindFlags::empty().bits());
+        assert_eq!(unknown.xdp_flags().bits(), XdpFlags::empty().bits());
+    }
+
+    #[test]
+    fn missing_interface_is_an_error() {
+        assert!(Capabilities::query(&"xsk_no_such_dev".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,442 @@
+//! Validating the IPv4, TCP and UDP checksums of received frames in
+//! software.
+//!
+//! In zero-copy mode a driver may be able to report whether the NIC
+//! validated a packet's checksums, but in copy mode, or with drivers
+//! that don't, nothing is known about them by the time a frame
+//! reaches userspace. [`validate`] fills that gap, parsing the
+//! Ethernet, VLAN and IP headers of a frame and checking each
+//! checksum it finds:
+//!
+//! ```
+//! use xsk_rs::checksum::{self, Validity};
+//!
+//! # let frame = [0u8; 64];
+//! let checksums = checksum::validate(&frame);
+//!
+//! if checksums.l4() == Validity::Invalid {
+//!     // Drop it.
+//! }
+//! ```
+//!
+//! The sum is taken 32 bits at a time into a 64 bit accumulator, a
+//! loop the compiler vectorises for whichever SIMD instructions the
+//! target enables, so building with for example `-C
+//! target-cpu=native` lets it make use of AVX2 where available.
+
+// Ethernet.
+const ETH_HDR_LEN: usize = 14;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88a8;
+
+// IP.
+const IPV4_MIN_HDR_LEN: usize = 20;
+const IPV6_HDR_LEN: usize = 40;
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+// Transport.
+const TCP_MIN_HDR_LEN: usize = 20;
+const UDP_HDR_LEN: usize = 8;
+
+/// The outcome of checking a single checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validity {
+    /// The checksum is correct.
+    Valid,
+    /// The checksum is wrong, or the header it covers is truncated.
+    Invalid,
+    /// There was nothing to check, for example an IPv6 header, which
+    /// has no checksum, a UDP datagram sent without one, or a
+    /// fragment whose transport header is spread over several frames.
+    Unchecked,
+}
+
+/// The transport protocol of a frame whose checksum was checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L4Protocol {
+    /// TCP, IP protocol 6.
+    Tcp,
+    /// UDP, IP protocol 17.
+    Udp,
+}
+
+/// The checksums found in a frame and whether each is correct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checksums {
+    ipv4: Validity,
+    l4: Validity,
+    l4_protocol: Option<L4Protocol>,
+}
+
+impl Checksums {
+    const UNCHECKED: Checksums = Checksums {
+        ipv4: Validity::Unchecked,
+        l4: Validity::Unchecked,
+        l4_protocol: None,
+    };
+
+    /// The IPv4 header checksum. [`Unchecked`](Validity::Unchecked)
+    /// if the frame doesn't carry IPv4.
+    #[inline]
+    pub fn ipv4(&self) -> Validity {
+        self.ipv4
+    }
+
+    /// The TCP or UDP checksum, covering the pseudo header, the
+    /// transport header and its payload.
+    #[inline]
+    pub fn l4(&self) -> Validity {
+        self.l4
+    }
+
+    /// The transport protocol, if TCP or UDP.
+    #[inline]
+    pub fn l4_protocol(&self) -> Option<L4Protocol> {
+        self.l4_protocol
+    }
+
+    /// Whether none of the checksums are invalid. Frames with nothing
+    /// to check count as valid.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.ipv4 != Validity::Invalid && self.l4 != Validity::Invalid
+    }
+}
+
+/// The internet checksum (RFC 1071) of `data`, that is the ones'
+/// complement of the ones' complement sum of its 16 bit words, with
+/// an odd trailing byte padded with zero.
+///
+/// Data whose checksum field is already filled in sums to zero.
+#[inline]
+pub fn checksum(data: &[u8]) -> u16 {
+    !fold(sum(data))
+}
+
+/// Validate the checksums of the Ethernet frame in `frame`, looking
+/// through up to two VLAN tags for an IPv4 or IPv6 header, and past
+/// that a TCP or UDP one.
+///
+/// IPv6 extension headers aren't followed, so the transport checksum
+/// of a packet carrying any is left unchecked.
+pub fn validate(frame: &[u8]) -> Checksums {
+    if frame.len() < ETH_HDR_LEN {
+        return Checksums::UNCHECKED;
+    }
+
+    let mut off = ETH_HDR_LEN;
+    let mut ethertype = be16(frame, off - 2);
+
+    for _ in 0..2 {
+        if (ethertype != ETH_P_8021Q && ethertype != ETH_P_8021AD) || frame.len() < off + 4 {
+            break;
+        }
+
+        ethertype = be16(frame, off + 2);
+        off += 4;
+    }
+
+    match ethertype {
+        ETH_P_IP => validate_ipv4(&frame[off..]),
+        ETH_P_IPV6 => validate_ipv6(&frame[off..]),
+        _ => Checksums::UNCHECKED,
+    }
+}
+
+fn validate_ipv4(pkt: &[u8]) -> Checksums {
+    let invalid = Checksums {
+        ipv4: Validity::Invalid,
+        ..Checksums::UNCHECKED
+    };
+
+    if pkt.len() < IPV4_MIN_HDR_LEN || pkt[0] >> 4 != 4 {
+        return invalid;
+    }
+
+    let hdr_len = (pkt[0] & 0x0f) as usize * 4;
+
+    if hdr_len < IPV4_MIN_HDR_LEN || pkt.len() < hdr_len {
+        return invalid;
+    }
+
+    let ipv4 = if fold(sum(&pkt[..hdr_len])) == 0xffff {
+        Validity::Valid
+    } else {
+        Validity::Invalid
+    };
+
+    let total_len = be16(pkt, 2) as usize;
+    let is_fragment = be16(pkt, 6) & 0x3fff != 0;
+
+    let (l4_protocol, l4) = if total_len < hdr_len || pkt.len() < total_len {
+        (l4_protocol(pkt[9]), Validity::Invalid)
+    } else if is_fragment {
+        (l4_protocol(pkt[9]), Validity::Unchecked)
+    } else {
+        let payload = &pkt[hdr_len..total_len];
+
+        let pseudo = sum(&pkt[12..20]) + pkt[9] as u64 + payload.len() as u64;
+
+        validate_l4(pkt[9], payload, pseudo, false)
+    };
+
+    Checksums {
+        ipv4,
+        l4,
+        l4_protocol,
+    }
+}
+
+fn validate_ipv6(pkt: &[u8]) -> Checksums {
+    if pkt.len() < IPV6_HDR_LEN || pkt[0] >> 4 != 6 {
+        return Checksums::UNCHECKED;
+    }
+
+    let next_header = pkt[6];
+    let end = IPV6_HDR_LEN + be16(pkt, 4) as usize;
+
+    let (l4_protocol, l4) = if pkt.len() < end {
+        (l4_protocol(next_header), Validity::Invalid)
+    } else {
+        let payload = &pkt[IPV6_HDR_LEN..end];
+
+        let pseudo = sum(&pkt[8..40]) + next_header as u64 + payload.len() as u64;
+
+        validate_l4(next_header, payload, pseudo, true)
+    };
+
+    Checksums {
+        ipv4: Validity::Unchecked,
+        l4,
+        l4_protocol,
+    }
+}
+
+fn validate_l4(
+    protocol: u8,
+    payload: &[u8],
+    pseudo: u64,
+    is_ipv6: bool,
+) -> (Option<L4Protocol>, Validity) {
+    let l4_protocol = l4_protocol(protocol);
+
+    let validity = match l4_protocol {
+        None => Validity::Unchecked,
+        Some(L4Protocol::Tcp) if payload.len() < TCP_MIN_HDR_LEN => Validity::Invalid,
+        Some(L4Protocol::Udp) if payload.len() < UDP_HDR_LEN => Validity::Invalid,
+        // A zero UDP checksum means none was computed, which is only
+        // allowed over IPv4.
+        Some(L4Protocol::Udp) if be16(payload, 6) == 0 => {
+            if is_ipv6 {
+                Validity::Invalid
+            } else {
+                Validity::Unchecked
+            }
+        }
+        Some(_) => {
+            if fold(pseudo + sum(payload)) == 0xffff {
+                Validity::Valid
+            } else {
+                Validity::Invalid
+            }
+        }
+    };
+
+    (l4_protocol, validity)
+}
+
+#[inline]
+fn l4_protocol(protocol: u8) -> Option<L4Protocol> {
+    match protocol {
+        IPPROTO_TCP => Some(L4Protocol::Tcp),
+        IPPROTO_UDP => Some(L4Protocol::Udp),
+        _ => None,
+    }
+}
+
+#[inline]
+fn be16(buf: &[u8], off: usize) -> u16 {
+    u16::from_be_bytes([buf[off], buf[off + 1]])
+}
+
+/// The sum of `data` as big endian 32 bit words, with a partial
+/// trailing word padded with zeroes. Since 2^16 is congruent to 1
+/// modulo 2^16 - 1, folding it gives the same result as summing 16
+/// bit words, while halving the number of additions and leaving the
+/// loop simple enough to vectorise.
+#[inline]
+fn sum(data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(4);
+
+    let sum: u64 = (&mut chunks)
+        .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]) as u64)
+        .sum();
+
+    let mut tail = [0; 4];
+    let rem = chunks.remainder();
+    tail[..rem.len()].copy_from_slice(rem);
+
+    sum + u32::from_be_bytes(tail) as u64
+}
+
+/// Fold a sum down to 16 bits, adding back the carries.
+#[inline]
+fn fold(mut sum: u64) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    sum as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC_V4: [u8; 4] = [192, 168, 0, 1];
+    const DST_V4: [u8; 4] = [192, 168, 0, 199];
+
+    fn eth(ethertype: u16) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame
+    }
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let len = (UDP_HDR_LEN + payload.len()) as u16;
+
+        let mut dgram = vec![0x30, 0x39, 0x00, 0x35];
+        dgram.extend_from_slice(&len.to_be_bytes());
+        dgram.extend_from_slice(&[0, 0]);
+        dgram.extend_from_slice(payload);
+        dgram
+    }
+
+    fn ipv4_udp(payload: &[u8]) -> Vec<u8> {
+        let mut dgram = udp(payload);
+        let total_len = (IPV4_MIN_HDR_LEN + dgram.len()) as u16;
+
+        let mut pseudo = SRC_V4.to_vec();
+        pseudo.extend_from_slice(&DST_V4);
+        pseudo.extend_from_slice(&[0, IPPROTO_UDP]);
+        pseudo.extend_from_slice(&(dgram.len() as u16).to_be_bytes());
+        pseudo.extend_from_slice(&dgram);
+
+        let csum = checksum(&pseudo);
+        dgram[6..8].copy_from_slice(&csum.to_be_bytes());
+
+        let mut hdr = vec![0x45, 0];
+        hdr.extend_from_slice(&total_len.to_be_bytes());
+        hdr.extend_from_slice(&[0, 0, 0x40, 0, 64, IPPROTO_UDP, 0, 0]);
+        hdr.extend_from_slice(&SRC_V4);
+        hdr.extend_from_slice(&DST_V4);
+
+        let csum = checksum(&hdr);
+        hdr[10..12].copy_from_slice(&csum.to_be_bytes());
+
+        let mut frame = eth(ETH_P_IP);
+        frame.extend_from_slice(&hdr);
+        frame.extend_from_slice(&dgram);
+        frame
+    }
+
+    #[test]
+    fn checksum_matches_known_ipv4_header() {
+        let hdr = [
+            0x45, 0x00, 0x00, 0x73, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x00, 0x01, 0xc0, 0xa8, 0x00, 0xc7,
+        ];
+
+        assert_eq!(checksum(&hdr), 0xb861);
+        assert_eq!(checksum(&[0x12, 0x34, 0x56]), !0x6834);
+    }
+
+    #[test]
+    fn ipv4_udp_checksums_are_validated() {
+        let frame = ipv4_udp(b"hello");
+
+        let checksums = validate(&frame);
+        assert_eq!(checksums.ipv4(), Validity::Valid);
+        assert_eq!(checksums.l4(), Validity::Valid);
+        assert_eq!(checksums.l4_protocol(), Some(L4Protocol::Udp));
+
+        let mut corrupt = frame.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+
+        let checksums = validate(&corrupt);
+        assert_eq!(checksums.ipv4(), Validity::Valid);
+        assert_eq!(checksums.l4(), Validity::Invalid);
+        assert!(!checksums.is_valid());
+
+        let mut corrupt = frame.clone();
+        corrupt[ETH_HDR_LEN + 8] -= 1;
+
+        assert_eq!(validate(&corrupt).ipv4(), Validity::Invalid);
+
+        let mut no_csum = frame.clone();
+        no_csum[ETH_HDR_LEN + IPV4_MIN_HDR_LEN + 6..][..2].fill(0);
+
+        assert_eq!(validate(&no_csum).l4(), Validity::Unchecked);
+        assert!(validate(&no_csum).is_valid());
+
+        assert_eq!(validate(&frame[..frame.len() - 1]).l4(), Validity::Invalid);
+    }
+
+    #[test]
+    fn vlan_tags_are_skipped() {
+        let frame = ipv4_udp(b"tagged");
+
+        let mut tagged = eth(ETH_P_8021AD);
+        tagged.extend_from_slice(&[0, 10]);
+        tagged.extend_from_slice(&ETH_P_8021Q.to_be_bytes());
+        tagged.extend_from_slice(&[0, 20]);
+        tagged.extend_from_slice(&frame[12..]);
+
+        let checksums = validate(&tagged);
+        assert_eq!(checksums.ipv4(), Validity::Valid);
+        assert_eq!(checksums.l4(), Validity::Valid);
+    }
+
+    #[test]
+    fn ipv6_udp_checksum_is_validated() {
+        let src = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let dst = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+        let mut dgram = udp(b"hello, world");
+
+        let mut pseudo = src.to_vec();
+        pseudo.extend_from_slice(&dst);
+        pseudo.extend_from_slice(&(dgram.len() as u32).to_be_bytes());
+        pseudo.extend_from_slice(&[0, 0, 0, IPPROTO_UDP]);
+        pseudo.extend_from_slice(&dgram);
+
+        let csum = checksum(&pseudo);
+        dgram[6..8].copy_from_slice(&csum.to_be_bytes());
+
+        let mut frame = eth(ETH_P_IPV6);
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&(dgram.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[IPPROTO_UDP, 64]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&dgram);
+
+        let checksums = validate(&frame);
+        assert_eq!(checksums.ipv4(), Validity::Unchecked);
+        assert_eq!(checksums.l4(), Validity::Valid);
+
+        let off = ETH_HDR_LEN + IPV6_HDR_LEN + 6;
+        frame[off..off + 2].fill(0);
+
+        assert_eq!(validate(&frame).l4(), Validity::Invalid);
+    }
+
+    #[test]
+    fn other_frames_are_unchecked() {
+        assert_eq!(validate(&[0; 10]), Checksums::UNCHECKED);
+        assert_eq!(validate(&eth(0x0806)), Checksums::UNCHECKED);
+    }
+}
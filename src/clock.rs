@@ -0,0 +1,271 @@
+//! Pluggable sources of time, and pacing built on them.
+//!
+//! Anything in this crate which samples the time, such as a
+//! [`StatisticsTracker`](crate::stats::StatisticsTracker), an
+//! [`ActiveStandby`](crate::ActiveStandby) or a [`Pacer`], reads it
+//! from a [`Clock`]. The default, [`MonotonicClock`], matches
+//! [`Instant`](std::time::Instant). Deployments whose timing must
+//! line up with PTP synchronised hardware can use [`TaiClock`]
+//! instead, and tests can use a [`MockClock`] to step time by hand.
+
+use std::{
+    fmt, io,
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A source of time.
+pub trait Clock: fmt::Debug {
+    /// The current time, as the time elapsed since some fixed point
+    /// particular to the clock. Must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    #[inline]
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    #[inline]
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// Read `clock_id` with `clock_gettime`.
+pub(crate) fn clock_gettime(clock_id: libc::clockid_t) -> io::Result<Duration> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    if unsafe { libc::clock_gettime(clock_id, &mut ts) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+/// `CLOCK_MONOTONIC`, the clock behind [`Instant`](std::time::Instant).
+/// Subject to NTP frequency adjustments, but never jumps.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    #[inline]
+    fn now(&self) -> Duration {
+        clock_gettime(libc::CLOCK_MONOTONIC).expect("CLOCK_MONOTONIC is always available")
+    }
+}
+
+/// `CLOCK_MONOTONIC_RAW`, the hardware clock free of any NTP
+/// adjustments.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicRawClock;
+
+impl Clock for MonotonicRawClock {
+    #[inline]
+    fn now(&self) -> Duration {
+        clock_gettime(libc::CLOCK_MONOTONIC_RAW).expect("CLOCK_MONOTONIC_RAW is always available")
+    }
+}
+
+/// `CLOCK_TAI`, international atomic time since the epoch, as used by
+/// PTP and by `SO_TXTIME` launch times.
+///
+/// Unlike the monotonic clocks it can be stepped, for example while a
+/// PTP daemon first synchronises, so should only be relied on once
+/// the system clock is in sync. Should it step backwards, the last
+/// time read is returned until it catches up.
+#[derive(Debug, Default)]
+pub struct TaiClock {
+    last: AtomicU64,
+}
+
+impl TaiClock {
+    /// Create a new `TaiClock`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clock for TaiClock {
+    fn now(&self) -> Duration {
+        let now = clock_gettime(libc::CLOCK_TAI).expect("CLOCK_TAI is always available");
+        let now = now.as_nanos() as u64;
+
+        let last = self.last.fetch_max(now, Ordering::Relaxed);
+
+        Duration::from_nanos(last.max(now))
+    }
+}
+
+/// A clock which only moves when told to, for tests. Clones share the
+/// same time, so one can be handed to the code under test while
+/// another is used to advance it.
+#[derive(Debug, Default, Clone)]
+pub struct MockClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Create a new `MockClock`, starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.nanos
+            .fetch_add(by.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    #[inline]
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Limits the rate frames are sent at, as a token bucket which fills
+/// at `rate` frames per second and holds at most `burst` frames.
+///
+/// Before each send, ask for [`available`](Self::available), send at
+/// most that many frames, then report how many were sent with
+/// [`consume`](Self::consume). [`delay`](Self::delay) gives how long
+/// to wait before more can be sent.
+#[derive(Debug, Clone)]
+pub struct Pacer<C = MonotonicClock> {
+    clock: C,
+    rate: u128,
+    // Both in frames times nanoseconds, so that partial frames
+    // accrue without rounding.
+    credit: u128,
+    capacity: u128,
+    last: Duration,
+}
+
+impl Pacer<MonotonicClock> {
+    /// Create a new `Pacer`, starting with a full bucket.
+    pub fn new(rate: NonZeroU32, burst: NonZeroU32) -> Self {
+        Self::with_clock(rate, burst, MonotonicClock)
+    }
+}
+
+impl<C: Clock> Pacer<C> {
+    /// Same as [`new`](Pacer::new), but reading the time from `clock`.
+    pub fn with_clock(rate: NonZeroU32, burst: NonZeroU32, clock: C) -> Self {
+        let capacity = burst.get() as u128 * NANOS_PER_SEC;
+
+        Self {
+            last: clock.now(),
+            clock,
+            rate: rate.get() as u128,
+            credit: capacity,
+            capacity,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+
+        if let Some(elapsed) = now.checked_sub(self.last) {
+            self.credit = (self.credit + elapsed.as_nanos() * self.rate).min(self.capacity);
+            self.last = now;
+        }
+    }
+
+    /// The number of frames which may be sent now.
+    pub fn available(&mut self) -> usize {
+        self.refill();
+
+        (self.credit / NANOS_PER_SEC) as usize
+    }
+
+    /// Record that `cnt` frames were sent.
+    pub fn consume(&mut self, cnt: usize) {
+        self.credit = self.credit.saturating_sub(cnt as u128 * NANOS_PER_SEC);
+    }
+
+    /// How long until `cnt` frames may be sent, zero if they may be
+    /// sent now. Never more than the time to fill the bucket, so asking
+    /// for more than `burst` frames gives the wait for `burst`.
+    pub fn delay(&mut self, cnt: usize) -> Duration {
+        self.refill();
+
+        let needed = (cnt as u128 * NANOS_PER_SEC)
+            .min(self.capacity)
+            .saturating_sub(self.credit);
+
+        // Round up, so that the frames are available once waited for.
+        Duration::from_nanos(needed.div_ceil(self.rate) as u64)
+    }
+
+    /// The clock the pacer reads the time from.
+    #[inline]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    #[test]
+    fn system_clocks_do_not_go_backwards() {
+        let clocks: [&dyn Clock; 3] = [&MonotonicClock, &MonotonicRawClock, &TaiClock::new()];
+
+        for clock in clocks.iter() {
+            let a = clock.now();
+            assert!(clock.now() >= a);
+        }
+    }
+
+    #[test]
+    fn mock_clock_is_shared_between_clones() {
+        let clock = MockClock::new();
+        let other = clock.clone();
+
+        clock.advance(Duration::from_millis(5));
+
+        assert_eq!(other.now(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn pacer_refills_at_rate_up_to_burst() {
+        let clock = MockClock::new();
+
+        let mut pacer = Pacer::with_clock(
+            1000.try_into().unwrap(),
+            4.try_into().unwrap(),
+            clock.clone(),
+        );
+
+        assert_eq!(pacer.available(), 4);
+        pacer.consume(4);
+
+        assert_eq!(pacer.available(), 0);
+        assert_eq!(pacer.delay(1), Duration::from_millis(1));
+        assert_eq!(pacer.delay(10), Duration::from_millis(4));
+
+        clock.advance(Duration::from_micros(2500));
+        assert_eq!(pacer.available(), 2);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(pacer.available(), 4);
+        assert_eq!(pacer.delay(4), Duration::ZERO);
+    }
+}
@@ -3,14 +3,14 @@
 
 mod socket;
 pub use socket::{
-    BindFlags, Config as SocketConfig, ConfigBuilder as SocketConfigBuilder, Interface,
-    LibxdpFlags, XdpFlags,
+    BindFlags, BindStrategy, Config as SocketConfig, ConfigBuilder as SocketConfigBuilder,
+    Interface, LibxdpFlags, XdpFlags,
 };
 
 mod umem;
 pub use umem::{
     Config as UmemConfig, ConfigBuildError as UmemConfigBuilderError,
-    ConfigBuilder as UmemConfigBuilder,
+    ConfigBuilder as UmemConfigBuilder, PrefaultMode, UmemSizeError,
 };
 
 use std::{convert::TryFrom, error, fmt};
@@ -6,7 +6,9 @@ use libxdp_sys::{
 use std::{
     convert::{TryFrom, TryInto},
     ffi::{CStr, CString, NulError},
+    io,
     str::FromStr,
+    time::Duration,
 };
 
 use super::QueueSize;
@@ -62,8 +64,29 @@ bitflags! {
     }
 }
 
+/// How [`Socket::new`](crate::Socket::new) chooses the mode to bind
+/// in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BindStrategy {
+    /// Bind with the [`BindFlags`] and [`XdpFlags`] as configured.
+    #[default]
+    AsConfigured,
+    /// First try zero-copy in driver mode, setting
+    /// [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY) and
+    /// [`XDP_FLAGS_DRV_MODE`](XdpFlags::XDP_FLAGS_DRV_MODE). If the
+    /// interface doesn't support either, failing with `EOPNOTSUPP`,
+    /// fall back to copy mode, setting
+    /// [`XDP_COPY`](BindFlags::XDP_COPY) and
+    /// [`XDP_FLAGS_SKB_MODE`](XdpFlags::XDP_FLAGS_SKB_MODE). Any other
+    /// copy or attach mode flags configured are replaced.
+    ///
+    /// The flags the socket ended up bound with are given by its
+    /// [`SocketInfo`](crate::socket::SocketInfo).
+    PreferZeroCopy,
+}
+
 /// A device interface name.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Interface(CString);
 
 impl Interface {
@@ -72,7 +95,66 @@ impl Interface {
         Self(name)
     }
 
-    pub(crate) fn as_cstr(&self) -> &CStr {
+    /// The interface whose index is `index`.
+    pub fn from_index(index: u32) -> io::Result<Self> {
+        let mut buf = [0 as libc::c_char; libc::IF_NAMESIZE];
+
+        let ptr = unsafe { libc::if_indextoname(index, buf.as_mut_ptr()) };
+
+        if ptr.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: on success the name written to `buf` is nul
+        // terminated.
+        let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+
+        Ok(Self(name.to_owned()))
+    }
+
+    /// The interface's index, as used by netlink and XDP maps.
+    pub fn index(&self) -> io::Result<u32> {
+        let index = unsafe { libc::if_nametoindex(self.0.as_ptr()) };
+
+        if index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(index)
+    }
+
+    /// Every interface on the system, along with its index, in the
+    /// order the kernel lists them.
+    pub fn list() -> io::Result<Vec<(u32, Self)>> {
+        let head = unsafe { libc::if_nameindex() };
+
+        if head.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut interfaces = Vec::new();
+        let mut entry = head;
+
+        // SAFETY: the array is terminated by an entry with a zero
+        // index and null name, and isn't freed until we're done.
+        unsafe {
+            while (*entry).if_index != 0 && !(*entry).if_name.is_null() {
+                let name = CStr::from_ptr((*entry).if_name).to_owned();
+
+                interfaces.push(((*entry).if_index, Self(name)));
+
+                entry = entry.add(1);
+            }
+
+            libc::if_freenameindex(head);
+        }
+
+        Ok(interfaces)
+    }
+
+    /// The interface's name.
+    #[inline]
+    pub fn as_cstr(&self) -> &CStr {
         &self.0
     }
 }
@@ -147,6 +229,51 @@ impl ConfigBuilder {
         self
     }
 
+    /// If set, socket creation fails when the kernel binds the socket
+    /// in copy mode without it being requested, rather than logging a
+    /// warning. Copy mode is considered requested if either
+    /// [`BindFlags::XDP_COPY`] or [`XdpFlags::XDP_FLAGS_SKB_MODE`] is
+    /// set. Default is `false`.
+    ///
+    /// This typically happens when the driver lacks native XDP or
+    /// zero-copy support and the kernel silently falls back to
+    /// generic (skb) mode, which is far slower.
+    pub fn strict_mode(&mut self, strict: bool) -> &mut Self {
+        self.config.strict_mode = strict;
+        self
+    }
+
+    /// Retry binding up to `retries` times if it fails with `EBUSY`,
+    /// which may happen for a short while after a socket bound to the
+    /// same interface and queue is closed. Waits `backoff` before the
+    /// first retry, doubling the wait after each further failure.
+    /// Default is no retries.
+    pub fn busy_retries(&mut self, retries: u32, backoff: Duration) -> &mut Self {
+        self.config.busy_retries = retries;
+        self.config.busy_backoff = backoff;
+        self
+    }
+
+    /// Set the [`BindStrategy`]. Default is
+    /// [`AsConfigured`](BindStrategy::AsConfigured).
+    pub fn bind_strategy(&mut self, strategy: BindStrategy) -> &mut Self {
+        self.config.bind_strategy = strategy;
+        self
+    }
+
+    /// Enable preferred busy polling once the socket is bound,
+    /// setting `SO_PREFER_BUSY_POLL`, `SO_BUSY_POLL` to `timeout` and
+    /// `SO_BUSY_POLL_BUDGET` to `budget`. Default is disabled.
+    ///
+    /// Socket creation fails if any of them can't be set. They can
+    /// also be set individually later with
+    /// [`Fd::set_busy_poll`](crate::socket::Fd::set_busy_poll) and
+    /// friends.
+    pub fn busy_poll(&mut self, timeout: Duration, budget: u16) -> &mut Self {
+        self.config.busy_poll = Some((timeout, budget));
+        self
+    }
+
     /// Build a [`SocketConfig`](Config) instance using the values set
     /// in this builder.
     pub fn build(&self) -> Config {
@@ -162,6 +289,11 @@ pub struct Config {
     libxdp_flags: LibxdpFlags,
     xdp_flags: XdpFlags,
     bind_flags: BindFlags,
+    bind_strategy: BindStrategy,
+    strict_mode: bool,
+    busy_retries: u32,
+    busy_backoff: Duration,
+    busy_poll: Option<(Duration, u16)>,
 }
 
 impl Config {
@@ -170,6 +302,33 @@ impl Config {
         ConfigBuilder::new()
     }
 
+    /// Creates a [`SocketConfigBuilder`](ConfigBuilder) instance
+    /// starting out with this config's values rather than the
+    /// defaults, so that only the values which differ need setting.
+    ///
+    /// Useful when binding a socket to each of several queues, where
+    /// each socket's config is the same save for, say, its queue
+    /// sizes.
+    ///
+    /// ```
+    /// use xsk_rs::config::{QueueSize, SocketConfig};
+    ///
+    /// let base = SocketConfig::builder()
+    ///     .rx_queue_size(QueueSize::new(4096).unwrap())
+    ///     .build();
+    ///
+    /// let small = base
+    ///     .with_overrides()
+    ///     .rx_queue_size(QueueSize::new(512).unwrap())
+    ///     .build();
+    ///
+    /// assert_eq!(small.rx_queue_size().get(), 512);
+    /// assert_eq!(small.tx_queue_size().get(), base.tx_queue_size().get());
+    /// ```
+    pub fn with_overrides(&self) -> ConfigBuilder {
+        ConfigBuilder { config: *self }
+    }
+
     /// The socket's [`RxQueue`](crate::RxQueue) size.
     pub fn rx_queue_size(&self) -> QueueSize {
         self.rx_queue_size
@@ -194,6 +353,64 @@ impl Config {
     pub fn bind_flags(&self) -> &BindFlags {
         &self.bind_flags
     }
+
+    /// The [`BindStrategy`] set.
+    pub fn bind_strategy(&self) -> BindStrategy {
+        self.bind_strategy
+    }
+
+    /// Whether socket creation fails if the socket is bound in copy
+    /// mode without it being requested.
+    pub fn strict_mode(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// The number of times binding is retried if it fails with
+    /// `EBUSY`.
+    pub fn busy_retries(&self) -> u32 {
+        self.busy_retries
+    }
+
+    /// The wait before the first retry after binding fails with
+    /// `EBUSY`.
+    pub fn busy_backoff(&self) -> Duration {
+        self.busy_backoff
+    }
+
+    /// The `SO_BUSY_POLL` timeout and `SO_BUSY_POLL_BUDGET` set
+    /// along with `SO_PREFER_BUSY_POLL` on creation, if enabled.
+    pub fn busy_poll(&self) -> Option<(Duration, u16)> {
+        self.busy_poll
+    }
+
+    /// Whether copy mode was asked for, either explicitly or by
+    /// forcing generic (skb) mode.
+    pub(crate) fn copy_mode_requested(&self) -> bool {
+        self.bind_flags.contains(BindFlags::XDP_COPY)
+            || self.xdp_flags.contains(XdpFlags::XDP_FLAGS_SKB_MODE)
+    }
+
+    /// A copy of this config bound with `bind_mode`, one of
+    /// [`XDP_COPY`](BindFlags::XDP_COPY) or
+    /// [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY), and attached with
+    /// `attach_mode`, replacing any such flags already set.
+    pub(crate) fn with_mode(&self, bind_mode: BindFlags, attach_mode: XdpFlags) -> Self {
+        let mut config = *self;
+
+        config
+            .bind_flags
+            .remove(BindFlags::XDP_COPY | BindFlags::XDP_ZEROCOPY);
+        config.bind_flags.insert(bind_mode);
+
+        config.xdp_flags.remove(
+            XdpFlags::XDP_FLAGS_SKB_MODE
+                | XdpFlags::XDP_FLAGS_DRV_MODE
+                | XdpFlags::XDP_FLAGS_HW_MODE,
+        );
+        config.xdp_flags.insert(attach_mode);
+
+        config
+    }
 }
 
 impl Default for Config {
@@ -204,6 +421,11 @@ impl Default for Config {
             libxdp_flags: LibxdpFlags::empty(),
             xdp_flags: XdpFlags::empty(),
             bind_flags: BindFlags::empty(),
+            bind_strategy: BindStrategy::AsConfigured,
+            strict_mode: false,
+            busy_retries: 0,
+            busy_backoff: Duration::ZERO,
+            busy_poll: None,
         }
     }
 }
@@ -223,3 +445,26 @@ impl From<Config> for xsk_socket_config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_resolves_between_name_and_index() {
+        let lo: Interface = "lo".parse().unwrap();
+
+        let index = lo.index().unwrap();
+
+        assert_eq!(Interface::from_index(index).unwrap(), lo);
+        assert!(Interface::list().unwrap().contains(&(index, lo)));
+    }
+
+    #[test]
+    fn unknown_interfaces_fail_to_resolve() {
+        let missing: Interface = "xsk-rs-missing".parse().unwrap();
+
+        assert!(missing.index().is_err());
+        assert!(Interface::from_index(u32::MAX).is_err());
+    }
+}
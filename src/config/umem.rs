@@ -1,12 +1,35 @@
 use libxdp_sys::{
-    xsk_umem_config, XDP_PACKET_HEADROOM, XSK_RING_CONS__DEFAULT_NUM_DESCS,
-    XSK_RING_PROD__DEFAULT_NUM_DESCS, XSK_UMEM__DEFAULT_FRAME_HEADROOM,
-    XSK_UMEM__DEFAULT_FRAME_SIZE,
+    xsk_umem_config, XDP_PACKET_HEADROOM, XDP_UMEM_UNALIGNED_CHUNK_FLAG,
+    XSK_RING_CONS__DEFAULT_NUM_DESCS, XSK_RING_PROD__DEFAULT_NUM_DESCS,
+    XSK_UMEM__DEFAULT_FRAME_HEADROOM, XSK_UMEM__DEFAULT_FRAME_SIZE,
 };
-use std::{error, fmt};
+use std::{convert::TryFrom, error, fmt, num::NonZeroU32};
 
 use super::{FrameSize, QueueSize};
 
+/// How the pages backing a [`Umem`](crate::Umem) are faulted in when
+/// it's created.
+///
+/// The memory is always zeroed, being an anonymous mapping, so this
+/// only affects when the cost of allocating it is paid. Pages which
+/// aren't faulted in up front are faulted in the first time either
+/// the kernel or userspace touches them, which for a large `Umem` may
+/// add latency to the first packets through each frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PrefaultMode {
+    /// Map the memory with `MAP_POPULATE`, so all pages are faulted
+    /// in before creation returns. The default.
+    #[default]
+    Populate,
+    /// Advise the kernel with `MADV_WILLNEED` that the memory will be
+    /// needed soon, without waiting for it to be faulted in.
+    WillNeed,
+    /// Don't fault in any pages up front. They can be faulted in
+    /// later, at a time of the user's choosing, with
+    /// [`Umem::prefault`](crate::Umem::prefault).
+    Lazy,
+}
+
 /// Builder for a [`UmemConfig`](Config).
 #[derive(Debug, Default, Clone, Copy)]
 pub struct ConfigBuilder {
@@ -50,6 +73,48 @@ impl ConfigBuilder {
         self
     }
 
+    /// Set how the `Umem`'s pages are faulted in on creation. Default
+    /// is [`PrefaultMode::Populate`].
+    pub fn prefault_mode(&mut self, mode: PrefaultMode) -> &mut Self {
+        self.config.prefault_mode = mode;
+        self
+    }
+
+    /// Set whether the `Umem`'s memory, and the rings of it and any
+    /// socket bound with it, are locked into memory with `mlock`.
+    /// Default is `false`.
+    ///
+    /// A strict mode for latency critical deployments which can't
+    /// tolerate page faults once traffic is flowing. The memory is
+    /// faulted in and locked on creation, which then fails if it
+    /// can't be, for example because `RLIMIT_MEMLOCK` is too low. See
+    /// also [`Umem::warm_up`](crate::Umem::warm_up).
+    pub fn lock_memory(&mut self, enabled: bool) -> &mut Self {
+        self.config.lock_memory = enabled;
+        self
+    }
+
+    /// Set whether the `Umem` is registered in unaligned chunk mode,
+    /// using [`XDP_UMEM_UNALIGNED_CHUNK_FLAG`]. Default is `false`.
+    ///
+    /// In the default aligned mode the kernel requires the frame size
+    /// to be a power of two, and rounds any address on the fill ring
+    /// down to the start of its frame. In unaligned mode frames may
+    /// be of any size and start at any address, so packets can be
+    /// placed at arbitrary offsets within the `Umem` and frames packed
+    /// tightly into huge pages. The kernel then encodes the offset of
+    /// received packets in the upper bits of rx addresses, which are
+    /// decoded by the queues, but see [`unaligned`](crate::umem::unaligned)
+    /// for working with such addresses directly.
+    ///
+    /// In unaligned mode the address of each descriptor given to the
+    /// [`FillQueue`](crate::FillQueue) is taken as the start of the
+    /// frame, see [`Umem::chunk_desc`](crate::Umem::chunk_desc).
+    pub fn unaligned_chunks(&mut self, enabled: bool) -> &mut Self {
+        self.config.unaligned_chunks = enabled;
+        self
+    }
+
     /// Build a [`UmemConfig`](Config) instance using the values set
     /// in this builder.
     ///
@@ -84,6 +149,9 @@ pub struct Config {
     fill_queue_size: QueueSize,
     comp_queue_size: QueueSize,
     frame_headroom: u32,
+    prefault_mode: PrefaultMode,
+    unaligned_chunks: bool,
+    lock_memory: bool,
 }
 
 impl Config {
@@ -93,6 +161,12 @@ impl Config {
         ConfigBuilder::new()
     }
 
+    /// Creates a [`UmemConfigBuilder`](ConfigBuilder) starting from
+    /// this config's values, for building a variation on it.
+    pub fn to_builder(&self) -> ConfigBuilder {
+        ConfigBuilder { config: *self }
+    }
+
     /// The size of each frame in the [`Umem`](crate::Umem).
     pub fn frame_size(&self) -> FrameSize {
         self.frame_size
@@ -118,6 +192,21 @@ impl Config {
         self.frame_headroom
     }
 
+    /// How the `Umem`'s pages are faulted in on creation.
+    pub fn prefault_mode(&self) -> PrefaultMode {
+        self.prefault_mode
+    }
+
+    /// Whether the `Umem` is registered in unaligned chunk mode.
+    pub fn unaligned_chunks(&self) -> bool {
+        self.unaligned_chunks
+    }
+
+    /// Whether the `Umem`'s memory and rings are locked into memory.
+    pub fn lock_memory(&self) -> bool {
+        self.lock_memory
+    }
+
     /// The maximum transmission unit, or the length of the packet
     /// data segment of the frame.
     ///
@@ -126,6 +215,29 @@ impl Config {
     pub fn mtu(&self) -> u32 {
         self.frame_size.get() - (self.xdp_headroom() + self.frame_headroom)
     }
+
+    /// The length in bytes of the memory region of a
+    /// [`Umem`](crate::Umem) with `frame_count` frames of this
+    /// config's frame size.
+    ///
+    /// Fails if the length can't be addressed on this target, that is
+    /// if it exceeds `isize::MAX`. On 32-bit targets, for example,
+    /// UMEMs of 2GiB or more can't be created.
+    pub fn umem_len(&self, frame_count: NonZeroU32) -> Result<usize, UmemSizeError> {
+        let frame_count = frame_count.get();
+        let frame_size = self.frame_size.get();
+
+        // Can't overflow since both are at most `u32::MAX`.
+        let len = frame_count as u64 * frame_size as u64;
+
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len <= isize::MAX as usize)
+            .ok_or(UmemSizeError {
+                frame_count,
+                frame_size,
+            })
+    }
 }
 
 impl Default for Config {
@@ -135,6 +247,9 @@ impl Default for Config {
             fill_queue_size: QueueSize(XSK_RING_PROD__DEFAULT_NUM_DESCS),
             comp_queue_size: QueueSize(XSK_RING_CONS__DEFAULT_NUM_DESCS),
             frame_headroom: XSK_UMEM__DEFAULT_FRAME_HEADROOM,
+            prefault_mode: PrefaultMode::default(),
+            unaligned_chunks: false,
+            lock_memory: false,
         }
     }
 }
@@ -146,7 +261,11 @@ impl From<Config> for xsk_umem_config {
             comp_size: c.comp_queue_size.get(),
             frame_size: c.frame_size.get(),
             frame_headroom: c.frame_headroom,
-            flags: 0,
+            flags: if c.unaligned_chunks {
+                XDP_UMEM_UNALIGNED_CHUNK_FLAG
+            } else {
+                0
+            },
         }
     }
 }
@@ -170,6 +289,38 @@ impl fmt::Display for ConfigBuildError {
 
 impl error::Error for ConfigBuildError {}
 
+/// Error signifying that a [`Umem`](crate::Umem) with the requested
+/// number of frames is too large to address on this target.
+#[derive(Debug)]
+pub struct UmemSizeError {
+    frame_count: u32,
+    frame_size: u32,
+}
+
+impl UmemSizeError {
+    /// The number of frames requested.
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// The size of each frame.
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+}
+
+impl fmt::Display for UmemSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} frames of size {} exceeds the maximum addressable UMEM length",
+            self.frame_count, self.frame_size
+        )
+    }
+}
+
+impl error::Error for UmemSizeError {}
+
 #[cfg(test)]
 mod tests {
     use std::convert::TryInto;
@@ -208,4 +359,92 @@ mod tests {
             XDP_UMEM_MIN_CHUNK_SIZE - (frame_headroom + XDP_PACKET_HEADROOM)
         );
     }
+
+    #[test]
+    fn builder_seeded_from_config_keeps_every_field() {
+        let config = ConfigBuilder::new()
+            .frame_size(4096.try_into().unwrap())
+            .frame_headroom(64)
+            .prefault_mode(PrefaultMode::Lazy)
+            .unaligned_chunks(true)
+            .lock_memory(true)
+            .build()
+            .unwrap();
+
+        let resized = config
+            .to_builder()
+            .fill_queue_size(QueueSize::new(64).unwrap())
+            .build()
+            .unwrap();
+
+        assert_eq!(resized.fill_queue_size().get(), 64);
+        assert_eq!(
+            resized.comp_queue_size().get(),
+            config.comp_queue_size().get()
+        );
+        assert_eq!(resized.frame_size().get(), 4096);
+        assert_eq!(resized.frame_headroom(), 64);
+        assert_eq!(resized.prefault_mode(), PrefaultMode::Lazy);
+        assert!(resized.unaligned_chunks());
+        assert!(resized.lock_memory());
+    }
+
+    #[test]
+    fn umem_len_handles_regions_beyond_4gib() {
+        let config = ConfigBuilder::new()
+            .frame_size(4096.try_into().unwrap())
+            .build()
+            .unwrap();
+
+        let len = |frame_count: u32| config.umem_len(frame_count.try_into().unwrap());
+
+        assert_eq!(len(1).unwrap(), 4096);
+        assert_eq!(len((1 << 19) - 1).unwrap(), (1 << 31) - 4096);
+
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(len(1 << 20).unwrap() as u64, 1 << 32);
+            assert_eq!(len(u32::MAX).unwrap() as u64, u32::MAX as u64 * 4096);
+        } else {
+            assert!(len(1 << 19).is_err());
+            assert!(len(u32::MAX).is_err());
+        }
+
+        let max_frame_size = ConfigBuilder::new()
+            .frame_size(u32::MAX.try_into().unwrap())
+            .build()
+            .unwrap();
+
+        // Just shy of 2^64 bytes, past `isize::MAX` even on 64-bit
+        // targets.
+        assert!(max_frame_size
+            .umem_len(u32::MAX.try_into().unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn prefault_mode_defaults_to_populate() {
+        assert_eq!(
+            ConfigBuilder::new().build().unwrap().prefault_mode(),
+            PrefaultMode::Populate
+        );
+
+        assert_eq!(
+            ConfigBuilder::new()
+                .prefault_mode(PrefaultMode::Lazy)
+                .build()
+                .unwrap()
+                .prefault_mode(),
+            PrefaultMode::Lazy
+        );
+    }
+
+    #[test]
+    fn memory_is_not_locked_by_default() {
+        assert!(!ConfigBuilder::new().build().unwrap().lock_memory());
+        assert!(ConfigBuilder::new()
+            .lock_memory(true)
+            .build()
+            .unwrap()
+            .lock_memory());
+    }
 }
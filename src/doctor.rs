@@ -0,0 +1,559 @@
+//! Diagnosing the environment AF_XDP sockets are to be created in.
+//!
+//! [`doctor`] runs through the setup problems which most often stop a
+//! socket from being created, or leave it running far slower than
+//! expected, and collects what it finds into a [`Report`]:
+//!
+//! ```no_run
+//! let if_name = "eth0".parse().unwrap();
+//! let report = xsk_rs::doctor(Some(&if_name));
+//!
+//! println!("{}", report);
+//!
+//! if !report.is_ok() {
+//!     std::process::exit(1);
+//! }
+//! ```
+
+use std::{ffi::CStr, fmt, fs, mem, path::Path};
+
+use crate::{caps::Capabilities, config::Interface, socket::XdpFeatures};
+
+// From `linux/capability.h`.
+const CAP_NET_ADMIN: u32 = 12;
+const CAP_NET_RAW: u32 = 13;
+const CAP_IPC_LOCK: u32 = 14;
+const CAP_SYS_ADMIN: u32 = 21;
+const CAP_BPF: u32 = 39;
+
+// From `linux/magic.h`.
+const BPF_FS_MAGIC: libc::c_long = 0xcafe4a11;
+
+const BPF_FS_PATH: &str = "/sys/fs/bpf";
+
+/// The largest MTU which fits in a single 4KiB page once the kernel's
+/// XDP headroom and `skb_shared_info` are accounted for. Drivers
+/// without multi-buffer support refuse to attach native XDP programs
+/// above it.
+const MAX_SINGLE_PAGE_MTU: u32 = 3498;
+
+/// The outcome of a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    /// Nothing wrong was found.
+    Ok,
+    /// Sockets can likely be created, but something may get in the
+    /// way or slow them down.
+    Warn,
+    /// Sockets likely can't be created.
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Status::Ok => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+
+        write!(f, "{:>4}", s)
+    }
+}
+
+/// A single check made by [`doctor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: String) -> Self {
+        Self {
+            name,
+            status,
+            detail,
+        }
+    }
+
+    /// What was checked, for example `kernel`.
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The outcome.
+    #[inline]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// What was found, and what to do about it if anything.
+    #[inline]
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+/// The findings of [`doctor`], one line per check when displayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    /// Every check made, in the order they were made.
+    #[inline]
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    /// The worst outcome of any check.
+    pub fn status(&self) -> Status {
+        self.checks
+            .iter()
+            .map(|c| c.status)
+            .max()
+            .unwrap_or(Status::Ok)
+    }
+
+    /// Whether no check failed outright.
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.status() != Status::Fail
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            writeln!(f, "[{}] {}: {}", check.status, check.name, check.detail)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the kernel version, privileges, memlock limit, huge pages,
+/// BPF filesystem and libxdp setup of this machine, along with the
+/// XDP support of `if_name` if given.
+///
+/// Nothing is changed, and no socket is created, so it's safe to run
+/// before deciding whether to go ahead.
+pub fn doctor(if_name: Option<&Interface>) -> Report {
+    let caps = effective_caps();
+
+    let mut checks = vec![
+        kernel_check(kernel_release().as_deref()),
+        privilege_check(caps),
+        memlock_check(memlock_limit(), caps.map(|c| has_cap(c, CAP_IPC_LOCK))),
+        huge_pages_check(fs::read_to_string("/proc/meminfo").ok().as_deref()),
+        bpf_fs_check(),
+        libxdp_check(
+            std::env::var_os("LIBXDP_OBJECT_PATH")
+                .as_deref()
+                .map(Path::new),
+        ),
+    ];
+
+    if let Some(if_name) = if_name {
+        checks.push(interface_check(if_name));
+    }
+
+    Report { checks }
+}
+
+fn kernel_release() -> Option<String> {
+    let mut uts: libc::utsname = unsafe { mem::zeroed() };
+
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return None;
+    }
+
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) };
+
+    Some(release.to_string_lossy().into_owned())
+}
+
+/// The major and minor version of a kernel release such as
+/// `6.8.0-45-generic`.
+fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+
+    Some((major, minor))
+}
+
+fn kernel_check(release: Option<&str>) -> Check {
+    let name = "kernel";
+
+    let release = match release {
+        Some(release) => release,
+        None => return Check::new(name, Status::Warn, "failed to read kernel release".into()),
+    };
+
+    let (status, detail) = match parse_kernel_version(release) {
+        None => (Status::Warn, "unrecognised release format"),
+        Some(v) if v < (4, 18) => (Status::Fail, "AF_XDP requires linux 4.18 or later"),
+        Some(v) if v < (5, 4) => (
+            Status::Warn,
+            "XDP_USE_NEED_WAKEUP and shared UMEMs require linux 5.4 or later",
+        ),
+        Some(v) if v < (6, 3) => (
+            Status::Ok,
+            "driver XDP features can't be queried before linux 6.3",
+        ),
+        Some(_) => (Status::Ok, "supports every feature used"),
+    };
+
+    Check::new(name, status, format!("linux {}, {}", release, detail))
+}
+
+/// The effective capability set of this process, from
+/// `/proc/self/status`.
+fn effective_caps() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+
+    parse_cap_eff(&status)
+}
+
+fn parse_cap_eff(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("CapEff:"))?;
+
+    u64::from_str_radix(line["CapEff:".len()..].trim(), 16).ok()
+}
+
+#[inline]
+fn has_cap(caps: u64, cap: u32) -> bool {
+    caps & (1 << cap) != 0
+}
+
+fn privilege_check(caps: Option<u64>) -> Check {
+    let name = "privileges";
+
+    let caps = match caps {
+        Some(caps) => caps,
+        None => {
+            return Check::new(
+                name,
+                Status::Warn,
+                "failed to read the effective capabilities".into(),
+            )
+        }
+    };
+
+    let mut missing = Vec::new();
+
+    if !has_cap(caps, CAP_NET_RAW) {
+        missing.push("CAP_NET_RAW, needed to create AF_XDP sockets");
+    }
+
+    if !has_cap(caps, CAP_NET_ADMIN) {
+        missing.push("CAP_NET_ADMIN, needed to attach XDP programs");
+    }
+
+    if !has_cap(caps, CAP_BPF) && !has_cap(caps, CAP_SYS_ADMIN) {
+        missing.push("CAP_BPF or CAP_SYS_ADMIN, needed to load XDP programs");
+    }
+
+    if missing.is_empty() {
+        return Check::new(name, Status::Ok, "all required capabilities held".into());
+    }
+
+    let status = if has_cap(caps, CAP_NET_RAW) {
+        Status::Warn
+    } else {
+        Status::Fail
+    };
+
+    Check::new(name, status, format!("missing {}", missing.join("; ")))
+}
+
+/// The soft `RLIMIT_MEMLOCK` limit in bytes, [`None`] if unlimited.
+fn memlock_limit() -> Option<Option<u64>> {
+    let mut rlim = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut rlim) } != 0 {
+        return None;
+    }
+
+    Some(if rlim.rlim_cur == libc::RLIM_INFINITY {
+        None
+    } else {
+        Some(rlim.rlim_cur)
+    })
+}
+
+fn memlock_check(limit: Option<Option<u64>>, ipc_lock: Option<bool>) -> Check {
+    let name = "memlock";
+
+    match (limit, ipc_lock) {
+        (None, _) => Check::new(name, Status::Warn, "failed to read RLIMIT_MEMLOCK".into()),
+        (Some(None), _) => Check::new(name, Status::Ok, "RLIMIT_MEMLOCK is unlimited".into()),
+        (Some(Some(limit)), Some(true)) => Check::new(
+            name,
+            Status::Ok,
+            format!(
+                "RLIMIT_MEMLOCK is {} KiB, but CAP_IPC_LOCK lifts it for UMEMs",
+                limit / 1024
+            ),
+        ),
+        (Some(Some(limit)), _) => Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "RLIMIT_MEMLOCK is {} KiB, registering a larger UMEM fails with ENOBUFS; \
+                 raise it with `ulimit -l` or grant CAP_IPC_LOCK",
+                limit / 1024
+            ),
+        ),
+    }
+}
+
+/// The value of field `key` in `/proc/meminfo`.
+fn meminfo_field(meminfo: &str, key: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn huge_pages_check(meminfo: Option<&str>) -> Check {
+    let name = "huge pages";
+
+    let fields = meminfo.and_then(|m| {
+        Some((
+            meminfo_field(m, "HugePages_Total")?,
+            meminfo_field(m, "HugePages_Free")?,
+            meminfo_field(m, "Hugepagesize")?,
+        ))
+    });
+
+    match fields {
+        None => Check::new(name, Status::Warn, "failed to read /proc/meminfo".into()),
+        Some((0, _, _)) => Check::new(
+            name,
+            Status::Warn,
+            "none reserved, so UMEMs can't use huge pages; reserve some with \
+             `sysctl vm.nr_hugepages`"
+                .into(),
+        ),
+        Some((total, 0, _)) => Check::new(
+            name,
+            Status::Warn,
+            format!("all {} reserved huge pages are in use", total),
+        ),
+        Some((total, free, size)) => Check::new(
+            name,
+            Status::Ok,
+            format!("{} of {} {} KiB huge pages free", free, total, size),
+        ),
+    }
+}
+
+fn bpf_fs_check() -> Check {
+    let name = "bpffs";
+
+    let mut buf: libc::statfs = unsafe { mem::zeroed() };
+    let path = b"/sys/fs/bpf\0";
+
+    let mounted = unsafe { libc::statfs(path.as_ptr() as *const libc::c_char, &mut buf) } == 0
+        && buf.f_type as libc::c_long == BPF_FS_MAGIC;
+
+    if mounted {
+        Check::new(name, Status::Ok, format!("mounted at {}", BPF_FS_PATH))
+    } else {
+        Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "not mounted at {}, so libxdp can't attach more than one XDP program \
+                 per interface; mount it with `mount -t bpf bpf {}`",
+                BPF_FS_PATH, BPF_FS_PATH
+            ),
+        )
+    }
+}
+
+fn libxdp_check(object_path: Option<&Path>) -> Check {
+    let name = "libxdp";
+
+    match object_path {
+        Some(path) if !path.is_dir() => Check::new(
+            name,
+            Status::Warn,
+            format!(
+                "LIBXDP_OBJECT_PATH is set to {}, which isn't a directory, so libxdp \
+                 can't find its XDP programs",
+                path.display()
+            ),
+        ),
+        Some(path) => Check::new(
+            name,
+            Status::Ok,
+            format!(
+                "linked in through libxdp-sys, loading XDP programs from {}",
+                path.display()
+            ),
+        ),
+        None => Check::new(
+            name,
+            Status::Ok,
+            "linked in through libxdp-sys, along with libbpf".into(),
+        ),
+    }
+}
+
+fn interface_check(if_name: &Interface) -> Check {
+    let name = "interface";
+    let if_str = if_name.as_cstr().to_string_lossy();
+
+    let caps = match Capabilities::query(if_name) {
+        Ok(caps) => caps,
+        Err(e) => {
+            return Check::new(
+                name,
+                Status::Fail,
+                format!("failed to query {}: {}", if_str, e),
+            )
+        }
+    };
+
+    let mut status = Status::Ok;
+    let mut notes = vec![format!("mtu {}", caps.mtu())];
+
+    if let Some(queues) = caps.queue_count() {
+        notes.push(format!("{} queues", queues));
+    }
+
+    match caps.xdp_features() {
+        None => {
+            status = Status::Warn;
+            notes.push("XDP features unknown".into());
+        }
+        Some(features) => {
+            if caps.native_xdp() == Some(true) {
+                notes.push("native XDP".into());
+            } else {
+                status = Status::Warn;
+                notes.push("no native XDP, so only generic (skb) mode is available".into());
+            }
+
+            if caps.zero_copy() == Some(true) {
+                notes.push("zero-copy".into());
+            } else {
+                status = Status::Warn;
+                notes.push("no zero-copy, so sockets run in copy mode".into());
+            }
+
+            if caps.mtu() > MAX_SINGLE_PAGE_MTU && !features.contains(XdpFeatures::RX_SG) {
+                status = Status::Warn;
+                notes.push(format!(
+                    "mtu above {} without multi-buffer support, so native XDP will \
+                     likely fail to attach",
+                    MAX_SINGLE_PAGE_MTU
+                ));
+            }
+        }
+    }
+
+    Check::new(name, status, format!("{}: {}", if_str, notes.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_versions_are_graded() {
+        assert_eq!(parse_kernel_version("6.8.0-45-generic"), Some((6, 8)));
+        assert_eq!(parse_kernel_version("4.19.0"), Some((4, 19)));
+        assert_eq!(parse_kernel_version("nonsense"), None);
+
+        assert_eq!(kernel_check(Some("4.15.0")).status(), Status::Fail);
+        assert_eq!(kernel_check(Some("5.3.18")).status(), Status::Warn);
+        assert_eq!(kernel_check(Some("5.15.0")).status(), Status::Ok);
+        assert_eq!(kernel_check(None).status(), Status::Warn);
+    }
+
+    #[test]
+    fn missing_capabilities_are_reported() {
+        assert_eq!(
+            parse_cap_eff("Name:\tx\nCapEff:\t0000000000003000\n"),
+            Some(0x3000)
+        );
+
+        let all = (1 << CAP_NET_RAW) | (1 << CAP_NET_ADMIN) | (1 << CAP_BPF);
+        assert_eq!(privilege_check(Some(all)).status(), Status::Ok);
+
+        let check = privilege_check(Some(1 << CAP_NET_RAW));
+        assert_eq!(check.status(), Status::Warn);
+        assert!(check.detail().contains("CAP_NET_ADMIN"));
+
+        assert_eq!(privilege_check(Some(0)).status(), Status::Fail);
+    }
+
+    #[test]
+    fn memlock_limit_only_matters_without_ipc_lock() {
+        assert_eq!(memlock_check(Some(None), None).status(), Status::Ok);
+        assert_eq!(
+            memlock_check(Some(Some(8 << 20)), Some(true)).status(),
+            Status::Ok
+        );
+        assert_eq!(
+            memlock_check(Some(Some(8 << 20)), Some(false)).status(),
+            Status::Warn
+        );
+    }
+
+    #[test]
+    fn huge_pages_are_read_from_meminfo() {
+        let meminfo = |total, free| {
+            format!(
+                "MemTotal:       16000000 kB\nHugePages_Total:    {}\n\
+                 HugePages_Free:     {}\nHugepagesize:       2048 kB\n",
+                total, free
+            )
+        };
+
+        assert_eq!(
+            huge_pages_check(Some(&meminfo(0, 0))).status(),
+            Status::Warn
+        );
+        assert_eq!(
+            huge_pages_check(Some(&meminfo(8, 0))).status(),
+            Status::Warn
+        );
+
+        let check = huge_pages_check(Some(&meminfo(8, 2)));
+        assert_eq!(check.status(), Status::Ok);
+        assert_eq!(check.detail(), "2 of 8 2048 KiB huge pages free");
+    }
+
+    #[test]
+    fn report_shows_the_worst_status() {
+        let report = Report {
+            checks: vec![
+                Check::new("a", Status::Ok, "fine".into()),
+                Check::new("b", Status::Warn, "hmm".into()),
+            ],
+        };
+
+        assert_eq!(report.status(), Status::Warn);
+        assert!(report.is_ok());
+        assert_eq!(report.to_string(), "[  ok] a: fine\n[warn] b: hmm\n");
+
+        let report = doctor(Some(&"xsk_no_such_dev".parse().unwrap()));
+        assert!(!report.is_ok());
+    }
+}
@@ -0,0 +1,357 @@
+//! A simple, pcap-like interface to an AF_XDP socket, for when
+//! control over frames and rings isn't needed.
+//!
+//! An [`XskDevice`] owns its [`Umem`], socket and queues, and takes
+//! care of keeping the fill ring topped up and reclaiming frames off
+//! the completion ring. Packets are copied into a frame on sending and
+//! read in place on receiving, so no frame ever leaves the device.
+//!
+//! Requires the `easy` feature.
+//!
+//! ```no_run
+//! use xsk_rs::easy::{DeviceConfig, XskDevice};
+//!
+//! let mut dev = XskDevice::open("eth0", 0, DeviceConfig::default())
+//!     .expect("failed to open device");
+//!
+//! dev.send(b"hello").expect("failed to send packet");
+//!
+//! loop {
+//!     if dev.poll(100).unwrap() {
+//!         dev.recv(|pkt| println!("received {} bytes", pkt.len())).unwrap();
+//!     }
+//! }
+//! ```
+
+use std::{convert::TryInto, error::Error, ffi::NulError, fmt, io, num::NonZeroU32};
+
+use crate::{
+    config::{Interface, SocketConfig, UmemConfig},
+    socket::{RxQueue, Socket, SocketCreateError, TxQueue},
+    umem::{frame::FrameDesc, CompQueue, FillQueue, Umem, UmemCreateError},
+    util,
+};
+
+/// The most packets taken off the rx ring per call to
+/// [`XskDevice::recv`].
+const RX_BATCH_SIZE: usize = 64;
+
+/// Configuration for an [`XskDevice`].
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceConfig {
+    /// The config of the device's [`Umem`].
+    pub umem: UmemConfig,
+    /// The config of the device's socket.
+    pub socket: SocketConfig,
+    /// The number of frames in the [`Umem`]. Up to half, capped at
+    /// the size of the fill queue, are used for receiving and the
+    /// rest for sending.
+    pub frame_count: NonZeroU32,
+    /// Whether to back the [`Umem`] with huge pages, see
+    /// [`Umem::new`].
+    pub use_huge_pages: bool,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            umem: UmemConfig::default(),
+            socket: SocketConfig::default(),
+            frame_count: 4096.try_into().unwrap(),
+            use_huge_pages: false,
+        }
+    }
+}
+
+/// Error opening an [`XskDevice`].
+#[derive(Debug)]
+pub enum OpenError {
+    /// The interface name contains a nul byte.
+    Interface(NulError),
+    /// Creating the [`Umem`] failed.
+    Umem(UmemCreateError),
+    /// Creating the socket failed.
+    Socket(SocketCreateError),
+    /// Placing frames on the fill ring failed.
+    Fill(io::Error),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Interface(_) => write!(f, "invalid interface name"),
+            OpenError::Umem(_) => write!(f, "failed to create UMEM"),
+            OpenError::Socket(_) => write!(f, "failed to create socket"),
+            OpenError::Fill(_) => write!(f, "failed to populate fill ring"),
+        }
+    }
+}
+
+impl Error for OpenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            OpenError::Interface(e) => Some(e),
+            OpenError::Umem(e) => Some(e),
+            OpenError::Socket(e) => Some(e),
+            OpenError::Fill(e) => Some(e),
+        }
+    }
+}
+
+/// An AF_XDP socket bound to a single interface queue, along with its
+/// own [`Umem`] and the bookkeeping needed to send and receive raw
+/// packets. See the [module docs](self).
+#[derive(Debug)]
+pub struct XskDevice {
+    umem: Umem,
+    fq: FillQueue,
+    cq: CompQueue,
+    tx_q: TxQueue,
+    rx_q: RxQueue,
+    tx_frames: Vec<FrameDesc>,
+    rx_descs: Vec<FrameDesc>,
+}
+
+impl XskDevice {
+    /// Create a [`Umem`] and bind a socket using it to queue
+    /// `queue_id` of the interface named `if_name`.
+    pub fn open(if_name: &str, queue_id: u32, config: DeviceConfig) -> Result<Self, OpenError> {
+        let if_name: Interface = if_name.parse().map_err(OpenError::Interface)?;
+
+        let (umem, descs) = Umem::new(config.umem, config.frame_count, config.use_huge_pages)
+            .map_err(OpenError::Umem)?;
+
+        // SAFETY: the UMEM was only just created so isn't shared.
+        let (tx_q, rx_q, fq_and_cq) =
+            unsafe { Socket::new(config.socket, &umem, &if_name, queue_id) }
+                .map_err(OpenError::Socket)?;
+
+        let (fq, cq) = fq_and_cq.expect("UMEM isn't shared so has its own fill and comp queue");
+
+        // SAFETY: the frames and queues all belong to the new UMEM,
+        // and none of the frames have been used yet.
+        unsafe { Self::from_parts(umem, fq, cq, tx_q, rx_q, descs) }.map_err(OpenError::Fill)
+    }
+
+    /// Create a device from a bound socket's queues, the [`Umem`]
+    /// they belong to and the frames available for use, placing
+    /// frames for receiving on the fill ring.
+    ///
+    /// # Safety
+    ///
+    /// `descs` must belong to `umem`, as must the queues, and none of
+    /// the frames may be in use elsewhere, either in userspace or by
+    /// the kernel.
+    pub unsafe fn from_parts(
+        umem: Umem,
+        mut fq: FillQueue,
+        cq: CompQueue,
+        tx_q: TxQueue,
+        mut rx_q: RxQueue,
+        mut descs: Vec<FrameDesc>,
+    ) -> io::Result<Self> {
+        let fill_count = util::min_usize(
+            descs.len() / 2,
+            umem.config().fill_queue_size().get() as usize,
+        );
+
+        let fill_descs: Vec<FrameDesc> = descs.drain(..fill_count).collect();
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // frames belong to `umem` and are free for use.
+        let cnt = unsafe { fq.produce_and_wakeup(&fill_descs, rx_q.fd_mut(), 0)? };
+
+        debug_assert_eq!(cnt, fill_descs.len());
+
+        Ok(Self {
+            umem,
+            fq,
+            cq,
+            tx_q,
+            rx_q,
+            tx_frames: descs,
+            rx_descs: vec![FrameDesc::default(); RX_BATCH_SIZE],
+        })
+    }
+
+    /// Copy `pkt` into a free frame and submit it for transmitting.
+    ///
+    /// Fails with [`io::ErrorKind::WouldBlock`] if there are no frames
+    /// free for sending or the tx ring is full, in which case the
+    /// packet can be sent again once the kernel has caught up, and
+    /// with [`io::ErrorKind::InvalidInput`] if `pkt` doesn't fit in a
+    /// frame.
+    pub fn send(&mut self, pkt: &[u8]) -> io::Result<()> {
+        if self.tx_frames.is_empty() {
+            self.reclaim();
+        }
+
+        let mut desc = self.tx_frames.pop().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::WouldBlock, "no frames free for sending")
+        })?;
+
+        // SAFETY: the frame belongs to this device's UMEM and isn't
+        // in use by the kernel.
+        let written = unsafe { self.umem.data_mut(&mut desc) }
+            .cursor()
+            .extend_from_slice(pkt);
+
+        if written.is_err() {
+            self.tx_frames.push(desc);
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet larger than a frame",
+            ));
+        }
+
+        // SAFETY: as above, and the frame isn't touched again until
+        // it comes back off the completion ring.
+        match unsafe { self.tx_q.produce_and_wakeup(&[desc]) } {
+            Ok(1) => Ok(()),
+            Ok(_) => {
+                self.tx_frames.push(Self::cleared(desc));
+
+                Err(io::Error::new(io::ErrorKind::WouldBlock, "tx ring full"))
+            }
+            // The frame is on the ring, only the wakeup failed.
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Call `f` with each packet waiting on the rx ring, up to a
+    /// batch of them, then hand their frames back to the fill ring.
+    /// Returns the number of packets received, without blocking if
+    /// there are none. See [`poll`](Self::poll) to wait for packets.
+    pub fn recv<F>(&mut self, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(&[u8]),
+    {
+        // SAFETY: all frames on the fill ring belong to this device's
+        // UMEM, and are only read from before being handed back.
+        let cnt = unsafe { self.rx_q.consume(&mut self.rx_descs) };
+
+        for desc in self.rx_descs[..cnt].iter() {
+            f(unsafe { self.umem.data(desc) }.contents());
+        }
+
+        if cnt > 0 {
+            // Can't come up short since the frames were just taken off
+            // the rx ring, so there's room for them on the fill ring.
+            let filled = unsafe {
+                self.fq
+                    .produce_and_wakeup(&self.rx_descs[..cnt], self.rx_q.fd_mut(), 0)?
+            };
+
+            debug_assert_eq!(filled, cnt);
+        }
+
+        Ok(cnt)
+    }
+
+    /// Wait up to `timeout_ms` milliseconds for packets to arrive,
+    /// returning whether there are any to [`recv`](Self::recv).
+    #[inline]
+    pub fn poll(&mut self, timeout_ms: i32) -> io::Result<bool> {
+        self.rx_q.poll(timeout_ms)
+    }
+
+    /// Take frames the kernel has finished sending off the completion
+    /// ring, making them free for sending again. Returns the number
+    /// reclaimed. Called by [`send`](Self::send) whenever it runs out
+    /// of frames, so is only needed to free frames sooner.
+    pub fn reclaim(&mut self) -> usize {
+        // SAFETY: the comp queue belongs to this device's UMEM.
+        unsafe { self.cq.consume_into(&mut self.tx_frames, usize::MAX) }
+    }
+
+    /// The number of frames free for sending, not counting any
+    /// waiting to be [reclaimed](Self::reclaim).
+    #[inline]
+    pub fn free_tx_frames(&self) -> usize {
+        self.tx_frames.len()
+    }
+
+    /// The device's [`Umem`].
+    #[inline]
+    pub fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    fn cleared(mut desc: FrameDesc) -> FrameDesc {
+        desc.lengths = Default::default();
+        desc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{SimConfig, SimXsk};
+
+    use super::*;
+
+    fn build(frame_count: u32) -> (XskDevice, crate::sim::SimKernel) {
+        let xsk = SimXsk::with_sim_config(
+            frame_count,
+            SimConfig {
+                loopback: true,
+                ..SimConfig::default()
+            },
+        );
+
+        let dev = unsafe {
+            XskDevice::from_parts(xsk.umem, xsk.fq, xsk.cq, xsk.tx_q, xsk.rx_q, xsk.descs)
+        }
+        .unwrap();
+
+        (dev, xsk.kernel)
+    }
+
+    #[test]
+    fn sent_packets_are_received_back_on_loopback() {
+        let (mut dev, mut kernel) = build(16);
+
+        dev.send(b"hello").unwrap();
+        dev.send(b"world").unwrap();
+
+        kernel.run_until_idle(64);
+
+        let mut pkts = vec![];
+
+        assert_eq!(dev.recv(|pkt| pkts.push(pkt.to_vec())).unwrap(), 2);
+        assert_eq!(pkts, [b"hello".to_vec(), b"world".to_vec()]);
+
+        assert_eq!(dev.recv(|_| panic!("no more packets")).unwrap(), 0);
+    }
+
+    #[test]
+    fn send_reclaims_frames_once_they_run_out() {
+        let (mut dev, mut kernel) = build(8);
+
+        assert_eq!(dev.free_tx_frames(), 4);
+
+        for _ in 0..4 {
+            dev.send(b"ping").unwrap();
+        }
+
+        let err = dev.send(b"ping").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        kernel.run_until_idle(64);
+        dev.recv(|_| ()).unwrap();
+
+        dev.send(b"ping").unwrap();
+        assert_eq!(dev.free_tx_frames(), 3);
+    }
+
+    #[test]
+    fn oversized_packets_are_rejected() {
+        let (mut dev, _kernel) = build(8);
+
+        let pkt = vec![0; dev.umem().config().frame_size().get() as usize];
+
+        let err = dev.send(&pkt).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(dev.free_tx_frames(), 4);
+    }
+}
@@ -0,0 +1,730 @@
+//! Spreading transmitted frames across several sockets, for example
+//! ones bound to different interfaces, in the manner of a bonded
+//! interface, or sending on one and failing over to another.
+
+use std::{collections::HashMap, io, num::NonZeroU32, time::Duration};
+
+use crate::{
+    clock::{Clock, MonotonicClock},
+    socket::{LinkStatus, LinkWatcher, TxQueue},
+    umem::{
+        frame::FrameDesc,
+        pool::{AllocOrder, Lifo},
+        CompQueue, FrameAllocator,
+    },
+};
+
+/// How a [`TxFanout`] picks the path each frame is sent on.
+#[derive(Debug, Clone, Copy)]
+pub enum FanoutPolicy {
+    /// Take turns, skipping any path whose tx ring is full.
+    RoundRobin,
+    /// Take turns in proportion to each path's weight, see
+    /// [`TxFanout::set_weight`], skipping any path whose tx ring is
+    /// full.
+    Weighted,
+    /// Send each frame on the path picked by hashing its packet data
+    /// with the given function, so packets of the same flow always
+    /// take the same path. Sending stops at the first frame whose
+    /// path is full.
+    Hash(fn(&[u8]) -> u64),
+}
+
+/// Picks the path for each frame, given how much room each has left.
+#[derive(Debug)]
+struct Scheduler {
+    policy: FanoutPolicy,
+    next: usize,
+    weights: Vec<i64>,
+    current: Vec<i64>,
+}
+
+impl Scheduler {
+    fn new(policy: FanoutPolicy) -> Self {
+        Self {
+            policy,
+            next: 0,
+            weights: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    fn add_path(&mut self) {
+        self.weights.push(1);
+        self.current.push(0);
+    }
+
+    fn pick(&mut self, room: &[usize], hash: Option<u64>) -> Option<usize> {
+        let n = room.len();
+
+        if n == 0 {
+            return None;
+        }
+
+        match self.policy {
+            FanoutPolicy::RoundRobin => {
+                let path = (0..n)
+                    .map(|i| (self.next + i) % n)
+                    .find(|&path| room[path] > 0)?;
+
+                self.next = (path + 1) % n;
+
+                Some(path)
+            }
+            FanoutPolicy::Weighted => {
+                // Smooth weighted round robin, as used by nginx, which
+                // interleaves paths rather than sending runs of frames
+                // down each in turn.
+                let mut total = 0;
+                let mut best: Option<usize> = None;
+
+                for path in (0..n).filter(|&path| room[path] > 0) {
+                    self.current[path] += self.weights[path];
+                    total += self.weights[path];
+
+                    match best {
+                        Some(best) if self.current[best] >= self.current[path] => (),
+                        _ => best = Some(path),
+                    }
+                }
+
+                let path = best?;
+                self.current[path] -= total;
+
+                Some(path)
+            }
+            FanoutPolicy::Hash(_) => {
+                let path = (hash.unwrap_or(0) % n as u64) as usize;
+
+                if room[path] > 0 {
+                    Some(path)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// One of the sockets a [`TxFanout`] sends on.
+#[derive(Debug)]
+struct Path {
+    tx_q: TxQueue,
+    cq: CompQueue,
+    sent: u64,
+}
+
+/// Distributes frames to be sent across several sockets, or paths,
+/// according to a [`FanoutPolicy`], for balancing egress across
+/// interfaces or queues.
+///
+/// Every path's socket must use the same [`Umem`](crate::Umem) memory
+/// as the [`FrameAllocator`] the fanout was created with. Frames are
+/// taken from the allocator, written and passed to
+/// [`send`](Self::send), and [`reclaim`](Self::reclaim) returns them
+/// to it once sent, whichever path they were sent on.
+#[derive(Debug)]
+pub struct TxFanout<O = Lifo> {
+    paths: Vec<Path>,
+    alloc: FrameAllocator<O>,
+    sched: Scheduler,
+    room: Vec<usize>,
+    buckets: Vec<Vec<FrameDesc>>,
+}
+
+impl<O: AllocOrder> TxFanout<O> {
+    /// Create a new `TxFanout` with no paths, whose frames come from
+    /// `alloc`.
+    pub fn new(alloc: FrameAllocator<O>, policy: FanoutPolicy) -> Self {
+        Self {
+            paths: Vec::new(),
+            alloc,
+            sched: Scheduler::new(policy),
+            room: Vec::new(),
+            buckets: Vec::new(),
+        }
+    }
+
+    /// Add a path which sends on `tx_q`, with frames coming back on
+    /// `cq`, and a weight of one. Returns its index.
+    ///
+    /// # Panics
+    ///
+    /// If either queue belongs to a [`Umem`](crate::Umem) not sharing
+    /// the allocator's memory.
+    pub fn add_path(&mut self, tx_q: TxQueue, cq: CompQueue) -> usize {
+        assert!(
+            tx_q.umem().shares_mem(self.alloc.umem()) && cq.umem().shares_mem(self.alloc.umem()),
+            "queues belong to a different UMEM"
+        );
+
+        self.paths.push(Path { tx_q, cq, sent: 0 });
+        self.sched.add_path();
+        self.room.push(0);
+        self.buckets.push(Vec::new());
+
+        self.paths.len() - 1
+    }
+
+    /// Set the weight of `path`, used by [`FanoutPolicy::Weighted`].
+    ///
+    /// # Panics
+    ///
+    /// If there's no path with index `path`.
+    pub fn set_weight(&mut self, path: usize, weight: NonZeroU32) {
+        assert!(path < self.paths.len(), "no such path");
+
+        self.sched.weights[path] = weight.get() as i64;
+    }
+
+    /// The allocator frames are taken from and returned to.
+    #[inline]
+    pub fn alloc(&self) -> &FrameAllocator<O> {
+        &self.alloc
+    }
+
+    /// The number of paths.
+    #[inline]
+    pub fn path_count(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// The number of frames sent on `path` so far.
+    ///
+    /// # Panics
+    ///
+    /// If there's no path with index `path`.
+    #[inline]
+    pub fn sent(&self, path: usize) -> u64 {
+        self.paths[path].sent
+    }
+
+    /// Send frames from the front of `descs`, each on the path picked
+    /// by the policy, waking up the kernel for any path which needs
+    /// it. Returns the number sent, which stops short of `descs.len()`
+    /// once a frame's path, or every path, is full. The rest can be
+    /// passed in a later call.
+    ///
+    /// # Safety
+    ///
+    /// The frames must have been taken from this fanout's allocator,
+    /// and must not be used again until returned to it by
+    /// [`reclaim`](Self::reclaim). See also
+    /// [`TxQueue::produce`].
+    pub unsafe fn send(&mut self, descs: &[FrameDesc]) -> io::Result<usize> {
+        for (path, room) in self.paths.iter_mut().zip(self.room.iter_mut()) {
+            *room = path.tx_q.nb_free(descs.len());
+        }
+
+        let mut cnt = 0;
+
+        for desc in descs {
+            let hash = match self.sched.policy {
+                // SAFETY: the frame belongs to the allocator's UMEM,
+                // and is ours until sent.
+                FanoutPolicy::Hash(f) => {
+                    Some(f(unsafe { self.alloc.umem().data(desc) }.contents()))
+                }
+                _ => None,
+            };
+
+            let path = match self.sched.pick(&self.room, hash) {
+                Some(path) => path,
+                None => break,
+            };
+
+            self.room[path] -= 1;
+            self.buckets[path].push(*desc);
+
+            cnt += 1;
+        }
+
+        for (path, bucket) in self.paths.iter_mut().zip(self.buckets.iter_mut()) {
+            if bucket.is_empty() {
+                continue;
+            }
+
+            // SAFETY: the unsafe contract of this function. There's
+            // room for every frame, since we checked beforehand and
+            // hold the only reference to the queue.
+            let produced = unsafe { path.tx_q.produce(bucket) };
+
+            debug_assert_eq!(produced, bucket.len());
+
+            path.sent += produced as u64;
+            bucket.clear();
+
+            if path.tx_q.needs_wakeup() {
+                path.tx_q.wakeup()?;
+            }
+        }
+
+        Ok(cnt)
+    }
+
+    /// Return the frames each path has finished sending to the
+    /// allocator. Returns the number reclaimed.
+    pub fn reclaim(&mut self) -> usize {
+        let alloc = &self.alloc;
+
+        self.paths
+            .iter_mut()
+            .map(|path| alloc.reclaim(&mut path.cq))
+            .sum()
+    }
+}
+
+/// One of the two sockets of an [`ActiveStandby`].
+#[derive(Debug)]
+struct FailoverPath {
+    tx_q: TxQueue,
+    cq: CompQueue,
+    watcher: LinkWatcher,
+    // Frames sent and not yet completed, by address.
+    in_flight: HashMap<usize, FrameDesc>,
+    failed_at: Option<Duration>,
+    last_progress: Duration,
+}
+
+impl FailoverPath {
+    fn new(tx_q: TxQueue, cq: CompQueue, now: Duration) -> Self {
+        let watcher = LinkWatcher::new(tx_q.socket().if_name());
+
+        Self {
+            tx_q,
+            cq,
+            watcher,
+            in_flight: HashMap::new(),
+            failed_at: None,
+            last_progress: now,
+        }
+    }
+
+    /// The link status, or [`None`] if it couldn't be queried.
+    fn link_status(&self) -> Option<LinkStatus> {
+        if self.tx_q.is_paused() {
+            return Some(LinkStatus::Down);
+        }
+
+        self.watcher.status().ok()
+    }
+
+    /// Whether frames have been in flight for `timeout` without any
+    /// completing.
+    fn is_stalled(&self, now: Duration, timeout: Duration) -> bool {
+        !self.in_flight.is_empty() && now.saturating_sub(self.last_progress) >= timeout
+    }
+}
+
+/// Sends frames on one of two sockets, typically bound to different
+/// interfaces, failing over to the other if the active one's link
+/// goes down or its sends stop completing.
+///
+/// Both sockets must use the same [`Umem`](crate::Umem) memory as the
+/// [`FrameAllocator`] the pair was created with. Frames are taken from
+/// the allocator, written and passed to [`send`](Self::send), and
+/// [`reclaim`](Self::reclaim) returns them to it once sent.
+///
+/// Frames still in flight on a path when it fails are returned once
+/// they complete, or once the pair's timeout has passed since the
+/// failure, whichever comes first. Completions for them arriving
+/// after that are ignored.
+#[derive(Debug)]
+pub struct ActiveStandby<O = Lifo, C = MonotonicClock> {
+    paths: [FailoverPath; 2],
+    active: usize,
+    alloc: FrameAllocator<O>,
+    timeout: Duration,
+    failovers: u64,
+    clock: C,
+}
+
+impl<O: AllocOrder> ActiveStandby<O> {
+    /// Create a new `ActiveStandby` which sends on `primary` until it
+    /// fails over to `standby`, each given as a tx queue and the
+    /// completion queue its frames come back on.
+    ///
+    /// `timeout` is both how long frames may go without completing
+    /// before the active path is considered stalled, and how long
+    /// after a path fails its in-flight frames are returned to the
+    /// allocator regardless.
+    ///
+    /// # Safety
+    ///
+    /// `timeout` must be long enough that the kernel has finished with
+    /// any frame still in flight on a failed path, since the frame may
+    /// be reused once it's passed.
+    ///
+    /// # Panics
+    ///
+    /// If any queue belongs to a [`Umem`](crate::Umem) not sharing the
+    /// allocator's memory.
+    pub unsafe fn new(
+        alloc: FrameAllocator<O>,
+        primary: (TxQueue, CompQueue),
+        standby: (TxQueue, CompQueue),
+        timeout: Duration,
+    ) -> Self {
+        // SAFETY: the unsafe contract of this function.
+        unsafe { Self::with_clock(alloc, primary, standby, timeout, MonotonicClock) }
+    }
+}
+
+impl<O: AllocOrder, C: Clock> ActiveStandby<O, C> {
+    /// Same as [`new`](ActiveStandby::new), but reading the time from
+    /// `clock`.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](ActiveStandby::new).
+    pub unsafe fn with_clock(
+        alloc: FrameAllocator<O>,
+        primary: (TxQueue, CompQueue),
+        standby: (TxQueue, CompQueue),
+        timeout: Duration,
+        clock: C,
+    ) -> Self {
+        for (tx_q, cq) in [&primary, &standby] {
+            assert!(
+                tx_q.umem().shares_mem(alloc.umem()) && cq.umem().shares_mem(alloc.umem()),
+                "queues belong to a different UMEM"
+            );
+        }
+
+        let now = clock.now();
+
+        Self {
+            paths: [
+                FailoverPath::new(primary.0, primary.1, now),
+                FailoverPath::new(standby.0, standby.1, now),
+            ],
+            active: 0,
+            alloc,
+            timeout,
+            failovers: 0,
+            clock,
+        }
+    }
+
+    /// The allocator frames are taken from and returned to.
+    #[inline]
+    pub fn alloc(&self) -> &FrameAllocator<O> {
+        &self.alloc
+    }
+
+    /// The index of the path being sent on, `0` for the primary and
+    /// `1` for the standby.
+    #[inline]
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    /// The number of times the pair has failed over.
+    #[inline]
+    pub fn failovers(&self) -> u64 {
+        self.failovers
+    }
+
+    /// The number of frames sent on `path` and not yet returned to the
+    /// allocator.
+    ///
+    /// # Panics
+    ///
+    /// If `path` is neither `0` nor `1`.
+    #[inline]
+    pub fn in_flight(&self, path: usize) -> usize {
+        self.paths[path].in_flight.len()
+    }
+
+    /// Switch to sending on the other path, marking the current one as
+    /// failed.
+    pub fn fail_over(&mut self) {
+        let now = self.clock.now();
+
+        self.paths[self.active].failed_at = Some(now);
+
+        self.active = 1 - self.active;
+
+        let path = &mut self.paths[self.active];
+        path.failed_at = None;
+        path.last_progress = now;
+
+        self.failovers += 1;
+    }
+
+    /// Fail over if the active path's link is down, or it has stalled,
+    /// and the other path's link is up. Returns whether it failed
+    /// over.
+    ///
+    /// Called by [`send`](Self::send), so only needed to fail over
+    /// while idle.
+    pub fn check(&mut self) -> bool {
+        let active = &self.paths[self.active];
+
+        let unhealthy = active.link_status() == Some(LinkStatus::Down)
+            || active.is_stalled(self.clock.now(), self.timeout);
+
+        if unhealthy && self.paths[1 - self.active].link_status() == Some(LinkStatus::Up) {
+            self.fail_over();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check the active path, see [`check`](Self::check), then send
+    /// frames from the front of `descs` on it, waking up the kernel if
+    /// it needs it. Returns the number sent, which stops short of
+    /// `descs.len()` once its tx ring is full. The rest can be passed
+    /// in a later call.
+    ///
+    /// # Safety
+    ///
+    /// The frames must have been taken from this pair's allocator, and
+    /// must not be used again until returned to it by
+    /// [`reclaim`](Self::reclaim). See also [`TxQueue::produce`].
+    pub unsafe fn send(&mut self, descs: &[FrameDesc]) -> io::Result<usize> {
+        self.check();
+
+        let path = &mut self.paths[self.active];
+
+        // SAFETY: the unsafe contract of this function.
+        let cnt = unsafe { path.tx_q.produce_partial(descs) };
+
+        if cnt > 0 {
+            if path.in_flight.is_empty() {
+                path.last_progress = self.clock.now();
+            }
+
+            path.in_flight
+                .extend(descs[..cnt].iter().map(|desc| (desc.addr, *desc)));
+        }
+
+        if path.tx_q.needs_wakeup() {
+            path.tx_q.wakeup()?;
+        }
+
+        Ok(cnt)
+    }
+
+    /// Return the frames either path has finished sending to the
+    /// allocator, along with any still in flight on a failed path once
+    /// the timeout has passed since it failed. Returns the number
+    /// reclaimed.
+    pub fn reclaim(&mut self) -> usize {
+        let now = self.clock.now();
+
+        let mut buf = [FrameDesc::default(); 64];
+        let mut done = Vec::new();
+
+        for (idx, path) in self.paths.iter_mut().enumerate() {
+            loop {
+                // SAFETY: `cq` belongs to the allocator's UMEM.
+                let cnt = unsafe { path.cq.consume(&mut buf) };
+
+                if cnt == 0 {
+                    break;
+                }
+
+                // Frames already returned after a timeout aren't in
+                // flight, so can't be returned twice.
+                done.extend(
+                    buf[..cnt]
+                        .iter()
+                        .filter_map(|desc| path.in_flight.remove(&desc.addr)),
+                );
+
+                path.last_progress = now;
+            }
+
+            match path.failed_at {
+                Some(failed_at)
+                    if idx != self.active && now.saturating_sub(failed_at) >= self.timeout =>
+                {
+                    done.extend(path.in_flight.drain().map(|(_, desc)| desc));
+                }
+                _ => (),
+            }
+        }
+
+        // SAFETY: the frames were taken from the allocator and have
+        // either completed or been in flight on a failed path for
+        // longer than the timeout, see `new`.
+        unsafe { self.alloc.free(&done) };
+
+        done.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        clock::MockClock,
+        config::SocketConfig,
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    fn sim_pair() -> (SimXsk, SimXsk) {
+        let primary = SimXsk::with_defaults(8);
+
+        let standby = primary
+            .new_shared(SocketConfig::default(), SimConfig::default())
+            .unwrap();
+
+        (primary, standby)
+    }
+
+    fn picks(sched: &mut Scheduler, room: &mut [usize], n: usize) -> Vec<usize> {
+        (0..n)
+            .map_while(|_| {
+                let path = sched.pick(room, None)?;
+                room[path] -= 1;
+                Some(path)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_skips_full_paths() {
+        let mut sched = Scheduler::new(FanoutPolicy::RoundRobin);
+
+        for _ in 0..3 {
+            sched.add_path();
+        }
+
+        assert_eq!(picks(&mut sched, &mut [2, 0, 4], 8), vec![0, 2, 0, 2, 2, 2]);
+    }
+
+    #[test]
+    fn weighted_paths_are_interleaved_in_proportion() {
+        let mut sched = Scheduler::new(FanoutPolicy::Weighted);
+
+        sched.add_path();
+        sched.add_path();
+        sched.weights[0] = 3;
+
+        assert_eq!(
+            picks(&mut sched, &mut [8, 8], 8),
+            vec![0, 0, 1, 0, 0, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn hashed_frames_stick_to_their_path() {
+        let mut sched = Scheduler::new(FanoutPolicy::Hash(|_| 0));
+
+        sched.add_path();
+        sched.add_path();
+
+        assert_eq!(sched.pick(&[1, 1], Some(3)), Some(1));
+        assert_eq!(sched.pick(&[1, 0], Some(3)), None);
+    }
+
+    #[test]
+    fn sent_frames_are_reclaimed_into_the_pool() {
+        let SimXsk {
+            umem,
+            descs,
+            tx_q,
+            cq,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(8);
+
+        let alloc = unsafe { FrameAllocator::new(&umem, descs) };
+
+        let mut fanout = TxFanout::new(alloc, FanoutPolicy::RoundRobin);
+        assert_eq!(fanout.add_path(tx_q, cq), 0);
+
+        let mut frames = vec![FrameDesc::default(); 4];
+        assert_eq!(fanout.alloc().alloc_into(&mut frames), 4);
+
+        assert_eq!(unsafe { fanout.send(&frames) }.unwrap(), 4);
+        assert_eq!(fanout.sent(0), 4);
+
+        assert!(kernel.run_until_idle(64));
+
+        assert_eq!(fanout.reclaim(), 4);
+        assert_eq!(fanout.alloc().available(), 8);
+    }
+
+    #[test]
+    fn in_flight_frames_complete_after_fail_over() {
+        let (primary, standby) = sim_pair();
+        let mut kernel = primary.kernel;
+
+        let alloc = unsafe { FrameAllocator::new(&primary.umem, primary.descs) };
+
+        let mut pair = unsafe {
+            ActiveStandby::new(
+                alloc,
+                (primary.tx_q, primary.cq),
+                (standby.tx_q, standby.cq),
+                Duration::from_secs(3600),
+            )
+        };
+
+        let mut frames = vec![FrameDesc::default(); 4];
+        assert_eq!(pair.alloc().alloc_into(&mut frames), 4);
+
+        assert_eq!(unsafe { pair.send(&frames) }.unwrap(), 4);
+        assert_eq!(pair.in_flight(0), 4);
+
+        pair.fail_over();
+        assert_eq!(pair.active(), 1);
+        assert_eq!(pair.failovers(), 1);
+
+        assert_eq!(pair.reclaim(), 0);
+
+        assert!(kernel.run_until_idle(64));
+
+        assert_eq!(pair.reclaim(), 4);
+        assert_eq!(pair.in_flight(0), 0);
+        assert_eq!(pair.alloc().available(), 8);
+    }
+
+    #[test]
+    fn timed_out_frames_are_reclaimed_once() {
+        let (primary, standby) = sim_pair();
+        let mut kernel = primary.kernel;
+
+        let alloc = unsafe { FrameAllocator::new(&primary.umem, primary.descs) };
+
+        let clock = MockClock::new();
+
+        let mut pair = unsafe {
+            ActiveStandby::with_clock(
+                alloc,
+                (primary.tx_q, primary.cq),
+                (standby.tx_q, standby.cq),
+                Duration::from_secs(1),
+                clock.clone(),
+            )
+        };
+
+        let mut frames = vec![FrameDesc::default(); 2];
+        assert_eq!(pair.alloc().alloc_into(&mut frames), 2);
+
+        assert_eq!(unsafe { pair.send(&frames) }.unwrap(), 2);
+
+        pair.fail_over();
+
+        assert_eq!(pair.reclaim(), 0);
+
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(pair.reclaim(), 2);
+        assert_eq!(pair.alloc().available(), 8);
+
+        // The late completions are ignored rather than freeing the
+        // frames a second time.
+        assert!(kernel.run_until_idle(64));
+
+        assert_eq!(pair.reclaim(), 0);
+        assert_eq!(pair.alloc().available(), 8);
+    }
+}
@@ -0,0 +1,350 @@
+//! A [`Stream`] and [`Sink`] interface to an AF_XDP socket, using the
+//! [`Decoder`] and [`Encoder`] traits from `tokio-util` to convert
+//! packets to and from frames.
+//!
+//! Requires the `framed` feature.
+
+use bytes::BytesMut;
+use futures_core::{ready, Stream};
+use futures_sink::Sink;
+use std::{
+    io::{self, Write},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::io::unix::AsyncFd;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    socket::{RxQueue, SocketFd, TxQueue},
+    umem::{frame::FrameDesc, CompQueue, FillQueue, Umem},
+    util,
+};
+
+/// A unified [`Stream`] and [`Sink`] interface to an AF_XDP socket,
+/// analogous to `tokio-util`'s `UdpFramed`.
+///
+/// Each packet received is decoded on its own with
+/// [`Decoder::decode_eof`], so at most one item is produced per
+/// packet. Each item sent is encoded into a single frame and
+/// transmitted as one packet.
+///
+/// Frames are managed internally. Received frames are copied out and
+/// handed straight back to the [`FillQueue`], and frames used for
+/// transmitting are reclaimed from the [`CompQueue`] once the kernel
+/// is done with them.
+#[derive(Debug)]
+pub struct XskFramed<C> {
+    // The socket's registration with the reactor, shared with the
+    // queues' async methods.
+    fd: Arc<AsyncFd<SocketFd>>,
+    umem: Umem,
+    fq: FillQueue,
+    cq: CompQueue,
+    tx_q: TxQueue,
+    rx_q: RxQueue,
+    codec: C,
+    tx_frames: Vec<FrameDesc>,
+    rx_desc: FrameDesc,
+    rd: BytesMut,
+    wr: BytesMut,
+    is_readable: bool,
+    flushed: bool,
+}
+
+impl<C> XskFramed<C> {
+    /// Create a new `XskFramed` from a bound socket's queues, the
+    /// [`Umem`] they belong to and the frames available for use.
+    ///
+    /// Up to half of `descs`, capped at the size of the
+    /// [`FillQueue`], are placed on the fill ring for receiving
+    /// packets and the remainder are used for transmitting. The
+    /// allocation backing `descs` is kept and used to track frames
+    /// available for transmitting, so no further descriptor storage
+    /// is allocated.
+    ///
+    /// Must be called from within a tokio runtime.
+    ///
+    /// # Safety
+    ///
+    /// `descs` must belong to `umem`, as must the queues, and none of
+    /// the frames may be in use elsewhere, either in userspace or by
+    /// the kernel.
+    pub unsafe fn new(
+        umem: Umem,
+        mut fq: FillQueue,
+        cq: CompQueue,
+        tx_q: TxQueue,
+        mut rx_q: RxQueue,
+        mut descs: Vec<FrameDesc>,
+        codec: C,
+    ) -> io::Result<Self> {
+        let fill_count = util::min_usize(
+            descs.len() / 2,
+            umem.config().fill_queue_size().get() as usize,
+        );
+
+        let fill_descs: Vec<FrameDesc> = descs.drain(..fill_count).collect();
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // frames belong to `umem` and are free for use.
+        let cnt = unsafe { fq.produce_and_wakeup(&fill_descs, rx_q.fd_mut(), 0)? };
+
+        debug_assert_eq!(cnt, fill_descs.len());
+
+        let fd = rx_q.socket().async_fd()?;
+
+        Ok(Self {
+            fd,
+            umem,
+            fq,
+            cq,
+            tx_q,
+            rx_q,
+            codec,
+            tx_frames: descs,
+            rx_desc: FrameDesc::default(),
+            rd: BytesMut::new(),
+            wr: BytesMut::new(),
+            is_readable: false,
+            flushed: true,
+        })
+    }
+
+    /// A reference to the codec.
+    #[inline]
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// A mutable reference to the codec.
+    #[inline]
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    /// The [`Umem`] backing the socket.
+    #[inline]
+    pub fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    /// Take a packet off the rx ring, copy it into the read buffer and
+    /// return its frame to the fill ring. Returns `false` if there
+    /// was nothing to receive.
+    fn recv_one(&mut self) -> io::Result<bool> {
+        // SAFETY: all frames passed to the fill ring belong to this
+        // `Umem`, and the frame is only read from before being handed
+        // back.
+        unsafe {
+            if self.rx_q.consume_one(&mut self.rx_desc) == 0 {
+                return Ok(false);
+            }
+
+            self.rd
+                .extend_from_slice(self.umem.data(&self.rx_desc).contents());
+
+            // Can't fail since the frame was just taken off the rx
+            // ring, so there's room for it on the fill ring.
+            self.fq
+                .produce_one_and_wakeup(&self.rx_desc, self.rx_q.fd_mut(), 0)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Move any frames the kernel has finished transmitting back into
+    /// the pool of frames available for sending.
+    ///
+    /// Completed descriptors are consumed straight into the pool's
+    /// spare capacity, which is never exceeded since the pool was
+    /// created from the caller's descriptor `Vec`. So no allocation
+    /// takes place here.
+    fn reclaim_tx_frames(&mut self) {
+        let len = self.tx_frames.len();
+
+        let space = util::min_usize(
+            self.tx_frames.capacity() - len,
+            self.umem.config().comp_queue_size().get() as usize,
+        );
+
+        self.tx_frames.resize(len + space, FrameDesc::default());
+
+        // SAFETY: all frames passed to the tx ring belong to this
+        // `Umem`.
+        let cnt = unsafe { self.cq.consume(&mut self.tx_frames[len..]) };
+
+        self.tx_frames.truncate(len + cnt);
+    }
+}
+
+impl<C: Decoder + Unpin> Stream for XskFramed<C> {
+    type Item = Result<C::Item, C::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.is_readable {
+                let frame = this.codec.decode_eof(&mut this.rd);
+
+                this.rd.clear();
+                this.is_readable = false;
+
+                if let Some(frame) = frame? {
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+            }
+
+            if this.recv_one()? {
+                this.is_readable = true;
+                continue;
+            }
+
+            // Readiness is cleared before trying again so that a
+            // packet arriving in between isn't missed.
+            ready!(this.fd.poll_read_ready(cx))?.clear_ready();
+        }
+    }
+}
+
+impl<I, C: Encoder<I> + Unpin> Sink<I> for XskFramed<C> {
+    type Error = C::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.flushed {
+            Poll::Ready(Ok(()))
+        } else {
+            self.poll_flush(cx)
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+
+        this.codec.encode(item, &mut this.wr)?;
+        this.flushed = false;
+
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+
+        if this.flushed {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.tx_frames.is_empty() {
+            this.reclaim_tx_frames();
+        }
+
+        let mut desc = match this.tx_frames.pop() {
+            Some(desc) => desc,
+            None => {
+                // There is no readiness event for the completion ring
+                // so kick the kernel and try again shortly.
+                this.tx_q.wakeup()?;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        };
+
+        // SAFETY: the frame came from either the original set of
+        // descriptors or the completion ring, so belongs to this
+        // `Umem` and isn't in use by the kernel.
+        let written = unsafe {
+            let mut data = this.umem.data_mut(&mut desc);
+            let mut cursor = data.cursor();
+
+            cursor.set_pos(0);
+            cursor.write_all(&this.wr)
+        };
+
+        if written.is_err() {
+            this.tx_frames.push(desc);
+            this.wr.clear();
+            this.flushed = true;
+
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "encoded item does not fit in a single frame",
+            )
+            .into()));
+        }
+
+        loop {
+            // SAFETY: see above.
+            if unsafe { this.tx_q.produce_one_and_wakeup(&desc)? } == 1 {
+                break;
+            }
+
+            let mut guard = match this.fd.poll_write_ready(cx)? {
+                Poll::Ready(guard) => guard,
+                Poll::Pending => {
+                    this.tx_frames.push(desc);
+                    return Poll::Pending;
+                }
+            };
+
+            guard.clear_ready();
+        }
+
+        this.wr.clear();
+        this.flushed = true;
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use std::time::Duration;
+    use tokio_util::codec::BytesCodec;
+
+    use crate::sim::{SimConfig, SimXsk};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn sent_items_are_received_back_over_loopback() {
+        let SimXsk {
+            umem,
+            descs,
+            fq,
+            cq,
+            tx_q,
+            rx_q,
+            mut kernel,
+        } = SimXsk::with_sim_config(
+            32,
+            SimConfig {
+                loopback: true,
+                ..SimConfig::default()
+            },
+        );
+
+        let mut framed =
+            unsafe { XskFramed::new(umem, fq, cq, tx_q, rx_q, descs, BytesCodec::new()) }.unwrap();
+
+        for i in 0..64u8 {
+            framed.send(bytes::Bytes::from(vec![i; 3])).await.unwrap();
+
+            assert!(kernel.run_until_idle(16));
+
+            let pkt = tokio::time::timeout(Duration::from_secs(1), framed.next())
+                .await
+                .expect("timed out waiting for packet")
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(&pkt[..], &[i; 3]);
+        }
+    }
+}
@@ -43,12 +43,14 @@
 //!
 //! // Bind an AF_XDP socket to the interface named `xsk_dev1`, on
 //! // queue 0.
-//! let (mut dev1_tx_q, _dev1_rx_q, _dev1_fq_and_cq) = Socket::new(
-//!     SocketConfig::default(),
-//!     &dev1_umem,
-//!     &"xsk_dev1".parse().unwrap(),
-//!     0,
-//! )
+//! let (mut dev1_tx_q, _dev1_rx_q, _dev1_fq_and_cq) = unsafe {
+//!     Socket::new(
+//!         SocketConfig::default(),
+//!         &dev1_umem,
+//!         &"xsk_dev1".parse().unwrap(),
+//!         0,
+//!     )
+//! }
 //! .expect("failed to create dev1 socket");
 //!
 //! // Create a UMEM for dev2. Another option is to use the same UMEM
@@ -62,12 +64,14 @@
 //!
 //! // Bind an AF_XDP socket to the interface named `xsk_dev2`, on
 //! // queue 0.
-//! let (_dev2_tx_q, mut dev2_rx_q, dev2_fq_and_cq) = Socket::new(
-//!     SocketConfig::default(),
-//!     &dev2_umem,
-//!     &"xsk_dev2".parse().unwrap(),
-//!     0,
-//! )
+//! let (_dev2_tx_q, mut dev2_rx_q, dev2_fq_and_cq) = unsafe {
+//!     Socket::new(
+//!         SocketConfig::default(),
+//!         &dev2_umem,
+//!         &"xsk_dev2".parse().unwrap(),
+//!         0,
+//!     )
+//! }
 //! .expect("failed to create dev2 socket");
 //!
 //! let (mut dev2_fq, _dev2_cq) = dev2_fq_and_cq.expect("missing dev2 fill queue and comp queue");
@@ -114,21 +118,74 @@
 #![deny(missing_docs)]
 #![deny(missing_debug_implementations)]
 #![deny(unsafe_op_in_unsafe_fn)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(all(target_pointer_width = "64", target_family = "unix"))] {
         pub mod umem;
-        pub use umem::{frame::FrameDesc, CompQueue, FillQueue, Umem};
+        pub use umem::{
+            frame::{Frame, FrameDesc},
+            CompQueue, FillQueue, FrameAllocator, FramePool, Umem, UmemBacking,
+        };
 
         pub mod socket;
         pub use socket::{RxQueue, Socket, TxQueue};
 
+        pub mod fanout;
+        pub use fanout::{ActiveStandby, TxFanout};
+
         pub mod config;
 
+        pub mod caps;
+
+        pub mod doctor;
+        pub use doctor::doctor;
+
+        pub mod net;
+
+        pub mod checksum;
+
+        pub mod pipeline;
+
+        pub mod meta;
+
+        pub mod runtime;
+
+        pub mod stats;
+
+        pub mod clock;
+
+        pub mod ptp;
+
+        pub mod record;
+
+        pub mod probe;
+
+        pub mod xdp;
+        pub use xdp::XdpProgram;
+
+        pub mod systemd;
+
+        #[cfg(feature = "framed")]
+        pub mod framed;
+        #[cfg(feature = "framed")]
+        pub use framed::XskFramed;
+
+        #[cfg(feature = "easy")]
+        pub mod easy;
+
+        pub mod safe;
+
         mod ring;
+        pub use ring::{
+            DropCallback, DropPolicy, NoHooks, ProducePolicy, QueueHooks, RingKind, RingSetupError,
+        };
         mod util;
 
+        #[cfg(any(test, feature = "testing"))]
+        pub mod sim;
+
         #[cfg(test)]
         mod tests {
             use std::mem;
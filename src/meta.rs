@@ -0,0 +1,219 @@
+//! Structured per-frame metadata passed from an XDP program to
+//! userspace.
+//!
+//! An XDP program can reserve space in front of a packet with
+//! `bpf_xdp_adjust_meta`, and AF_XDP hands it to userspace along with
+//! the packet, immediately before the address in the rx descriptor.
+//! The kernel requires its length to be a multiple of [`META_ALIGN`]
+//! bytes and no more than [`META_MAX_LEN`].
+//!
+//! Types implementing [`XdpMetadata`] describe such a layout, and can
+//! be read with [`Frame::metadata`](crate::Frame::metadata) or
+//! [`Umem::metadata`](crate::Umem::metadata). [`RxMetadata`] is a
+//! ready made layout for the rx hints available to XDP programs via
+//! the `bpf_xdp_metadata_rx_*` kfuncs, following the `struct
+//! xdp_meta` used by the kernel's own tests.
+//!
+//! # XDP program side
+//!
+//! The program grows the metadata area by the layout's length,
+//! checks it against the packet start and writes the struct there:
+//!
+//! ```c
+//! struct rx_meta {
+//!     __u64 rx_timestamp;
+//!     __u64 xdp_timestamp;
+//!     __u32 rx_hash;
+//!     __u32 rx_hash_type;
+//!     __u16 rx_vlan_tci;
+//!     __u16 rx_vlan_proto;
+//!     __u32 flags;
+//! };
+//!
+//! if (bpf_xdp_adjust_meta(ctx, -(int)sizeof(struct rx_meta)))
+//!     return XDP_PASS;
+//!
+//! struct rx_meta *meta = (void *)(long)ctx->data_meta;
+//! if ((void *)(meta + 1) > (void *)(long)ctx->data)
+//!     return XDP_PASS;
+//!
+//! meta->flags = 0;
+//! if (!bpf_xdp_metadata_rx_timestamp(ctx, &meta->rx_timestamp))
+//!     meta->flags |= 1; // RxMetadata::RX_TIMESTAMP
+//! ```
+//!
+//! The field offsets are exported as associated constants on
+//! [`RxMetadata`], for generating or checking the C definition.
+
+use std::mem;
+
+use crate::net::VlanTag;
+
+/// The kernel requires the metadata length to be a multiple of this
+/// many bytes.
+pub const META_ALIGN: usize = 4;
+
+/// The maximum metadata length accepted by `bpf_xdp_adjust_meta`.
+pub const META_MAX_LEN: usize = 32;
+
+/// A metadata layout, written by an XDP program in front of the
+/// packet.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]`, valid for any bit pattern, and
+/// have a size which is a multiple of [`META_ALIGN`] and no more than
+/// [`META_MAX_LEN`].
+pub unsafe trait XdpMetadata: Copy {
+    /// The length of the metadata in bytes.
+    const LEN: usize = mem::size_of::<Self>();
+
+    /// The offset of the metadata from the start of the packet data,
+    /// i.e. the delta the XDP program passes to
+    /// `bpf_xdp_adjust_meta`.
+    const OFFSET: i32 = -(mem::size_of::<Self>() as i32);
+}
+
+/// Rx hints gathered by an XDP program, in the layout described in
+/// the [module docs](self).
+///
+/// Which hints are present is given by [`flags`](Self::flags), since
+/// not every driver supports every hint. The accessors return `None`
+/// for those which are missing.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RxMetadata {
+    rx_timestamp: u64,
+    xdp_timestamp: u64,
+    rx_hash: u32,
+    rx_hash_type: u32,
+    rx_vlan_tci: u16,
+    rx_vlan_proto: u16,
+    flags: u32,
+}
+
+// SAFETY: `RxMetadata` is `repr(C)`, made up of integers only and 32
+// bytes long.
+unsafe impl XdpMetadata for RxMetadata {}
+
+impl RxMetadata {
+    /// Offset of the hardware rx timestamp, a `__u64`.
+    pub const RX_TIMESTAMP_OFFSET: usize = 0;
+    /// Offset of the time the XDP program ran, a `__u64`.
+    pub const XDP_TIMESTAMP_OFFSET: usize = 8;
+    /// Offset of the rx hash, a `__u32`.
+    pub const RX_HASH_OFFSET: usize = 16;
+    /// Offset of the rx hash type, a `__u32`.
+    pub const RX_HASH_TYPE_OFFSET: usize = 20;
+    /// Offset of the VLAN tag control information, a `__u16`.
+    pub const RX_VLAN_TCI_OFFSET: usize = 24;
+    /// Offset of the VLAN protocol, a `__u16`.
+    pub const RX_VLAN_PROTO_OFFSET: usize = 26;
+    /// Offset of the flags, a `__u32`.
+    pub const FLAGS_OFFSET: usize = 28;
+
+    /// Flag set if the hardware rx timestamp is present.
+    pub const RX_TIMESTAMP: u32 = 1 << 0;
+    /// Flag set if the XDP timestamp is present.
+    pub const XDP_TIMESTAMP: u32 = 1 << 1;
+    /// Flag set if the rx hash and its type are present.
+    pub const RX_HASH: u32 = 1 << 2;
+    /// Flag set if the VLAN tag is present.
+    pub const RX_VLAN_TAG: u32 = 1 << 3;
+
+    fn get<T>(&self, flag: u32, val: T) -> Option<T> {
+        if self.flags & flag != 0 {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// Which hints are present.
+    #[inline]
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// The hardware rx timestamp in nanoseconds, as returned by
+    /// `bpf_xdp_metadata_rx_timestamp`.
+    #[inline]
+    pub fn rx_timestamp(&self) -> Option<u64> {
+        self.get(Self::RX_TIMESTAMP, self.rx_timestamp)
+    }
+
+    /// The time the XDP program ran, usually from
+    /// `bpf_ktime_get_tai_ns`.
+    #[inline]
+    pub fn xdp_timestamp(&self) -> Option<u64> {
+        self.get(Self::XDP_TIMESTAMP, self.xdp_timestamp)
+    }
+
+    /// The rx hash and its `enum xdp_rss_hash_type`, as returned by
+    /// `bpf_xdp_metadata_rx_hash`.
+    #[inline]
+    pub fn rx_hash(&self) -> Option<(u32, u32)> {
+        self.get(Self::RX_HASH, (self.rx_hash, self.rx_hash_type))
+    }
+
+    /// The VLAN protocol, in network byte order, and tag control
+    /// information, as returned by `bpf_xdp_metadata_rx_vlan_tag`.
+    #[inline]
+    pub fn rx_vlan_tag(&self) -> Option<(u16, u16)> {
+        self.get(Self::RX_VLAN_TAG, (self.rx_vlan_proto, self.rx_vlan_tci))
+    }
+
+    /// The VLAN tag stripped from the packet by the NIC, if any.
+    ///
+    /// Drivers which offload VLAN stripping remove the tag from the
+    /// packet data itself, so this is the only place it can be found.
+    /// It can be put back with
+    /// [`DataMut::push_vlan`](crate::umem::frame::DataMut::push_vlan).
+    #[inline]
+    pub fn vlan(&self) -> Option<VlanTag> {
+        self.rx_vlan_tag()
+            .map(|(proto, tci)| VlanTag::from_parts(u16::from_be(proto), tci))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! offset_of {
+        ($field:ident) => {{
+            let meta = RxMetadata::default();
+            (&meta.$field as *const _ as usize) - (&meta as *const _ as usize)
+        }};
+    }
+
+    #[test]
+    fn rx_metadata_matches_exported_layout() {
+        assert_eq!(RxMetadata::LEN, META_MAX_LEN);
+        assert_eq!(RxMetadata::LEN % META_ALIGN, 0);
+        assert_eq!(RxMetadata::OFFSET, -32);
+
+        assert_eq!(offset_of!(rx_timestamp), RxMetadata::RX_TIMESTAMP_OFFSET);
+        assert_eq!(offset_of!(xdp_timestamp), RxMetadata::XDP_TIMESTAMP_OFFSET);
+        assert_eq!(offset_of!(rx_hash), RxMetadata::RX_HASH_OFFSET);
+        assert_eq!(offset_of!(rx_hash_type), RxMetadata::RX_HASH_TYPE_OFFSET);
+        assert_eq!(offset_of!(rx_vlan_tci), RxMetadata::RX_VLAN_TCI_OFFSET);
+        assert_eq!(offset_of!(rx_vlan_proto), RxMetadata::RX_VLAN_PROTO_OFFSET);
+        assert_eq!(offset_of!(flags), RxMetadata::FLAGS_OFFSET);
+    }
+
+    #[test]
+    fn vlan_tag_is_only_present_if_flagged() {
+        let mut meta = RxMetadata {
+            rx_vlan_proto: VlanTag::TPID_8021Q.to_be(),
+            rx_vlan_tci: 42,
+            ..RxMetadata::default()
+        };
+
+        assert_eq!(meta.vlan(), None);
+
+        meta.flags = RxMetadata::RX_VLAN_TAG;
+
+        assert_eq!(meta.vlan(), Some(VlanTag::new(42, 0)));
+    }
+}
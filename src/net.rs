@@ -0,0 +1,343 @@
+//! Link and network layer address types, for use when building
+//! packets and configuring interfaces, and VLAN tags.
+
+use std::{convert::TryFrom, error, fmt, net::Ipv4Addr, str::FromStr};
+
+/// A MAC address.
+///
+/// Parsed from and displayed as six colon separated pairs of hex
+/// digits, e.g. `f6:e0:f6:c9:60:0a`. Hyphens are also accepted as
+/// separators when parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr([u8; 6]);
+
+impl MacAddr {
+    /// The broadcast address, `ff:ff:ff:ff:ff:ff`.
+    pub const BROADCAST: MacAddr = MacAddr([0xff; 6]);
+
+    /// Create a new `MacAddr` from its octets.
+    pub const fn new(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+
+    /// The address's octets.
+    #[inline]
+    pub fn octets(&self) -> [u8; 6] {
+        self.0
+    }
+}
+
+impl From<[u8; 6]> for MacAddr {
+    fn from(octets: [u8; 6]) -> Self {
+        Self(octets)
+    }
+}
+
+impl From<MacAddr> for [u8; 6] {
+    fn from(addr: MacAddr) -> Self {
+        addr.0
+    }
+}
+
+impl FromStr for MacAddr {
+    type Err = MacAddrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || MacAddrError(s.into());
+
+        let mut octets = [0; 6];
+        let mut parts = s.split(&[':', '-'][..]);
+
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or_else(err)?;
+
+            if part.len() != 2 {
+                return Err(err());
+            }
+
+            *octet = u8::from_str_radix(part, 16).map_err(|_| err())?;
+        }
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self(octets))
+    }
+}
+
+impl TryFrom<&str> for MacAddr {
+    type Error = MacAddrError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+/// Error signifying an invalid [`MacAddr`] string.
+#[derive(Debug)]
+pub struct MacAddrError(String);
+
+impl fmt::Display for MacAddrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid MAC address: {:?}", self.0)
+    }
+}
+
+impl error::Error for MacAddrError {}
+
+/// An IPv4 address along with the prefix length of its network, e.g.
+/// `192.168.69.1/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv4Net {
+    addr: Ipv4Addr,
+    prefix_len: u8,
+}
+
+impl Ipv4Net {
+    /// Create a new `Ipv4Net`. Fails if `prefix_len` is greater than
+    /// 32.
+    pub fn new(addr: Ipv4Addr, prefix_len: u8) -> Result<Self, Ipv4NetError> {
+        if prefix_len > 32 {
+            Err(Ipv4NetError(format!("{}/{}", addr, prefix_len)))
+        } else {
+            Ok(Self { addr, prefix_len })
+        }
+    }
+
+    /// The address.
+    #[inline]
+    pub fn addr(&self) -> Ipv4Addr {
+        self.addr
+    }
+
+    /// The network prefix length.
+    #[inline]
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// The address's octets.
+    #[inline]
+    pub fn octets(&self) -> [u8; 4] {
+        self.addr.octets()
+    }
+
+    /// The network address, i.e. the address with all bits beyond
+    /// the prefix cleared.
+    pub fn network(&self) -> Ipv4Addr {
+        let mask = u32::MAX
+            .checked_shl(32 - self.prefix_len as u32)
+            .unwrap_or(0);
+
+        Ipv4Addr::from(u32::from(self.addr) & mask)
+    }
+}
+
+impl From<Ipv4Net> for [u8; 4] {
+    fn from(net: Ipv4Net) -> Self {
+        net.octets()
+    }
+}
+
+impl FromStr for Ipv4Net {
+    type Err = Ipv4NetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Ipv4NetError(s.into());
+
+        let (addr, prefix_len) = match s.find('/') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => return Err(err()),
+        };
+
+        let addr = addr.parse().map_err(|_| err())?;
+        let prefix_len = prefix_len.parse().map_err(|_| err())?;
+
+        Self::new(addr, prefix_len).map_err(|_| err())
+    }
+}
+
+impl TryFrom<&str> for Ipv4Net {
+    type Error = Ipv4NetError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Ipv4Net {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Error signifying an invalid [`Ipv4Net`].
+#[derive(Debug)]
+pub struct Ipv4NetError(String);
+
+impl fmt::Display for Ipv4NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid IPv4 network: {:?}", self.0)
+    }
+}
+
+impl error::Error for Ipv4NetError {}
+
+/// An 802.1Q VLAN tag, as found between the source address and
+/// ethertype of an Ethernet header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VlanTag {
+    tpid: u16,
+    tci: u16,
+}
+
+impl VlanTag {
+    /// The tag protocol identifier of a customer VLAN tag, 802.1Q.
+    pub const TPID_8021Q: u16 = 0x8100;
+    /// The tag protocol identifier of a service VLAN tag, 802.1ad.
+    pub const TPID_8021AD: u16 = 0x88a8;
+
+    /// The length of a tag in bytes.
+    pub const LEN: usize = 4;
+
+    /// Create a new 802.1Q tag for VLAN `vid` with priority code point
+    /// `pcp`. Only the low 12 bits of `vid` and low 3 bits of `pcp`
+    /// are used.
+    pub fn new(vid: u16, pcp: u8) -> Self {
+        Self::from_parts(Self::TPID_8021Q, (pcp as u16 & 0x7) << 13 | (vid & 0xfff))
+    }
+
+    /// Create a new tag from its protocol identifier and tag control
+    /// information.
+    pub const fn from_parts(tpid: u16, tci: u16) -> Self {
+        Self { tpid, tci }
+    }
+
+    /// Read the tag from its four bytes on the wire, returning
+    /// [`None`] if the protocol identifier isn't that of a VLAN tag.
+    pub fn from_bytes(bytes: [u8; 4]) -> Option<Self> {
+        let tpid = u16::from_be_bytes([bytes[0], bytes[1]]);
+
+        if tpid != Self::TPID_8021Q && tpid != Self::TPID_8021AD {
+            return None;
+        }
+
+        Some(Self::from_parts(
+            tpid,
+            u16::from_be_bytes([bytes[2], bytes[3]]),
+        ))
+    }
+
+    /// The tag's four bytes as they appear on the wire.
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let [p0, p1] = self.tpid.to_be_bytes();
+        let [t0, t1] = self.tci.to_be_bytes();
+
+        [p0, p1, t0, t1]
+    }
+
+    /// The tag protocol identifier.
+    #[inline]
+    pub fn tpid(&self) -> u16 {
+        self.tpid
+    }
+
+    /// The tag control information, made up of the priority code
+    /// point, drop eligible indicator and VLAN id.
+    #[inline]
+    pub fn tci(&self) -> u16 {
+        self.tci
+    }
+
+    /// The VLAN id.
+    #[inline]
+    pub fn vid(&self) -> u16 {
+        self.tci & 0xfff
+    }
+
+    /// The priority code point.
+    #[inline]
+    pub fn pcp(&self) -> u8 {
+        (self.tci >> 13) as u8
+    }
+
+    /// The drop eligible indicator.
+    #[inline]
+    pub fn dei(&self) -> bool {
+        self.tci & 0x1000 != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac_addr_round_trips_through_string() {
+        let addr: MacAddr = "f6:e0:f6:c9:60:0a".parse().unwrap();
+
+        assert_eq!(addr.octets(), [0xf6, 0xe0, 0xf6, 0xc9, 0x60, 0x0a]);
+        assert_eq!(addr.to_string(), "f6:e0:f6:c9:60:0a");
+        assert_eq!(MacAddr::try_from("F6-E0-F6-C9-60-0A").unwrap(), addr);
+
+        for bad in [
+            "",
+            "f6:e0:f6:c9:60",
+            "f6:e0:f6:c9:60:0a:00",
+            "f6:e0:f6:c9:60:g0",
+            "f:e0:f6:c9:60:0a0",
+        ] {
+            assert!(bad.parse::<MacAddr>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn vlan_tag_fields_are_packed_into_the_tci() {
+        let tag = VlanTag::new(100, 5);
+
+        assert_eq!(tag.tpid(), VlanTag::TPID_8021Q);
+        assert_eq!(tag.vid(), 100);
+        assert_eq!(tag.pcp(), 5);
+        assert!(!tag.dei());
+        assert_eq!(tag.to_bytes(), [0x81, 0x00, 0xa0, 0x64]);
+
+        assert_eq!(VlanTag::from_bytes(tag.to_bytes()), Some(tag));
+        assert_eq!(VlanTag::from_bytes([0x08, 0x00, 0xa0, 0x64]), None);
+    }
+
+    #[test]
+    fn ipv4_net_round_trips_through_string() {
+        let net: Ipv4Net = "192.168.69.1/24".parse().unwrap();
+
+        assert_eq!(net.octets(), [192, 168, 69, 1]);
+        assert_eq!(net.prefix_len(), 24);
+        assert_eq!(net.network(), Ipv4Addr::new(192, 168, 69, 0));
+        assert_eq!(net.to_string(), "192.168.69.1/24");
+
+        assert_eq!(
+            Ipv4Net::try_from("10.0.0.1/0").unwrap().network(),
+            Ipv4Addr::UNSPECIFIED
+        );
+
+        for bad in [
+            "192.168.69.1",
+            "192.168.69.1/33",
+            "192.168.69/24",
+            "192.168.69.1/",
+        ] {
+            assert!(bad.parse::<Ipv4Net>().is_err(), "{}", bad);
+        }
+    }
+}
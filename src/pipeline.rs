@@ -0,0 +1,386 @@
+//! Composable, allocation free packet processing over received
+//! frames.
+//!
+//! A pipeline is built up from stages, each of which either passes a
+//! [`Packet`] on to the next or drops it, and finished off with a
+//! closure to handle the packets which make it through:
+//!
+//! ```
+//! use xsk_rs::pipeline::pipeline;
+//!
+//! const ETH_P_IPV4: u16 = 0x0800;
+//!
+//! let mut pipeline = pipeline()
+//!     .parse_eth()
+//!     .filter(|pkt| pkt.eth().map(|eth| eth.ethertype()) == Some(ETH_P_IPV4))
+//!     .for_each(|pkt| println!("{} byte IPv4 packet", pkt.payload().len()));
+//!
+//! let mut frame = [0u8; 64];
+//! frame[12..14].copy_from_slice(&ETH_P_IPV4.to_be_bytes());
+//!
+//! assert!(pipeline.run(&frame));
+//! assert!(!pipeline.run(&frame[..10]));
+//! ```
+//!
+//! Every stage and closure is a type parameter of the pipeline, so
+//! the whole thing is monomorphized into a single loop with no
+//! dynamic dispatch. Packets can be fed in one at a time with
+//! [`run`](Sink::run), a batch of descriptors at a time with
+//! [`run_batch`](Sink::run_batch), or straight off an [`RxQueue`]
+//! with [`run_rx`](Sink::run_rx).
+
+use crate::{
+    net::MacAddr,
+    socket::RxQueue,
+    umem::{frame::FrameDesc, FillQueue, Umem},
+};
+
+const ETH_HDR_LEN: usize = 14;
+const VLAN_HDR_LEN: usize = 4;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88a8;
+
+/// The number of frames ahead to prefetch in
+/// [`run_batch`](Sink::run_batch).
+const PREFETCH_AHEAD: usize = 4;
+
+/// An Ethernet header, with at most one VLAN tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthHeader {
+    dst: MacAddr,
+    src: MacAddr,
+    vlan_tci: Option<u16>,
+    ethertype: u16,
+    len: usize,
+}
+
+impl EthHeader {
+    /// Parse the Ethernet header at the start of `data`, returning
+    /// [`None`] if it's too short.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < ETH_HDR_LEN {
+            return None;
+        }
+
+        let be16 = |off: usize| u16::from_be_bytes([data[off], data[off + 1]]);
+
+        let mut dst = [0; 6];
+        let mut src = [0; 6];
+        dst.copy_from_slice(&data[0..6]);
+        src.copy_from_slice(&data[6..12]);
+
+        let mut hdr = Self {
+            dst: dst.into(),
+            src: src.into(),
+            vlan_tci: None,
+            ethertype: be16(12),
+            len: ETH_HDR_LEN,
+        };
+
+        if hdr.ethertype == ETH_P_8021Q || hdr.ethertype == ETH_P_8021AD {
+            if data.len() < ETH_HDR_LEN + VLAN_HDR_LEN {
+                return None;
+            }
+
+            hdr.vlan_tci = Some(be16(14));
+            hdr.ethertype = be16(16);
+            hdr.len += VLAN_HDR_LEN;
+        }
+
+        Some(hdr)
+    }
+
+    /// The destination address.
+    #[inline]
+    pub fn dst(&self) -> MacAddr {
+        self.dst
+    }
+
+    /// The source address.
+    #[inline]
+    pub fn src(&self) -> MacAddr {
+        self.src
+    }
+
+    /// The tag control information of the VLAN tag, if any.
+    #[inline]
+    pub fn vlan_tci(&self) -> Option<u16> {
+        self.vlan_tci
+    }
+
+    /// The ethertype of the payload, after any VLAN tag.
+    #[inline]
+    pub fn ethertype(&self) -> u16 {
+        self.ethertype
+    }
+
+    /// The length of the header, including any VLAN tag.
+    #[inline]
+    pub fn header_len(&self) -> usize {
+        self.len
+    }
+}
+
+/// A packet passing through a pipeline.
+#[derive(Debug, Clone, Copy)]
+pub struct Packet<'a> {
+    data: &'a [u8],
+    eth: Option<EthHeader>,
+}
+
+impl<'a> Packet<'a> {
+    /// A packet with contents `data`, as yet unparsed.
+    #[inline]
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, eth: None }
+    }
+
+    /// The whole packet.
+    #[inline]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The Ethernet header, if parsed by an earlier
+    /// [`parse_eth`](Pipeline::parse_eth) stage.
+    #[inline]
+    pub fn eth(&self) -> Option<&EthHeader> {
+        self.eth.as_ref()
+    }
+
+    /// The packet following any headers parsed so far.
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        let off = self.eth.map_or(0, |eth| eth.header_len());
+
+        &self.data[off..]
+    }
+}
+
+/// A step in a pipeline, passing a packet on or dropping it.
+pub trait Stage {
+    /// Process `pkt`, returning it, possibly with more parsed, to
+    /// pass it on or [`None`] to drop it.
+    fn apply<'a>(&mut self, pkt: Packet<'a>) -> Option<Packet<'a>>;
+}
+
+/// The empty stage a pipeline starts with, passing every packet on.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Identity;
+
+impl Stage for Identity {
+    #[inline(always)]
+    fn apply<'a>(&mut self, pkt: Packet<'a>) -> Option<Packet<'a>> {
+        Some(pkt)
+    }
+}
+
+/// Runs stage `A` then stage `B`.
+#[derive(Debug, Clone, Copy)]
+pub struct Then<A, B>(A, B);
+
+impl<A: Stage, B: Stage> Stage for Then<A, B> {
+    #[inline(always)]
+    fn apply<'a>(&mut self, pkt: Packet<'a>) -> Option<Packet<'a>> {
+        self.0.apply(pkt).and_then(|pkt| self.1.apply(pkt))
+    }
+}
+
+/// Parses the Ethernet header, dropping packets too short for one.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ParseEth;
+
+impl Stage for ParseEth {
+    #[inline]
+    fn apply<'a>(&mut self, mut pkt: Packet<'a>) -> Option<Packet<'a>> {
+        pkt.eth = Some(EthHeader::parse(pkt.data)?);
+        Some(pkt)
+    }
+}
+
+/// Drops packets for which the closure returns `false`.
+#[derive(Debug, Clone, Copy)]
+pub struct Filter<F>(F);
+
+impl<F> Stage for Filter<F>
+where
+    F: FnMut(&Packet<'_>) -> bool,
+{
+    #[inline]
+    fn apply<'a>(&mut self, pkt: Packet<'a>) -> Option<Packet<'a>> {
+        if (self.0)(&pkt) {
+            Some(pkt)
+        } else {
+            None
+        }
+    }
+}
+
+/// Start building a pipeline.
+#[inline]
+pub fn pipeline() -> Pipeline<Identity> {
+    Pipeline { stage: Identity }
+}
+
+/// A pipeline under construction, see the [module docs](self).
+#[derive(Debug, Clone, Copy)]
+pub struct Pipeline<S> {
+    stage: S,
+}
+
+impl<S: Stage> Pipeline<S> {
+    /// Add a stage.
+    #[inline]
+    pub fn then<T: Stage>(self, stage: T) -> Pipeline<Then<S, T>> {
+        Pipeline {
+            stage: Then(self.stage, stage),
+        }
+    }
+
+    /// Parse the Ethernet header, dropping packets too short for one.
+    #[inline]
+    pub fn parse_eth(self) -> Pipeline<Then<S, ParseEth>> {
+        self.then(ParseEth)
+    }
+
+    /// Drop packets for which `f` returns `false`.
+    #[inline]
+    pub fn filter<F>(self, f: F) -> Pipeline<Then<S, Filter<F>>>
+    where
+        F: FnMut(&Packet<'_>) -> bool,
+    {
+        self.then(Filter(f))
+    }
+
+    /// Finish the pipeline, handing the packets which make it through
+    /// to `f`.
+    #[inline]
+    pub fn for_each<F>(self, f: F) -> Sink<S, F>
+    where
+        F: FnMut(Packet<'_>),
+    {
+        Sink {
+            stage: self.stage,
+            f,
+        }
+    }
+}
+
+/// A finished pipeline, ready to be run over packets.
+#[derive(Debug, Clone, Copy)]
+pub struct Sink<S, F> {
+    stage: S,
+    f: F,
+}
+
+impl<S, F> Sink<S, F>
+where
+    S: Stage,
+    F: FnMut(Packet<'_>),
+{
+    /// Run the packet `data` through the pipeline. Returns whether it
+    /// made it through to the end.
+    #[inline]
+    pub fn run(&mut self, data: &[u8]) -> bool {
+        match self.stage.apply(Packet::new(data)) {
+            Some(pkt) => {
+                (self.f)(pkt);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run each of the received frames in `descs` through the
+    /// pipeline, prefetching a few frames ahead. Returns the number
+    /// which made it through to the end.
+    ///
+    /// # Safety
+    ///
+    /// The frames must belong to `umem`, and must not be written to
+    /// by either userspace or the kernel for the duration of the call,
+    /// see [`Umem::data`].
+    pub unsafe fn run_batch(&mut self, umem: &Umem, descs: &[FrameDesc]) -> usize {
+        umem.prefetch_data(&descs[..descs.len().min(PREFETCH_AHEAD)]);
+
+        let mut passed = 0;
+
+        for (i, desc) in descs.iter().enumerate() {
+            if let Some(ahead) = descs.get(i + PREFETCH_AHEAD) {
+                umem.prefetch_data(std::slice::from_ref(ahead));
+            }
+
+            // SAFETY: the unsafe contract of this function.
+            if self.run(unsafe { umem.data(desc) }.contents()) {
+                passed += 1;
+            }
+        }
+
+        passed
+    }
+
+    /// Receive up to `max` frames from `rx_q`, run each through the
+    /// pipeline and recycle it back on to `fq`. Returns the number
+    /// received. See [`RxQueue::consume_with`].
+    ///
+    /// # Panics
+    ///
+    /// If `fq` belongs to a [`Umem`] not sharing `rx_q`'s memory.
+    pub fn run_rx(&mut self, rx_q: &mut RxQueue, fq: &mut FillQueue, max: usize) -> usize {
+        rx_q.consume_with(fq, max, |data| {
+            self.run(data.contents());
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ethertype: u16, vlan: Option<u16>) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&[2, 0, 0, 0, 0, 1]);
+
+        if let Some(tci) = vlan {
+            frame.extend_from_slice(&ETH_P_8021Q.to_be_bytes());
+            frame.extend_from_slice(&tci.to_be_bytes());
+        }
+
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(b"payload");
+        frame
+    }
+
+    #[test]
+    fn vlan_tags_are_skipped_over() {
+        let data = frame(0x0800, Some(42));
+        let eth = EthHeader::parse(&data).unwrap();
+
+        assert_eq!(eth.dst(), MacAddr::BROADCAST);
+        assert_eq!(eth.src(), MacAddr::new([2, 0, 0, 0, 0, 1]));
+        assert_eq!(eth.vlan_tci(), Some(42));
+        assert_eq!(eth.ethertype(), 0x0800);
+        assert_eq!(eth.header_len(), 18);
+
+        assert_eq!(EthHeader::parse(&data[..16]), None);
+    }
+
+    #[test]
+    fn only_packets_passing_every_stage_reach_the_sink() {
+        let mut payloads = Vec::new();
+
+        {
+            let mut sink = pipeline()
+                .parse_eth()
+                .filter(|pkt| pkt.eth().unwrap().ethertype() == 0x86dd)
+                .for_each(|pkt| payloads.push(pkt.payload().to_vec()));
+
+            assert!(sink.run(&frame(0x86dd, None)));
+            assert!(sink.run(&frame(0x86dd, Some(7))));
+            assert!(!sink.run(&frame(0x0800, None)));
+            assert!(!sink.run(&[0; 8]));
+        }
+
+        assert_eq!(payloads, vec![b"payload".to_vec(), b"payload".to_vec()]);
+    }
+}
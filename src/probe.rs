@@ -0,0 +1,425 @@
+//! A smoke test for validating that packets make it from a
+//! [`TxQueue`] to an [`RxQueue`].
+//!
+//! An [`EchoProbe`] sends a single, recognisable frame out of one
+//! socket and waits for it to arrive on another, either bound to the
+//! same device or to its peer, e.g. the other end of a veth pair or a
+//! cable. If it doesn't arrive the socket statistics and link state
+//! are used to hazard a guess as to why.
+
+use std::{
+    cmp, io,
+    io::Write,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    socket::{LinkStatus, XdpStatistics},
+    umem::frame::FrameDesc,
+    CompQueue, FillQueue, RxQueue, TxQueue, Umem,
+};
+
+/// Experimental ethertype reserved by IEEE 802 for local use.
+const PROBE_ETHERTYPE: [u8; 2] = [0x88, 0xb5];
+
+const PROBE_MAGIC: &[u8; 8] = b"XSKPROBE";
+
+/// Minimum ethernet frame length, excluding the FCS.
+const PROBE_LEN: usize = 60;
+
+/// How long to block in `poll` at a time while waiting.
+const POLL_INTERVAL_MS: i32 = 10;
+
+/// The result of an [`EchoProbe`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoDiagnosis {
+    /// The probe was received, `elapsed` after being sent.
+    Received {
+        /// Time between submitting the probe and receiving it.
+        elapsed: Duration,
+    },
+    /// One of the interfaces was down, so the probe wasn't sent.
+    LinkDown,
+    /// The probe was sent but the kernel rejected its descriptor.
+    TxInvalidDesc,
+    /// The probe was never completed, so may not have been sent.
+    TxNotCompleted,
+    /// The probe was sent but there were no frames on the receiving
+    /// side's fill ring to receive it into.
+    FillQueueEmpty,
+    /// The probe reached the receiving socket but was dropped, for
+    /// example because its rx ring was full.
+    RxDropped,
+    /// The probe was sent but never reached the receiving socket. The
+    /// most likely reason is that no XDP program is redirecting
+    /// packets on the receiving interface and queue to it.
+    NoXdpProgram,
+}
+
+/// The sending side of an [`EchoProbe`].
+#[derive(Debug)]
+pub struct EchoTx<'a> {
+    /// The [`Umem`] that `frame` belongs to.
+    pub umem: &'a Umem,
+    /// The queue the probe is sent on.
+    pub tx_q: &'a mut TxQueue,
+    /// The completion queue of `umem`.
+    pub cq: &'a mut CompQueue,
+    /// A free frame to write the probe into.
+    pub frame: FrameDesc,
+    /// Where frames other than the probe's that complete while
+    /// waiting are placed.
+    pub free: &'a mut Vec<FrameDesc>,
+}
+
+/// The receiving side of an [`EchoProbe`].
+#[derive(Debug)]
+pub struct EchoRx<'a> {
+    /// The [`Umem`] frames are received into.
+    pub umem: &'a Umem,
+    /// The queue the probe is expected on.
+    pub rx_q: &'a mut RxQueue,
+    /// The fill queue of `umem`.
+    pub fq: &'a mut FillQueue,
+    /// Free frames, used to top up the fill ring while waiting.
+    /// Frames received while waiting are returned here before being
+    /// placed back on the fill ring.
+    pub free: &'a mut Vec<FrameDesc>,
+}
+
+/// Sends a probe frame and waits for it to be received.
+#[derive(Debug, Clone, Copy)]
+pub struct EchoProbe {
+    timeout: Duration,
+    nonce: u64,
+}
+
+impl EchoProbe {
+    /// Create a new `EchoProbe` which waits up to `timeout` for the
+    /// probe to arrive.
+    pub fn new(timeout: Duration) -> Self {
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Self { timeout, nonce }
+    }
+
+    /// How long to wait for the probe.
+    #[inline]
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// The probe frame, a broadcast ethernet frame with an
+    /// experimental ethertype carrying a magic number and this
+    /// probe's nonce.
+    pub fn frame(&self) -> [u8; PROBE_LEN] {
+        let mut frame = [0; PROBE_LEN];
+
+        frame[0..6].copy_from_slice(&[0xff; 6]);
+        frame[12..14].copy_from_slice(&PROBE_ETHERTYPE);
+        frame[14..22].copy_from_slice(PROBE_MAGIC);
+        frame[22..30].copy_from_slice(&self.nonce.to_le_bytes());
+
+        frame
+    }
+
+    fn is_probe(&self, pkt: &[u8]) -> bool {
+        pkt.len() >= 30 && pkt[..30] == self.frame()[..30]
+    }
+
+    /// Send the probe from `tx` and wait for it to arrive on `rx`.
+    ///
+    /// Should be run while the queues are otherwise idle. Any other
+    /// packets received in the meantime are discarded, and any other
+    /// frames completed are placed in `tx.free`.
+    ///
+    /// If the diagnosis is [`TxNotCompleted`] then `tx.frame` is
+    /// still owned by the kernel and must not be reused until it has
+    /// been completed.
+    ///
+    /// # Safety
+    ///
+    /// `tx.frame` and the frames in `rx.free` must belong to
+    /// `tx.umem` and `rx.umem` respectively and must not be in use
+    /// elsewhere. The queues must belong to their respective `Umem`s.
+    ///
+    /// [`TxNotCompleted`]: EchoDiagnosis::TxNotCompleted
+    pub unsafe fn run(&self, mut tx: EchoTx<'_>, rx: EchoRx<'_>) -> io::Result<EchoDiagnosis> {
+        let link_down = |status: io::Result<LinkStatus>| matches!(status, Ok(LinkStatus::Down));
+
+        if link_down(tx.tx_q.link_status()) || link_down(rx.rx_q.link_status()) {
+            return Ok(EchoDiagnosis::LinkDown);
+        }
+
+        let tx_stats_before = tx.tx_q.fd().xdp_statistics().ok();
+        let rx_stats_before = rx.rx_q.fd().xdp_statistics().ok();
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // frames belong to their `Umem`s and are free.
+        unsafe { rx.fq.fill_from(rx.free) };
+
+        let mut frame = tx.frame;
+
+        {
+            // SAFETY: see above.
+            let mut data = unsafe { tx.umem.data_mut(&mut frame) };
+            let mut cursor = data.cursor();
+            cursor.set_pos(0);
+            cursor.write_all(&self.frame())?;
+        }
+
+        let sent_at = Instant::now();
+        let deadline = sent_at + self.timeout;
+
+        // SAFETY: see above.
+        if unsafe { tx.tx_q.produce_and_wakeup(&[frame]) }? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "no room on the tx ring for the probe",
+            ));
+        }
+
+        let mut completed = false;
+        let mut received = None;
+        let mut descs = [FrameDesc::default(); 16];
+
+        loop {
+            if !completed {
+                completed = unsafe { complete(&mut tx, &frame) };
+
+                if !completed && tx.tx_q.needs_wakeup() {
+                    tx.tx_q.wakeup()?;
+                }
+            }
+
+            if received.is_none() {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let poll_ms = cmp::min(remaining.as_millis() as i32, POLL_INTERVAL_MS);
+
+                // SAFETY: see above.
+                let cnt = unsafe { rx.rx_q.poll_and_consume(&mut descs, poll_ms)? };
+
+                for desc in descs.iter().take(cnt) {
+                    // SAFETY: the frame was just received so the
+                    // kernel is done with it.
+                    if self.is_probe(unsafe { rx.umem.data(desc) }.contents()) {
+                        received = Some(sent_at.elapsed());
+                    }
+                }
+
+                rx.free.extend_from_slice(&descs[..cnt]);
+
+                // SAFETY: see above.
+                unsafe { rx.fq.fill_from(rx.free) };
+
+                if rx.fq.needs_wakeup() {
+                    rx.fq.wakeup(rx.rx_q.fd_mut(), 0)?;
+                }
+            }
+
+            if (completed && received.is_some()) || Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        if let Some(elapsed) = received {
+            return Ok(EchoDiagnosis::Received { elapsed });
+        }
+
+        let delta = |before: Option<XdpStatistics>, now: io::Result<XdpStatistics>| {
+            before.zip(now.ok()).map(|(b, n)| StatsDelta::new(&b, &n))
+        };
+
+        if !completed {
+            let tx_delta = delta(tx_stats_before, tx.tx_q.fd().xdp_statistics());
+
+            return Ok(match tx_delta {
+                Some(d) if d.tx_invalid_descs > 0 => EchoDiagnosis::TxInvalidDesc,
+                _ => EchoDiagnosis::TxNotCompleted,
+            });
+        }
+
+        let rx_delta = delta(rx_stats_before, rx.rx_q.fd().xdp_statistics());
+
+        Ok(match rx_delta {
+            Some(d) if d.rx_fill_ring_empty_descs > 0 => EchoDiagnosis::FillQueueEmpty,
+            Some(d) if d.rx_dropped > 0 || d.rx_ring_full > 0 => EchoDiagnosis::RxDropped,
+            Some(_) => EchoDiagnosis::NoXdpProgram,
+            // Without statistics the best that can be done is to check
+            // whether the receiving side had any frames to give.
+            None if rx.fq.ring_info().occupancy() == 0 && rx.free.is_empty() => {
+                EchoDiagnosis::FillQueueEmpty
+            }
+            None => EchoDiagnosis::NoXdpProgram,
+        })
+    }
+}
+
+/// Consume the completion queue, returning `true` if `frame` was
+/// among the frames completed. Any others are placed in `tx.free`.
+///
+/// # Safety
+///
+/// See [`EchoProbe::run`].
+unsafe fn complete(tx: &mut EchoTx<'_>, frame: &FrameDesc) -> bool {
+    let mut desc = FrameDesc::default();
+    let mut found = false;
+
+    while unsafe { tx.cq.consume_one(&mut desc) } > 0 {
+        if desc.addr() == frame.addr() {
+            found = true;
+        } else {
+            tx.free.push(desc);
+        }
+    }
+
+    found
+}
+
+#[derive(Debug)]
+struct StatsDelta {
+    rx_dropped: u64,
+    rx_ring_full: u64,
+    rx_fill_ring_empty_descs: u64,
+    tx_invalid_descs: u64,
+}
+
+impl StatsDelta {
+    fn new(before: &XdpStatistics, now: &XdpStatistics) -> Self {
+        Self {
+            rx_dropped: now.rx_dropped().wrapping_sub(before.rx_dropped()),
+            rx_ring_full: now.rx_ring_full().wrapping_sub(before.rx_ring_full()),
+            rx_fill_ring_empty_descs: now
+                .rx_fill_ring_empty_descs()
+                .wrapping_sub(before.rx_fill_ring_empty_descs()),
+            tx_invalid_descs: now
+                .tx_invalid_descs()
+                .wrapping_sub(before.tx_invalid_descs()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+    };
+
+    use crate::sim::{SimConfig, SimKernel, SimXsk};
+
+    use super::*;
+
+    fn build(loopback: bool) -> SimXsk {
+        SimXsk::with_sim_config(
+            16,
+            SimConfig {
+                loopback,
+                ..SimConfig::default()
+            },
+        )
+    }
+
+    /// Run the probe while stepping the simulated kernel on another
+    /// thread.
+    fn run_probe(
+        kernel: &mut SimKernel,
+        f: impl FnOnce() -> EchoDiagnosis + Send,
+    ) -> EchoDiagnosis {
+        let stop = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    kernel.step();
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+
+            let diagnosis = f();
+            stop.store(true, Ordering::Relaxed);
+            diagnosis
+        })
+    }
+
+    #[test]
+    fn probe_is_received_over_loopback() {
+        let SimXsk {
+            umem,
+            descs,
+            mut fq,
+            mut cq,
+            mut tx_q,
+            mut rx_q,
+            mut kernel,
+        } = build(true);
+
+        let mut tx_free = Vec::new();
+        let mut rx_free = descs[1..].to_vec();
+
+        let diagnosis = run_probe(&mut kernel, || unsafe {
+            EchoProbe::new(Duration::from_secs(5))
+                .run(
+                    EchoTx {
+                        umem: &umem,
+                        tx_q: &mut tx_q,
+                        cq: &mut cq,
+                        frame: descs[0],
+                        free: &mut tx_free,
+                    },
+                    EchoRx {
+                        umem: &umem,
+                        rx_q: &mut rx_q,
+                        fq: &mut fq,
+                        free: &mut rx_free,
+                    },
+                )
+                .unwrap()
+        });
+
+        assert!(matches!(diagnosis, EchoDiagnosis::Received { .. }));
+        assert!(tx_free.is_empty());
+        assert_eq!(rx_free.len() + fq.ring_info().occupancy() as usize, 15);
+    }
+
+    #[test]
+    fn empty_fill_queue_is_diagnosed() {
+        let SimXsk {
+            umem,
+            descs,
+            mut fq,
+            mut cq,
+            mut tx_q,
+            mut rx_q,
+            mut kernel,
+        } = build(true);
+
+        let mut tx_free = Vec::new();
+        let mut rx_free = Vec::new();
+
+        let diagnosis = run_probe(&mut kernel, || unsafe {
+            EchoProbe::new(Duration::from_millis(50))
+                .run(
+                    EchoTx {
+                        umem: &umem,
+                        tx_q: &mut tx_q,
+                        cq: &mut cq,
+                        frame: descs[0],
+                        free: &mut tx_free,
+                    },
+                    EchoRx {
+                        umem: &umem,
+                        rx_q: &mut rx_q,
+                        fq: &mut fq,
+                        free: &mut rx_free,
+                    },
+                )
+                .unwrap()
+        });
+
+        assert_eq!(diagnosis, EchoDiagnosis::FillQueueEmpty);
+    }
+}
@@ -0,0 +1,269 @@
+//! Reading a NIC's PTP hardware clock (PHC), and correlating its
+//! timestamps with system time.
+//!
+//! The hardware rx timestamps an XDP program reads with
+//! `bpf_xdp_metadata_rx_timestamp`, and passes on in
+//! [`RxMetadata`](crate::meta::RxMetadata), are taken from the NIC's
+//! PHC rather than any system clock. To compare them with times read
+//! in userspace, for example the send time of a probe carried in its
+//! payload, they have to be moved onto a common timescale first.
+//!
+//! A [`PhcClock`] is opened on the `/dev/ptpN` device belonging to an
+//! interface, and [`correlate`](PhcClock::correlate) samples it
+//! against `CLOCK_TAI`, the clock behind
+//! [`TaiClock`](crate::clock::TaiClock) and `bpf_ktime_get_tai_ns`.
+//! The resulting [`PhcCorrelation`] converts between the two.
+//!
+//! The two clocks drift apart unless something like `phc2sys` keeps
+//! them in step, so a correlation should be refreshed regularly.
+
+use libc::{AF_INET, IFNAMSIZ, SOCK_CLOEXEC, SOCK_DGRAM};
+use std::{
+    fs::File,
+    io,
+    num::NonZeroU32,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+    time::Duration,
+};
+
+use crate::{clock, config::Interface};
+
+// From `linux/ethtool.h`.
+const ETHTOOL_GET_TS_INFO: u32 = 0x41;
+
+/// Layout of `struct ethtool_ts_info`.
+#[repr(C)]
+#[derive(Default)]
+struct EthtoolTsInfo {
+    cmd: u32,
+    so_timestamping: u32,
+    phc_index: i32,
+    tx_types: u32,
+    tx_reserved: [u32; 3],
+    rx_filters: u32,
+    rx_reserved: [u32; 3],
+}
+
+/// Layout of `struct ifreq` as used by `SIOCETHTOOL`, where only the
+/// data member of the union is of interest.
+#[repr(C)]
+struct IfReqData {
+    name: [libc::c_char; IFNAMSIZ],
+    data: *mut libc::c_void,
+    // Pads the union out to its full size.
+    _pad: [u8; 16],
+}
+
+/// The index of `if_name`'s PHC, that is the `N` in `/dev/ptpN`, as
+/// shown by `ethtool -T`. Returns [`None`] if the interface has no
+/// PHC.
+pub fn phc_index(if_name: &Interface) -> io::Result<Option<u32>> {
+    let name = if_name.as_cstr().to_bytes_with_nul();
+
+    if name.len() > IFNAMSIZ {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "interface name exceeds IFNAMSIZ",
+        ));
+    }
+
+    let mut info = EthtoolTsInfo {
+        cmd: ETHTOOL_GET_TS_INFO,
+        ..EthtoolTsInfo::default()
+    };
+
+    let mut req = IfReqData {
+        name: [0; IFNAMSIZ],
+        data: &mut info as *mut EthtoolTsInfo as *mut libc::c_void,
+        _pad: [0; 16],
+    };
+
+    for (dst, src) in req.name.iter_mut().zip(name) {
+        *dst = *src as libc::c_char;
+    }
+
+    let fd = unsafe { libc::socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0) };
+
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `socket` succeeded so the fd is open and owned by us
+    // alone.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let err = unsafe { libc::ioctl(fd.as_raw_fd(), libc::SIOCETHTOOL as _, &mut req) };
+
+    if err < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(if info.phc_index < 0 {
+        None
+    } else {
+        Some(info.phc_index as u32)
+    })
+}
+
+/// The dynamic clock id of the PHC open as `fd`, from `FD_TO_CLOCKID`
+/// in the kernel's `testptp.c`.
+#[inline]
+fn fd_to_clock_id(fd: libc::c_int) -> libc::clockid_t {
+    ((!fd) << 3) | 3
+}
+
+/// An open PTP hardware clock.
+#[derive(Debug)]
+pub struct PhcClock {
+    file: File,
+}
+
+impl PhcClock {
+    /// Open the PHC device at `path`, for example `/dev/ptp0`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        Ok(Self { file })
+    }
+
+    /// Open the PHC belonging to `if_name`. Fails with
+    /// [`NotFound`](io::ErrorKind::NotFound) if the interface has
+    /// none.
+    pub fn for_interface(if_name: &Interface) -> io::Result<Self> {
+        match phc_index(if_name)? {
+            Some(idx) => Self::open(format!("/dev/ptp{}", idx)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "interface has no PTP hardware clock",
+            )),
+        }
+    }
+
+    /// The current time of the PHC, which is usually TAI once synced
+    /// by `ptp4l`.
+    pub fn now(&self) -> io::Result<Duration> {
+        clock::clock_gettime(fd_to_clock_id(self.file.as_raw_fd()))
+    }
+
+    /// Sample the PHC against `CLOCK_TAI` `samples` times, keeping the
+    /// sample read in the shortest window, which is the least
+    /// disturbed by scheduling and the PHC's read latency.
+    pub fn correlate(&self, samples: NonZeroU32) -> io::Result<PhcCorrelation> {
+        let mut best = self.sample()?;
+
+        for _ in 1..samples.get() {
+            let sample = self.sample()?;
+
+            if sample.window < best.window {
+                best = sample;
+            }
+        }
+
+        Ok(best)
+    }
+
+    fn sample(&self) -> io::Result<PhcCorrelation> {
+        let before = clock::clock_gettime(libc::CLOCK_TAI)?;
+        let phc = self.now()?;
+        let after = clock::clock_gettime(libc::CLOCK_TAI)?;
+
+        Ok(PhcCorrelation::from_sample(before, phc, after))
+    }
+}
+
+impl AsRawFd for PhcClock {
+    #[inline]
+    fn as_raw_fd(&self) -> libc::c_int {
+        self.file.as_raw_fd()
+    }
+}
+
+/// A pairing of PHC time with `CLOCK_TAI`, for converting timestamps
+/// from one to the other. All times are in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhcCorrelation {
+    phc: Duration,
+    sys: Duration,
+    window: Duration,
+}
+
+impl PhcCorrelation {
+    /// A correlation from a PHC read of `phc`, between system clock
+    /// reads of `before` and `after`. The PHC is taken to have been
+    /// read halfway between the two.
+    pub fn from_sample(before: Duration, phc: Duration, after: Duration) -> Self {
+        let window = after.saturating_sub(before);
+
+        Self {
+            phc,
+            sys: before + window / 2,
+            window,
+        }
+    }
+
+    /// The system time less the PHC time, in nanoseconds.
+    #[inline]
+    pub fn offset_ns(&self) -> i64 {
+        self.sys.as_nanos() as i64 - self.phc.as_nanos() as i64
+    }
+
+    /// How far either way the offset may be out, half the time the
+    /// sample took to read.
+    #[inline]
+    pub fn uncertainty(&self) -> Duration {
+        self.window / 2
+    }
+
+    /// Convert the PHC timestamp `phc_ns`, such as a hardware rx
+    /// timestamp, to system time.
+    #[inline]
+    pub fn to_system(&self, phc_ns: u64) -> u64 {
+        (phc_ns as i64).wrapping_add(self.offset_ns()) as u64
+    }
+
+    /// Convert the system time `sys_ns` to PHC time.
+    #[inline]
+    pub fn to_phc(&self, sys_ns: u64) -> u64 {
+        (sys_ns as i64).wrapping_sub(self.offset_ns()) as u64
+    }
+
+    /// The one-way delay of a packet sent at system time `sent_ns`
+    /// and received with hardware rx timestamp `rx_timestamp`, in
+    /// nanoseconds. Negative if the sender's clock is ahead of ours
+    /// by more than the delay.
+    #[inline]
+    pub fn one_way_delay_ns(&self, sent_ns: u64, rx_timestamp: u64) -> i64 {
+        self.to_system(rx_timestamp).wrapping_sub(sent_ns) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phc_timestamps_are_moved_onto_system_time() {
+        let corr = PhcCorrelation::from_sample(
+            Duration::from_nanos(1_000_037_000),
+            Duration::from_nanos(1_000_000_500),
+            Duration::from_nanos(1_000_038_000),
+        );
+
+        assert_eq!(corr.offset_ns(), 37_000);
+        assert_eq!(corr.uncertainty(), Duration::from_nanos(500));
+
+        assert_eq!(corr.to_system(2_000_000_000), 2_000_037_000);
+        assert_eq!(corr.to_phc(2_000_037_000), 2_000_000_000);
+
+        assert_eq!(corr.one_way_delay_ns(1_999_987_000, 1_999_950_000), 0);
+        assert_eq!(corr.one_way_delay_ns(2_000_000_000, 2_000_000_000), 37_000);
+        assert_eq!(corr.one_way_delay_ns(2_000_040_000, 2_000_000_000), -3_000);
+    }
+
+    #[test]
+    fn dynamic_clock_ids_match_the_kernel() {
+        assert_eq!(fd_to_clock_id(3), -29);
+        assert_eq!(fd_to_clock_id(0), -5);
+    }
+}
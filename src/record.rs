@@ -0,0 +1,399 @@
+//! Recording of the descriptors passing through a socket's queues.
+//!
+//! A [`Recorder`] writes each descriptor handed to or taken from a
+//! ring, along with which ring operation it passed through and when,
+//! to a compact binary log. The log can be read back with a
+//! [`RecordReader`] for postmortem analysis of frame accounting bugs,
+//! for example finding a frame which was placed on the fill ring
+//! twice, or one which was never handed back.
+//!
+//! Recording is explicit: call [`Recorder::record`] with the
+//! descriptors after each queue operation.
+//!
+//! # Format
+//!
+//! All integers are little-endian. The log starts with a 24 byte
+//! header:
+//!
+//! | Offset | Size | Field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 8    | Magic, `b"XSKDREC\0"`                      |
+//! | 8      | 2    | Format version, currently [`VERSION`]      |
+//! | 10     | 2    | Record size, currently [`RECORD_SIZE`]     |
+//! | 12     | 4    | Reserved, zero                             |
+//! | 16     | 8    | Start time, in nanoseconds since the epoch |
+//!
+//! Followed by any number of records, one per descriptor:
+//!
+//! | Offset | Size | Field                                      |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 8    | Nanoseconds since the start time           |
+//! | 8      | 8    | Descriptor address                         |
+//! | 16     | 4    | Descriptor length                          |
+//! | 20     | 4    | Descriptor options                         |
+//! | 24     | 1    | [`RingOp`]                                 |
+//! | 25     | 7    | Reserved, zero                             |
+//!
+//! Readers should skip any bytes past the fields they know of, using
+//! the record size from the header, so that fields can be added in
+//! later versions without breaking them.
+
+use std::{
+    convert::TryInto,
+    io::{self, Read, Write},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::umem::frame::FrameDesc;
+
+const MAGIC: &[u8; 8] = b"XSKDREC\0";
+
+const HEADER_SIZE: usize = 24;
+
+/// The log format version written by this version of the library.
+pub const VERSION: u16 = 1;
+
+/// The size in bytes of each record written by this version of the
+/// library.
+pub const RECORD_SIZE: u16 = 32;
+
+/// The ring operation a recorded descriptor passed through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingOp {
+    /// Handed to the kernel via the [`FillQueue`](crate::FillQueue).
+    FillProduce,
+    /// Taken back from the kernel via the
+    /// [`CompQueue`](crate::CompQueue).
+    CompConsume,
+    /// Handed to the kernel via the [`TxQueue`](crate::TxQueue).
+    TxProduce,
+    /// Taken back from the kernel via the [`RxQueue`](crate::RxQueue).
+    RxConsume,
+}
+
+impl RingOp {
+    fn as_u8(self) -> u8 {
+        match self {
+            RingOp::FillProduce => 0,
+            RingOp::CompConsume => 1,
+            RingOp::TxProduce => 2,
+            RingOp::RxConsume => 3,
+        }
+    }
+
+    fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(RingOp::FillProduce),
+            1 => Some(RingOp::CompConsume),
+            2 => Some(RingOp::TxProduce),
+            3 => Some(RingOp::RxConsume),
+            _ => None,
+        }
+    }
+}
+
+/// A single descriptor read from a log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DescRecord {
+    timestamp_ns: u64,
+    op: RingOp,
+    addr: u64,
+    len: u32,
+    options: u32,
+}
+
+impl DescRecord {
+    /// Nanoseconds between the start of the recording and the
+    /// operation.
+    #[inline]
+    pub fn timestamp_ns(&self) -> u64 {
+        self.timestamp_ns
+    }
+
+    /// The ring operation the descriptor passed through.
+    #[inline]
+    pub fn op(&self) -> RingOp {
+        self.op
+    }
+
+    /// The descriptor's address.
+    #[inline]
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    /// The length of the descriptor's packet data.
+    #[inline]
+    pub fn data_len(&self) -> u32 {
+        self.len
+    }
+
+    /// The descriptor's options.
+    #[inline]
+    pub fn options(&self) -> u32 {
+        self.options
+    }
+
+    fn encode(&self) -> [u8; RECORD_SIZE as usize] {
+        let mut buf = [0; RECORD_SIZE as usize];
+
+        buf[0..8].copy_from_slice(&self.timestamp_ns.to_le_bytes());
+        buf[8..16].copy_from_slice(&self.addr.to_le_bytes());
+        buf[16..20].copy_from_slice(&self.len.to_le_bytes());
+        buf[20..24].copy_from_slice(&self.options.to_le_bytes());
+        buf[24] = self.op.as_u8();
+
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Self> {
+        let op = RingOp::from_u8(buf[24]).ok_or_else(|| invalid_data("unknown ring op"))?;
+
+        // Slice lengths are fixed so the conversions can't fail.
+        Ok(Self {
+            timestamp_ns: u64::from_le_bytes(buf[0..8].try_into().unwrap()),
+            addr: u64::from_le_bytes(buf[8..16].try_into().unwrap()),
+            len: u32::from_le_bytes(buf[16..20].try_into().unwrap()),
+            options: u32::from_le_bytes(buf[20..24].try_into().unwrap()),
+            op,
+        })
+    }
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Writes descriptors to a log. See the [module docs](self) for the
+/// format.
+///
+/// Writes aren't buffered, so wrap `W` in a
+/// [`BufWriter`](std::io::BufWriter) if it's a file or socket.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    out: W,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Create a new `Recorder`, writing the log header to `out`.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        let start = Instant::now();
+
+        let start_unix_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        let mut header = [0; HEADER_SIZE];
+
+        header[0..8].copy_from_slice(MAGIC);
+        header[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        header[10..12].copy_from_slice(&RECORD_SIZE.to_le_bytes());
+        header[16..24].copy_from_slice(&start_unix_ns.to_le_bytes());
+
+        out.write_all(&header)?;
+
+        Ok(Self { out, start })
+    }
+
+    /// Record that `descs` passed through `op`. All are given the
+    /// same timestamp.
+    pub fn record(&mut self, op: RingOp, descs: &[FrameDesc]) -> io::Result<()> {
+        let timestamp_ns = self.start.elapsed().as_nanos() as u64;
+
+        for desc in descs {
+            let record = DescRecord {
+                timestamp_ns,
+                op,
+                addr: desc.addr as u64,
+                len: desc.lengths.data as u32,
+                options: desc.options,
+            };
+
+            self.out.write_all(&record.encode())?;
+        }
+
+        Ok(())
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Consume the `Recorder`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads descriptors back from a log written by a [`Recorder`].
+///
+/// Iterating yields each record in turn, stopping at the end of the
+/// log. A log which ends part way through a record, as might happen
+/// if the recording process crashed, yields an
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof) error for it.
+#[derive(Debug)]
+pub struct RecordReader<R: Read> {
+    inp: R,
+    start_unix_ns: u64,
+    record_size: usize,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Create a new `RecordReader`, reading and checking the log
+    /// header from `inp`.
+    ///
+    /// Fails if the header is malformed or the log was written with
+    /// a newer, incompatible version of the format.
+    pub fn new(mut inp: R) -> io::Result<Self> {
+        let mut header = [0; HEADER_SIZE];
+
+        inp.read_exact(&mut header)?;
+
+        if &header[0..8] != MAGIC {
+            return Err(invalid_data("not a descriptor log"));
+        }
+
+        let version = u16::from_le_bytes([header[8], header[9]]);
+
+        if version != VERSION {
+            return Err(invalid_data("unsupported descriptor log version"));
+        }
+
+        let record_size = u16::from_le_bytes([header[10], header[11]]) as usize;
+
+        if record_size < RECORD_SIZE as usize {
+            return Err(invalid_data("descriptor log record size too small"));
+        }
+
+        let start_unix_ns = u64::from_le_bytes(header[16..24].try_into().unwrap());
+
+        Ok(Self {
+            inp,
+            start_unix_ns,
+            record_size,
+            buf: vec![0; record_size],
+        })
+    }
+
+    /// The time the recording was started, in nanoseconds since the
+    /// epoch.
+    #[inline]
+    pub fn start_unix_ns(&self) -> u64 {
+        self.start_unix_ns
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<DescRecord>> {
+        let mut filled = 0;
+
+        while filled < self.record_size {
+            match self.inp.read(&mut self.buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "descriptor log ends part way through a record",
+                    ))
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        DescRecord::decode(&self.buf).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<DescRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::umem::frame::SegmentLengths;
+
+    use super::*;
+
+    fn desc(addr: usize, len: usize) -> FrameDesc {
+        FrameDesc {
+            addr,
+            options: 0,
+            region: 0,
+            lengths: SegmentLengths {
+                headroom: 0,
+                data: len,
+            },
+        }
+    }
+
+    #[test]
+    fn records_are_read_back_in_order() {
+        let mut rec = Recorder::new(Vec::new()).unwrap();
+
+        rec.record(RingOp::FillProduce, &[desc(256, 0), desc(2304, 0)])
+            .unwrap();
+        rec.record(RingOp::RxConsume, &[desc(256, 60)]).unwrap();
+
+        let log = rec.into_inner();
+
+        assert_eq!(log.len(), HEADER_SIZE + 3 * RECORD_SIZE as usize);
+
+        let records = RecordReader::new(&log[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+
+        let summary = records
+            .iter()
+            .map(|r| (r.op(), r.addr(), r.data_len()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                (RingOp::FillProduce, 256, 0),
+                (RingOp::FillProduce, 2304, 0),
+                (RingOp::RxConsume, 256, 60),
+            ]
+        );
+
+        assert!(records[0].timestamp_ns() <= records[2].timestamp_ns());
+    }
+
+    #[test]
+    fn truncated_record_is_an_error() {
+        let mut rec = Recorder::new(Vec::new()).unwrap();
+
+        rec.record(RingOp::TxProduce, &[desc(0, 1), desc(2048, 1)])
+            .unwrap();
+
+        let log = rec.into_inner();
+        let log = &log[..log.len() - 1];
+
+        let mut reader = RecordReader::new(log).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn bad_header_is_rejected() {
+        assert_eq!(
+            RecordReader::new(&[0u8; HEADER_SIZE][..])
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
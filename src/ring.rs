@@ -1,71 +1,880 @@
-use std::ptr;
+use std::{
+    error::Error,
+    fmt, io, mem,
+    os::unix::prelude::RawFd,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU32, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
 
 use libxdp_sys::{xsk_ring_cons, xsk_ring_prod};
+use log::{error, warn};
 
+use crate::{
+    socket::{RingInfo, RingOffsets},
+    umem::frame::FrameDesc,
+    util,
+};
+
+/// What to do when asked to produce more frames than there is room
+/// for on a ring. See [`FillQueue::produce_with_policy`] and
+/// [`TxQueue::produce_with_policy`].
+///
+/// [`FillQueue::produce_with_policy`]: crate::FillQueue::produce_with_policy
+/// [`TxQueue::produce_with_policy`]: crate::TxQueue::produce_with_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProducePolicy {
+    /// Submit as many frames as there are free spaces for, leaving
+    /// the rest to be passed in a later call.
+    SplitAcrossCalls,
+    /// Fail with [`InvalidInput`](io::ErrorKind::InvalidInput) if
+    /// there are more frames than the ring can ever hold. Otherwise
+    /// submit all of them or none, as with `produce`.
+    ErrorOut,
+    /// Ignore any frames beyond the ring's size, then submit the rest
+    /// all or none, as with `produce`.
+    TruncateToCapacity,
+}
+
+/// One of the rings of a [`Umem`](crate::Umem) or
+/// [`Socket`](crate::Socket).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RingKind {
+    /// The [`FillQueue`](crate::FillQueue).
+    Fill,
+    /// The [`CompQueue`](crate::CompQueue).
+    Completion,
+    /// The [`RxQueue`](crate::RxQueue).
+    Rx,
+    /// The [`TxQueue`](crate::TxQueue).
+    Tx,
+}
+
+impl fmt::Display for RingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RingKind::Fill => "fill",
+            RingKind::Completion => "completion",
+            RingKind::Rx => "rx",
+            RingKind::Tx => "tx",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Callbacks run around the ring operations of a queue, for tracing,
+/// fault injection or keeping a shadow account of frames.
+///
+/// Set on a queue with `with_hooks`, for example
+/// [`TxQueue::with_hooks`](crate::TxQueue::with_hooks). Queues are
+/// generic over their hooks, which default to [`NoHooks`], so calls
+/// are resolved at compile time and unused hooks cost nothing.
+///
+/// Every method has a default doing nothing, so only those needed
+/// have to be implemented. The `pre_` hooks are only called for
+/// operations which could move frames, and each is followed by the
+/// matching `post_` hook.
+pub trait QueueHooks {
+    /// Called before up to `nb` frames are submitted to `ring`,
+    /// returning the most that may be. Returning fewer than `nb`
+    /// makes the ring look to have only that much room, so for
+    /// example a [`produce`](crate::TxQueue::produce) of `nb` frames
+    /// submits none at all.
+    ///
+    /// For calls taking an iterator, `nb` is the room on the ring.
+    #[inline]
+    fn pre_produce(&mut self, _ring: RingKind, nb: usize) -> usize {
+        nb
+    }
+
+    /// Called for each frame as it's written to `ring`.
+    #[inline]
+    fn on_produce(&mut self, _ring: RingKind, _desc: &FrameDesc) {}
+
+    /// Called once `cnt` frames have been submitted to `ring`.
+    #[inline]
+    fn post_produce(&mut self, _ring: RingKind, _cnt: usize) {}
+
+    /// Called before up to `nb` frames are read off `ring`, returning
+    /// the most that may be. Returning fewer than `nb` makes the ring
+    /// look to hold at most that many.
+    #[inline]
+    fn pre_consume(&mut self, _ring: RingKind, nb: usize) -> usize {
+        nb
+    }
+
+    /// Called for each frame as it's read off `ring`.
+    #[inline]
+    fn on_consume(&mut self, _ring: RingKind, _desc: &FrameDesc) {}
+
+    /// Called once `cnt` frames have been read off `ring`.
+    #[inline]
+    fn post_consume(&mut self, _ring: RingKind, _cnt: usize) {}
+}
+
+/// The default [`QueueHooks`], which do nothing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NoHooks;
+
+impl QueueHooks for NoHooks {}
+
+/// What a [`TxQueue`](crate::TxQueue) or
+/// [`FillQueue`](crate::FillQueue) does about frames still on its
+/// ring, not yet taken off by the kernel, when it's dropped.
+///
+/// Those frames are otherwise lost to the application: once the queue
+/// has gone the kernel may or may not get round to them, and nothing
+/// records which they were. Set with `set_drop_policy`, for example
+/// [`TxQueue::set_drop_policy`](crate::TxQueue::set_drop_policy).
+#[derive(Default)]
+pub enum DropPolicy {
+    /// Drop the queue straight away, leaving the frames where they
+    /// are. The default.
+    #[default]
+    Leak,
+    /// As with [`Leak`](Self::Leak), but log a warning with the number
+    /// of frames left behind.
+    Warn,
+    /// Wait up to the given time for the kernel to take the frames off
+    /// the ring, waking it up as needed, then warn about any it
+    /// didn't. Frames taken off a tx ring end up on the completion
+    /// ring, so can still be reaped through the
+    /// [`CompQueue`](crate::CompQueue).
+    ///
+    /// Frames on a fill ring are only taken off as packets arrive, so
+    /// this can wait out the full time on a quiet interface.
+    Wait(Duration),
+    /// Call the closure with the addresses of the frames left behind,
+    /// if there are any, so they can be accounted for elsewhere. The
+    /// kernel may still consume them while the socket is open.
+    Callback(DropCallback),
+}
+
+/// The closure called under [`DropPolicy::Callback`].
+pub type DropCallback = Box<dyn FnMut(&[usize]) + Send>;
+
+impl fmt::Debug for DropPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DropPolicy::Leak => write!(f, "Leak"),
+            DropPolicy::Warn => write!(f, "Warn"),
+            DropPolicy::Wait(timeout) => f.debug_tuple("Wait").field(timeout).finish(),
+            DropPolicy::Callback(_) => f.debug_tuple("Callback").finish_non_exhaustive(),
+        }
+    }
+}
+
+/// A ring which libxdp reported as created was never mapped, found
+/// as a null ring pointer after a successful call.
+///
+/// Available from [`UmemCreateError::ring_setup_error`] or
+/// [`SocketCreateError::ring_setup_error`].
+///
+/// [`UmemCreateError::ring_setup_error`]: crate::umem::UmemCreateError::ring_setup_error
+/// [`SocketCreateError::ring_setup_error`]: crate::socket::SocketCreateError::ring_setup_error
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RingSetupError {
+    ring: RingKind,
+    hint: &'static str,
+}
+
+impl RingSetupError {
+    /// Hint for when libxdp should always have mapped the ring.
+    pub(crate) const VERSION_HINT: &'static str =
+        "the libxdp loaded at runtime may not match the version libxdp-sys was built against";
+
+    /// Hint for when only one of a fill and completion ring pair was
+    /// mapped.
+    pub(crate) const SHARED_HINT: &'static str =
+        "if sharing the UMEM, a socket bound to an already bound (if_name, queue_id) pair \
+         must set XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD, otherwise the libxdp loaded at runtime \
+         may not match the version libxdp-sys was built against";
+
+    pub(crate) fn new(ring: RingKind, hint: &'static str) -> Self {
+        Self { ring, hint }
+    }
+
+    /// The ring which wasn't set up.
+    pub fn ring(&self) -> RingKind {
+        self.ring
+    }
+
+    /// The likely cause.
+    pub fn hint(&self) -> &'static str {
+        self.hint
+    }
+}
+
+impl fmt::Display for RingSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ring was not set up: {}", self.ring, self.hint)
+    }
+}
+
+impl Error for RingSetupError {}
+
+impl From<RingSetupError> for io::Error {
+    fn from(e: RingSetupError) -> Self {
+        io::Error::other(e)
+    }
+}
+
+/// Find a [`RingSetupError`] behind `err`, if it's the cause.
+pub(crate) fn ring_setup_error(err: &io::Error) -> Option<&RingSetupError> {
+    err.get_ref().and_then(|e| e.downcast_ref())
+}
+
+#[cfg(any(test, feature = "testing"))]
+use std::sync::Arc;
+
+#[cfg(any(test, feature = "testing"))]
+use crate::sim::RingMem;
+
+/// Snapshot of the addresses and indices of a ring whose entries are
+/// of type `T`.
+///
+/// # Safety
+///
+/// `producer` and `consumer` must be valid and properly aligned.
+unsafe fn ring_info<T>(
+    producer: *mut u32,
+    consumer: *mut u32,
+    flags: *mut u32,
+    ring: *mut libc::c_void,
+    size: u32,
+) -> RingInfo {
+    // The indices are shared with the kernel so must be read
+    // atomically.
+    let (prod, cons) = unsafe {
+        (
+            (*(producer as *const AtomicU32)).load(Ordering::Acquire),
+            (*(consumer as *const AtomicU32)).load(Ordering::Acquire),
+        )
+    };
+
+    RingInfo::new(
+        producer as usize,
+        consumer as usize,
+        flags as usize,
+        ring as usize,
+        size,
+        mem::size_of::<T>(),
+        prod,
+        cons,
+    )
+}
+
+/// A ring mapped from a socket's file descriptor by this crate rather
+/// than by libxdp. Unmapped on drop.
 #[derive(Debug)]
-pub struct XskRingCons(xsk_ring_cons);
+pub struct RingMmap {
+    addr: NonNull<libc::c_void>,
+    len: usize,
+    offsets: RingOffsets,
+    size: u32,
+}
+
+unsafe impl Send for RingMmap {}
+
+impl RingMmap {
+    /// Map the ring at page offset `pgoff` of `fd`, which has `size`
+    /// entries of type `T` and whose fields lie at `offsets`.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an AF_XDP socket with a ring of `size` entries of
+    /// type `T` at `pgoff`, and `offsets` must be the offsets the
+    /// kernel reported for that ring.
+    pub unsafe fn map<T>(
+        fd: RawFd,
+        pgoff: u64,
+        offsets: RingOffsets,
+        size: u32,
+    ) -> io::Result<Self> {
+        let len = offsets.desc() as usize + size as usize * mem::size_of::<T>();
+
+        // Same flags as libxdp uses when mapping a socket's rings.
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                pgoff as libc::off_t,
+            )
+        };
+
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            addr: NonNull::new(addr).expect("ptr non-null since we confirmed `mmap()` succeeded"),
+            len,
+            offsets,
+            size,
+        })
+    }
+
+    fn ptr<T>(&self, offset: u64) -> *mut T {
+        // SAFETY: the offsets lie within the mapping, as checked by
+        // the kernel when it was mapped.
+        unsafe { (self.addr.as_ptr() as *mut u8).add(offset as usize) as *mut T }
+    }
+
+    fn producer_ptr(&self) -> *mut u32 {
+        self.ptr(self.offsets.producer())
+    }
+
+    fn consumer_ptr(&self) -> *mut u32 {
+        self.ptr(self.offsets.consumer())
+    }
+
+    fn flags_ptr(&self) -> *mut u32 {
+        self.ptr(self.offsets.flags())
+    }
+
+    fn ring_ptr(&self) -> *mut libc::c_void {
+        self.ptr(self.offsets.desc())
+    }
+
+    fn load(ptr: *mut u32) -> u32 {
+        // SAFETY: the indices are valid, aligned and shared with the
+        // kernel, so must be read atomically.
+        unsafe { (*(ptr as *const AtomicU32)).load(Ordering::Acquire) }
+    }
+}
+
+impl Drop for RingMmap {
+    fn drop(&mut self) {
+        let err = unsafe { libc::munmap(self.addr.as_ptr(), self.len) };
+
+        if err != 0 {
+            error!(
+                "`munmap()` of ring failed with error: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct XskRingCons {
+    ring: xsk_ring_cons,
+    // Heap memory backing the ring when it isn't mmap'd from a
+    // socket, kept alive for as long as the ring is.
+    #[cfg(any(test, feature = "testing"))]
+    _mem: Option<Arc<RingMem>>,
+}
 
 impl XskRingCons {
     pub fn as_mut(&mut self) -> &mut xsk_ring_cons {
-        &mut self.0
+        &mut self.ring
     }
 
     pub fn as_ref(&self) -> &xsk_ring_cons {
-        &self.0
+        &self.ring
     }
 
     pub fn is_ring_null(&self) -> bool {
-        self.0.ring.is_null()
+        self.ring.ring.is_null()
+    }
+
+    /// Addresses and indices of the ring, whose entries are of type
+    /// `T`. Must not be called on a null ring.
+    pub fn info<T>(&self) -> RingInfo {
+        assert!(!self.is_ring_null(), "ring is null");
+
+        let r = &self.ring;
+
+        // SAFETY: the ring is non-null so was set up by libxdp, or is
+        // backed by simulated ring memory, in which case the index
+        // pointers are valid.
+        unsafe { ring_info::<T>(r.producer, r.consumer, r.flags, r.ring, r.size) }
+    }
+
+    /// Set up a consumer ring over `mmap`.
+    ///
+    /// # Safety
+    ///
+    /// The returned ring must not outlive `mmap`.
+    pub unsafe fn from_mmap(mmap: &RingMmap) -> Self {
+        Self {
+            ring: xsk_ring_cons {
+                cached_prod: RingMmap::load(mmap.producer_ptr()),
+                cached_cons: RingMmap::load(mmap.consumer_ptr()),
+                mask: mmap.size - 1,
+                size: mmap.size,
+                producer: mmap.producer_ptr(),
+                consumer: mmap.consumer_ptr(),
+                ring: mmap.ring_ptr(),
+                flags: mmap.flags_ptr(),
+            },
+            #[cfg(any(test, feature = "testing"))]
+            _mem: None,
+        }
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn from_mem(mem: Arc<RingMem>) -> Self {
+        Self {
+            ring: xsk_ring_cons {
+                cached_prod: mem.producer(),
+                cached_cons: mem.consumer(),
+                mask: mem.size() - 1,
+                size: mem.size(),
+                producer: mem.producer_ptr(),
+                consumer: mem.consumer_ptr(),
+                ring: mem.ring_ptr(),
+                flags: mem.flags_ptr(),
+            },
+            _mem: Some(mem),
+        }
     }
 }
 
 impl Default for XskRingCons {
     fn default() -> Self {
-        Self(xsk_ring_cons {
-            cached_prod: 0,
-            cached_cons: 0,
-            mask: 0,
-            size: 0,
-            producer: ptr::null_mut(),
-            consumer: ptr::null_mut(),
-            ring: ptr::null_mut(),
-            flags: ptr::null_mut(),
-        })
+        Self {
+            ring: xsk_ring_cons {
+                cached_prod: 0,
+                cached_cons: 0,
+                mask: 0,
+                size: 0,
+                producer: ptr::null_mut(),
+                consumer: ptr::null_mut(),
+                ring: ptr::null_mut(),
+                flags: ptr::null_mut(),
+            },
+            #[cfg(any(test, feature = "testing"))]
+            _mem: None,
+        }
     }
 }
 
 unsafe impl Send for XskRingCons {}
 
 #[derive(Debug)]
-pub struct XskRingProd(xsk_ring_prod);
+pub struct XskRingProd {
+    ring: xsk_ring_prod,
+    // See `XskRingCons`.
+    #[cfg(any(test, feature = "testing"))]
+    _mem: Option<Arc<RingMem>>,
+}
 
 impl XskRingProd {
     pub fn as_mut(&mut self) -> &mut xsk_ring_prod {
-        &mut self.0
+        &mut self.ring
     }
 
     pub fn as_ref(&self) -> &xsk_ring_prod {
-        &self.0
+        &self.ring
     }
 
     pub fn is_ring_null(&self) -> bool {
-        self.0.ring.is_null()
+        self.ring.ring.is_null()
+    }
+
+    /// The prefix of `descs` that should be passed to an
+    /// all-or-nothing produce under `policy`.
+    pub fn policy_prefix<'a>(
+        &mut self,
+        descs: &'a [FrameDesc],
+        policy: ProducePolicy,
+    ) -> io::Result<&'a [FrameDesc]> {
+        let size = self.ring.size as usize;
+
+        let len = match policy {
+            ProducePolicy::SplitAcrossCalls => {
+                let free =
+                    unsafe { libxdp_sys::xsk_prod_nb_free(&mut self.ring, descs.len() as u32) };
+
+                util::min_usize(free as usize, descs.len())
+            }
+            ProducePolicy::ErrorOut => {
+                if descs.len() > size {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "more frames than the ring can hold",
+                    ));
+                }
+                descs.len()
+            }
+            ProducePolicy::TruncateToCapacity => util::min_usize(size, descs.len()),
+        };
+
+        Ok(&descs[..len])
+    }
+
+    /// See [`XskRingCons::info`].
+    pub fn info<T>(&self) -> RingInfo {
+        assert!(!self.is_ring_null(), "ring is null");
+
+        let r = &self.ring;
+
+        // SAFETY: the ring is non-null so was set up by libxdp, or is
+        // backed by simulated ring memory, in which case the index
+        // pointers are valid.
+        unsafe { ring_info::<T>(r.producer, r.consumer, r.flags, r.ring, r.size) }
+    }
+
+    /// Set up a producer ring over `mmap`.
+    ///
+    /// # Safety
+    ///
+    /// See [`XskRingCons::from_mmap`].
+    pub unsafe fn from_mmap(mmap: &RingMmap) -> Self {
+        Self {
+            ring: xsk_ring_prod {
+                cached_prod: RingMmap::load(mmap.producer_ptr()),
+                // See `from_mem`.
+                cached_cons: RingMmap::load(mmap.consumer_ptr()).wrapping_add(mmap.size),
+                mask: mmap.size - 1,
+                size: mmap.size,
+                producer: mmap.producer_ptr(),
+                consumer: mmap.consumer_ptr(),
+                ring: mmap.ring_ptr(),
+                flags: mmap.flags_ptr(),
+            },
+            #[cfg(any(test, feature = "testing"))]
+            _mem: None,
+        }
+    }
+
+    #[cfg(any(test, feature = "testing"))]
+    pub fn from_mem(mem: Arc<RingMem>) -> Self {
+        Self {
+            ring: xsk_ring_prod {
+                cached_prod: mem.producer(),
+                // Matches what libxdp does when setting up a producer
+                // ring, the cached consumer index sits a full ring
+                // ahead so that all entries are initially free.
+                cached_cons: mem.consumer().wrapping_add(mem.size()),
+                mask: mem.size() - 1,
+                size: mem.size(),
+                producer: mem.producer_ptr(),
+                consumer: mem.consumer_ptr(),
+                ring: mem.ring_ptr(),
+                flags: mem.flags_ptr(),
+            },
+            _mem: Some(mem),
+        }
     }
 }
 
 impl Default for XskRingProd {
     fn default() -> Self {
-        Self(xsk_ring_prod {
-            cached_prod: 0,
-            cached_cons: 0,
-            mask: 0,
-            size: 0,
-            producer: ptr::null_mut(),
-            consumer: ptr::null_mut(),
-            ring: ptr::null_mut(),
-            flags: ptr::null_mut(),
-        })
+        Self {
+            ring: xsk_ring_prod {
+                cached_prod: 0,
+                cached_cons: 0,
+                mask: 0,
+                size: 0,
+                producer: ptr::null_mut(),
+                consumer: ptr::null_mut(),
+                ring: ptr::null_mut(),
+                flags: ptr::null_mut(),
+            },
+            #[cfg(any(test, feature = "testing"))]
+            _mem: None,
+        }
     }
 }
 
 unsafe impl Send for XskRingProd {}
+
+/// Applies a queue's [`DropPolicy`] to the frames left on its
+/// producer ring when it's dropped.
+///
+/// Kept apart from the ring so queues can still be taken apart by
+/// moving out of their fields. Must be dropped before anything
+/// keeping the ring mapped, so should be the queue's first field.
+#[derive(Debug)]
+pub(crate) struct OnDrop {
+    policy: DropPolicy,
+    kind: RingKind,
+    producer: *mut u32,
+    consumer: *mut u32,
+    ring: *mut libc::c_void,
+    mask: u32,
+    entry_size: usize,
+    // The socket to wake up while waiting, for a tx ring.
+    wakeup_fd: Option<RawFd>,
+}
+
+unsafe impl Send for OnDrop {}
+
+impl OnDrop {
+    /// Watch `ring`, whose entries are of type `T` and start with a
+    /// `u64` frame address.
+    pub fn new<T>(ring: &XskRingProd, kind: RingKind, wakeup_fd: Option<RawFd>) -> Self {
+        let r = ring.as_ref();
+
+        Self {
+            policy: DropPolicy::default(),
+            kind,
+            producer: r.producer,
+            consumer: r.consumer,
+            ring: r.ring,
+            mask: r.mask,
+            entry_size: mem::size_of::<T>(),
+            wakeup_fd,
+        }
+    }
+
+    pub fn policy(&self) -> &DropPolicy {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: DropPolicy) {
+        self.policy = policy;
+    }
+
+    /// The producer and consumer indices of the ring.
+    fn indices(&self) -> (u32, u32) {
+        // SAFETY: the pointers are non-null, so point to indices
+        // shared with the kernel which are still mapped.
+        unsafe {
+            (
+                (*(self.producer as *const AtomicU32)).load(Ordering::Acquire),
+                (*(self.consumer as *const AtomicU32)).load(Ordering::Acquire),
+            )
+        }
+    }
+
+    fn outstanding(&self) -> usize {
+        let (prod, cons) = self.indices();
+        prod.wrapping_sub(cons) as usize
+    }
+
+    /// The addresses of the frames the kernel is yet to consume.
+    fn outstanding_addrs(&self) -> Vec<usize> {
+        let (prod, cons) = self.indices();
+
+        (0..prod.wrapping_sub(cons))
+            .map(|i| {
+                let idx = (cons.wrapping_add(i) & self.mask) as usize;
+
+                // SAFETY: the entry is within the ring, and between
+                // the consumer and producer indices so written by us
+                // and not yet overwritten.
+                unsafe {
+                    *((self.ring as *const u8).add(idx * self.entry_size) as *const u64) as usize
+                }
+            })
+            .collect()
+    }
+
+    fn wait(&self, timeout: Duration) -> usize {
+        let start = Instant::now();
+
+        loop {
+            let outstanding = self.outstanding();
+
+            if outstanding == 0 || start.elapsed() >= timeout {
+                return outstanding;
+            }
+
+            if let Some(fd) = self.wakeup_fd {
+                // Errors are left to the timeout, there's nothing
+                // more to be done about them here.
+                unsafe { libc::sendto(fd, ptr::null(), 0, libc::MSG_DONTWAIT, ptr::null(), 0) };
+            }
+
+            thread::sleep(DROP_WAIT_INTERVAL);
+        }
+    }
+}
+
+/// How long to sleep between checks of the ring when waiting under
+/// [`DropPolicy::Wait`].
+const DROP_WAIT_INTERVAL: Duration = Duration::from_millis(1);
+
+impl Drop for OnDrop {
+    fn drop(&mut self) {
+        if self.ring.is_null() {
+            return;
+        }
+
+        let outstanding = match mem::take(&mut self.policy) {
+            DropPolicy::Leak => return,
+            DropPolicy::Warn => self.outstanding(),
+            DropPolicy::Wait(timeout) => self.wait(timeout),
+            DropPolicy::Callback(mut f) => {
+                let addrs = self.outstanding_addrs();
+
+                if !addrs.is_empty() {
+                    f(&addrs);
+                }
+
+                return;
+            }
+        };
+
+        if outstanding > 0 {
+            warn!(
+                "{} queue dropped with {} frames still on its ring",
+                self.kind, outstanding
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::sim::SimXsk;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Recorder {
+        limit: Option<usize>,
+        produced: Vec<usize>,
+        consumed: Vec<usize>,
+        posts: Vec<(RingKind, usize)>,
+    }
+
+    impl QueueHooks for Recorder {
+        fn pre_produce(&mut self, _ring: RingKind, nb: usize) -> usize {
+            self.limit.unwrap_or(nb)
+        }
+
+        fn on_produce(&mut self, _ring: RingKind, desc: &FrameDesc) {
+            self.produced.push(desc.addr());
+        }
+
+        fn post_produce(&mut self, ring: RingKind, cnt: usize) {
+            self.posts.push((ring, cnt));
+        }
+
+        fn on_consume(&mut self, _ring: RingKind, desc: &FrameDesc) {
+            self.consumed.push(desc.addr());
+        }
+
+        fn post_consume(&mut self, ring: RingKind, cnt: usize) {
+            self.posts.push((ring, cnt));
+        }
+    }
+
+    #[test]
+    fn hooks_see_each_frame_moved() {
+        let SimXsk {
+            descs,
+            tx_q,
+            cq,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(16);
+
+        let mut tx_q = tx_q.with_hooks(Recorder::default());
+        let mut cq = cq.with_hooks(Recorder::default());
+
+        assert_eq!(unsafe { tx_q.produce(&descs[..3]) }, 3);
+        assert!(kernel.run_until_idle(16));
+
+        let mut completed = [FrameDesc::default(); 4];
+        assert_eq!(unsafe { cq.consume(&mut completed) }, 3);
+
+        let addrs: Vec<_> = descs[..3].iter().map(|d| d.addr()).collect();
+
+        assert_eq!(tx_q.hooks().produced, addrs);
+        assert_eq!(tx_q.hooks().posts, [(RingKind::Tx, 3)]);
+        assert_eq!(cq.hooks().consumed, addrs);
+        assert_eq!(cq.hooks().posts, [(RingKind::Completion, 3)]);
+    }
+
+    #[test]
+    fn drop_callback_is_given_the_frames_left_on_the_ring() {
+        let SimXsk {
+            descs,
+            mut tx_q,
+            mut fq,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(16);
+
+        let left = Arc::new(Mutex::new(Vec::new()));
+
+        let callback = |left: &Arc<Mutex<Vec<usize>>>| {
+            let left = Arc::clone(left);
+            DropPolicy::Callback(Box::new(move |addrs| {
+                left.lock().unwrap().extend_from_slice(addrs)
+            }))
+        };
+
+        tx_q.set_drop_policy(callback(&left));
+        fq.set_drop_policy(callback(&left));
+
+        assert_eq!(unsafe { tx_q.produce(&descs[..2]) }, 2);
+        assert_eq!(unsafe { fq.produce(&descs[4..6]) }, 2);
+
+        assert!(kernel.run_until_idle(16));
+        assert!(kernel.inject(b"hello"));
+
+        assert_eq!(unsafe { tx_q.produce(&descs[2..3]) }, 1);
+
+        drop(tx_q);
+        assert_eq!(*left.lock().unwrap(), [descs[2].addr()]);
+
+        drop(fq);
+        assert_eq!(*left.lock().unwrap(), [descs[2].addr(), descs[5].addr()]);
+    }
+
+    #[test]
+    fn drop_waits_for_the_kernel_to_consume_the_ring() {
+        let SimXsk {
+            descs,
+            mut tx_q,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(16);
+
+        tx_q.set_drop_policy(DropPolicy::Wait(Duration::from_secs(10)));
+
+        assert_eq!(unsafe { tx_q.produce(&descs[..2]) }, 2);
+
+        let kernel = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            kernel.run_until_idle(16);
+            kernel
+        });
+
+        let start = Instant::now();
+        drop(tx_q);
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(kernel.join().unwrap().stats().tx_packets, 2);
+    }
+
+    #[test]
+    fn pre_produce_limits_the_room_on_the_ring() {
+        let SimXsk { descs, fq, .. } = SimXsk::with_defaults(16);
+
+        let mut fq = fq.with_hooks(Recorder {
+            limit: Some(2),
+            ..Recorder::default()
+        });
+
+        // All or nothing, so nothing.
+        assert_eq!(unsafe { fq.produce(&descs[..4]) }, 0);
+        assert_eq!(unsafe { fq.produce_iter(&descs[..4]) }, 2);
+
+        fq.hooks_mut().limit = None;
+        assert_eq!(unsafe { fq.produce(&descs[2..4]) }, 2);
+
+        assert_eq!(
+            fq.hooks().posts,
+            [
+                (RingKind::Fill, 0),
+                (RingKind::Fill, 2),
+                (RingKind::Fill, 2)
+            ]
+        );
+        assert_eq!(fq.hooks().produced.len(), 4);
+    }
+}
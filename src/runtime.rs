@@ -0,0 +1,906 @@
+//! A receive worker that drives an [`RxQueue`] and [`FillQueue`],
+//! with a [`ConfigHandle`] for tuning it while it runs, and an
+//! [`AdaptiveBusyPoll`] controller for switching it between busy
+//! polling and interrupts.
+//!
+//! For servicing many sockets from one thread, a [`SocketGroup`]
+//! shares a per-iteration budget between them by weight.
+
+use std::{
+    convert::TryInto,
+    io,
+    num::NonZeroU32,
+    os::unix::prelude::AsRawFd,
+    sync::{
+        atomic::{AtomicI32, AtomicU32, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    clock::{Clock, MonotonicClock},
+    socket::{Fd, RxQueue},
+    umem::{frame::Data, frame::FrameDesc, FillQueue, Umem},
+    util,
+};
+
+/// The default maximum number of frames an [`RxWorker`] receives per
+/// iteration, whatever its configured
+/// [`batch_size`](WorkerConfig::batch_size). Its descriptors are kept
+/// in an array of this length rather than on the heap, see
+/// [`RxWorker::with_batch_capacity`] for choosing a different one.
+pub const MAX_BATCH: usize = 64;
+
+/// When a worker wakes up the kernel after handing frames back to the
+/// [`FillQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeupPolicy {
+    /// Only wake up the kernel if the fill ring's
+    /// [`XDP_USE_NEED_WAKEUP`](crate::config::BindFlags::XDP_USE_NEED_WAKEUP)
+    /// flag is set.
+    IfNeeded,
+    /// Always wake up the kernel.
+    Always,
+}
+
+impl WakeupPolicy {
+    fn from_u8(val: u8) -> Self {
+        match val {
+            1 => WakeupPolicy::Always,
+            _ => WakeupPolicy::IfNeeded,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            WakeupPolicy::IfNeeded => 0,
+            WakeupPolicy::Always => 1,
+        }
+    }
+}
+
+/// A snapshot of the settings held by a [`ConfigHandle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerConfig {
+    batch_size: NonZeroU32,
+    poll_timeout: i32,
+    wakeup_policy: WakeupPolicy,
+}
+
+impl WorkerConfig {
+    /// Create a new `WorkerConfig`. The default is a batch size of
+    /// 64, a poll timeout of 100ms and [`WakeupPolicy::IfNeeded`].
+    pub fn new(batch_size: NonZeroU32, poll_timeout: i32, wakeup_policy: WakeupPolicy) -> Self {
+        Self {
+            batch_size,
+            poll_timeout,
+            wakeup_policy,
+        }
+    }
+
+    /// The maximum number of frames received per iteration. Capped at
+    /// the batch capacity of the worker, by default [`MAX_BATCH`].
+    #[inline]
+    pub fn batch_size(&self) -> NonZeroU32 {
+        self.batch_size
+    }
+
+    /// The poll timeout in milliseconds. Negative values block
+    /// indefinitely.
+    #[inline]
+    pub fn poll_timeout(&self) -> i32 {
+        self.poll_timeout
+    }
+
+    /// The [`WakeupPolicy`] in use.
+    #[inline]
+    pub fn wakeup_policy(&self) -> WakeupPolicy {
+        self.wakeup_policy
+    }
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self::new(
+            (MAX_BATCH as u32).try_into().unwrap(),
+            100,
+            WakeupPolicy::IfNeeded,
+        )
+    }
+}
+
+#[derive(Debug)]
+struct AtomicConfig {
+    batch_size: AtomicU32,
+    poll_timeout: AtomicI32,
+    wakeup_policy: AtomicU8,
+}
+
+/// A shared handle to a worker's settings.
+///
+/// Clones refer to the same settings, so a clone may be kept
+/// elsewhere to tune a running worker. Changes are picked up at the
+/// start of the worker's next iteration. Each setting is updated
+/// independently, so an iteration may see some but not all of a set
+/// of changes made concurrently.
+#[derive(Debug, Clone)]
+pub struct ConfigHandle {
+    inner: Arc<AtomicConfig>,
+}
+
+impl ConfigHandle {
+    /// Create a new `ConfigHandle` with the settings in `config`.
+    pub fn new(config: WorkerConfig) -> Self {
+        Self {
+            inner: Arc::new(AtomicConfig {
+                batch_size: AtomicU32::new(config.batch_size.get()),
+                poll_timeout: AtomicI32::new(config.poll_timeout),
+                wakeup_policy: AtomicU8::new(config.wakeup_policy.as_u8()),
+            }),
+        }
+    }
+
+    /// The current settings.
+    pub fn load(&self) -> WorkerConfig {
+        let batch_size = self.inner.batch_size.load(Ordering::Relaxed);
+
+        WorkerConfig {
+            // Only ever set from a `NonZeroU32`.
+            batch_size: NonZeroU32::new(batch_size).unwrap(),
+            poll_timeout: self.inner.poll_timeout.load(Ordering::Relaxed),
+            wakeup_policy: WakeupPolicy::from_u8(self.inner.wakeup_policy.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Set the maximum number of frames received per iteration.
+    pub fn set_batch_size(&self, size: NonZeroU32) {
+        self.inner.batch_size.store(size.get(), Ordering::Relaxed);
+    }
+
+    /// Set the poll timeout in milliseconds.
+    pub fn set_poll_timeout(&self, timeout: i32) {
+        self.inner.poll_timeout.store(timeout, Ordering::Relaxed);
+    }
+
+    /// Set the [`WakeupPolicy`].
+    pub fn set_wakeup_policy(&self, policy: WakeupPolicy) {
+        self.inner
+            .wakeup_policy
+            .store(policy.as_u8(), Ordering::Relaxed);
+    }
+}
+
+impl Default for ConfigHandle {
+    fn default() -> Self {
+        Self::new(WorkerConfig::default())
+    }
+}
+
+/// How a socket is currently being polled by an [`AdaptiveBusyPoll`]
+/// controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollMode {
+    /// Preferred busy polling is enabled, and the socket should be
+    /// polled without blocking, so the device's interrupts stay
+    /// deferred.
+    Busy,
+    /// Busy polling is disabled, and the socket is left to wait on
+    /// the device's interrupts.
+    Interrupt,
+}
+
+/// Switches a socket between busy polling and interrupt driven
+/// polling depending on traffic.
+///
+/// The socket starts off busy polling, with `SO_PREFER_BUSY_POLL`,
+/// `SO_BUSY_POLL` and `SO_BUSY_POLL_BUDGET` set. Once no packets have
+/// been received for `idle_after` busy polling is disabled, so an
+/// idle socket doesn't keep a core spinning, and it's re-enabled as
+/// soon as a packet arrives.
+///
+/// Call [`update`](Self::update) after every receive, and poll with
+/// the timeout given by [`poll_timeout`](Self::poll_timeout). An
+/// [`RxWorker`] can do both, see
+/// [`set_adaptive_busy_poll`](RxWorker::set_adaptive_busy_poll).
+#[derive(Debug)]
+pub struct AdaptiveBusyPoll<C = MonotonicClock> {
+    busy_timeout: Duration,
+    budget: u16,
+    idle_after: Duration,
+    mode: PollMode,
+    last_traffic: Duration,
+    clock: C,
+}
+
+impl AdaptiveBusyPoll<MonotonicClock> {
+    /// Create a new `AdaptiveBusyPoll`, busy polling with
+    /// `SO_BUSY_POLL` set to `busy_timeout` and `SO_BUSY_POLL_BUDGET`
+    /// to `budget`, and falling back to interrupts after `idle_after`
+    /// without traffic. See [`Fd::set_busy_poll`] and friends for
+    /// the privileges these need.
+    pub fn new(busy_timeout: Duration, budget: u16, idle_after: Duration) -> Self {
+        Self::with_clock(busy_timeout, budget, idle_after, MonotonicClock)
+    }
+}
+
+impl<C: Clock> AdaptiveBusyPoll<C> {
+    /// Same as [`new`](AdaptiveBusyPoll::new), but reading the time
+    /// from `clock`.
+    pub fn with_clock(busy_timeout: Duration, budget: u16, idle_after: Duration, clock: C) -> Self {
+        Self {
+            busy_timeout,
+            budget,
+            idle_after,
+            // Not yet applied to any socket, so the first update
+            // enables busy polling.
+            mode: PollMode::Interrupt,
+            last_traffic: clock.now(),
+            clock,
+        }
+    }
+
+    /// The mode the socket was last switched to.
+    #[inline]
+    pub fn mode(&self) -> PollMode {
+        self.mode
+    }
+
+    /// The timeout to poll the socket with, zero while busy polling
+    /// and `idle_timeout` otherwise.
+    #[inline]
+    pub fn poll_timeout(&self, idle_timeout: i32) -> i32 {
+        match self.mode {
+            PollMode::Busy => 0,
+            PollMode::Interrupt => idle_timeout,
+        }
+    }
+
+    /// The mode to be in having just received `cnt` packets.
+    fn next_mode(&mut self, cnt: usize) -> PollMode {
+        let now = self.clock.now();
+
+        if cnt > 0 {
+            self.last_traffic = now;
+            PollMode::Busy
+        } else if now.saturating_sub(self.last_traffic) >= self.idle_after {
+            PollMode::Interrupt
+        } else {
+            self.mode
+        }
+    }
+
+    /// Record that `cnt` packets were just received on the socket
+    /// `fd`, switching its mode if need be. Returns the mode it's now
+    /// in.
+    ///
+    /// If the socket options can't be set the error is returned and
+    /// the mode left unchanged, so the switch is retried on the next
+    /// update.
+    pub fn update(&mut self, fd: &Fd, cnt: usize) -> io::Result<PollMode> {
+        let mode = self.next_mode(cnt);
+
+        if mode != self.mode {
+            match mode {
+                PollMode::Busy => fd.enable_busy_poll(self.busy_timeout, self.budget)?,
+                PollMode::Interrupt => {
+                    fd.set_prefer_busy_poll(false)?;
+                    fd.set_busy_poll(Duration::ZERO)?;
+                }
+            }
+
+            self.mode = mode;
+        }
+
+        Ok(mode)
+    }
+}
+
+/// Receives packets in batches, handing each to a callback before
+/// returning its frame to the [`FillQueue`].
+///
+/// At most `N` frames are received per iteration, with their
+/// descriptors held in an array of that length.
+#[derive(Debug)]
+pub struct RxWorker<const N: usize = MAX_BATCH> {
+    umem: Umem,
+    fq: FillQueue,
+    rx_q: RxQueue,
+    free: Vec<FrameDesc>,
+    descs: [FrameDesc; N],
+    config: ConfigHandle,
+    busy_poll: Option<AdaptiveBusyPoll>,
+}
+
+impl RxWorker {
+    /// Create a new `RxWorker` with a batch capacity of [`MAX_BATCH`],
+    /// placing as many of `frames` on the
+    /// fill ring as will fit. Any left over are used to top the fill
+    /// ring back up should the kernel hand frames back faster than
+    /// they can be returned.
+    ///
+    /// # Safety
+    ///
+    /// `frames` must belong to `umem`, as must the queues, and none of
+    /// the frames may be in use elsewhere, either in userspace or by
+    /// the kernel.
+    pub unsafe fn new(
+        umem: Umem,
+        fq: FillQueue,
+        rx_q: RxQueue,
+        frames: Vec<FrameDesc>,
+        config: ConfigHandle,
+    ) -> Self {
+        // SAFETY: upheld by the caller.
+        unsafe { Self::with_batch_capacity(umem, fq, rx_q, frames, config) }
+    }
+}
+
+impl<const N: usize> RxWorker<N> {
+    /// Same as [`new`](RxWorker::new), but receiving at most `N`
+    /// frames per iteration rather than [`MAX_BATCH`].
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](RxWorker::new).
+    ///
+    /// # Panics
+    ///
+    /// If `N` is zero.
+    pub unsafe fn with_batch_capacity(
+        umem: Umem,
+        mut fq: FillQueue,
+        rx_q: RxQueue,
+        mut frames: Vec<FrameDesc>,
+        config: ConfigHandle,
+    ) -> Self {
+        assert!(N > 0, "batch capacity must be non-zero");
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // frames belong to `umem` and are free for use.
+        unsafe { fq.fill_from(&mut frames) };
+
+        Self {
+            umem,
+            fq,
+            rx_q,
+            free: frames,
+            descs: [FrameDesc::default(); N],
+            config,
+            busy_poll: None,
+        }
+    }
+
+    /// The handle to this worker's settings.
+    #[inline]
+    pub fn config(&self) -> &ConfigHandle {
+        &self.config
+    }
+
+    /// Have `busy_poll` switch the worker's socket between busy
+    /// polling and interrupts. While busy polling the worker polls
+    /// without blocking, otherwise it uses the configured
+    /// [`poll_timeout`](WorkerConfig::poll_timeout).
+    pub fn set_adaptive_busy_poll(&mut self, busy_poll: AdaptiveBusyPoll) {
+        self.busy_poll = Some(busy_poll);
+    }
+
+    /// The worker's busy polling controller, if any.
+    #[inline]
+    pub fn adaptive_busy_poll(&self) -> Option<&AdaptiveBusyPoll> {
+        self.busy_poll.as_ref()
+    }
+
+    /// Poll for up to one batch of packets, passing the data of each
+    /// to `f`. Returns the number of packets received.
+    pub fn run_once<F>(&mut self, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(Data<'_>),
+    {
+        let config = self.config.load();
+
+        let batch_size = util::min_usize(config.batch_size.get() as usize, N);
+
+        let poll_timeout = match &self.busy_poll {
+            Some(busy_poll) => busy_poll.poll_timeout(config.poll_timeout),
+            None => config.poll_timeout,
+        };
+
+        // SAFETY: all frames on the fill ring came from `new` or
+        // were handed back below, so belong to this `Umem`.
+        let cnt = unsafe {
+            self.rx_q
+                .poll_and_consume(&mut self.descs[..batch_size], poll_timeout)?
+        };
+
+        if let Some(busy_poll) = &mut self.busy_poll {
+            busy_poll.update(self.rx_q.fd(), cnt)?;
+        }
+
+        for desc in self.descs.iter().take(cnt) {
+            // SAFETY: the frame was just received so the kernel is
+            // done with it, and it isn't handed back until after `f`
+            // is done with its data.
+            f(unsafe { self.umem.data(desc) });
+        }
+
+        // SAFETY: see above.
+        unsafe {
+            if self.fq.produce(&self.descs[..cnt]) != cnt {
+                self.free.extend_from_slice(&self.descs[..cnt]);
+            }
+
+            self.fq.fill_from(&mut self.free);
+        }
+
+        let wakeup = match config.wakeup_policy {
+            WakeupPolicy::IfNeeded => self.fq.needs_wakeup(),
+            WakeupPolicy::Always => true,
+        };
+
+        if wakeup {
+            self.fq.wakeup(self.rx_q.fd_mut(), 0)?;
+        }
+
+        Ok(cnt)
+    }
+}
+
+/// How a member of a [`SocketGroup`] has been serviced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemberStats {
+    received: u64,
+    quota_exhausted: u64,
+    starved: u64,
+    starved_streak: u64,
+    longest_starvation: u64,
+}
+
+impl MemberStats {
+    /// The number of packets received.
+    #[inline]
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// The number of iterations in which the member used its whole
+    /// quota and still had packets waiting.
+    #[inline]
+    pub fn quota_exhausted(&self) -> u64 {
+        self.quota_exhausted
+    }
+
+    /// The number of iterations in which the member had packets
+    /// waiting but the group's budget ran out before its turn.
+    #[inline]
+    pub fn starved(&self) -> u64 {
+        self.starved
+    }
+
+    /// The most consecutive iterations the member has been starved
+    /// for.
+    #[inline]
+    pub fn longest_starvation(&self) -> u64 {
+        self.longest_starvation
+    }
+
+    fn record_starved(&mut self) {
+        self.starved += 1;
+        self.starved_streak += 1;
+        self.longest_starvation = self.longest_starvation.max(self.starved_streak);
+    }
+}
+
+#[derive(Debug)]
+struct Member {
+    rx_q: RxQueue,
+    fq: FillQueue,
+    weight: u32,
+    stats: MemberStats,
+}
+
+/// Services several [`RxQueue`]s from one thread, sharing a budget of
+/// packets per iteration between them in proportion to their weights.
+///
+/// Each iteration a member may receive up to its quota, its weighted
+/// share of the budget and at least one packet, until the budget runs
+/// out. The member served first moves along by one each iteration,
+/// so that when the quotas add up to more than the budget, no member
+/// is always the one left without. [`MemberStats`] show how often
+/// each member has gone without.
+///
+/// Suited to appliances terminating many low-rate interfaces, where a
+/// thread per socket would mostly sit idle.
+#[derive(Debug)]
+pub struct SocketGroup {
+    members: Vec<Member>,
+    budget: NonZeroU32,
+    total_weight: u64,
+    next: usize,
+    pollfds: Vec<libc::pollfd>,
+}
+
+impl SocketGroup {
+    /// Create an empty `SocketGroup`, receiving at most `budget`
+    /// packets per iteration across all its members.
+    pub fn new(budget: NonZeroU32) -> Self {
+        Self {
+            members: Vec::new(),
+            budget,
+            total_weight: 0,
+            next: 0,
+            pollfds: Vec::new(),
+        }
+    }
+
+    /// Add a socket's queues to the group with `weight`, returning the
+    /// index it's known by in calls to [`run_once`](Self::run_once).
+    ///
+    /// Frames received on `rx_q` are recycled back on to `fq`, so the
+    /// fill ring should be populated beforehand.
+    ///
+    /// # Panics
+    ///
+    /// If `fq` belongs to a [`Umem`] not sharing `rx_q`'s memory.
+    pub fn add(&mut self, rx_q: RxQueue, fq: FillQueue, weight: NonZeroU32) -> usize {
+        assert!(
+            fq.umem().shares_mem(rx_q.umem()),
+            "fill queue belongs to a different UMEM"
+        );
+
+        self.pollfds.push(libc::pollfd {
+            fd: rx_q.fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        });
+
+        self.total_weight += weight.get() as u64;
+
+        self.members.push(Member {
+            rx_q,
+            fq,
+            weight: weight.get(),
+            stats: MemberStats::default(),
+        });
+
+        self.members.len() - 1
+    }
+
+    /// The number of members.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Whether the group has no members.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// The statistics of member `idx`.
+    #[inline]
+    pub fn stats(&self, idx: usize) -> Option<&MemberStats> {
+        self.members.get(idx).map(|m| &m.stats)
+    }
+
+    /// The queues of member `idx`.
+    #[inline]
+    pub fn queues_mut(&mut self, idx: usize) -> Option<(&mut RxQueue, &mut FillQueue)> {
+        self.members.get_mut(idx).map(|m| (&mut m.rx_q, &mut m.fq))
+    }
+
+    /// The most packets member `idx` may receive in an iteration.
+    fn quota(&self, idx: usize) -> usize {
+        let share = self.budget.get() as u64 * self.members[idx].weight as u64 / self.total_weight;
+
+        share.max(1) as usize
+    }
+
+    /// Run one iteration, passing the index of the member and the data
+    /// of each packet received to `f`. Returns the number of packets
+    /// received.
+    ///
+    /// If no member has packets waiting, first polls every member's
+    /// socket for up to `poll_timeout` milliseconds.
+    pub fn run_once<F>(&mut self, poll_timeout: i32, mut f: F) -> io::Result<usize>
+    where
+        F: FnMut(usize, Data<'_>),
+    {
+        let len = self.members.len();
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        if poll_timeout != 0 && self.members.iter_mut().all(|m| m.rx_q.available() == 0) {
+            let ret = unsafe {
+                libc::poll(
+                    self.pollfds.as_mut_ptr(),
+                    self.pollfds.len() as libc::nfds_t,
+                    poll_timeout,
+                )
+            };
+
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+
+        let mut remaining = self.budget.get() as usize;
+
+        for i in (self.next..len).chain(0..self.next) {
+            let quota = self.quota(i);
+            let member = &mut self.members[i];
+
+            if remaining == 0 {
+                if member.rx_q.available() > 0 {
+                    member.stats.record_starved();
+                }
+                continue;
+            }
+
+            let max = quota.min(remaining);
+
+            let cnt = member
+                .rx_q
+                .consume_with(&mut member.fq, max, |data| f(i, data));
+
+            remaining -= cnt;
+
+            member.stats.received += cnt as u64;
+            member.stats.starved_streak = 0;
+
+            if cnt == max && member.rx_q.available() > 0 {
+                member.stats.quota_exhausted += 1;
+            }
+
+            if cnt > 0 && member.fq.needs_wakeup() {
+                member.fq.wakeup(member.rx_q.fd_mut(), 0)?;
+            }
+        }
+
+        self.next = (self.next + 1) % len;
+
+        Ok(self.budget.get() as usize - remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        clock::MockClock,
+        config::{QueueSize, SocketConfig, UmemConfig},
+        sim::{SimConfig, SimKernel, SimXsk},
+    };
+
+    use super::*;
+
+    #[test]
+    fn busy_polling_relaxes_when_idle_and_resumes_under_load() {
+        let clock = MockClock::new();
+
+        let mut busy_poll = AdaptiveBusyPoll::with_clock(
+            Duration::from_micros(20),
+            8,
+            Duration::from_millis(10),
+            clock.clone(),
+        );
+
+        assert_eq!(busy_poll.next_mode(0), PollMode::Interrupt);
+        assert_eq!(busy_poll.next_mode(4), PollMode::Busy);
+        busy_poll.mode = PollMode::Busy;
+
+        assert_eq!(busy_poll.poll_timeout(100), 0);
+
+        clock.advance(Duration::from_millis(9));
+        assert_eq!(busy_poll.next_mode(0), PollMode::Busy);
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(busy_poll.next_mode(0), PollMode::Interrupt);
+        busy_poll.mode = PollMode::Interrupt;
+
+        assert_eq!(busy_poll.poll_timeout(100), 100);
+        assert_eq!(busy_poll.next_mode(1), PollMode::Busy);
+    }
+
+    #[test]
+    fn socket_options_follow_the_mode() {
+        let xsk = SimXsk::with_defaults(16);
+
+        let clock = MockClock::new();
+
+        let mut busy_poll =
+            AdaptiveBusyPoll::with_clock(Duration::ZERO, 8, Duration::from_secs(1), clock.clone());
+
+        assert_eq!(busy_poll.update(xsk.rx_q.fd(), 1).unwrap(), PollMode::Busy);
+
+        clock.advance(Duration::from_secs(1));
+
+        assert_eq!(
+            busy_poll.update(xsk.rx_q.fd(), 0).unwrap(),
+            PollMode::Interrupt
+        );
+        assert_eq!(busy_poll.mode(), PollMode::Interrupt);
+    }
+
+    fn member(group: &mut SocketGroup, weight: u32) -> SimKernel {
+        let SimXsk {
+            descs,
+            mut fq,
+            rx_q,
+            kernel,
+            ..
+        } = SimXsk::with_defaults(32);
+
+        assert_eq!(unsafe { fq.produce(&descs) }, 32);
+
+        group.add(rx_q, fq, weight.try_into().unwrap());
+
+        kernel
+    }
+
+    #[test]
+    fn budget_is_shared_by_weight() {
+        let mut group = SocketGroup::new(8.try_into().unwrap());
+
+        let mut kernels = [member(&mut group, 3), member(&mut group, 1)];
+
+        for kernel in kernels.iter_mut() {
+            for _ in 0..10 {
+                assert!(kernel.inject(b"hello"));
+            }
+        }
+
+        let mut received = [0; 2];
+
+        assert_eq!(group.run_once(0, |i, _| received[i] += 1).unwrap(), 8);
+        assert_eq!(received, [6, 2]);
+
+        for i in 0..2 {
+            assert_eq!(group.stats(i).unwrap().quota_exhausted(), 1);
+            assert_eq!(group.stats(i).unwrap().starved(), 0);
+        }
+
+        // The first has fewer than its quota left, so the budget
+        // isn't used up.
+        assert_eq!(group.run_once(0, |i, _| received[i] += 1).unwrap(), 6);
+        assert_eq!(received, [10, 4]);
+        assert_eq!(group.stats(0).unwrap().quota_exhausted(), 1);
+    }
+
+    #[test]
+    fn starvation_rotates_between_members() {
+        let mut group = SocketGroup::new(2.try_into().unwrap());
+
+        let mut kernels: Vec<_> = (0..3).map(|_| member(&mut group, 1)).collect();
+
+        for kernel in kernels.iter_mut() {
+            for _ in 0..3 {
+                assert!(kernel.inject(b"hello"));
+            }
+        }
+
+        for _ in 0..3 {
+            assert_eq!(group.run_once(0, |_, _| ()).unwrap(), 2);
+        }
+
+        for i in 0..3 {
+            let stats = group.stats(i).unwrap();
+
+            assert_eq!(stats.received(), 2);
+            assert_eq!(stats.starved(), 1);
+            assert_eq!(stats.longest_starvation(), 1);
+        }
+
+        // Nothing waiting, so the group polls and times out.
+        for _ in 0..2 {
+            group.run_once(0, |_, _| ()).unwrap();
+        }
+        assert_eq!(group.run_once(1, |_, _| ()).unwrap(), 0);
+    }
+
+    #[test]
+    fn config_changes_are_seen_through_clones() {
+        let handle = ConfigHandle::default();
+        let clone = handle.clone();
+
+        clone.set_batch_size(8.try_into().unwrap());
+        clone.set_poll_timeout(-1);
+        clone.set_wakeup_policy(WakeupPolicy::Always);
+
+        let config = handle.load();
+
+        assert_eq!(config.batch_size().get(), 8);
+        assert_eq!(config.poll_timeout(), -1);
+        assert_eq!(config.wakeup_policy(), WakeupPolicy::Always);
+    }
+
+    #[test]
+    fn batch_size_change_applies_to_next_iteration() {
+        let umem_config = UmemConfig::builder()
+            .fill_queue_size(QueueSize::new(16).unwrap())
+            .build()
+            .unwrap();
+
+        let SimXsk {
+            umem,
+            descs,
+            fq,
+            rx_q,
+            mut kernel,
+            ..
+        } = SimXsk::new(
+            umem_config,
+            SocketConfig::default(),
+            32.try_into().unwrap(),
+            SimConfig::default(),
+        )
+        .unwrap();
+
+        let handle = ConfigHandle::new(WorkerConfig::new(
+            4.try_into().unwrap(),
+            0,
+            WakeupPolicy::IfNeeded,
+        ));
+
+        let mut worker = unsafe { RxWorker::new(umem, fq, rx_q, descs, handle.clone()) };
+
+        for i in 0..12u8 {
+            assert!(kernel.inject(&[i]));
+        }
+
+        let mut received = Vec::new();
+
+        assert_eq!(
+            worker
+                .run_once(|data| received.push(data.contents()[0]))
+                .unwrap(),
+            4
+        );
+
+        handle.set_batch_size(8.try_into().unwrap());
+
+        assert_eq!(
+            worker
+                .run_once(|data| received.push(data.contents()[0]))
+                .unwrap(),
+            8
+        );
+
+        assert_eq!(received, (0..12).collect::<Vec<_>>());
+
+        // All frames were handed back, so there's room to receive more.
+        for i in 0..16u8 {
+            assert!(kernel.inject(&[i]));
+        }
+    }
+
+    #[test]
+    fn batch_size_is_capped_at_the_batch_capacity() {
+        let SimXsk {
+            umem,
+            descs,
+            fq,
+            rx_q,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(32);
+
+        let handle = ConfigHandle::new(WorkerConfig::default());
+
+        let mut worker =
+            unsafe { RxWorker::<4>::with_batch_capacity(umem, fq, rx_q, descs, handle) };
+
+        for i in 0..6u8 {
+            assert!(kernel.inject(&[i]));
+        }
+
+        assert_eq!(worker.run_once(|_| ()).unwrap(), 4);
+        assert_eq!(worker.run_once(|_| ()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,85 @@
+//! The subset of the crate usable without `unsafe`, for codebases
+//! which `forbid(unsafe_code)`.
+//!
+//! Everything here can be driven entirely through safe methods, and
+//! this module is itself compiled with `forbid(unsafe_code)`. The
+//! types re-exported do also have `unsafe` methods, working with
+//! [`FrameDesc`](crate::FrameDesc)s rather than owned [`Frame`]s, but
+//! those can't be called from a crate forbidding `unsafe`, so sticking
+//! to this module is enough to stay within the safe subset:
+//!
+//! - A socket and its [`Umem`] are created together with
+//!   [`Socket::new_owned`], with further sockets on the same queue
+//!   bound via [`Socket::new_shared`].
+//! - Frames are owned [`Frame`]s, read and written through
+//!   [`Frame::data`] and [`Frame::data_mut`], and pooled in a plain
+//!   `Vec<Frame>`.
+//! - Frames are handed to and taken back from the kernel with
+//!   [`FillQueue::produce_frames`], [`RxQueue::consume_frames`],
+//!   [`TxQueue::produce_frames`] and [`CompQueue::consume_frames`].
+//! - Many sockets can be waited on at once with a [`PollSet`].
+//! - With the `easy` feature, `XskDevice` bundles all of the above
+//!   behind `send` and `recv` methods.
+//!
+//! ```no_run
+//! #![forbid(unsafe_code)]
+//!
+//! use std::{convert::TryInto, io::Write};
+//! use xsk_rs::safe::{
+//!     config::{SocketConfig, UmemConfig},
+//!     OwnedSocket, Socket,
+//! };
+//!
+//! let OwnedSocket {
+//!     mut frames,
+//!     mut fq,
+//!     mut tx_q,
+//!     mut rx_q,
+//!     ..
+//! } = Socket::new_owned(
+//!     SocketConfig::default(),
+//!     UmemConfig::default(),
+//!     64.try_into().unwrap(),
+//!     &"eth0".parse().unwrap(),
+//!     0,
+//! )
+//! .expect("failed to create socket");
+//!
+//! // Send a packet from one frame, and receive into the rest.
+//! let mut tx_frames = frames.split_off(frames.len() - 1);
+//!
+//! tx_frames[0]
+//!     .data_mut()
+//!     .cursor()
+//!     .write_all(b"hello")
+//!     .unwrap();
+//!
+//! tx_q.produce_frames(&mut tx_frames);
+//! tx_q.wakeup().unwrap();
+//!
+//! fq.produce_frames(&mut frames);
+//!
+//! if rx_q.poll(100).unwrap() {
+//!     rx_q.consume_frames(&mut frames, 64);
+//!
+//!     for frame in frames.iter() {
+//!         println!("received {} bytes", frame.data().contents().len());
+//!     }
+//! }
+//! ```
+#![forbid(unsafe_code)]
+
+pub use crate::{config, DropCallback, DropPolicy};
+
+pub use crate::socket::{
+    OwnedSocket, PollEvents, PollSet, Ready, RxQueue, Socket, SocketCreateError,
+    SocketCreateErrorKind, TxQueue, XdpStatistics,
+};
+
+pub use crate::umem::{
+    frame::{Cursor, Data, DataMut, Frame, Headroom, HeadroomMut},
+    CompQueue, FillQueue, Umem,
+};
+
+#[cfg(feature = "easy")]
+pub use crate::easy::{DeviceConfig, OpenError, XskDevice};
@@ -0,0 +1,182 @@
+//! Injecting rare kernel behaviour into the simulated backend.
+
+use crate::ring::{QueueHooks, RingKind};
+
+use super::SimRng;
+
+/// How often to inject failures, each given as a chance between `0.0`
+/// and `1.0`. The default injects none.
+///
+/// The kernel side failures are injected by the [`SimKernel`] given
+/// this config in [`SimConfig::faults`], while produce failures need
+/// [`FaultHooks`] setting on the queues.
+///
+/// [`SimKernel`]: super::SimKernel
+/// [`SimConfig::faults`]: super::SimConfig::faults
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FaultConfig {
+    /// Seed for the generator deciding when failures are injected.
+    /// Kept apart from [`SimConfig::seed`](super::SimConfig::seed) so
+    /// enabling failures leaves the rest of the schedule as it was.
+    pub seed: u64,
+    /// The chance of the kernel ignoring a wakeup, as happens when the
+    /// `sendto` behind [`TxQueue::wakeup`](crate::TxQueue::wakeup)
+    /// fails with `EAGAIN`. Only noticeable if the kernel needs
+    /// waking up, see
+    /// [`XDP_USE_NEED_WAKEUP`](crate::config::BindFlags::XDP_USE_NEED_WAKEUP).
+    pub lost_wakeup: f64,
+    /// The chance of the kernel not signalling the socket readable
+    /// when the rx ring has entries, so that polling times out
+    /// despite there being packets to consume. Rolled again each time
+    /// the kernel steps or receives a packet.
+    pub missed_rx_signal: f64,
+    /// The chance of a produce finding room for only half its frames,
+    /// rounded down. Calls which submit all or nothing, such as
+    /// [`TxQueue::produce`](crate::TxQueue::produce), then submit
+    /// none.
+    pub partial_produce: f64,
+}
+
+/// [`QueueHooks`] injecting the produce failures of a
+/// [`FaultConfig`]. Set on a queue with `with_hooks`, for example
+/// [`FillQueue::with_hooks`](crate::FillQueue::with_hooks).
+#[derive(Debug, Clone)]
+pub struct FaultHooks {
+    rng: SimRng,
+    partial_produce: f64,
+    injected: u64,
+}
+
+impl FaultHooks {
+    /// Create a new `FaultHooks` injecting failures as per `config`.
+    pub fn new(config: FaultConfig) -> Self {
+        Self {
+            rng: SimRng::new(config.seed),
+            partial_produce: config.partial_produce,
+            injected: 0,
+        }
+    }
+
+    /// The number of failures injected so far.
+    #[inline]
+    pub fn injected(&self) -> u64 {
+        self.injected
+    }
+}
+
+impl QueueHooks for FaultHooks {
+    fn pre_produce(&mut self, _ring: RingKind, nb: usize) -> usize {
+        if self.rng.chance(self.partial_produce) {
+            self.injected += 1;
+            nb / 2
+        } else {
+            nb
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::{
+        config::{BindFlags, SocketConfig, UmemConfig},
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    fn build(faults: FaultConfig) -> SimXsk {
+        let socket_config = SocketConfig::builder()
+            .bind_flags(BindFlags::XDP_USE_NEED_WAKEUP)
+            .build();
+
+        let sim_config = SimConfig {
+            faults,
+            ..SimConfig::default()
+        };
+
+        SimXsk::new(
+            UmemConfig::default(),
+            socket_config,
+            16.try_into().unwrap(),
+            sim_config,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn lost_wakeups_leave_the_kernel_asleep() {
+        let mut xsk = build(FaultConfig {
+            lost_wakeup: 1.0,
+            ..FaultConfig::default()
+        });
+
+        // Sent without a wakeup, after which the kernel sleeps.
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..1]) }, 1);
+        assert!(xsk.kernel.run_until_idle(16));
+        assert!(xsk.tx_q.needs_wakeup());
+
+        assert_eq!(
+            unsafe { xsk.tx_q.produce_and_wakeup(&xsk.descs[1..2]) }.unwrap(),
+            1
+        );
+        assert!(!xsk.kernel.run_until_idle(16));
+
+        let stats = xsk.kernel.stats();
+
+        assert_eq!(stats.lost_wakeups, 1);
+        assert_eq!(stats.tx_packets, 1);
+    }
+
+    #[test]
+    fn missed_rx_signals_make_polls_time_out() {
+        let mut xsk = build(FaultConfig {
+            missed_rx_signal: 1.0,
+            ..FaultConfig::default()
+        });
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..1]) }, 1);
+        assert!(xsk.kernel.inject(b"hello"));
+
+        assert!(!xsk.rx_q.poll(0).unwrap());
+        assert_eq!(xsk.rx_q.available(), 1);
+        assert_eq!(xsk.kernel.stats().missed_rx_signals, 1);
+    }
+
+    #[test]
+    fn fault_hooks_halve_produces() {
+        let SimXsk { descs, tx_q, .. } = build(FaultConfig::default());
+
+        let mut tx_q = tx_q.with_hooks(FaultHooks::new(FaultConfig {
+            partial_produce: 1.0,
+            ..FaultConfig::default()
+        }));
+
+        assert_eq!(unsafe { tx_q.produce(&descs[..4]) }, 0);
+        assert_eq!(unsafe { tx_q.produce_partial(&descs[..5]) }, 2);
+        assert_eq!(tx_q.hooks().injected(), 2);
+    }
+
+    #[test]
+    fn the_same_seed_injects_the_same_failures() {
+        let config = FaultConfig {
+            seed: 7,
+            partial_produce: 0.5,
+            ..FaultConfig::default()
+        };
+
+        let schedule = || {
+            let mut hooks = FaultHooks::new(config);
+
+            (0..64)
+                .map(|_| hooks.pre_produce(RingKind::Fill, 8))
+                .collect::<Vec<_>>()
+        };
+
+        let first = schedule();
+
+        assert_eq!(first, schedule());
+        assert!(first.contains(&4) && first.contains(&8));
+    }
+}
@@ -0,0 +1,984 @@
+//! A simulated AF_XDP backend.
+//!
+//! Builds the usual set of queues on top of heap-backed rings and an
+//! unregistered [`Umem`], with a [`SimKernel`] sitting on the other
+//! side of each ring playing the part of the kernel. The kernel
+//! consumes fill ring entries to receive injected packets, consumes
+//! the tx ring and hands frames back over the completion ring after
+//! a delay, all according to a schedule determined by a seed. This
+//! makes it possible to exercise frame recycling logic
+//! deterministically and without root permissions or a veth pair.
+//!
+//! Readiness is modelled with a unix socket pair so that polling and
+//! waking up the queues behaves as it would with a real socket: the
+//! socket is readable while the rx ring is non-empty, and wakeups
+//! sent via [`TxQueue::wakeup`] are seen by the kernel.
+//!
+//! Rare kernel behaviour, such as lost wakeups, can be injected on a
+//! seeded schedule of its own to test an application's recovery from
+//! it, see [`FaultConfig`].
+//!
+//! Requires the `testing` feature.
+
+mod ring;
+use ring::KernelRing;
+pub(crate) use ring::RingMem;
+
+mod rng;
+pub(crate) use rng::SimRng;
+
+mod faults;
+pub use faults::{FaultConfig, FaultHooks};
+
+pub mod replay;
+
+use libc::{AF_UNIX, MSG_DONTWAIT, SOCK_CLOEXEC, SOCK_DGRAM, SOCK_NONBLOCK};
+use libxdp_sys::{xdp_desc, XDP_RING_NEED_WAKEUP, XSK_UNALIGNED_BUF_OFFSET_SHIFT};
+use std::{
+    collections::VecDeque,
+    io,
+    num::NonZeroU32,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    ptr,
+};
+
+use crate::{
+    config::{BindFlags, SocketConfig, UmemConfig},
+    ring::{XskRingCons, XskRingProd},
+    socket::{RxQueue, Socket, TxQueue},
+    umem::{
+        frame::FrameDesc,
+        ledger::{FrameState, Ledger},
+        unaligned, CompQueue, FillQueue, Umem,
+    },
+};
+
+/// Configuration for the simulated kernel's schedule.
+#[derive(Debug, Clone, Copy)]
+pub struct SimConfig {
+    /// Seed for the generator driving the schedule. The same seed
+    /// and sequence of user actions always produces the same
+    /// outcome.
+    pub seed: u64,
+    /// The maximum number of tx ring entries processed per step. The
+    /// actual number is chosen randomly between one and this value.
+    pub max_tx_batch: u32,
+    /// Inclusive range, in steps, by which completions are delayed
+    /// after the frame is taken off the tx ring.
+    pub completion_delay: (u64, u64),
+    /// If set then transmitted packets are received back on the rx
+    /// ring, as if the socket were bound to a loopback device.
+    pub loopback: bool,
+    /// The initial producer and consumer index of every ring. Useful
+    /// for exercising index wrap-around.
+    pub ring_start_idx: u32,
+    /// Failures for the kernel to inject. Default is none.
+    pub faults: FaultConfig,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            max_tx_batch: 16,
+            completion_delay: (0, 4),
+            loopback: false,
+            ring_start_idx: 0,
+            faults: FaultConfig::default(),
+        }
+    }
+}
+
+/// Counters maintained by the simulated kernel. The first few mirror
+/// those found in [`XdpStatistics`](crate::socket::XdpStatistics).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SimStats {
+    /// Packets dropped for reasons other than a full rx ring or an
+    /// empty fill ring.
+    pub rx_dropped: u64,
+    /// Invalid descriptors found on the fill ring.
+    pub rx_invalid_descs: u64,
+    /// Invalid descriptors found on the tx ring.
+    pub tx_invalid_descs: u64,
+    /// Packets dropped because the rx ring was full.
+    pub rx_ring_full: u64,
+    /// Packets dropped because the fill ring was empty.
+    pub rx_fill_ring_empty_descs: u64,
+    /// Packets placed on the rx ring.
+    pub rx_packets: u64,
+    /// Packets taken off the tx ring and sent.
+    pub tx_packets: u64,
+    /// Frames handed back over the completion ring.
+    pub completions: u64,
+    /// Wakeups received from user-space.
+    pub wakeups: u64,
+    /// Wakeups ignored as per [`FaultConfig::lost_wakeup`].
+    pub lost_wakeups: u64,
+    /// Times the socket wasn't signalled readable as per
+    /// [`FaultConfig::missed_rx_signal`].
+    pub missed_rx_signals: u64,
+}
+
+/// A full set of queues bound to a simulated kernel.
+#[derive(Debug)]
+pub struct SimXsk {
+    /// The UMEM shared by the queues.
+    pub umem: Umem,
+    /// A descriptor for each of the UMEM's frames.
+    pub descs: Vec<FrameDesc>,
+    /// The UMEM's fill queue.
+    pub fq: FillQueue,
+    /// The UMEM's completion queue.
+    pub cq: CompQueue,
+    /// The socket's tx queue.
+    pub tx_q: TxQueue,
+    /// The socket's rx queue.
+    pub rx_q: RxQueue,
+    /// The simulated kernel on the other side of the rings.
+    pub kernel: SimKernel,
+}
+
+impl SimXsk {
+    /// Build a simulated socket and UMEM. Queue sizes are taken from
+    /// `umem_config` and `socket_config` as usual, and the kernel
+    /// will require wakeups if the
+    /// [`XDP_USE_NEED_WAKEUP`](BindFlags::XDP_USE_NEED_WAKEUP) bind
+    /// flag is set.
+    pub fn new(
+        umem_config: UmemConfig,
+        socket_config: SocketConfig,
+        frame_count: NonZeroU32,
+        sim_config: SimConfig,
+    ) -> io::Result<Self> {
+        let (umem, descs) =
+            Umem::new_unregistered(umem_config, frame_count).map_err(io::Error::other)?;
+
+        Self::with_umem(umem, descs, socket_config, sim_config)
+    }
+
+    /// Build another simulated socket sharing this one's UMEM, with
+    /// its own simulated kernel and fill and completion rings, as if
+    /// bound to a different `(if_name, queue_id)` pair.
+    ///
+    /// Its `descs` are empty, since the UMEM's frames are all
+    /// described by this socket's.
+    pub fn new_shared(
+        &self,
+        socket_config: SocketConfig,
+        sim_config: SimConfig,
+    ) -> io::Result<Self> {
+        Self::with_umem(self.umem.clone(), Vec::new(), socket_config, sim_config)
+    }
+
+    /// A simulated socket and UMEM of `frame_count` frames, with the
+    /// default configuration throughout.
+    #[cfg(test)]
+    pub(crate) fn with_defaults(frame_count: u32) -> Self {
+        Self::with_sim_config(frame_count, SimConfig::default())
+    }
+
+    /// As [`with_defaults`](Self::with_defaults), but with the kernel
+    /// simulated as per `sim_config`.
+    #[cfg(test)]
+    pub(crate) fn with_sim_config(frame_count: u32, sim_config: SimConfig) -> Self {
+        Self::new(
+            UmemConfig::default(),
+            SocketConfig::default(),
+            NonZeroU32::new(frame_count).expect("frame count is non-zero"),
+            sim_config,
+        )
+        .unwrap()
+    }
+
+    /// A simulated socket and UMEM of 32 frames, with every ring 16
+    /// entries long so that tests can easily fill them.
+    #[cfg(test)]
+    pub(crate) fn with_small_rings(sim_config: SimConfig, bind_flags: BindFlags) -> Self {
+        use crate::config::QueueSize;
+
+        let umem_config = UmemConfig::builder()
+            .fill_queue_size(QueueSize::new(16).unwrap())
+            .comp_queue_size(QueueSize::new(16).unwrap())
+            .build()
+            .unwrap();
+
+        let socket_config = SocketConfig::builder()
+            .rx_queue_size(QueueSize::new(16).unwrap())
+            .tx_queue_size(QueueSize::new(16).unwrap())
+            .bind_flags(bind_flags)
+            .build();
+
+        Self::new(
+            umem_config,
+            socket_config,
+            NonZeroU32::new(32).unwrap(),
+            sim_config,
+        )
+        .unwrap()
+    }
+
+    fn with_umem(
+        umem: Umem,
+        descs: Vec<FrameDesc>,
+        socket_config: SocketConfig,
+        sim_config: SimConfig,
+    ) -> io::Result<Self> {
+        let umem_config = *umem.config();
+
+        let start_idx = sim_config.ring_start_idx;
+
+        let fq_mem = RingMem::new::<u64>(umem_config.fill_queue_size().get(), start_idx);
+        let cq_mem = RingMem::new::<u64>(umem_config.comp_queue_size().get(), start_idx);
+        let rx_mem = RingMem::new::<xdp_desc>(socket_config.rx_queue_size().get(), start_idx);
+        let tx_mem = RingMem::new::<xdp_desc>(socket_config.tx_queue_size().get(), start_idx);
+
+        let (user_fd, kernel_fd) = socket_pair()?;
+
+        let socket_fd = user_fd.as_raw_fd();
+        let if_name = "sim".parse().expect("valid interface name");
+        let socket = Socket::from_owned_fd(user_fd, &umem, &if_name, 0, socket_config);
+
+        let kernel = SimKernel {
+            umem: umem.clone(),
+            umem_config,
+            fill: KernelRing::new(fq_mem.clone()),
+            comp: KernelRing::new(cq_mem.clone()),
+            rx: KernelRing::new(rx_mem.clone()),
+            tx: KernelRing::new(tx_mem.clone()),
+            socket_fd,
+            _socket: socket.clone(),
+            fd: kernel_fd,
+            rng: SimRng::new(sim_config.seed),
+            fault_rng: SimRng::new(sim_config.faults.seed),
+            config: sim_config,
+            need_wakeup: socket_config
+                .bind_flags()
+                .contains(BindFlags::XDP_USE_NEED_WAKEUP),
+            now: 0,
+            awake: true,
+            rx_signalled: false,
+            pending_comps: VecDeque::new(),
+            stats: SimStats::default(),
+        };
+
+        Ok(Self {
+            fq: FillQueue::new(XskRingProd::from_mem(fq_mem), umem.clone()),
+            cq: CompQueue::new(XskRingCons::from_mem(cq_mem), umem.clone()),
+            tx_q: TxQueue::new(XskRingProd::from_mem(tx_mem), socket.clone()),
+            rx_q: RxQueue::new(XskRingCons::from_mem(rx_mem), socket),
+            umem,
+            descs,
+            kernel,
+        })
+    }
+}
+
+/// The simulated kernel side of a [`SimXsk`].
+///
+/// Nothing happens until [`step`](Self::step) is called, with the
+/// exception of [`inject`](Self::inject) which delivers a packet
+/// immediately.
+#[derive(Debug)]
+pub struct SimKernel {
+    umem: Umem,
+    umem_config: UmemConfig,
+    fill: KernelRing<u64>,
+    comp: KernelRing<u64>,
+    rx: KernelRing<xdp_desc>,
+    tx: KernelRing<xdp_desc>,
+    // Keeps the user's end of the socket pair open.
+    _socket: Socket,
+    socket_fd: RawFd,
+    fd: OwnedFd,
+    rng: SimRng,
+    fault_rng: SimRng,
+    config: SimConfig,
+    need_wakeup: bool,
+    now: u64,
+    awake: bool,
+    rx_signalled: bool,
+    // Ordered by due time.
+    pending_comps: VecDeque<(u64, u64)>,
+    stats: SimStats,
+}
+
+impl SimKernel {
+    /// The kernel's counters so far.
+    pub fn stats(&self) -> SimStats {
+        self.stats
+    }
+
+    /// Receive a packet, placing it in a frame taken from the fill
+    /// ring and submitting that frame to the rx ring. Returns `false`
+    /// if the packet was dropped.
+    pub fn inject(&mut self, pkt: &[u8]) -> bool {
+        let delivered = self.receive(pkt);
+        self.update_rx_readiness();
+        delivered
+    }
+
+    /// Advance the simulation by one step. Processes any wakeups,
+    /// consumes a randomly sized batch from the tx ring and submits
+    /// any completions that have come due.
+    pub fn step(&mut self) {
+        self.now += 1;
+
+        self.drain_wakeups();
+        self.transmit();
+        self.complete();
+        self.update_fill_wakeup_flag();
+        self.update_rx_readiness();
+    }
+
+    /// Step until there are no more frames waiting on the tx ring or
+    /// for completion, or until `max_steps` steps have been taken.
+    /// Returns `true` if the kernel went idle, which won't happen if
+    /// it's asleep waiting on a wakeup with frames on the tx ring.
+    pub fn run_until_idle(&mut self, max_steps: u64) -> bool {
+        for _ in 0..max_steps {
+            if self.is_idle() {
+                return true;
+            }
+            self.step();
+        }
+        self.is_idle()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.tx.len() == 0 && self.pending_comps.is_empty()
+    }
+
+    /// Addresses of all frames not currently owned by user-space,
+    /// i.e. those sitting on any of the four rings or waiting to be
+    /// completed. Addresses are normalised to point at the start of
+    /// the frame's packet data segment, as with the descriptors
+    /// returned by [`Umem::new`].
+    pub fn outstanding(&self) -> Vec<usize> {
+        let fill = self.fill.entries();
+        let comp = self.comp.entries();
+        let rx = self.rx.entries().map(|d| d.addr);
+        let tx = self.tx.entries().map(|d| d.addr);
+        let pending = self.pending_comps.iter().map(|(_, addr)| *addr);
+
+        fill.chain(comp)
+            .chain(rx)
+            .chain(tx)
+            .chain(pending)
+            .map(|addr| self.data_addr(addr) as usize)
+            .collect()
+    }
+
+    /// A [`Ledger`] of the UMEM's frames as this kernel sees them.
+    /// Frames on the fill ring, or holding a packet on the rx ring,
+    /// are on the fill ring. Frames on the tx ring, waiting to be
+    /// completed or on the completion ring are on the tx ring. Any
+    /// others are owned by the user.
+    ///
+    /// Only this kernel's rings are looked at, so with a shared UMEM
+    /// frames on another socket's rings show as owned by the user.
+    pub fn ledger(&self) -> Ledger {
+        let ledger = Ledger::for_umem(&self.umem);
+
+        let fill = self
+            .fill
+            .entries()
+            .chain(self.rx.entries().map(|d| unaligned::extract_addr(d.addr)));
+
+        let tx = self
+            .tx
+            .entries()
+            .map(|d| d.addr)
+            .chain(self.pending_comps.iter().map(|(_, addr)| *addr))
+            .chain(self.comp.entries());
+
+        // Invalid addresses and frames submitted twice are tolerated
+        // by the kernel, so are left out rather than failing.
+        for addr in fill {
+            let _ = ledger.submit(addr as usize, FrameState::FillRing);
+        }
+
+        for addr in tx {
+            let _ = ledger.submit(addr as usize, FrameState::TxRing);
+        }
+
+        ledger
+    }
+
+    fn frame_size(&self) -> u64 {
+        self.umem_config.frame_size().get() as u64
+    }
+
+    fn headroom(&self) -> u64 {
+        (self.umem_config.xdp_headroom() + self.umem_config.frame_headroom()) as u64
+    }
+
+    /// The address of the packet data segment of the frame filled
+    /// with `addr`. In unaligned chunk mode `addr` is the start of the
+    /// frame, otherwise anywhere within it.
+    fn data_addr(&self, addr: u64) -> u64 {
+        if self.umem_config.unaligned_chunks() {
+            addr + self.headroom()
+        } else {
+            addr - (addr % self.frame_size()) + self.headroom()
+        }
+    }
+
+    /// The address to report on the rx ring for a packet received into
+    /// the frame filled with `addr`, encoding the offset in the upper
+    /// bits in unaligned chunk mode as the kernel does.
+    fn rx_addr(&self, addr: u64) -> u64 {
+        if self.umem_config.unaligned_chunks() {
+            addr | (self.headroom() << XSK_UNALIGNED_BUF_OFFSET_SHIFT)
+        } else {
+            self.data_addr(addr)
+        }
+    }
+
+    fn is_valid_addr(&self, addr: u64) -> bool {
+        addr < self.umem.len() as u64
+    }
+
+    fn is_valid_desc(&self, desc: &xdp_desc) -> bool {
+        let chunk_end = addr_chunk_end(desc.addr, self.frame_size());
+        let end = desc.addr + desc.len as u64;
+
+        // Packets may cross frame boundaries in unaligned chunk mode.
+        let within_chunk = self.umem_config.unaligned_chunks() || end <= chunk_end;
+
+        self.is_valid_addr(desc.addr) && within_chunk && end <= self.umem.len() as u64
+    }
+
+    fn receive(&mut self, pkt: &[u8]) -> bool {
+        if pkt.len() > self.umem_config.mtu() as usize {
+            self.stats.rx_dropped += 1;
+            return false;
+        }
+
+        loop {
+            if self.fill.len() == 0 {
+                self.stats.rx_fill_ring_empty_descs += 1;
+                return false;
+            }
+
+            if self.rx.free() == 0 {
+                self.stats.rx_ring_full += 1;
+                return false;
+            }
+
+            let addr = self.fill.peek(0);
+            self.fill.release(1);
+
+            if !self.is_valid_addr(addr) {
+                self.stats.rx_invalid_descs += 1;
+                continue;
+            }
+
+            let data_addr = self.data_addr(addr);
+
+            // SAFETY: `data_addr` lies within the UMEM and `pkt` fits
+            // in the frame's packet data segment since it is no
+            // larger than the MTU. The frame was handed over via the
+            // fill ring so user-space won't be accessing it.
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    pkt.as_ptr(),
+                    (self.umem.as_ptr() as *mut u8).add(data_addr as usize),
+                    pkt.len(),
+                );
+            }
+
+            let pushed = self.rx.push(xdp_desc {
+                addr: self.rx_addr(addr),
+                len: pkt.len() as u32,
+                options: 0,
+            });
+
+            debug_assert!(pushed);
+
+            self.stats.rx_packets += 1;
+
+            return true;
+        }
+    }
+
+    fn transmit(&mut self) {
+        if self.need_wakeup && !self.awake {
+            return;
+        }
+
+        // Like the kernel, only take frames off the tx ring if there
+        // is guaranteed to be room to complete them.
+        let comp_budget = (self.comp.free() as u64).saturating_sub(self.pending_comps.len() as u64);
+
+        let batch = self.rng.range(1, self.config.max_tx_batch.max(1) as u64);
+        let n = (self.tx.len() as u64).min(batch).min(comp_budget) as u32;
+
+        for i in 0..n {
+            let desc = self.tx.peek(i);
+
+            if !self.is_valid_desc(&desc) {
+                // Invalid descriptors are skipped and never completed.
+                self.stats.tx_invalid_descs += 1;
+                continue;
+            }
+
+            if self.config.loopback {
+                let mut pkt = vec![0; desc.len as usize];
+
+                // SAFETY: descriptor was validated above to lie
+                // within the UMEM, and the frame is owned by the
+                // kernel until completed.
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        (self.umem.as_ptr() as *const u8).add(desc.addr as usize),
+                        pkt.as_mut_ptr(),
+                        pkt.len(),
+                    );
+                }
+
+                self.receive(&pkt);
+            }
+
+            let delay = self.rng.range(
+                self.config.completion_delay.0,
+                self.config.completion_delay.1,
+            );
+
+            // Completions are handed back in the order they were sent.
+            let due = self
+                .pending_comps
+                .back()
+                .map_or(self.now + delay, |(last, _)| (*last).max(self.now + delay));
+
+            self.pending_comps.push_back((due, desc.addr));
+
+            self.stats.tx_packets += 1;
+        }
+
+        self.tx.release(n);
+
+        if self.need_wakeup && self.tx.len() == 0 {
+            self.awake = false;
+            self.tx.set_flags(XDP_RING_NEED_WAKEUP);
+        }
+    }
+
+    fn complete(&mut self) {
+        while let Some((due, addr)) = self.pending_comps.front().copied() {
+            if due > self.now || !self.comp.push(addr) {
+                break;
+            }
+            self.pending_comps.pop_front();
+            self.stats.completions += 1;
+        }
+    }
+
+    fn drain_wakeups(&mut self) {
+        let mut buf = [0u8; 1];
+
+        loop {
+            let ret = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    MSG_DONTWAIT,
+                )
+            };
+
+            if ret < 0 {
+                break;
+            }
+
+            self.stats.wakeups += 1;
+
+            if self.fault_rng.chance(self.config.faults.lost_wakeup) {
+                self.stats.lost_wakeups += 1;
+                continue;
+            }
+
+            self.awake = true;
+            self.tx.clear_flags(XDP_RING_NEED_WAKEUP);
+        }
+    }
+
+    fn update_fill_wakeup_flag(&mut self) {
+        if !self.need_wakeup {
+            return;
+        }
+
+        if self.fill.len() == 0 {
+            self.fill.set_flags(XDP_RING_NEED_WAKEUP);
+        } else {
+            self.fill.clear_flags(XDP_RING_NEED_WAKEUP);
+        }
+    }
+
+    /// Make the user's end of the socket pair readable iff the rx
+    /// ring has entries waiting.
+    fn update_rx_readiness(&mut self) {
+        let has_entries = self.rx.len() > 0;
+
+        if has_entries && !self.rx_signalled {
+            if self.fault_rng.chance(self.config.faults.missed_rx_signal) {
+                self.stats.missed_rx_signals += 1;
+                return;
+            }
+
+            let buf = [0u8; 1];
+            let ret = unsafe {
+                libc::send(
+                    self.fd.as_raw_fd(),
+                    buf.as_ptr() as *const libc::c_void,
+                    buf.len(),
+                    MSG_DONTWAIT,
+                )
+            };
+            self.rx_signalled = ret >= 0;
+        } else if !has_entries && self.rx_signalled {
+            let mut buf = [0u8; 1];
+            unsafe {
+                libc::recv(
+                    self.socket_fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    MSG_DONTWAIT,
+                )
+            };
+            self.rx_signalled = false;
+        }
+    }
+}
+
+fn addr_chunk_end(addr: u64, frame_size: u64) -> u64 {
+    addr - (addr % frame_size) + frame_size
+}
+
+fn socket_pair() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+
+    let err = unsafe {
+        libc::socketpair(
+            AF_UNIX,
+            SOCK_DGRAM | SOCK_NONBLOCK | SOCK_CLOEXEC,
+            0,
+            fds.as_mut_ptr(),
+        )
+    };
+
+    if err != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `socketpair` succeeded so both fds are open and owned
+    // by us alone.
+    unsafe { Ok((OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1]))) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, io::Write};
+
+    use super::*;
+
+    #[test]
+    fn injected_packets_are_received_in_fill_ring_frames() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+
+        assert!(!xsk.rx_q.poll(0).unwrap());
+
+        assert!(xsk.kernel.inject(b"hello"));
+        assert!(xsk.kernel.inject(b"world!"));
+        assert!(!xsk.kernel.inject(b"dropped"));
+
+        assert!(xsk.rx_q.poll(0).unwrap());
+
+        let mut descs = vec![FrameDesc::default(); 4];
+        assert_eq!(unsafe { xsk.rx_q.consume(&mut descs) }, 2);
+
+        assert_eq!(descs[0].addr(), xsk.descs[0].addr());
+        assert_eq!(unsafe { xsk.umem.data(&descs[0]) }.contents(), b"hello");
+        assert_eq!(unsafe { xsk.umem.data(&descs[1]) }.contents(), b"world!");
+
+        assert_eq!(xsk.kernel.stats().rx_fill_ring_empty_descs, 1);
+
+        xsk.kernel.step();
+        assert!(!xsk.rx_q.poll(0).unwrap());
+    }
+
+    #[test]
+    fn completions_are_delayed_and_in_order() {
+        let config = SimConfig {
+            completion_delay: (2, 2),
+            ..SimConfig::default()
+        };
+
+        let mut xsk = SimXsk::with_small_rings(config, BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..3]) }, 3);
+
+        let mut descs = vec![FrameDesc::default(); 4];
+
+        xsk.kernel.step();
+        xsk.kernel.step();
+        assert_eq!(unsafe { xsk.cq.consume(&mut descs) }, 0);
+
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(unsafe { xsk.cq.consume(&mut descs) }, 3);
+
+        for (sent, completed) in xsk.descs[..3].iter().zip(descs.iter()) {
+            assert_eq!(sent.addr(), completed.addr());
+        }
+    }
+
+    #[test]
+    fn tx_is_only_processed_after_wakeup_when_required() {
+        let mut xsk =
+            SimXsk::with_small_rings(SimConfig::default(), BindFlags::XDP_USE_NEED_WAKEUP);
+
+        // Initially awake, goes to sleep once the tx ring drains.
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..1]) }, 1);
+        assert!(xsk.kernel.run_until_idle(16));
+        assert!(xsk.tx_q.needs_wakeup());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[1..2]) }, 1);
+        xsk.kernel.step();
+        assert_eq!(xsk.kernel.stats().tx_packets, 1);
+
+        xsk.tx_q.wakeup().unwrap();
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(xsk.kernel.stats().wakeups, 1);
+        assert_eq!(xsk.kernel.stats().tx_packets, 2);
+    }
+
+    #[test]
+    fn invalid_tx_descs_are_never_completed() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        let mut desc = xsk.descs[0];
+        desc.lengths.data = 1 << 20;
+
+        assert_eq!(unsafe { xsk.tx_q.produce_one(&desc) }, 1);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(xsk.kernel.stats().tx_invalid_descs, 1);
+        assert_eq!(xsk.kernel.stats().completions, 0);
+    }
+
+    /// Runs a randomised workload in which user-space echoes every
+    /// received frame back out of the tx ring and recycles completed
+    /// frames on to the fill ring. Returns the kernel's final stats
+    /// after checking that no frame was lost or duplicated along the
+    /// way.
+    fn echo_workload(seed: u64, ring_start_idx: u32) -> SimStats {
+        let config = SimConfig {
+            seed,
+            loopback: false,
+            completion_delay: (0, 8),
+            max_tx_batch: 8,
+            ring_start_idx,
+            ..SimConfig::default()
+        };
+
+        let mut xsk = SimXsk::with_small_rings(config, BindFlags::XDP_USE_NEED_WAKEUP);
+        let mut rng = SimRng::new(seed ^ 0xdead_beef);
+
+        let all: HashSet<usize> = xsk.descs.iter().map(|d| d.addr()).collect();
+
+        let mut free: Vec<FrameDesc> = xsk.descs.clone();
+        let mut scratch = vec![FrameDesc::default(); xsk.descs.len()];
+
+        for i in 0..2000u32 {
+            match rng.range(0, 4) {
+                0 => {
+                    let n = rng.range(0, free.len() as u64) as usize;
+                    let start = free.len() - n;
+                    let cnt = unsafe { xsk.fq.produce(&free[start..]) };
+                    if cnt > 0 {
+                        free.truncate(start);
+                    }
+                }
+                1 => {
+                    let len = rng.range(1, 64) as usize;
+                    xsk.kernel.inject(&i.to_le_bytes().repeat(len));
+                }
+                2 => {
+                    let cnt = unsafe { xsk.rx_q.consume(&mut scratch) };
+                    for desc in scratch[..cnt].iter_mut() {
+                        unsafe { xsk.umem.data_mut(desc) }
+                            .cursor()
+                            .write_all(b"echo")
+                            .unwrap();
+                    }
+                    let sent = unsafe { xsk.tx_q.produce_and_wakeup(&scratch[..cnt]) }.unwrap();
+                    if sent == 0 {
+                        free.extend_from_slice(&scratch[..cnt]);
+                    }
+                }
+                3 => {
+                    let cnt = unsafe { xsk.cq.consume(&mut scratch) };
+                    free.extend_from_slice(&scratch[..cnt]);
+                }
+                _ => xsk.kernel.step(),
+            }
+
+            let mut seen: Vec<usize> = free.iter().map(|d| d.addr()).collect();
+            seen.extend(xsk.kernel.outstanding());
+
+            assert_eq!(seen.len(), all.len(), "frame lost or duplicated");
+            assert_eq!(seen.into_iter().collect::<HashSet<_>>(), all);
+
+            let ledger = xsk.kernel.ledger();
+
+            assert_eq!(ledger.count(FrameState::User), free.len());
+            assert!(free.iter().all(|d| ledger.is_user_owned(d.addr())));
+
+            #[cfg(feature = "debug-tracking")]
+            for state in [FrameState::User, FrameState::FillRing, FrameState::TxRing] {
+                assert!(xsk
+                    .umem
+                    .ledger()
+                    .unwrap()
+                    .frames(state)
+                    .eq(ledger.frames(state)));
+            }
+        }
+
+        xsk.kernel.stats()
+    }
+
+    #[test]
+    fn every_ring_op_handles_index_wrap_around() {
+        let config = SimConfig {
+            ring_start_idx: u32::MAX - 5,
+            ..SimConfig::default()
+        };
+
+        let mut xsk = SimXsk::with_small_rings(config, BindFlags::empty());
+
+        // Each round moves every ring's indices on by eight, so the
+        // first crosses the wrap and the rest run after it.
+        for round in 0..4u32 {
+            let descs = &xsk.descs[..8];
+
+            // Reserves the whole ring, so the producer index wraps
+            // before the unused entries are handed back.
+            assert_eq!(unsafe { xsk.fq.produce_iter(&descs[..4]) }, 4);
+            assert_eq!(unsafe { xsk.fq.produce(&descs[4..7]) }, 3);
+            assert_eq!(unsafe { xsk.fq.produce_one(&descs[7]) }, 1);
+
+            let fill = xsk.fq.ring_info();
+            assert_eq!(fill.occupancy(), 8);
+            assert_eq!(
+                fill.producer(),
+                (u32::MAX - 5).wrapping_add(8 * (round + 1))
+            );
+
+            for i in 0..8u8 {
+                assert!(xsk.kernel.inject(&[round as u8, i]));
+            }
+
+            assert_eq!(xsk.rx_q.available(), 8);
+
+            let mut received = vec![FrameDesc::default(); 8];
+            assert_eq!(unsafe { xsk.rx_q.consume(&mut received[..5]) }, 5);
+            assert_eq!(unsafe { xsk.rx_q.consume_one(&mut received[5]) }, 1);
+            assert_eq!(unsafe { xsk.rx_q.consume(&mut received[6..]) }, 2);
+
+            for (i, desc) in received.iter().enumerate() {
+                assert_eq!(desc.addr(), xsk.descs[i].addr());
+                assert_eq!(
+                    unsafe { xsk.umem.data(desc) }.contents(),
+                    [round as u8, i as u8]
+                );
+            }
+
+            assert_eq!(unsafe { xsk.tx_q.produce_iter(&received[..4]) }, 4);
+            assert_eq!(unsafe { xsk.tx_q.produce(&received[4..7]) }, 3);
+            assert_eq!(unsafe { xsk.tx_q.produce_one(&received[7]) }, 1);
+
+            assert!(xsk.kernel.run_until_idle(16));
+
+            assert_eq!(xsk.cq.available(), 8);
+
+            let mut completed = vec![FrameDesc::default(); 3];
+            assert_eq!(unsafe { xsk.cq.consume(&mut completed) }, 3);
+
+            let mut desc = FrameDesc::default();
+            assert_eq!(unsafe { xsk.cq.consume_one(&mut desc) }, 1);
+            completed.push(desc);
+
+            assert_eq!(unsafe { xsk.cq.consume_into(&mut completed, 8) }, 4);
+
+            for (sent, completed) in received.iter().zip(&completed) {
+                assert_eq!(sent.addr(), completed.addr());
+            }
+
+            assert_eq!(xsk.cq.ring_info().occupancy(), 0);
+        }
+
+        assert_eq!(xsk.kernel.stats().tx_packets, 32);
+    }
+
+    #[test]
+    fn frames_are_conserved_across_index_wrap_around() {
+        for seed in 0..8 {
+            let stats = echo_workload(seed, u32::MAX - 64);
+            assert!(stats.rx_packets > 0);
+        }
+    }
+
+    #[test]
+    fn frames_are_conserved_under_random_schedules() {
+        for seed in 0..32 {
+            let stats = echo_workload(seed, 0);
+            assert!(stats.rx_packets > 0);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_outcome() {
+        assert_eq!(echo_workload(1234, 0), echo_workload(1234, 0));
+    }
+
+    #[cfg(feature = "debug-tracking")]
+    #[test]
+    #[should_panic(expected = "submitted twice: frame at address 4352 is on the fill ring")]
+    fn filling_a_frame_twice_panics() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+
+        unsafe { xsk.fq.produce_one(&xsk.descs[1]) };
+    }
+
+    #[cfg(feature = "debug-tracking")]
+    #[test]
+    #[should_panic(expected = "accessed after submission: frame at address 256 is on the tx ring")]
+    fn accessing_a_frame_on_the_tx_ring_panics() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..1]) }, 1);
+
+        let _ = unsafe { xsk.umem.data(&xsk.descs[0]) };
+    }
+
+    #[cfg(feature = "debug-tracking")]
+    #[test]
+    fn completed_frames_can_be_reused() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..2]) }, 2);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        let mut descs = [FrameDesc::default(); 2];
+        assert_eq!(unsafe { xsk.cq.consume(&mut descs) }, 2);
+
+        assert_eq!(unsafe { xsk.umem.data(&xsk.descs[0]) }.len(), 0);
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+    }
+}
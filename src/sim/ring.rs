@@ -0,0 +1,184 @@
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    mem,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use crate::util;
+
+/// Heap memory standing in for a ring that would otherwise be mmap'd
+/// from an AF_XDP socket. Laid out the same way as far as the libxdp
+/// ring functions are concerned: a producer index, a consumer index,
+/// a flags word and a power-of-two sized array of entries.
+pub struct RingMem {
+    producer: AtomicU32,
+    consumer: AtomicU32,
+    flags: AtomicU32,
+    size: u32,
+    // Stored as `u64`s to guarantee the alignment of both ring entry
+    // types, `u64` addresses and `xdp_desc`s.
+    entries: Box<[UnsafeCell<u64>]>,
+}
+
+// SAFETY: entries are only ever accessed via raw pointers, by
+// user-space (through the libxdp ring functions) and the simulated
+// kernel, each of which only touches its own side of the ring as
+// delimited by the producer and consumer indices.
+unsafe impl Send for RingMem {}
+unsafe impl Sync for RingMem {}
+
+impl RingMem {
+    /// Allocate a ring with `size` entries of type `T`, with both
+    /// producer and consumer indices starting at `start_idx`.
+    pub fn new<T>(size: u32, start_idx: u32) -> Arc<Self> {
+        assert!(
+            util::is_pow_of_two(size),
+            "ring size must be a power of two"
+        );
+        assert!(mem::align_of::<T>() <= mem::align_of::<u64>());
+
+        let words = (size as usize * mem::size_of::<T>()).div_ceil(8);
+
+        Arc::new(Self {
+            producer: AtomicU32::new(start_idx),
+            consumer: AtomicU32::new(start_idx),
+            flags: AtomicU32::new(0),
+            size,
+            entries: (0..words).map(|_| UnsafeCell::new(0)).collect(),
+        })
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn producer(&self) -> u32 {
+        self.producer.load(Ordering::Acquire)
+    }
+
+    pub fn consumer(&self) -> u32 {
+        self.consumer.load(Ordering::Acquire)
+    }
+
+    pub fn producer_ptr(&self) -> *mut u32 {
+        &self.producer as *const AtomicU32 as *mut u32
+    }
+
+    pub fn consumer_ptr(&self) -> *mut u32 {
+        &self.consumer as *const AtomicU32 as *mut u32
+    }
+
+    pub fn flags_ptr(&self) -> *mut u32 {
+        &self.flags as *const AtomicU32 as *mut u32
+    }
+
+    pub fn ring_ptr(&self) -> *mut libc::c_void {
+        self.entries.as_ptr() as *mut libc::c_void
+    }
+}
+
+impl fmt::Debug for RingMem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RingMem")
+            .field("producer", &self.producer)
+            .field("consumer", &self.consumer)
+            .field("flags", &self.flags)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// The kernel's side of a simulated ring, holding entries of type
+/// `T`.
+///
+/// Depending on the ring the kernel is either its producer (rx and
+/// completion rings) or its consumer (fill and tx rings), and should
+/// only use the corresponding set of methods.
+#[derive(Debug)]
+pub struct KernelRing<T> {
+    mem: Arc<RingMem>,
+    _entry: PhantomData<T>,
+}
+
+impl<T: Copy> KernelRing<T> {
+    pub fn new(mem: Arc<RingMem>) -> Self {
+        Self {
+            mem,
+            _entry: PhantomData,
+        }
+    }
+
+    fn entry_ptr(&self, idx: u32) -> *mut T {
+        let offset = (idx & (self.mem.size - 1)) as usize;
+        // SAFETY: `offset` is masked to lie within the ring, which was
+        // allocated to hold `size` entries of type `T`.
+        unsafe { (self.mem.ring_ptr() as *mut T).add(offset) }
+    }
+
+    /// Number of entries produced but not yet consumed.
+    pub fn len(&self) -> u32 {
+        self.mem.producer().wrapping_sub(self.mem.consumer())
+    }
+
+    /// Number of entries that may be produced before the ring is
+    /// full.
+    pub fn free(&self) -> u32 {
+        self.mem.size - self.len()
+    }
+
+    /// The `n`th entry waiting to be consumed. Consumer side only.
+    pub fn peek(&self, n: u32) -> T {
+        assert!(n < self.len(), "peeking beyond the ring's producer index");
+
+        let idx = self.mem.consumer().wrapping_add(n);
+        // SAFETY: entries between the consumer and producer indices
+        // have been written and submitted by the producer.
+        unsafe { self.entry_ptr(idx).read() }
+    }
+
+    /// Release `n` entries back to the producer. Consumer side only.
+    pub fn release(&self, n: u32) {
+        assert!(n <= self.len(), "releasing more entries than available");
+
+        self.mem
+            .consumer
+            .store(self.mem.consumer().wrapping_add(n), Ordering::Release);
+    }
+
+    /// Write `entry` to the ring and submit it. Producer side
+    /// only. Returns `false` if the ring is full.
+    pub fn push(&self, entry: T) -> bool {
+        if self.free() == 0 {
+            return false;
+        }
+
+        let idx = self.mem.producer();
+        // SAFETY: the slot at `idx` lies outside the region owned by
+        // the consumer since the ring isn't full.
+        unsafe { self.entry_ptr(idx).write(entry) };
+
+        self.mem
+            .producer
+            .store(idx.wrapping_add(1), Ordering::Release);
+
+        true
+    }
+
+    /// All entries currently sitting in the ring, oldest first.
+    pub fn entries(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map(move |n| self.peek(n))
+    }
+
+    pub fn set_flags(&self, flags: u32) {
+        self.mem.flags.fetch_or(flags, Ordering::Release);
+    }
+
+    pub fn clear_flags(&self, flags: u32) {
+        self.mem.flags.fetch_and(!flags, Ordering::Release);
+    }
+}
@@ -0,0 +1,190 @@
+//! Creating a socket with a timeout, or cancelling its creation.
+
+use std::{
+    borrow::Borrow,
+    io,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{Interface, SocketConfig},
+    umem::{CompQueue, FillQueue, Umem},
+};
+
+use super::{RxQueue, Socket, SocketCreateError, SocketCreateErrorKind, TxQueue};
+
+type Queues = (TxQueue, RxQueue, Option<(FillQueue, CompQueue)>);
+
+/// A handle for cancelling a pending [`Socket::new_cancellable`] call,
+/// for example from another thread during shutdown.
+///
+/// Cancelling is permanent. It applies to every call the token, or a
+/// clone of it, is passed to, including those made after cancelling.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<(Mutex<bool>, Condvar)>);
+
+impl CancelToken {
+    /// Create a new token, not yet cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel any calls waiting on this token.
+    pub fn cancel(&self) {
+        let (state, cvar) = &*self.0;
+
+        *state.lock().unwrap() = true;
+        cvar.notify_all();
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        *self.0 .0.lock().unwrap()
+    }
+
+    /// Wake any calls waiting on this token, so they see a completed
+    /// bind.
+    fn notify_completion(&self) {
+        let (state, cvar) = &*self.0;
+
+        // Taking the lock ensures waiters are either yet to check for
+        // a result or already waiting.
+        let _state = state.lock().unwrap();
+        cvar.notify_all();
+    }
+}
+
+impl Socket {
+    /// Same as [`new`](Self::new) but gives up waiting once `timeout`
+    /// has passed, returning an error for which
+    /// [`timed_out`](SocketCreateError::timed_out) is true.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](Self::new).
+    pub unsafe fn new_with_timeout<C>(
+        config: C,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+        timeout: Duration,
+    ) -> Result<Queues, SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        unsafe {
+            Self::new_cancellable(
+                config,
+                umem,
+                if_name,
+                queue_id,
+                timeout,
+                &CancelToken::new(),
+            )
+        }
+    }
+
+    /// Same as [`new_with_timeout`](Self::new_with_timeout), but also
+    /// gives up waiting if `cancel` is cancelled, returning an error
+    /// for which [`cancelled`](SocketCreateError::cancelled) is true.
+    ///
+    /// Binding can block in the kernel for some time on misbehaving
+    /// drivers, so it's done on a helper thread while this one waits.
+    /// Since a blocked bind can't be interrupted, giving up leaves
+    /// the helper thread running. If the bind eventually succeeds the
+    /// socket is closed straight away. Until then the [`Umem`] remains
+    /// locked, so further attempts to create a socket with it will
+    /// block until the abandoned bind completes.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](Self::new).
+    pub unsafe fn new_cancellable<C>(
+        config: C,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+        timeout: Duration,
+        cancel: &CancelToken,
+    ) -> Result<Queues, SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        // Copied for the helper thread.
+        let config = *config.borrow();
+
+        let deadline = Instant::now() + timeout;
+
+        let result = Arc::new(Mutex::new(None));
+
+        {
+            let result = Arc::clone(&result);
+            let cancel = cancel.clone();
+            let umem = umem.clone();
+            let bind_if_name = if_name.clone();
+
+            thread::Builder::new()
+                .name("xsk-bind".into())
+                .spawn(move || {
+                    // SAFETY: the unsafe contract of `new_cancellable`.
+                    let res = unsafe { Self::new(config, &umem, &bind_if_name, queue_id) };
+
+                    // If the caller has given up the queues are
+                    // dropped along with `result`, closing the socket.
+                    *result.lock().unwrap() = Some(res);
+                    cancel.notify_completion();
+                })
+                .map_err(|e| {
+                    SocketCreateError::new(SocketCreateErrorKind::Thread, e).at(if_name, queue_id)
+                })?;
+        }
+
+        let (state, cvar) = &*cancel.0;
+        let mut cancelled = state.lock().unwrap();
+
+        loop {
+            if let Some(res) = result.lock().unwrap().take() {
+                return res;
+            }
+
+            if *cancelled {
+                return Err(SocketCreateError::new(
+                    SocketCreateErrorKind::Cancelled,
+                    io::Error::from(io::ErrorKind::Interrupted),
+                )
+                .at(if_name, queue_id));
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                return Err(SocketCreateError::new(
+                    SocketCreateErrorKind::TimedOut,
+                    io::Error::from(io::ErrorKind::TimedOut),
+                )
+                .at(if_name, queue_id));
+            }
+
+            cancelled = cvar.wait_timeout(cancelled, deadline - now).unwrap().0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelling_is_seen_by_clones() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+
+        assert!(!clone.is_cancelled());
+
+        token.cancel();
+
+        assert!(clone.is_cancelled());
+    }
+}
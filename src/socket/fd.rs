@@ -1,17 +1,39 @@
 //! File descriptor utilities.
 
+use bitflags::bitflags;
 use libc::{EINTR, POLLIN, POLLOUT, SOL_XDP};
-use libxdp_sys::{xdp_statistics, XDP_STATISTICS};
+use libxdp_sys::{
+    xdp_mmap_offsets, xdp_options, xdp_statistics, XDP_MMAP_OFFSETS, XDP_OPTIONS,
+    XDP_OPTIONS_ZEROCOPY, XDP_STATISTICS,
+};
 use std::{
     fmt,
     io::{self, ErrorKind},
     mem,
-    os::unix::prelude::{AsRawFd, RawFd},
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
 };
 
 use crate::util;
 
-const XDP_STATISTICS_SIZEOF: u32 = mem::size_of::<xdp_statistics>() as u32;
+use super::info::MmapOffsets;
+
+// Not exported by older versions of `libc`. Values for all
+// architectures other than mips, parisc and sparc.
+const SO_COOKIE: libc::c_int = 57;
+const SO_BUSY_POLL: libc::c_int = 46;
+const SO_PREFER_BUSY_POLL: libc::c_int = 69;
+const SO_BUSY_POLL_BUDGET: libc::c_int = 70;
+
+bitflags! {
+    /// The options a socket was bound with, as reported by the
+    /// `XDP_OPTIONS` socket option.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct XdpOptions: u32 {
+        /// The socket is running in zero-copy mode.
+        const ZEROCOPY = XDP_OPTIONS_ZEROCOPY;
+    }
+}
 
 #[derive(Clone, Copy)]
 struct PollFd(libc::pollfd);
@@ -45,7 +67,7 @@ pub struct Fd {
 }
 
 impl Fd {
-    pub(super) fn new(id: i32) -> Self {
+    pub(crate) fn new(id: i32) -> Self {
         let pollfd_read = PollFd(libc::pollfd {
             fd: id,
             events: POLLIN,
@@ -65,6 +87,20 @@ impl Fd {
         }
     }
 
+    /// Wrap a raw AF_XDP socket file descriptor which isn't managed
+    /// by this crate, for example one duplicated from another process
+    /// with `pidfd_getfd`, so that its statistics and ring offsets can
+    /// be queried. The file descriptor is not closed when the returned
+    /// `Fd` is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open AF_XDP socket file descriptor, and must
+    /// remain open for as long as the returned `Fd` is in use.
+    pub unsafe fn borrow_raw(fd: RawFd) -> Self {
+        Self::new(fd)
+    }
+
     pub(super) fn clone(&self) -> Self {
         Self {
             id: self.id,
@@ -88,14 +124,131 @@ impl Fd {
     pub fn xdp_statistics(&self) -> io::Result<XdpStatistics> {
         let mut stats = XdpStatistics::default();
 
-        let mut optlen = XDP_STATISTICS_SIZEOF;
+        self.xdp_sockopt(XDP_STATISTICS, &mut stats.0, "xdp_statistics")?;
+
+        Ok(stats)
+    }
+
+    /// The socket's cookie, a number assigned by the kernel which
+    /// uniquely identifies it for as long as the system is up. Unlike
+    /// the file descriptor it's never reused, and is the same from
+    /// any process the socket is shared with.
+    pub fn cookie(&self) -> io::Result<u64> {
+        let mut cookie: u64 = 0;
+        let mut optlen = mem::size_of::<u64>() as libc::socklen_t;
+
+        let err = unsafe {
+            libc::getsockopt(
+                self.as_raw_fd(),
+                libc::SOL_SOCKET,
+                SO_COOKIE,
+                &mut cookie as *mut u64 as *mut libc::c_void,
+                &mut optlen,
+            )
+        };
+
+        if err != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(cookie)
+    }
+
+    /// Set `SO_BUSY_POLL`, the time for which a blocking receive or
+    /// `poll` busy-polls the device queue before sleeping. Rounded
+    /// down to the microsecond. Setting it above the
+    /// `net.core.busy_read` sysctl requires `CAP_NET_ADMIN`.
+    pub fn set_busy_poll(&self, timeout: Duration) -> io::Result<()> {
+        let timeout_us = timeout.as_micros().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        self.set_socket_sockopt(SO_BUSY_POLL, timeout_us)
+    }
+
+    /// Set `SO_PREFER_BUSY_POLL`, which makes the kernel defer the
+    /// device's interrupts and softirq processing in favour of busy
+    /// polling from userspace, so long as the application keeps
+    /// polling. Requires linux 5.11 or later.
+    ///
+    /// Only takes effect if the device's `napi_defer_hard_irqs` and
+    /// `gro_flush_timeout` are also set, see the kernel's AF_XDP docs.
+    pub fn set_prefer_busy_poll(&self, prefer: bool) -> io::Result<()> {
+        self.set_socket_sockopt(SO_PREFER_BUSY_POLL, prefer as libc::c_int)
+    }
+
+    /// Set `SO_BUSY_POLL_BUDGET`, the maximum number of packets
+    /// processed per busy poll. Setting it above the default of 8
+    /// requires `CAP_NET_ADMIN`. Requires linux 5.11 or later.
+    pub fn set_busy_poll_budget(&self, budget: u16) -> io::Result<()> {
+        self.set_socket_sockopt(SO_BUSY_POLL_BUDGET, budget as libc::c_int)
+    }
+
+    /// Set all three busy polling options, in the order the kernel
+    /// docs recommend.
+    pub(crate) fn enable_busy_poll(&self, timeout: Duration, budget: u16) -> io::Result<()> {
+        self.set_prefer_busy_poll(true)?;
+        self.set_busy_poll(timeout)?;
+        self.set_busy_poll_budget(budget)
+    }
+
+    fn set_socket_sockopt(&self, optname: libc::c_int, val: libc::c_int) -> io::Result<()> {
+        let err = unsafe {
+            libc::setsockopt(
+                self.as_raw_fd(),
+                libc::SOL_SOCKET,
+                optname,
+                &val as *const libc::c_int as *const libc::c_void,
+                mem::size_of::<libc::c_int>() as libc::socklen_t,
+            )
+        };
+
+        if err != 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The offsets of the producer index, consumer index, descriptor
+    /// array and flags within each of the socket's mmap'd rings.
+    pub fn mmap_offsets(&self) -> io::Result<MmapOffsets> {
+        // SAFETY: `xdp_mmap_offsets` is made up solely of integers,
+        // for which all zeroes is a valid value.
+        let mut off: xdp_mmap_offsets = unsafe { mem::zeroed() };
+
+        self.xdp_sockopt(XDP_MMAP_OFFSETS, &mut off, "xdp_mmap_offsets")?;
+
+        Ok(off.into())
+    }
+
+    /// The options the socket was bound with, from the `XDP_OPTIONS`
+    /// socket option. Only available once bound, and requires linux
+    /// 5.3 or later.
+    ///
+    /// Check for [`XdpOptions::ZEROCOPY`] to make sure the socket
+    /// wasn't quietly bound in copy mode. Flags unknown to this
+    /// crate are kept as is.
+    pub fn xdp_options(&self) -> io::Result<XdpOptions> {
+        let mut opts = xdp_options { flags: 0 };
+
+        self.xdp_sockopt(XDP_OPTIONS, &mut opts, "xdp_options")?;
+
+        Ok(XdpOptions::from_bits_retain(opts.flags))
+    }
+
+    /// Retrieve the `SOL_XDP` level socket option `optname`, failing
+    /// if the length returned by the kernel doesn't match the size of
+    /// `T`, whose name is given by `type_name`.
+    fn xdp_sockopt<T>(&self, optname: u32, val: &mut T, type_name: &str) -> io::Result<()> {
+        let expected_len = mem::size_of::<T>() as u32;
+
+        let mut optlen = expected_len;
 
         let err = unsafe {
             libc::getsockopt(
                 self.as_raw_fd(),
                 SOL_XDP,
-                XDP_STATISTICS as i32,
-                &mut stats.0 as *mut _ as *mut libc::c_void,
+                optname as i32,
+                val as *mut T as *mut libc::c_void,
                 &mut optlen,
             )
         };
@@ -104,12 +257,15 @@ impl Fd {
             return Err(io::Error::last_os_error());
         }
 
-        if optlen == XDP_STATISTICS_SIZEOF {
-            Ok(stats)
+        if optlen == expected_len {
+            Ok(())
         } else {
             Err(io::Error::new(
                 ErrorKind::Other,
-                "`optlen` returned from `getsockopt` does not match `xdp_statistics` struct size",
+                format!(
+                    "`optlen` returned from `getsockopt` does not match `{}` struct size",
+                    type_name
+                ),
             ))
         }
     }
@@ -137,11 +293,24 @@ impl AsRawFd for Fd {
     }
 }
 
+impl AsFd for Fd {
+    /// The inner file descriptor, borrowed for as long as the `Fd` is,
+    /// for example to register the socket with an event loop such as
+    /// `mio` or tokio's `AsyncFd`.
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: the descriptor stays open for as long as the
+        // `Fd` does, either because its socket does or, for one
+        // created with `borrow_raw`, by its unsafe contract.
+        unsafe { BorrowedFd::borrow_raw(self.id) }
+    }
+}
+
 /// AF_XDP [`Socket`](crate::Socket) statistics.
 ///
 /// Can be retrieved by calling [`xdp_statistics`](Fd::xdp_statistics).
 #[derive(Debug, Clone, Copy)]
-pub struct XdpStatistics(xdp_statistics);
+pub struct XdpStatistics(pub(crate) xdp_statistics);
 
 impl Default for XdpStatistics {
     fn default() -> Self {
@@ -193,3 +362,52 @@ impl XdpStatistics {
         self.0.tx_ring_empty_descs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_poll_options_can_be_set_on_a_socket() {
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+        assert!(fd >= 0);
+
+        let sock = unsafe { Fd::borrow_raw(fd) };
+
+        sock.set_prefer_busy_poll(true).unwrap();
+        sock.set_busy_poll(Duration::ZERO).unwrap();
+        sock.set_busy_poll_budget(8).unwrap();
+
+        unsafe { libc::close(fd) };
+
+        assert!(sock.set_prefer_busy_poll(true).is_err());
+    }
+
+    #[test]
+    fn xdp_options_fail_on_non_xdp_sockets() {
+        let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0) };
+        assert!(fd >= 0);
+
+        let sock = unsafe { Fd::borrow_raw(fd) };
+
+        assert!(sock.xdp_options().is_err());
+
+        unsafe { libc::close(fd) };
+    }
+
+    #[test]
+    fn queues_expose_their_sockets_fd() {
+        use std::os::unix::prelude::AsFd;
+
+        use crate::sim::SimXsk;
+
+        let xsk = SimXsk::with_defaults(4);
+
+        let fd = xsk.rx_q.fd().as_raw_fd();
+
+        assert_eq!(xsk.rx_q.as_raw_fd(), fd);
+        assert_eq!(xsk.tx_q.as_raw_fd(), fd);
+        assert_eq!(xsk.rx_q.as_fd().as_raw_fd(), fd);
+        assert_eq!(xsk.tx_q.fd().as_fd().as_raw_fd(), fd);
+    }
+}
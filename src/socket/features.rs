@@ -0,0 +1,237 @@
+//! Checking a socket's config against the XDP features of the
+//! interface it's being bound to.
+
+use bitflags::bitflags;
+use std::{error::Error, fmt, io};
+
+use crate::config::{BindFlags, Interface, LibxdpFlags, SocketConfig, XdpFlags};
+
+use super::genl::{self, find_attr, invalid_data, GenlSocket};
+
+// From `linux/netdev.h`.
+const NETDEV_CMD_DEV_GET: u8 = 1;
+const NETDEV_A_DEV_IFINDEX: u16 = 1;
+const NETDEV_A_DEV_XDP_FEATURES: u16 = 3;
+
+bitflags! {
+    /// The XDP features of an interface's driver, as reported by the
+    /// kernel's `netdev` netlink family. Mirrors `enum
+    /// netdev_xdp_act` in `linux/netdev.h`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct XdpFeatures: u64 {
+        /// Supports native XDP, with the `XDP_PASS`, `XDP_DROP`,
+        /// `XDP_ABORTED` and `XDP_TX` actions.
+        const BASIC = 1;
+        /// Supports the `XDP_REDIRECT` action, which AF_XDP relies on.
+        const REDIRECT = 1 << 1;
+        /// Can be the target of a redirect.
+        const NDO_XMIT = 1 << 2;
+        /// Supports AF_XDP zero-copy.
+        const XSK_ZEROCOPY = 1 << 3;
+        /// Supports offloading XDP programs to hardware.
+        const HW_OFFLOAD = 1 << 4;
+        /// Supports multi-buffer packets on receive.
+        const RX_SG = 1 << 5;
+        /// Supports multi-buffer packets when the target of a
+        /// redirect.
+        const NDO_XMIT_SG = 1 << 6;
+    }
+}
+
+impl XdpFeatures {
+    /// Query the XDP features of `if_name`'s driver.
+    ///
+    /// Requires linux 6.3 or later, failing on older kernels since
+    /// they don't report them.
+    pub fn query(if_name: &Interface) -> io::Result<Self> {
+        let ifindex = if_name.index()?;
+
+        let sock = GenlSocket::open()?;
+        let family = sock.family_id("netdev")?;
+
+        let mut attrs = Vec::new();
+        genl::put_attr(&mut attrs, NETDEV_A_DEV_IFINDEX, &ifindex.to_ne_bytes());
+
+        let reply = sock.request(family, NETDEV_CMD_DEV_GET, &attrs)?;
+
+        let mut features = [0; 8];
+
+        match find_attr(&reply, NETDEV_A_DEV_XDP_FEATURES) {
+            Some(val) if val.len() >= 8 => features.copy_from_slice(&val[..8]),
+            _ => return Err(invalid_data("xdp features missing from reply")),
+        }
+
+        Ok(Self::from_bits_retain(u64::from_ne_bytes(features)))
+    }
+
+    /// Check the flags requested in `config` against these features,
+    /// returning every request which can't be satisfied.
+    pub fn check(&self, config: &SocketConfig) -> Result<(), UnsupportedFlags> {
+        check(config, Some(*self))
+    }
+}
+
+/// A requested flag, or combination of flags, which can't be
+/// satisfied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsupportedFlag {
+    /// [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY) was requested but the
+    /// driver doesn't support AF_XDP zero-copy.
+    ZeroCopy,
+    /// Both [`XDP_COPY`](BindFlags::XDP_COPY) and
+    /// [`XDP_ZEROCOPY`](BindFlags::XDP_ZEROCOPY) were requested.
+    CopyAndZeroCopy,
+    /// [`XDP_FLAGS_DRV_MODE`](XdpFlags::XDP_FLAGS_DRV_MODE) was
+    /// requested but the driver lacks native XDP or redirect support.
+    DriverMode,
+    /// [`XDP_FLAGS_HW_MODE`](XdpFlags::XDP_FLAGS_HW_MODE) was
+    /// requested, which can't redirect to an AF_XDP socket.
+    HwMode,
+    /// More than one XDP attach mode was requested.
+    MultipleModes,
+}
+
+impl fmt::Display for UnsupportedFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            UnsupportedFlag::ZeroCopy => {
+                "XDP_ZEROCOPY requested but driver lacks zero-copy support"
+            }
+            UnsupportedFlag::CopyAndZeroCopy => "both XDP_COPY and XDP_ZEROCOPY requested",
+            UnsupportedFlag::DriverMode => {
+                "XDP_FLAGS_DRV_MODE requested but driver lacks native XDP redirect support"
+            }
+            UnsupportedFlag::HwMode => "XDP_FLAGS_HW_MODE can't redirect to an AF_XDP socket",
+            UnsupportedFlag::MultipleModes => "more than one XDP attach mode requested",
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Every request in a socket's config which can't be satisfied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedFlags(Vec<UnsupportedFlag>);
+
+impl UnsupportedFlags {
+    /// The requests which can't be satisfied.
+    pub fn flags(&self) -> &[UnsupportedFlag] {
+        &self.0
+    }
+}
+
+impl fmt::Display for UnsupportedFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported flags requested: ")?;
+
+        for (i, flag) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", flag)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for UnsupportedFlags {}
+
+/// Check the flags requested in `config` against `features`, if
+/// known. Conflicting flags are reported either way.
+pub(super) fn check(
+    config: &SocketConfig,
+    features: Option<XdpFeatures>,
+) -> Result<(), UnsupportedFlags> {
+    let bind_flags = config.bind_flags();
+    let xdp_flags = config.xdp_flags();
+
+    let mut unsupported = Vec::new();
+
+    if bind_flags.contains(BindFlags::XDP_COPY | BindFlags::XDP_ZEROCOPY) {
+        unsupported.push(UnsupportedFlag::CopyAndZeroCopy);
+    }
+
+    let modes = *xdp_flags
+        & (XdpFlags::XDP_FLAGS_SKB_MODE
+            | XdpFlags::XDP_FLAGS_DRV_MODE
+            | XdpFlags::XDP_FLAGS_HW_MODE);
+
+    if modes.bits().count_ones() > 1 {
+        unsupported.push(UnsupportedFlag::MultipleModes);
+    }
+
+    // The XDP flags only matter if libxdp loads the program.
+    let loads_prog = !config
+        .libxdp_flags()
+        .contains(LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD);
+
+    if loads_prog && xdp_flags.contains(XdpFlags::XDP_FLAGS_HW_MODE) {
+        unsupported.push(UnsupportedFlag::HwMode);
+    }
+
+    if let Some(features) = features {
+        if bind_flags.contains(BindFlags::XDP_ZEROCOPY)
+            && !features.contains(XdpFeatures::XSK_ZEROCOPY)
+        {
+            unsupported.push(UnsupportedFlag::ZeroCopy);
+        }
+
+        if loads_prog
+            && xdp_flags.contains(XdpFlags::XDP_FLAGS_DRV_MODE)
+            && !features.contains(XdpFeatures::BASIC | XdpFeatures::REDIRECT)
+        {
+            unsupported.push(UnsupportedFlag::DriverMode);
+        }
+    }
+
+    if unsupported.is_empty() {
+        Ok(())
+    } else {
+        Err(UnsupportedFlags(unsupported))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(bind_flags: BindFlags, xdp_flags: XdpFlags) -> SocketConfig {
+        SocketConfig::builder()
+            .bind_flags(bind_flags)
+            .xdp_flags(xdp_flags)
+            .build()
+    }
+
+    #[test]
+    fn every_unsupported_request_is_reported() {
+        let config = config(
+            BindFlags::XDP_COPY | BindFlags::XDP_ZEROCOPY,
+            XdpFlags::XDP_FLAGS_DRV_MODE | XdpFlags::XDP_FLAGS_SKB_MODE,
+        );
+
+        let err = XdpFeatures::BASIC.check(&config).unwrap_err();
+
+        assert_eq!(
+            err.flags(),
+            &[
+                UnsupportedFlag::CopyAndZeroCopy,
+                UnsupportedFlag::MultipleModes,
+                UnsupportedFlag::ZeroCopy,
+                UnsupportedFlag::DriverMode,
+            ]
+        );
+    }
+
+    #[test]
+    fn supported_requests_pass() {
+        let config = config(BindFlags::XDP_ZEROCOPY, XdpFlags::XDP_FLAGS_DRV_MODE);
+
+        let features = XdpFeatures::BASIC | XdpFeatures::REDIRECT | XdpFeatures::XSK_ZEROCOPY;
+
+        assert!(features.check(&config).is_ok());
+
+        // Driver features are only checked if known.
+        assert!(check(&config, None).is_ok());
+    }
+}
@@ -0,0 +1,228 @@
+//! A minimal generic netlink client, for the few kernel queries which
+//! aren't available any other way.
+
+use std::{
+    io, mem,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+// From `linux/netlink.h` and `linux/genetlink.h`.
+const NETLINK_GENERIC: libc::c_int = 16;
+const NLM_F_REQUEST: u16 = 1;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_HDRLEN: usize = 16;
+const GENL_HDRLEN: usize = 4;
+const NLA_HDRLEN: usize = 4;
+const NLA_F_NESTED: u16 = 1 << 15;
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+pub(super) fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[inline]
+fn nla_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Append attribute `ty` with value `val` to `buf`, padded to
+/// alignment.
+pub(super) fn put_attr(buf: &mut Vec<u8>, ty: u16, val: &[u8]) {
+    let attr_len = NLA_HDRLEN + val.len();
+
+    buf.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+    buf.extend_from_slice(&ty.to_ne_bytes());
+    buf.extend_from_slice(val);
+    buf.resize(buf.len() + nla_align(attr_len) - attr_len, 0);
+}
+
+/// Append attribute `ty` to `buf`, nesting the attributes in `attrs`.
+pub(super) fn put_nested(buf: &mut Vec<u8>, ty: u16, attrs: &[u8]) {
+    put_attr(buf, ty | NLA_F_NESTED, attrs)
+}
+
+/// Find the value of attribute `ty` in a sequence of netlink
+/// attributes.
+pub(super) fn find_attr(mut buf: &[u8], ty: u16) -> Option<&[u8]> {
+    while buf.len() >= NLA_HDRLEN {
+        let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+        // Mask out the nested and byte order flags.
+        let attr_ty = u16::from_ne_bytes([buf[2], buf[3]]) & 0x3fff;
+
+        if len < NLA_HDRLEN || len > buf.len() {
+            return None;
+        }
+
+        if attr_ty == ty {
+            return Some(&buf[NLA_HDRLEN..len]);
+        }
+
+        buf = &buf[nla_align(len).min(buf.len())..];
+    }
+
+    None
+}
+
+/// Find the value of `u32` attribute `ty` in a sequence of netlink
+/// attributes.
+pub(super) fn find_u32(buf: &[u8], ty: u16) -> Option<u32> {
+    find_attr(buf, ty)
+        .filter(|val| val.len() >= 4)
+        .map(|val| u32::from_ne_bytes([val[0], val[1], val[2], val[3]]))
+}
+
+/// A generic netlink socket.
+pub(super) struct GenlSocket(OwnedFd);
+
+impl GenlSocket {
+    pub(super) fn open() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                NETLINK_GENERIC,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `fd` was just opened and nothing else owns it.
+        Ok(Self(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    /// Look up the id of the family called `name`.
+    pub(super) fn family_id(&self, name: &str) -> io::Result<u16> {
+        let mut attrs = Vec::new();
+
+        put_attr(
+            &mut attrs,
+            CTRL_ATTR_FAMILY_NAME,
+            &[name.as_bytes(), &[0]].concat(),
+        );
+
+        let reply = self.request(GENL_ID_CTRL, CTRL_CMD_GETFAMILY, &attrs)?;
+
+        find_attr(&reply, CTRL_ATTR_FAMILY_ID)
+            .filter(|val| val.len() >= 2)
+            .map(|val| u16::from_ne_bytes([val[0], val[1]]))
+            .ok_or_else(|| invalid_data("family id missing from reply"))
+    }
+
+    /// Send a request with the attributes in `attrs`, returning the
+    /// attributes of the reply.
+    pub(super) fn request(&self, family: u16, cmd: u8, attrs: &[u8]) -> io::Result<Vec<u8>> {
+        let msg_len = NLMSG_HDRLEN + GENL_HDRLEN + attrs.len();
+
+        let mut msg = Vec::with_capacity(msg_len);
+
+        // struct nlmsghdr
+        msg.extend_from_slice(&(msg_len as u32).to_ne_bytes());
+        msg.extend_from_slice(&family.to_ne_bytes());
+        msg.extend_from_slice(&NLM_F_REQUEST.to_ne_bytes());
+        msg.extend_from_slice(&1u32.to_ne_bytes()); // seq
+        msg.extend_from_slice(&0u32.to_ne_bytes()); // pid
+
+        // struct genlmsghdr
+        msg.extend_from_slice(&[cmd, 1, 0, 0]);
+
+        msg.extend_from_slice(attrs);
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+
+        let ret = unsafe {
+            libc::sendto(
+                self.0.as_raw_fd(),
+                msg.as_ptr() as *const libc::c_void,
+                msg.len(),
+                0,
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; 8192];
+
+        let len = unsafe {
+            libc::recv(
+                self.0.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let buf = &buf[..len as usize];
+
+        if buf.len() < NLMSG_HDRLEN + 4 {
+            return Err(invalid_data("netlink reply too short"));
+        }
+
+        let reply_len = u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let reply_ty = u16::from_ne_bytes([buf[4], buf[5]]);
+
+        if reply_ty == NLMSG_ERROR {
+            let err = i32::from_ne_bytes([buf[16], buf[17], buf[18], buf[19]]);
+
+            return Err(io::Error::from_raw_os_error(-err));
+        }
+
+        if reply_len > buf.len() || reply_len < NLMSG_HDRLEN + GENL_HDRLEN {
+            return Err(invalid_data("malformed netlink reply"));
+        }
+
+        Ok(buf[NLMSG_HDRLEN + GENL_HDRLEN..reply_len].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attrs_are_found_past_padding() {
+        let mut buf = Vec::new();
+
+        buf.extend_from_slice(&5u16.to_ne_bytes());
+        buf.extend_from_slice(&2u16.to_ne_bytes());
+        buf.extend_from_slice(&[b'x', 0, 0, 0]);
+        buf.extend_from_slice(&6u16.to_ne_bytes());
+        buf.extend_from_slice(&1u16.to_ne_bytes());
+        buf.extend_from_slice(&[7, 0, 0, 0]);
+
+        assert_eq!(find_attr(&buf, 2), Some(&b"x"[..]));
+        assert_eq!(find_attr(&buf, 1), Some(&[7, 0][..]));
+        assert_eq!(find_attr(&buf, 3), None);
+    }
+
+    #[test]
+    fn nested_attrs_round_trip() {
+        let mut inner = Vec::new();
+        put_attr(&mut inner, 2, b"eth0\0");
+
+        let mut buf = Vec::new();
+        put_nested(&mut buf, 1, &inner);
+        put_attr(&mut buf, 6, &4u32.to_ne_bytes());
+
+        assert_eq!(buf.len() % 4, 0);
+
+        let nested = find_attr(&buf, 1).unwrap();
+
+        assert_eq!(find_attr(nested, 2), Some(&b"eth0\0"[..]));
+        assert_eq!(find_u32(&buf, 6), Some(4));
+        assert_eq!(find_u32(&buf, 7), None);
+    }
+}
@@ -0,0 +1,434 @@
+//! Details of a bound [`Socket`](crate::Socket).
+
+use libxdp_sys::{xdp_mmap_offsets, xdp_ring_offset, XDP_OPTIONS_ZEROCOPY};
+use std::ops::Range;
+
+use crate::config::{BindFlags, Interface, QueueSize, SocketConfig, UmemConfig, XdpFlags};
+
+/// Offsets of the various fields of an mmap'd AF_XDP ring, relative
+/// to the start of the ring's mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingOffsets {
+    producer: u64,
+    consumer: u64,
+    desc: u64,
+    flags: u64,
+}
+
+impl RingOffsets {
+    /// Offset of the producer index.
+    #[inline]
+    pub fn producer(&self) -> u64 {
+        self.producer
+    }
+
+    /// Offset of the consumer index.
+    #[inline]
+    pub fn consumer(&self) -> u64 {
+        self.consumer
+    }
+
+    /// Offset of the descriptor array.
+    #[inline]
+    pub fn desc(&self) -> u64 {
+        self.desc
+    }
+
+    /// Offset of the ring flags, e.g. `XDP_RING_NEED_WAKEUP`.
+    #[inline]
+    pub fn flags(&self) -> u64 {
+        self.flags
+    }
+}
+
+impl From<xdp_ring_offset> for RingOffsets {
+    fn from(off: xdp_ring_offset) -> Self {
+        Self {
+            producer: off.producer,
+            consumer: off.consumer,
+            desc: off.desc,
+            flags: off.flags,
+        }
+    }
+}
+
+/// The ring offsets negotiated with the kernel, as returned by the
+/// `XDP_MMAP_OFFSETS` socket option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MmapOffsets {
+    rx: RingOffsets,
+    tx: RingOffsets,
+    fill: RingOffsets,
+    comp: RingOffsets,
+}
+
+impl MmapOffsets {
+    /// Offsets for the [`RxQueue`](crate::RxQueue) ring.
+    #[inline]
+    pub fn rx(&self) -> &RingOffsets {
+        &self.rx
+    }
+
+    /// Offsets for the [`TxQueue`](crate::TxQueue) ring.
+    #[inline]
+    pub fn tx(&self) -> &RingOffsets {
+        &self.tx
+    }
+
+    /// Offsets for the [`FillQueue`](crate::FillQueue) ring.
+    #[inline]
+    pub fn fill(&self) -> &RingOffsets {
+        &self.fill
+    }
+
+    /// Offsets for the [`CompQueue`](crate::CompQueue) ring.
+    #[inline]
+    pub fn comp(&self) -> &RingOffsets {
+        &self.comp
+    }
+}
+
+impl From<xdp_mmap_offsets> for MmapOffsets {
+    fn from(off: xdp_mmap_offsets) -> Self {
+        Self {
+            rx: off.rx.into(),
+            tx: off.tx.into(),
+            fill: off.fr.into(),
+            comp: off.cr.into(),
+        }
+    }
+}
+
+/// Addresses and indices of an AF_XDP ring in the current process,
+/// for use by debugging and profiling tools.
+///
+/// The addresses are only valid for as long as the queue the ring
+/// belongs to is alive, and the indices are a snapshot taken when the
+/// `RingInfo` was retrieved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingInfo {
+    producer_addr: usize,
+    consumer_addr: usize,
+    flags_addr: usize,
+    desc_addr: usize,
+    size: u32,
+    entry_size: usize,
+    producer: u32,
+    consumer: u32,
+}
+
+impl RingInfo {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        producer_addr: usize,
+        consumer_addr: usize,
+        flags_addr: usize,
+        desc_addr: usize,
+        size: u32,
+        entry_size: usize,
+        producer: u32,
+        consumer: u32,
+    ) -> Self {
+        Self {
+            producer_addr,
+            consumer_addr,
+            flags_addr,
+            desc_addr,
+            size,
+            entry_size,
+            producer,
+            consumer,
+        }
+    }
+
+    /// The range of addresses covered by the ring's indices, flags
+    /// and descriptors.
+    pub(crate) fn span(&self) -> Range<usize> {
+        let start = self
+            .producer_addr
+            .min(self.consumer_addr)
+            .min(self.flags_addr)
+            .min(self.desc_addr);
+
+        start..self.desc_addr + self.size as usize * self.entry_size
+    }
+
+    /// Address of the ring's producer index, a `u32`.
+    #[inline]
+    pub fn producer_addr(&self) -> usize {
+        self.producer_addr
+    }
+
+    /// Address of the ring's consumer index, a `u32`.
+    #[inline]
+    pub fn consumer_addr(&self) -> usize {
+        self.consumer_addr
+    }
+
+    /// Address of the ring's flags, a `u32`.
+    #[inline]
+    pub fn flags_addr(&self) -> usize {
+        self.flags_addr
+    }
+
+    /// Address of the first of the ring's descriptors.
+    #[inline]
+    pub fn desc_addr(&self) -> usize {
+        self.desc_addr
+    }
+
+    /// The number of entries in the ring.
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The size in bytes of a single ring entry.
+    #[inline]
+    pub fn entry_size(&self) -> usize {
+        self.entry_size
+    }
+
+    /// The producer index at the time of retrieval.
+    #[inline]
+    pub fn producer(&self) -> u32 {
+        self.producer
+    }
+
+    /// The consumer index at the time of retrieval.
+    #[inline]
+    pub fn consumer(&self) -> u32 {
+        self.consumer
+    }
+
+    /// The number of entries in the ring at the time of retrieval.
+    #[inline]
+    pub fn occupancy(&self) -> u32 {
+        self.producer.wrapping_sub(self.consumer)
+    }
+}
+
+/// Information about a bound AF_XDP [`Socket`](crate::Socket): where
+/// it is bound, the sizes of its rings and what the kernel agreed to
+/// when binding.
+///
+/// Can be retrieved by calling [`TxQueue::socket_info`] or
+/// [`RxQueue::socket_info`].
+///
+/// [`TxQueue::socket_info`]: crate::TxQueue::socket_info
+/// [`RxQueue::socket_info`]: crate::RxQueue::socket_info
+#[derive(Debug, Clone)]
+pub struct SocketInfo {
+    if_name: Interface,
+    queue_id: u32,
+    socket_config: SocketConfig,
+    umem_config: UmemConfig,
+    options: u32,
+    offsets: MmapOffsets,
+}
+
+impl SocketInfo {
+    pub(super) fn new(
+        if_name: Interface,
+        queue_id: u32,
+        socket_config: SocketConfig,
+        umem_config: UmemConfig,
+        options: u32,
+        offsets: MmapOffsets,
+    ) -> Self {
+        Self {
+            if_name,
+            queue_id,
+            socket_config,
+            umem_config,
+            options,
+            offsets,
+        }
+    }
+
+    /// The interface the socket is bound to.
+    #[inline]
+    pub fn if_name(&self) -> &Interface {
+        &self.if_name
+    }
+
+    /// The interface queue the socket is bound to.
+    #[inline]
+    pub fn queue_id(&self) -> u32 {
+        self.queue_id
+    }
+
+    /// The size of the [`RxQueue`](crate::RxQueue).
+    #[inline]
+    pub fn rx_queue_size(&self) -> QueueSize {
+        self.socket_config.rx_queue_size()
+    }
+
+    /// The size of the [`TxQueue`](crate::TxQueue).
+    #[inline]
+    pub fn tx_queue_size(&self) -> QueueSize {
+        self.socket_config.tx_queue_size()
+    }
+
+    /// The size of the [`FillQueue`](crate::FillQueue) associated
+    /// with the socket's [`Umem`](crate::Umem).
+    #[inline]
+    pub fn fill_queue_size(&self) -> QueueSize {
+        self.umem_config.fill_queue_size()
+    }
+
+    /// The size of the [`CompQueue`](crate::CompQueue) associated
+    /// with the socket's [`Umem`](crate::Umem).
+    #[inline]
+    pub fn comp_queue_size(&self) -> QueueSize {
+        self.umem_config.comp_queue_size()
+    }
+
+    /// The flags the socket was bound with.
+    #[inline]
+    pub fn bind_flags(&self) -> &BindFlags {
+        self.socket_config.bind_flags()
+    }
+
+    /// The XDP flags the socket was bound with. Along with
+    /// [`bind_flags`](Self::bind_flags), shows the mode chosen by a
+    /// [`BindStrategy`](crate::config::BindStrategy).
+    #[inline]
+    pub fn xdp_flags(&self) -> &XdpFlags {
+        self.socket_config.xdp_flags()
+    }
+
+    /// The raw flags returned by the `XDP_OPTIONS` socket option.
+    #[inline]
+    pub fn options(&self) -> u32 {
+        self.options
+    }
+
+    /// Whether the socket is running in zero-copy mode.
+    #[inline]
+    pub fn is_zero_copy(&self) -> bool {
+        self.options & XDP_OPTIONS_ZEROCOPY != 0
+    }
+
+    /// Whether the kernel bound the socket in copy mode without it
+    /// being requested, which usually means it fell back to generic
+    /// (skb) mode. See
+    /// [`SocketConfigBuilder::strict_mode`](crate::config::SocketConfigBuilder::strict_mode).
+    #[inline]
+    pub fn is_copy_mode_fallback(&self) -> bool {
+        !self.is_zero_copy() && !self.socket_config.copy_mode_requested()
+    }
+
+    /// The ring offsets negotiated with the kernel.
+    #[inline]
+    pub fn mmap_offsets(&self) -> &MmapOffsets {
+        &self.offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring_offset(base: u64) -> xdp_ring_offset {
+        xdp_ring_offset {
+            producer: base,
+            consumer: base + 1,
+            desc: base + 2,
+            flags: base + 3,
+        }
+    }
+
+    #[test]
+    fn mmap_offsets_map_to_the_correct_rings() {
+        let off: MmapOffsets = xdp_mmap_offsets {
+            rx: ring_offset(0),
+            tx: ring_offset(10),
+            fr: ring_offset(20),
+            cr: ring_offset(30),
+        }
+        .into();
+
+        assert_eq!(off.rx().producer(), 0);
+        assert_eq!(off.tx().consumer(), 11);
+        assert_eq!(off.fill().desc(), 22);
+        assert_eq!(off.comp().flags(), 33);
+    }
+
+    #[test]
+    fn zero_copy_is_read_from_options() {
+        let info = |options| {
+            SocketInfo::new(
+                "lo".parse().unwrap(),
+                0,
+                SocketConfig::default(),
+                UmemConfig::default(),
+                options,
+                xdp_mmap_offsets {
+                    rx: ring_offset(0),
+                    tx: ring_offset(0),
+                    fr: ring_offset(0),
+                    cr: ring_offset(0),
+                }
+                .into(),
+            )
+        };
+
+        assert!(!info(0).is_zero_copy());
+        assert!(info(XDP_OPTIONS_ZEROCOPY).is_zero_copy());
+    }
+
+    #[test]
+    fn copy_mode_is_only_a_fallback_if_not_requested() {
+        let info = |options, socket_config| {
+            SocketInfo::new(
+                "lo".parse().unwrap(),
+                0,
+                socket_config,
+                UmemConfig::default(),
+                options,
+                xdp_mmap_offsets {
+                    rx: ring_offset(0),
+                    tx: ring_offset(0),
+                    fr: ring_offset(0),
+                    cr: ring_offset(0),
+                }
+                .into(),
+            )
+        };
+
+        let copy = SocketConfig::builder()
+            .bind_flags(BindFlags::XDP_COPY)
+            .build();
+
+        let skb = SocketConfig::builder()
+            .xdp_flags(XdpFlags::XDP_FLAGS_SKB_MODE)
+            .build();
+
+        assert!(info(0, SocketConfig::default()).is_copy_mode_fallback());
+        assert!(!info(0, copy).is_copy_mode_fallback());
+        assert!(!info(0, skb).is_copy_mode_fallback());
+        assert!(!info(XDP_OPTIONS_ZEROCOPY, SocketConfig::default()).is_copy_mode_fallback());
+    }
+
+    #[test]
+    fn bind_mode_replaces_conflicting_flags() {
+        let config = SocketConfig::builder()
+            .bind_flags(BindFlags::XDP_ZEROCOPY | BindFlags::XDP_USE_NEED_WAKEUP)
+            .xdp_flags(XdpFlags::XDP_FLAGS_DRV_MODE | XdpFlags::XDP_FLAGS_UPDATE_IF_NOEXIST)
+            .build()
+            .with_mode(BindFlags::XDP_COPY, XdpFlags::XDP_FLAGS_SKB_MODE);
+
+        assert_eq!(
+            config.bind_flags().bits(),
+            (BindFlags::XDP_COPY | BindFlags::XDP_USE_NEED_WAKEUP).bits()
+        );
+        assert_eq!(
+            config.xdp_flags().bits(),
+            (XdpFlags::XDP_FLAGS_SKB_MODE | XdpFlags::XDP_FLAGS_UPDATE_IF_NOEXIST).bits()
+        );
+
+        // Copy mode was asked for, so isn't reported as a fallback.
+        assert!(config.copy_mode_requested());
+    }
+}
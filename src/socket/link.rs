@@ -0,0 +1,174 @@
+//! Tracking of the link state of the interface a
+//! [`Socket`](crate::Socket) is bound to.
+
+use libc::{AF_INET, IFF_RUNNING, IFF_UP, IFNAMSIZ, SOCK_CLOEXEC, SOCK_DGRAM};
+use std::{
+    io,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::config::Interface;
+
+/// Whether an interface is able to pass traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// The interface is administratively up and has carrier.
+    Up,
+    /// The interface is either down or has no carrier.
+    Down,
+}
+
+/// Layout of `struct ifreq` as used by `SIOCGIFFLAGS`, where only the
+/// flags member of the union is of interest.
+#[repr(C)]
+struct IfReqFlags {
+    name: [libc::c_char; IFNAMSIZ],
+    flags: libc::c_short,
+    // Pads the union out to its full size.
+    _pad: [u8; 22],
+}
+
+/// Queries the link status of an interface.
+#[derive(Debug, Clone)]
+pub struct LinkWatcher {
+    if_name: Interface,
+}
+
+impl LinkWatcher {
+    /// Create a new `LinkWatcher` for the interface `if_name`.
+    pub fn new(if_name: Interface) -> Self {
+        Self { if_name }
+    }
+
+    /// The interface being watched.
+    #[inline]
+    pub fn if_name(&self) -> &Interface {
+        &self.if_name
+    }
+
+    /// The current status of the interface, as reported by the
+    /// `SIOCGIFFLAGS` ioctl.
+    pub fn status(&self) -> io::Result<LinkStatus> {
+        let name = self.if_name.as_cstr().to_bytes_with_nul();
+
+        if name.len() > IFNAMSIZ {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "interface name exceeds IFNAMSIZ",
+            ));
+        }
+
+        let mut req = IfReqFlags {
+            name: [0; IFNAMSIZ],
+            flags: 0,
+            _pad: [0; 22],
+        };
+
+        for (dst, src) in req.name.iter_mut().zip(name) {
+            *dst = *src as libc::c_char;
+        }
+
+        let fd = unsafe { libc::socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: `socket` succeeded so the fd is open and owned by us
+        // alone.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let err = unsafe { libc::ioctl(fd.as_raw_fd(), libc::SIOCGIFFLAGS as _, &mut req) };
+
+        if err < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let flags = req.flags as libc::c_int;
+
+        if flags & IFF_UP != 0 && flags & IFF_RUNNING != 0 {
+            Ok(LinkStatus::Up)
+        } else {
+            Ok(LinkStatus::Down)
+        }
+    }
+}
+
+/// Link state shared between a socket's queues. The socket is marked
+/// paused once the kernel reports `ENETDOWN`, and stays that way
+/// until the watcher sees the interface come back up.
+#[derive(Debug)]
+pub(super) struct LinkState {
+    paused: AtomicBool,
+    watcher: LinkWatcher,
+}
+
+impl LinkState {
+    pub fn new(if_name: Interface) -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            watcher: LinkWatcher::new(if_name),
+        }
+    }
+
+    #[inline]
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// If paused, check whether the link has come back up and resume
+    /// if so. Only queries the interface when paused.
+    pub fn status(&self) -> io::Result<LinkStatus> {
+        if !self.is_paused() {
+            return Ok(LinkStatus::Up);
+        }
+
+        let status = self.watcher.status()?;
+
+        if status == LinkStatus::Up {
+            self.paused.store(false, Ordering::Relaxed);
+        }
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ifreq_flags_has_same_size_as_ifreq() {
+        assert_eq!(std::mem::size_of::<IfReqFlags>(), 40);
+    }
+
+    #[test]
+    fn missing_interface_is_an_error() {
+        let watcher = LinkWatcher::new("xsk_no_such_dev".parse().unwrap());
+        assert!(watcher.status().is_err());
+
+        let watcher = LinkWatcher::new("a_far_too_long_interface_name".parse().unwrap());
+        assert_eq!(
+            watcher.status().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn interface_is_only_queried_once_paused() {
+        let state = LinkState::new("xsk_no_such_dev".parse().unwrap());
+
+        assert_eq!(state.status().unwrap(), LinkStatus::Up);
+
+        state.pause();
+
+        assert!(state.status().is_err());
+        assert!(state.is_paused());
+    }
+}
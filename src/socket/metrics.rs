@@ -0,0 +1,123 @@
+//! Counters kept by the tx and rx queues with the `metrics` feature.
+
+#[cfg(feature = "metrics")]
+use std::cell::Cell;
+
+/// A snapshot of the counters kept by a [`TxQueue`] or [`RxQueue`],
+/// retrieved with [`TxQueue::metrics`] or [`RxQueue::metrics`].
+///
+/// Counts start from zero when the queue is created, and wrap on
+/// overflow. Requires the `metrics` feature.
+///
+/// [`TxQueue`]: crate::TxQueue
+/// [`RxQueue`]: crate::RxQueue
+/// [`TxQueue::metrics`]: crate::TxQueue::metrics
+/// [`RxQueue::metrics`]: crate::RxQueue::metrics
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMetrics {
+    /// Frames submitted to the tx ring, or consumed off the rx ring.
+    pub frames: u64,
+    /// Wakeups sent to the kernel. Always zero for an rx queue.
+    pub wakeups: u64,
+    /// Calls to `poll` which returned without the queue becoming
+    /// ready.
+    pub poll_timeouts: u64,
+}
+
+/// The counters behind [`QueueMetrics`]. Without the `metrics`
+/// feature this is empty and recording is a no-op, so queues can
+/// record unconditionally.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "metrics")]
+    frames: Cell<u64>,
+    #[cfg(feature = "metrics")]
+    wakeups: Cell<u64>,
+    #[cfg(feature = "metrics")]
+    poll_timeouts: Cell<u64>,
+}
+
+#[cfg(feature = "metrics")]
+fn bump(counter: &Cell<u64>, n: u64) {
+    counter.set(counter.get().wrapping_add(n));
+}
+
+impl Counters {
+    #[inline]
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub fn add_frames(&self, cnt: u32) {
+        #[cfg(feature = "metrics")]
+        bump(&self.frames, cnt as u64);
+    }
+
+    #[inline]
+    pub fn add_wakeup(&self) {
+        #[cfg(feature = "metrics")]
+        bump(&self.wakeups, 1);
+    }
+
+    /// Record the outcome of a poll, passing it back out.
+    #[inline]
+    pub fn add_poll(&self, ready: bool) -> bool {
+        #[cfg(feature = "metrics")]
+        if !ready {
+            bump(&self.poll_timeouts, 1);
+        }
+
+        ready
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn snapshot(&self) -> QueueMetrics {
+        QueueMetrics {
+            frames: self.frames.get(),
+            wakeups: self.wakeups.get(),
+            poll_timeouts: self.poll_timeouts.get(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use crate::{sim::SimXsk, umem::frame::FrameDesc};
+
+    use super::*;
+
+    #[test]
+    fn queues_count_frames_wakeups_and_poll_timeouts() {
+        let mut xsk = SimXsk::with_defaults(16);
+
+        assert!(!xsk.rx_q.poll(0).unwrap());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+        assert!(xsk.kernel.inject(b"hello"));
+
+        let mut descs = [FrameDesc::default(); 4];
+        assert_eq!(
+            unsafe { xsk.rx_q.poll_and_consume(&mut descs, 0) }.unwrap(),
+            1
+        );
+
+        assert_eq!(
+            xsk.rx_q.metrics(),
+            QueueMetrics {
+                frames: 1,
+                wakeups: 0,
+                poll_timeouts: 1,
+            }
+        );
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[2..5]) }, 3);
+        xsk.tx_q.wakeup().unwrap();
+
+        assert_eq!(
+            xsk.tx_q.metrics(),
+            QueueMetrics {
+                frames: 3,
+                wakeups: 1,
+                poll_timeouts: 0,
+            }
+        );
+    }
+}
@@ -1,27 +1,85 @@
 //! Types for creating and using an AF_XDP [`Socket`].
 
 mod fd;
-pub use fd::{Fd, XdpStatistics};
+pub use fd::{Fd, XdpOptions, XdpStatistics};
+
+mod deadline;
+pub use deadline::CancelToken;
+
+mod features;
+pub use features::{UnsupportedFlag, UnsupportedFlags, XdpFeatures};
+
+mod genl;
+
+mod info;
+pub use info::{MmapOffsets, RingInfo, RingOffsets, SocketInfo};
+
+mod link;
+use link::LinkState;
+pub use link::{LinkStatus, LinkWatcher};
+
+mod metrics;
+#[cfg(feature = "metrics")]
+pub use metrics::QueueMetrics;
+
+mod multi_queue;
+pub use multi_queue::{Channels, QueueHandles};
+
+mod netns;
+pub use netns::{NetNs, NetNsGuard};
+
+mod owned;
+pub use owned::OwnedSocket;
+
+mod poll_set;
+pub use poll_set::{PollEvents, PollSet, Ready};
 
 mod rx_queue;
 pub use rx_queue::RxQueue;
 
+mod rx_batch;
+pub use rx_batch::RxBatchGuard;
+
+mod shared;
+
 mod tx_queue;
-pub use tx_queue::TxQueue;
+pub use tx_queue::{TxQueue, WakeupStatus};
+
+mod tx_session;
+pub use tx_session::{Full, TxSession};
+
+#[cfg(feature = "io-uring")]
+mod uring;
+#[cfg(feature = "io-uring")]
+pub use uring::{Completion, WakeupRing};
+
+pub(crate) mod xskmap;
+
+#[cfg(feature = "async")]
+mod reactor;
+#[cfg(feature = "async")]
+pub(crate) use reactor::SocketFd;
 
 use libxdp_sys::xsk_socket;
+use log::{debug, info, warn};
 use std::{
     borrow::Borrow,
     error::Error,
     fmt, io,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd},
     ptr::{self, NonNull},
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, Weak},
+    thread,
 };
+#[cfg(feature = "async")]
+use tokio::io::unix::AsyncFd;
 
 use crate::{
-    config::{Interface, SocketConfig},
-    ring::{XskRingCons, XskRingProd},
-    umem::{CompQueue, FillQueue, Umem},
+    config::{BindFlags, BindStrategy, Interface, LibxdpFlags, SocketConfig, XdpFlags},
+    ring::{self, RingKind, RingMmap, RingSetupError, XskRingCons, XskRingProd},
+    stats::SocketStats,
+    umem::{CompQueue, FillQueue, Umem, UmemCreateErrorKind},
+    util,
 };
 
 /// Wrapper around a pointer to some AF_XDP socket.
@@ -55,16 +113,64 @@ unsafe impl Send for XskSocket {}
 
 #[derive(Debug)]
 struct SocketInner {
-    // `ptr` must appear before `umem` to ensure correct drop order.
-    _ptr: XskSocket,
-    _umem: Umem,
+    // `reactor` must appear before `ptr` and `fd` so that it's
+    // deregistered before the socket is closed. Set on first use by
+    // one of the async queue methods.
+    #[cfg(feature = "async")]
+    reactor: Option<Arc<AsyncFd<SocketFd>>>,
+    // `rings`, `ptr` and `fd` must appear before `umem` to ensure
+    // correct drop order.
+    // Rings mapped by this crate rather than libxdp, see
+    // `RxQueue::from_raw` and `TxQueue::from_raw`.
+    rings: Vec<RingMmap>,
+    _ptr: Option<XskSocket>,
+    // Only set if the socket's file descriptor is not managed by
+    // libxdp, in which case it is closed on drop.
+    _fd: Option<OwnedFd>,
+    umem: Umem,
+    if_name: Interface,
+    queue_id: u32,
+    config: SocketConfig,
 }
 
 impl SocketInner {
-    fn new(ptr: XskSocket, umem: Umem) -> Self {
+    fn new(
+        ptr: XskSocket,
+        umem: Umem,
+        if_name: Interface,
+        queue_id: u32,
+        config: SocketConfig,
+    ) -> Self {
+        Self {
+            #[cfg(feature = "async")]
+            reactor: None,
+            rings: Vec::new(),
+            _ptr: Some(ptr),
+            _fd: None,
+            umem,
+            if_name,
+            queue_id,
+            config,
+        }
+    }
+
+    fn with_owned_fd(
+        fd: OwnedFd,
+        umem: Umem,
+        if_name: Interface,
+        queue_id: u32,
+        config: SocketConfig,
+    ) -> Self {
         Self {
-            _ptr: ptr,
-            _umem: umem,
+            #[cfg(feature = "async")]
+            reactor: None,
+            rings: Vec::new(),
+            _ptr: None,
+            _fd: Some(fd),
+            umem,
+            if_name,
+            queue_id,
+            config,
         }
     }
 }
@@ -76,10 +182,36 @@ impl SocketInner {
 #[derive(Debug)]
 pub struct Socket {
     fd: Fd,
-    _inner: Arc<Mutex<SocketInner>>,
+    link: Arc<LinkState>,
+    inner: Arc<Mutex<SocketInner>>,
 }
 
 impl Socket {
+    /// Check the flags requested in `config` against the XDP
+    /// features of `if_name`'s driver, so that every unsupported
+    /// request is reported at once rather than as a single error from
+    /// the kernel on bind. If the features can't be queried, e.g. on
+    /// kernels older than 6.3, only conflicting flags are reported.
+    fn check_flags(config: &SocketConfig, if_name: &Interface) -> Result<(), SocketCreateError> {
+        let features = match XdpFeatures::query(if_name) {
+            Ok(features) => Some(features),
+            Err(e) => {
+                debug!(
+                    "failed to query xdp features of {:?}, skipping driver checks: {}",
+                    if_name, e
+                );
+                None
+            }
+        };
+
+        features::check(config, features).map_err(|unsupported| {
+            SocketCreateError::new(
+                SocketCreateErrorKind::UnsupportedFlags,
+                io::Error::new(io::ErrorKind::Unsupported, unsupported),
+            )
+        })
+    }
+
     /// Create and bind a new AF_XDP socket to a given interface and
     /// queue id using the underlying UMEM.
     ///
@@ -104,54 +236,147 @@ impl Socket {
     /// For further details on using a shared [`Umem`] please see the
     /// [docs](https://www.kernel.org/doc/html/latest/networking/af_xdp.html#xdp-shared-umem-bind-flag).
     ///
+    /// The mode the socket is bound in is chosen according to the
+    /// config's [`BindStrategy`].
+    ///
+    /// `config` may be passed by reference, so that one config can be
+    /// used for many sockets. For sockets whose configs differ only
+    /// slightly, for example in their queue sizes, see
+    /// [`SocketConfig::with_overrides`].
+    ///
     /// # Safety
     ///
     /// If sharing the [`Umem`] and the `(if_name, queue_id)` pair is
     /// already bound to, then the
     /// [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`] flag must be
     /// set. Otherwise, a double-free may occur when dropping sockets
-    /// if the program has already been detached.
+    /// if the program has already been detached. Use
+    /// [`new_shared`](Self::new_shared) for this case, which sets the
+    /// flag itself.
     ///
     /// [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`]: crate::config::LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD
     #[allow(clippy::new_ret_no_self)]
     #[allow(clippy::type_complexity)]
-    pub unsafe fn new(
+    pub unsafe fn new<C>(
+        config: C,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+    ) -> Result<(TxQueue, RxQueue, Option<(FillQueue, CompQueue)>), SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        // SAFETY: the unsafe contract of `new`.
+        unsafe { Self::create(*config.borrow(), umem, if_name, queue_id) }
+            .map_err(|e| e.at(if_name, queue_id))
+    }
+
+    #[allow(clippy::type_complexity)]
+    unsafe fn create(
         config: SocketConfig,
         umem: &Umem,
         if_name: &Interface,
         queue_id: u32,
     ) -> Result<(TxQueue, RxQueue, Option<(FillQueue, CompQueue)>), SocketCreateError> {
+        let (mut config, mut fallback) = match config.bind_strategy() {
+            BindStrategy::AsConfigured => (config, None),
+            BindStrategy::PreferZeroCopy => (
+                config.with_mode(BindFlags::XDP_ZEROCOPY, XdpFlags::XDP_FLAGS_DRV_MODE),
+                Some(config.with_mode(BindFlags::XDP_COPY, XdpFlags::XDP_FLAGS_SKB_MODE)),
+            ),
+        };
+
+        if let Err(e) = Self::check_flags(&config, if_name) {
+            match fallback.take() {
+                Some(copy_config) if e.kind() == SocketCreateErrorKind::UnsupportedFlags => {
+                    info!(
+                        "{:?} doesn't support zero-copy in driver mode, falling back to copy mode: {}",
+                        if_name, e.err
+                    );
+
+                    config = copy_config;
+                    Self::check_flags(&config, if_name)?;
+                }
+                _ => return Err(e),
+            }
+        }
+
         let mut socket_ptr = ptr::null_mut();
         let mut tx_q = XskRingProd::default();
         let mut rx_q = XskRingCons::default();
 
-        let (err, fq, cq) = unsafe {
-            umem.with_ptr_and_saved_queues(|xsk_umem, saved_fq_and_cq| {
-                let (mut fq, mut cq) = saved_fq_and_cq
-                    .take()
-                    .unwrap_or_else(|| (Box::default(), Box::default()));
-
-                let err = libxdp_sys::xsk_socket__create_shared(
-                    &mut socket_ptr,
-                    if_name.as_cstr().as_ptr(),
-                    queue_id,
-                    xsk_umem,
-                    rx_q.as_mut(),
-                    tx_q.as_mut(),
-                    fq.as_mut().as_mut(), // double deref due to Box
-                    cq.as_mut().as_mut(),
-                    &config.into(),
-                );
+        let mut retries = config.busy_retries();
+        let mut backoff = config.busy_backoff();
+
+        let (err, fq_and_cq) = loop {
+            let (err, fq_and_cq) = unsafe {
+                umem.with_ptr_and_saved_queues(|xsk_umem, saved_fq_and_cq| {
+                    let is_saved = saved_fq_and_cq.is_some();
+
+                    let (mut fq, mut cq) = saved_fq_and_cq
+                        .take()
+                        .unwrap_or_else(|| (Box::default(), Box::default()));
 
-                (err, fq, cq)
-            })
+                    let err = libxdp_sys::xsk_socket__create_shared(
+                        &mut socket_ptr,
+                        if_name.as_cstr().as_ptr(),
+                        queue_id,
+                        xsk_umem,
+                        rx_q.as_mut(),
+                        tx_q.as_mut(),
+                        fq.as_mut().as_mut(), // double deref due to Box
+                        cq.as_mut().as_mut(),
+                        &config.into(),
+                    );
+
+                    let retry = (err == -libc::EBUSY && retries > 0)
+                        || (err == -libc::EOPNOTSUPP && fallback.is_some());
+
+                    // Keep hold of the UMEM's queues so they can be
+                    // used by the next attempt.
+                    if retry && is_saved {
+                        *saved_fq_and_cq = Some((fq, cq));
+                        return (err, None);
+                    }
+
+                    (err, Some((fq, cq)))
+                })
+            };
+
+            if err == -libc::EOPNOTSUPP {
+                if let Some(copy_config) = fallback.take() {
+                    info!(
+                        "binding to {:?} in zero-copy driver mode not supported, \
+                         falling back to copy mode",
+                        if_name
+                    );
+
+                    config = copy_config;
+                } else {
+                    break (err, fq_and_cq);
+                }
+            } else if err != -libc::EBUSY || retries == 0 {
+                break (err, fq_and_cq);
+            } else {
+                retries -= 1;
+
+                thread::sleep(backoff);
+                backoff = backoff.saturating_mul(2);
+            }
+
+            tx_q = XskRingProd::default();
+            rx_q = XskRingCons::default();
         };
 
+        // Queues are only held back when there's another attempt to
+        // be made.
+        let (fq, cq) = fq_and_cq.expect("fill and comp queues returned on final attempt");
+
         if err != 0 {
-            return Err(SocketCreateError {
-                reason: "non-zero error code returned when creating AF_XDP socket",
-                err: io::Error::from_raw_os_error(-err),
-            });
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::Bind,
+                io::Error::from_raw_os_error(-err),
+            ));
         }
 
         let socket_ptr = match NonNull::new(socket_ptr) {
@@ -162,41 +387,57 @@ impl Socket {
                 unsafe { XskSocket::new(init_xsk) }
             }
             None => {
-                return Err(SocketCreateError {
-                    reason: "returned socket pointer was null",
-                    err: io::Error::from_raw_os_error(-err),
-                });
+                return Err(SocketCreateError::new(
+                    SocketCreateErrorKind::SocketNull,
+                    io::Error::from_raw_os_error(-err),
+                ));
             }
         };
 
         let fd = unsafe { libxdp_sys::xsk_socket__fd(socket_ptr.0.as_ref()) };
 
         if fd < 0 {
-            return Err(SocketCreateError {
-                reason: "failed to retrieve AF_XDP socket file descriptor",
-                err: io::Error::from_raw_os_error(-fd),
-            });
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::SocketFd,
+                io::Error::from_raw_os_error(-fd),
+            ));
         }
 
         let socket = Socket {
             fd: Fd::new(fd),
-            _inner: Arc::new(Mutex::new(SocketInner::new(socket_ptr, umem.clone()))),
+            link: Arc::new(LinkState::new(if_name.clone())),
+            inner: Arc::new(Mutex::new(SocketInner::new(
+                socket_ptr,
+                umem.clone(),
+                if_name.clone(),
+                queue_id,
+                config,
+            ))),
         };
 
+        socket.check_copy_mode_fallback(&config)?;
+
+        if let Some((timeout, budget)) = config.busy_poll() {
+            socket
+                .fd
+                .enable_busy_poll(timeout, budget)
+                .map_err(|e| SocketCreateError::new(SocketCreateErrorKind::BusyPoll, e))?;
+        }
+
         let tx_q = if tx_q.is_ring_null() {
-            return Err(SocketCreateError {
-                reason: "returned tx queue ring is null",
-                err: io::Error::from_raw_os_error(-err),
-            });
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::RingNull(RingKind::Tx),
+                RingSetupError::new(RingKind::Tx, RingSetupError::VERSION_HINT).into(),
+            ));
         } else {
             TxQueue::new(tx_q, socket.clone())
         };
 
         let rx_q = if rx_q.is_ring_null() {
-            return Err(SocketCreateError {
-                reason: "returned rx queue ring is null",
-                err: io::Error::from_raw_os_error(-err),
-            });
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::RingNull(RingKind::Rx),
+                RingSetupError::new(RingKind::Rx, RingSetupError::VERSION_HINT).into(),
+            ));
         } else {
             RxQueue::new(rx_q, socket)
         };
@@ -209,23 +450,541 @@ impl Socket {
 
                 Some((fq, cq))
             }
-            _ => {
-                return Err(SocketCreateError {
-                    reason: "fill queue xor comp queue ring is null, either both or neither should be non-null",
-                    err: io::Error::from_raw_os_error(-err),
-                });
+            (fq_null, _) => {
+                let ring = if fq_null {
+                    RingKind::Fill
+                } else {
+                    RingKind::Completion
+                };
+
+                return Err(SocketCreateError::new(
+                    SocketCreateErrorKind::RingNull(ring),
+                    RingSetupError::new(ring, RingSetupError::SHARED_HINT).into(),
+                ));
             }
         };
 
+        if umem.config().lock_memory() {
+            let mut spans = vec![tx_q.ring_info().span(), rx_q.ring_info().span()];
+
+            if let Some((fq, cq)) = &fq_and_cq {
+                spans.extend([fq.ring_info().span(), cq.ring_info().span()]);
+            }
+
+            for span in spans {
+                util::mlock(span)
+                    .map_err(|e| SocketCreateError::new(SocketCreateErrorKind::LockRings, e))?;
+            }
+        }
+
         Ok((tx_q, rx_q, fq_and_cq))
     }
+
+    /// Bind a replacement for the socket behind `rx_q` to `queue_id`
+    /// of the same interface, sharing its [`Umem`] and config, then
+    /// switch `xsks_map_fd` over to it. Used to move a socket to a
+    /// different queue at runtime, for example after the interface's
+    /// channel count has been changed.
+    ///
+    /// The replacement's entry in the `XSKMAP` is added before the
+    /// old socket's entry is removed, so there's no point at which
+    /// neither queue is redirected. If `queue_id` is the socket's
+    /// current queue then the entry is replaced in a single step.
+    ///
+    /// The old socket's queues should be drained and then dropped:
+    /// once its entry is removed no new packets arrive on its
+    /// [`RxQueue`], but some may still be on the ring, and frames on
+    /// its [`TxQueue`] are still completed via the [`CompQueue`] of
+    /// its `(if_name, queue_id)` pair. Frames on that pair's
+    /// [`FillQueue`] are only released once the old socket and its
+    /// fill queue are dropped.
+    ///
+    /// Returns the replacement's queues, as per [`new`](Self::new).
+    ///
+    /// # Safety
+    ///
+    /// The XDP program must be managed by the caller, so the socket's
+    /// config must have the [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`]
+    /// flag set, and `xsks_map_fd` must be the `XSKMAP` it redirects
+    /// packets through. See also the safety section of
+    /// [`new`](Self::new).
+    ///
+    /// [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`]: crate::config::LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn migrate(
+        rx_q: &RxQueue,
+        queue_id: u32,
+        xsks_map_fd: RawFd,
+    ) -> Result<(TxQueue, RxQueue, Option<(FillQueue, CompQueue)>), SocketCreateError> {
+        let (umem, if_name, old_queue_id, config) = {
+            let inner = rx_q.socket().inner.lock().unwrap();
+
+            (
+                inner.umem.clone(),
+                inner.if_name.clone(),
+                inner.queue_id,
+                inner.config,
+            )
+        };
+
+        if !config
+            .libxdp_flags()
+            .contains(LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD)
+        {
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::ProgLoadNotInhibited,
+                io::Error::from(io::ErrorKind::InvalidInput),
+            )
+            .at(&if_name, old_queue_id));
+        }
+
+        // SAFETY: the unsafe contract of this function.
+        let (tx_q, new_rx_q, fq_and_cq) = unsafe { Self::new(config, &umem, &if_name, queue_id)? };
+
+        new_rx_q.update_xskmap(xsks_map_fd).map_err(|err| {
+            SocketCreateError::new(SocketCreateErrorKind::XskMapAdd, err).at(&if_name, queue_id)
+        })?;
+
+        if old_queue_id != queue_id {
+            xskmap::delete(xsks_map_fd, old_queue_id).map_err(|err| {
+                SocketCreateError::new(SocketCreateErrorKind::XskMapRemove, err)
+                    .at(&if_name, old_queue_id)
+            })?;
+        }
+
+        Ok((tx_q, new_rx_q, fq_and_cq))
+    }
+
+    /// See [`RxQueue::update_xskmap`].
+    pub(crate) fn update_xskmap(&self, xsks_map_fd: RawFd) -> io::Result<()> {
+        let queue_id = self.inner.lock().unwrap().queue_id;
+
+        xskmap::update(xsks_map_fd, queue_id, self.fd.as_raw_fd())
+    }
+
+    /// Reassemble a socket from an AF_XDP socket file descriptor
+    /// that was created and bound elsewhere, for example by a C
+    /// library in-process or by a privileged helper which passed it
+    /// over a unix socket.
+    ///
+    /// Only the socket is created. Its queues can then be rebuilt
+    /// with [`RxQueue::from_raw`] and [`TxQueue::from_raw`]. The
+    /// [`FillQueue`] and [`CompQueue`] are those of `umem`, and must
+    /// be taken from wherever `umem` was first bound. If `umem` was
+    /// itself reassembled with [`Umem::from_parts`] and `fd` is the
+    /// socket it was registered through, they're rebuilt with
+    /// [`FillQueue::from_raw`] and [`CompQueue::from_raw`].
+    ///
+    /// The file descriptor is closed once the socket and all of its
+    /// queues are dropped. Fails if `fd` isn't an AF_XDP socket.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be bound to `queue_id` of `if_name`, with rings
+    /// of the sizes given in `config`, and its UMEM must be `umem`,
+    /// either having been registered through it or shared with it via
+    /// `XDP_SHARED_UMEM`.
+    pub unsafe fn from_parts<C>(
+        fd: OwnedFd,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+        config: C,
+    ) -> Result<Self, SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        let socket = Self::from_owned_fd(fd, umem, if_name, queue_id, *config.borrow());
+
+        // Only succeeds for AF_XDP sockets.
+        socket
+            .fd
+            .mmap_offsets()
+            .map_err(|err| socket.create_error(SocketCreateErrorKind::NotXdpSocket, err))?;
+
+        Ok(socket)
+    }
+
+    /// An error of `kind` for this socket's interface and queue.
+    pub(crate) fn create_error(
+        &self,
+        kind: SocketCreateErrorKind,
+        err: io::Error,
+    ) -> SocketCreateError {
+        let inner = self.inner.lock().unwrap();
+
+        SocketCreateError::new(kind, err).at(&inner.if_name, inner.queue_id)
+    }
+
+    /// Map one of the socket's rings, which stays mapped until the
+    /// socket and all of its queues are dropped. `select` picks the
+    /// ring's offsets from those reported by the kernel.
+    ///
+    /// # Safety
+    ///
+    /// See [`RingMmap::map`].
+    pub(crate) unsafe fn map_ring<T, R>(
+        &self,
+        pgoff: u64,
+        select: fn(&MmapOffsets) -> &RingOffsets,
+        size: u32,
+        ring: impl FnOnce(&RingMmap) -> R,
+    ) -> io::Result<R> {
+        let mmap = unsafe { self.mmap_ring::<T>(pgoff, select, size)? };
+
+        let ring = ring(&mmap);
+
+        // Moving the mapping doesn't move the memory it maps.
+        self.inner.lock().unwrap().rings.push(mmap);
+
+        Ok(ring)
+    }
+
+    /// Same as [`map_ring`](Self::map_ring) but for the fill and
+    /// completion rings, whose mappings are kept by the socket's UMEM
+    /// instead since its queues may outlive the socket.
+    ///
+    /// # Safety
+    ///
+    /// See [`RingMmap::map`].
+    pub(crate) unsafe fn map_umem_ring<T, R>(
+        &self,
+        pgoff: u64,
+        select: fn(&MmapOffsets) -> &RingOffsets,
+        size: u32,
+        ring: impl FnOnce(&RingMmap) -> R,
+    ) -> io::Result<R> {
+        let mmap = unsafe { self.mmap_ring::<T>(pgoff, select, size)? };
+
+        let ring = ring(&mmap);
+
+        self.umem().keep_ring(mmap);
+
+        Ok(ring)
+    }
+
+    unsafe fn mmap_ring<T>(
+        &self,
+        pgoff: u64,
+        select: fn(&MmapOffsets) -> &RingOffsets,
+        size: u32,
+    ) -> io::Result<RingMmap> {
+        let offsets = self.fd.mmap_offsets()?;
+
+        unsafe { RingMmap::map::<T>(self.fd.as_raw_fd(), pgoff, *select(&offsets), size) }
+    }
+
+    /// The config the socket was created with.
+    pub(crate) fn config(&self) -> SocketConfig {
+        self.inner.lock().unwrap().config
+    }
+
+    /// The interface the socket is bound to.
+    pub(crate) fn if_name(&self) -> Interface {
+        self.inner.lock().unwrap().if_name.clone()
+    }
+
+    /// The socket's registration with the tokio reactor, created on
+    /// first use. Must be called from within a tokio runtime.
+    #[cfg(feature = "async")]
+    pub(crate) fn async_fd(&self) -> io::Result<Arc<AsyncFd<SocketFd>>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some(fd) = &inner.reactor {
+            return Ok(Arc::clone(fd));
+        }
+
+        let fd = Arc::new(AsyncFd::new(SocketFd(self.fd.as_raw_fd()))?);
+
+        inner.reactor = Some(Arc::clone(&fd));
+
+        Ok(fd)
+    }
+
+    /// The UMEM the socket is bound to.
+    pub(crate) fn umem(&self) -> Umem {
+        self.inner.lock().unwrap().umem.clone()
+    }
+
+    /// Create a socket around a file descriptor that isn't managed by
+    /// libxdp, for example one end of the socket pair used by the
+    /// simulated backend. The file descriptor is closed once the
+    /// socket and all of its queues are dropped.
+    pub(crate) fn from_owned_fd(
+        fd: OwnedFd,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+        config: SocketConfig,
+    ) -> Self {
+        Socket {
+            fd: Fd::new(fd.as_raw_fd()),
+            link: Arc::new(LinkState::new(if_name.clone())),
+            inner: Arc::new(Mutex::new(SocketInner::with_owned_fd(
+                fd,
+                umem.clone(),
+                if_name.clone(),
+                queue_id,
+                config,
+            ))),
+        }
+    }
+
+    /// Check whether the kernel bound the socket in copy mode without
+    /// it being requested. Logs a warning if so, or fails if the
+    /// config is in strict mode.
+    fn check_copy_mode_fallback(&self, config: &SocketConfig) -> Result<(), SocketCreateError> {
+        if config.copy_mode_requested() {
+            return Ok(());
+        }
+
+        let options = match self.fd.xdp_options() {
+            Ok(options) => options,
+            Err(err) if config.strict_mode() => {
+                return Err(SocketCreateError::new(
+                    SocketCreateErrorKind::XdpOptions,
+                    err,
+                ));
+            }
+            // Kernels older than 5.3 don't support `XDP_OPTIONS`, so
+            // there's nothing to check.
+            Err(_) => return Ok(()),
+        };
+
+        if options.contains(XdpOptions::ZEROCOPY) {
+            return Ok(());
+        }
+
+        if config.strict_mode() {
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::CopyMode,
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "zero-copy mode unavailable on interface",
+                ),
+            ));
+        }
+
+        let inner = self.inner.lock().unwrap();
+
+        warn!(
+            "AF_XDP socket on {:?} queue {} was bound in copy mode despite it not being \
+             requested, the driver likely lacks native XDP or zero-copy support",
+            inner.if_name.as_cstr(),
+            inner.queue_id,
+        );
+
+        Ok(())
+    }
+
+    /// Details of where the socket is bound and the rings it was
+    /// bound with.
+    pub(crate) fn info(&self) -> io::Result<SocketInfo> {
+        let offsets = self.fd.mmap_offsets()?;
+        let options = self.fd.xdp_options()?.bits();
+
+        let inner = self.inner.lock().unwrap();
+
+        Ok(SocketInfo::new(
+            inner.if_name.clone(),
+            inner.queue_id,
+            inner.config,
+            *inner.umem.config(),
+            options,
+            offsets,
+        ))
+    }
+}
+
+/// A reference to a [`Socket`] which doesn't keep it open.
+#[derive(Debug)]
+pub(crate) struct WeakSocket {
+    fd: Fd,
+    inner: Weak<Mutex<SocketInner>>,
+}
+
+impl WeakSocket {
+    pub(crate) fn new(socket: &Socket) -> Self {
+        Self {
+            fd: socket.fd.clone(),
+            inner: Arc::downgrade(&socket.inner),
+        }
+    }
+
+    /// Whether the socket is still open.
+    pub(crate) fn is_alive(&self) -> bool {
+        self.inner.strong_count() > 0
+    }
+
+    /// Whether both refer to the same socket.
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
+
+    /// The socket's statistics labelled with where it's bound, or
+    /// `None` if it's been closed.
+    pub(crate) fn stats(&self) -> Option<io::Result<SocketStats>> {
+        // The file descriptor stays open for as long as `inner` does.
+        let inner = self.inner.upgrade()?;
+
+        let stats = self.fd.xdp_statistics().and_then(|stats| {
+            let cookie = self.fd.cookie()?;
+            let inner = inner.lock().unwrap();
+
+            Ok(SocketStats::new(
+                inner.if_name.clone(),
+                inner.queue_id,
+                cookie,
+                stats,
+            ))
+        });
+
+        Some(stats)
+    }
+}
+
+impl AsRawFd for Socket {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for Socket {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
 }
 
 impl Clone for Socket {
     fn clone(&self) -> Self {
         Self {
             fd: self.fd.clone(),
-            _inner: self._inner.clone(),
+            link: self.link.clone(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// What went wrong when creating a [`Socket`], see
+/// [`SocketCreateError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketCreateErrorKind {
+    /// The interface doesn't support the flags requested in the
+    /// config. The source is an [`UnsupportedFlags`] listing them.
+    UnsupportedFlags,
+    /// libxdp failed to create and bind the socket, or to load and
+    /// attach its XDP program. See [`SocketCreateError::errno`], for
+    /// example `EPERM` if lacking the required capabilities, `EBUSY`
+    /// if the queue is already bound to, or `EOPNOTSUPP` if the
+    /// driver doesn't support the requested bind mode.
+    Bind,
+    /// libxdp reported success but returned no socket.
+    SocketNull,
+    /// libxdp reported success but didn't set up one of the rings.
+    /// See [`SocketCreateError::ring_setup_error`].
+    RingNull(RingKind),
+    /// The socket's file descriptor couldn't be retrieved.
+    SocketFd,
+    /// The busy poll socket options couldn't be set.
+    BusyPoll,
+    /// The socket's rings couldn't be locked into memory.
+    LockRings,
+    /// The socket's `XDP_OPTIONS` couldn't be retrieved to check
+    /// which mode it was bound in.
+    XdpOptions,
+    /// The socket was bound in copy mode without it being requested,
+    /// and the config is in strict mode.
+    CopyMode,
+    /// The file descriptor passed to [`Socket::from_parts`] isn't an
+    /// AF_XDP socket.
+    NotXdpSocket,
+    /// One of the rings of a socket passed to
+    /// [`Socket::from_parts`] couldn't be mapped.
+    MapRing(RingKind),
+    /// [`Socket::migrate`] was called for a socket without the
+    /// `XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD` flag set.
+    ProgLoadNotInhibited,
+    /// The replacement socket couldn't be added to the `XSKMAP`.
+    XskMapAdd,
+    /// The old socket couldn't be removed from the `XSKMAP`.
+    XskMapRemove,
+    /// The interface's channels couldn't be queried.
+    Channels,
+    /// The interface reported having no queues.
+    NoQueues,
+    /// The fill and completion queues passed to
+    /// [`Socket::new_shared`] belong to a different UMEM.
+    ForeignQueues,
+    /// The queue passed to [`Socket::new_shared`] wasn't already
+    /// bound to with the UMEM.
+    NotShared,
+    /// The network namespace couldn't be entered.
+    NetNs,
+    /// The helper thread binding the socket couldn't be spawned.
+    Thread,
+    /// Creation gave up because it was cancelled, see
+    /// [`Socket::new_cancellable`].
+    Cancelled,
+    /// Creation gave up because its timeout passed, see
+    /// [`Socket::new_with_timeout`].
+    TimedOut,
+    /// The UMEM created for the socket by [`Socket::new_owned`]
+    /// couldn't be.
+    Umem(UmemCreateErrorKind),
+}
+
+impl fmt::Display for SocketCreateErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketCreateErrorKind::UnsupportedFlags => {
+                write!(f, "requested flags not supported by the interface")
+            }
+            SocketCreateErrorKind::Bind => write!(f, "failed to create AF_XDP socket"),
+            SocketCreateErrorKind::SocketNull => write!(f, "returned socket pointer was null"),
+            SocketCreateErrorKind::RingNull(ring) => write!(f, "returned {} ring is null", ring),
+            SocketCreateErrorKind::SocketFd => {
+                write!(f, "failed to retrieve AF_XDP socket file descriptor")
+            }
+            SocketCreateErrorKind::BusyPoll => write!(f, "failed to set busy poll socket options"),
+            SocketCreateErrorKind::LockRings => write!(f, "failed to lock socket rings"),
+            SocketCreateErrorKind::XdpOptions => write!(
+                f,
+                "failed to retrieve XDP_OPTIONS to check the socket's bind mode"
+            ),
+            SocketCreateErrorKind::CopyMode => write!(
+                f,
+                "socket was bound in copy mode despite it not being requested"
+            ),
+            SocketCreateErrorKind::NotXdpSocket => write!(
+                f,
+                "failed to retrieve mmap offsets, fd may not be an AF_XDP socket"
+            ),
+            SocketCreateErrorKind::MapRing(ring) => write!(f, "failed to map {} ring", ring),
+            SocketCreateErrorKind::ProgLoadNotInhibited => write!(
+                f,
+                "socket must be created with XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD to migrate"
+            ),
+            SocketCreateErrorKind::XskMapAdd => {
+                write!(f, "failed to add replacement socket to XSKMAP")
+            }
+            SocketCreateErrorKind::XskMapRemove => {
+                write!(f, "failed to remove old socket from XSKMAP")
+            }
+            SocketCreateErrorKind::Channels => write!(f, "failed to query interface channels"),
+            SocketCreateErrorKind::NoQueues => write!(f, "interface reported no queues"),
+            SocketCreateErrorKind::ForeignQueues => {
+                write!(f, "fill and completion queues belong to a different UMEM")
+            }
+            SocketCreateErrorKind::NotShared => {
+                write!(f, "interface queue was not already bound to with this UMEM")
+            }
+            SocketCreateErrorKind::NetNs => write!(f, "failed to enter network namespace"),
+            SocketCreateErrorKind::Thread => write!(f, "failed to spawn socket bind thread"),
+            SocketCreateErrorKind::Cancelled => write!(f, "socket creation cancelled"),
+            SocketCreateErrorKind::TimedOut => write!(f, "timed out waiting for socket to bind"),
+            SocketCreateErrorKind::Umem(kind) => write!(f, "failed to create UMEM: {}", kind),
         }
     }
 }
@@ -233,13 +992,92 @@ impl Clone for Socket {
 /// Error detailing why [`Socket`] creation failed.
 #[derive(Debug)]
 pub struct SocketCreateError {
-    reason: &'static str,
+    kind: SocketCreateErrorKind,
     err: io::Error,
+    if_name: Option<Interface>,
+    queue_id: Option<u32>,
+}
+
+impl SocketCreateError {
+    pub(crate) fn new(kind: SocketCreateErrorKind, err: io::Error) -> Self {
+        Self {
+            kind,
+            err,
+            if_name: None,
+            queue_id: None,
+        }
+    }
+
+    /// Record the interface creation failed for, unless already
+    /// recorded by whatever failed.
+    pub(crate) fn on(mut self, if_name: &Interface) -> Self {
+        if self.if_name.is_none() {
+            self.if_name = Some(if_name.clone());
+        }
+
+        self
+    }
+
+    /// Record the interface and queue creation failed for, unless
+    /// already recorded by whatever failed.
+    pub(crate) fn at(self, if_name: &Interface, queue_id: u32) -> Self {
+        let mut err = self.on(if_name);
+        err.queue_id.get_or_insert(queue_id);
+        err
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> SocketCreateErrorKind {
+        self.kind
+    }
+
+    /// The OS error code behind the failure, if there is one.
+    pub fn errno(&self) -> Option<i32> {
+        self.err.raw_os_error()
+    }
+
+    /// The interface creation failed for, if known.
+    pub fn if_name(&self) -> Option<&Interface> {
+        self.if_name.as_ref()
+    }
+
+    /// The queue id creation failed for, if known.
+    pub fn queue_id(&self) -> Option<u32> {
+        self.queue_id
+    }
+
+    /// The details of the ring which wasn't set up, if that's why
+    /// creation failed.
+    pub fn ring_setup_error(&self) -> Option<&RingSetupError> {
+        ring::ring_setup_error(&self.err)
+    }
+
+    /// Whether creation gave up because its timeout passed, see
+    /// [`Socket::new_with_timeout`].
+    pub fn timed_out(&self) -> bool {
+        self.kind == SocketCreateErrorKind::TimedOut
+    }
+
+    /// Whether creation gave up because it was cancelled, see
+    /// [`Socket::new_cancellable`].
+    pub fn cancelled(&self) -> bool {
+        self.kind == SocketCreateErrorKind::Cancelled
+    }
 }
 
 impl fmt::Display for SocketCreateError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.reason)
+        write!(f, "{}", self.kind)?;
+
+        if let Some(if_name) = &self.if_name {
+            write!(f, " on {:?}", if_name.as_cstr())?;
+        }
+
+        if let Some(queue_id) = self.queue_id {
+            write!(f, " queue {}", queue_id)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -248,3 +1086,57 @@ impl Error for SocketCreateError {
         Some(self.err.borrow())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryInto, net::UdpSocket};
+
+    use crate::config::UmemConfig;
+
+    use super::*;
+
+    #[test]
+    fn from_parts_rejects_non_xdp_sockets() {
+        let (umem, _descs) =
+            Umem::new_unregistered(UmemConfig::default(), 16.try_into().unwrap()).unwrap();
+
+        let fd = OwnedFd::from(UdpSocket::bind("127.0.0.1:0").unwrap());
+
+        let res = unsafe {
+            Socket::from_parts(
+                fd,
+                &umem,
+                &"lo".parse().unwrap(),
+                0,
+                SocketConfig::default(),
+            )
+        };
+
+        let err = res.unwrap_err();
+
+        assert_eq!(err.kind(), SocketCreateErrorKind::NotXdpSocket);
+        assert_eq!(err.if_name().unwrap().as_cstr().to_str(), Ok("lo"));
+        assert_eq!(err.queue_id(), Some(0));
+        assert!(err.to_string().ends_with(" on \"lo\" queue 0"));
+    }
+
+    #[test]
+    fn ring_setup_errors_can_be_recovered() {
+        let err = SocketCreateError::new(
+            SocketCreateErrorKind::RingNull(RingKind::Rx),
+            RingSetupError::new(RingKind::Rx, RingSetupError::VERSION_HINT).into(),
+        );
+
+        let ring_err = err.ring_setup_error().unwrap();
+
+        assert_eq!(ring_err.ring(), RingKind::Rx);
+        assert!(ring_err.to_string().starts_with("rx ring was not set up"));
+
+        let err = SocketCreateError::new(
+            SocketCreateErrorKind::SocketFd,
+            io::Error::from_raw_os_error(libc::EBADF),
+        );
+
+        assert!(err.ring_setup_error().is_none());
+    }
+}
@@ -0,0 +1,255 @@
+//! Binding a socket to every queue of an interface.
+
+use std::{borrow::Borrow, io};
+
+use crate::{
+    config::{Interface, SocketConfig},
+    umem::{CompQueue, FillQueue, Umem},
+};
+
+use super::{
+    genl::{self, find_u32, GenlSocket},
+    RxQueue, Socket, SocketCreateError, SocketCreateErrorKind, TxQueue,
+};
+
+// From `linux/ethtool_netlink.h`.
+const ETHTOOL_MSG_CHANNELS_GET: u8 = 17;
+const ETHTOOL_A_CHANNELS_HEADER: u16 = 1;
+const ETHTOOL_A_CHANNELS_RX_MAX: u16 = 2;
+const ETHTOOL_A_CHANNELS_TX_MAX: u16 = 3;
+const ETHTOOL_A_CHANNELS_OTHER_MAX: u16 = 4;
+const ETHTOOL_A_CHANNELS_COMBINED_MAX: u16 = 5;
+const ETHTOOL_A_CHANNELS_RX_COUNT: u16 = 6;
+const ETHTOOL_A_CHANNELS_TX_COUNT: u16 = 7;
+const ETHTOOL_A_CHANNELS_OTHER_COUNT: u16 = 8;
+const ETHTOOL_A_CHANNELS_COMBINED_COUNT: u16 = 9;
+const ETHTOOL_A_HEADER_DEV_NAME: u16 = 2;
+
+/// The number of channels an interface is currently configured with,
+/// as shown by `ethtool -l`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Channels {
+    rx: u32,
+    tx: u32,
+    other: u32,
+    combined: u32,
+    max_rx: u32,
+    max_tx: u32,
+    max_other: u32,
+    max_combined: u32,
+}
+
+impl Channels {
+    /// Query the channels of `if_name`.
+    ///
+    /// Requires linux 5.6 or later, which added the ethtool netlink
+    /// interface.
+    pub fn query(if_name: &Interface) -> io::Result<Self> {
+        let sock = GenlSocket::open()?;
+        let family = sock.family_id("ethtool")?;
+
+        let reply = sock.request(family, ETHTOOL_MSG_CHANNELS_GET, &request_attrs(if_name))?;
+
+        Ok(Self::from_attrs(&reply))
+    }
+
+    fn from_attrs(attrs: &[u8]) -> Self {
+        // Counts the driver doesn't support are left out of the reply.
+        let count = |ty| find_u32(attrs, ty).unwrap_or(0);
+
+        Self {
+            rx: count(ETHTOOL_A_CHANNELS_RX_COUNT),
+            tx: count(ETHTOOL_A_CHANNELS_TX_COUNT),
+            other: count(ETHTOOL_A_CHANNELS_OTHER_COUNT),
+            combined: count(ETHTOOL_A_CHANNELS_COMBINED_COUNT),
+            max_rx: count(ETHTOOL_A_CHANNELS_RX_MAX),
+            max_tx: count(ETHTOOL_A_CHANNELS_TX_MAX),
+            max_other: count(ETHTOOL_A_CHANNELS_OTHER_MAX),
+            max_combined: count(ETHTOOL_A_CHANNELS_COMBINED_MAX),
+        }
+    }
+
+    /// The number of receive-only channels.
+    #[inline]
+    pub fn rx(&self) -> u32 {
+        self.rx
+    }
+
+    /// The number of transmit-only channels.
+    #[inline]
+    pub fn tx(&self) -> u32 {
+        self.tx
+    }
+
+    /// The number of channels used for neither, for example link
+    /// interrupts.
+    #[inline]
+    pub fn other(&self) -> u32 {
+        self.other
+    }
+
+    /// The number of channels used for both receive and transmit.
+    #[inline]
+    pub fn combined(&self) -> u32 {
+        self.combined
+    }
+
+    /// The number of queue ids a socket can be bound to, that is the
+    /// number of receive queues.
+    #[inline]
+    pub fn queue_count(&self) -> u32 {
+        self.combined + self.rx
+    }
+
+    /// The most receive-only channels the driver supports.
+    #[inline]
+    pub fn max_rx(&self) -> u32 {
+        self.max_rx
+    }
+
+    /// The most transmit-only channels the driver supports.
+    #[inline]
+    pub fn max_tx(&self) -> u32 {
+        self.max_tx
+    }
+
+    /// The most channels used for neither the driver supports.
+    #[inline]
+    pub fn max_other(&self) -> u32 {
+        self.max_other
+    }
+
+    /// The most combined channels the driver supports.
+    #[inline]
+    pub fn max_combined(&self) -> u32 {
+        self.max_combined
+    }
+
+    /// An upper bound on the number of queue ids a socket could be
+    /// bound to, were the interface reconfigured with `ethtool -L`.
+    #[inline]
+    pub fn max_queue_count(&self) -> u32 {
+        self.max_combined + self.max_rx
+    }
+}
+
+fn request_attrs(if_name: &Interface) -> Vec<u8> {
+    let mut header = Vec::new();
+    genl::put_attr(
+        &mut header,
+        ETHTOOL_A_HEADER_DEV_NAME,
+        if_name.as_cstr().to_bytes_with_nul(),
+    );
+
+    let mut attrs = Vec::new();
+    genl::put_nested(&mut attrs, ETHTOOL_A_CHANNELS_HEADER, &header);
+
+    attrs
+}
+
+/// The queues of a socket bound to one of an interface's queues by
+/// [`Socket::new_for_all_queues`].
+#[derive(Debug)]
+pub struct QueueHandles {
+    /// The queue id the socket is bound to.
+    pub queue_id: u32,
+    /// The socket's tx queue.
+    pub tx_q: TxQueue,
+    /// The socket's rx queue.
+    pub rx_q: RxQueue,
+    /// The fill and completion queue for this queue id, if any. See
+    /// [`Socket::new`] for when to expect [`None`].
+    pub fq_and_cq: Option<(FillQueue, CompQueue)>,
+}
+
+impl Socket {
+    /// Bind a socket to each of `if_name`'s queues, all sharing
+    /// `umem`, returning their queues ordered by queue id.
+    ///
+    /// The number of queues is found with [`Channels::query`]. Each
+    /// socket is created with [`new`](Self::new) and `config`. If any
+    /// fails the sockets created so far are dropped
+    /// and the error returned.
+    ///
+    /// Since every socket shares `umem`, each queue id gets its own
+    /// [`FillQueue`] and [`CompQueue`], but frames must be divided up
+    /// between them.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](Self::new), which applies to every queue id.
+    pub unsafe fn new_for_all_queues<C>(
+        config: C,
+        umem: &Umem,
+        if_name: &Interface,
+    ) -> Result<Vec<QueueHandles>, SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        let channels = Channels::query(if_name)
+            .map_err(|e| SocketCreateError::new(SocketCreateErrorKind::Channels, e).on(if_name))?;
+
+        let queue_count = channels.queue_count();
+
+        if queue_count == 0 {
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::NoQueues,
+                io::Error::from(io::ErrorKind::NotFound),
+            )
+            .on(if_name));
+        }
+
+        (0..queue_count)
+            .map(|queue_id| {
+                // SAFETY: the unsafe contract of `new_for_all_queues`.
+                let (tx_q, rx_q, fq_and_cq) =
+                    unsafe { Self::new(config.borrow(), umem, if_name, queue_id)? };
+
+                Ok(QueueHandles {
+                    queue_id,
+                    tx_q,
+                    rx_q,
+                    fq_and_cq,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_are_parsed_from_reply() {
+        let mut attrs = request_attrs(&"eth0".parse().unwrap());
+        genl::put_attr(&mut attrs, ETHTOOL_A_CHANNELS_RX_COUNT, &2u32.to_ne_bytes());
+        genl::put_attr(
+            &mut attrs,
+            ETHTOOL_A_CHANNELS_COMBINED_COUNT,
+            &4u32.to_ne_bytes(),
+        );
+
+        let channels = Channels::from_attrs(&attrs);
+
+        assert_eq!(channels.rx(), 2);
+        assert_eq!(channels.tx(), 0);
+        assert_eq!(channels.other(), 0);
+        assert_eq!(channels.combined(), 4);
+        assert_eq!(channels.queue_count(), 6);
+        assert_eq!(channels.max_queue_count(), 0);
+
+        genl::put_attr(&mut attrs, ETHTOOL_A_CHANNELS_RX_MAX, &2u32.to_ne_bytes());
+        genl::put_attr(
+            &mut attrs,
+            ETHTOOL_A_CHANNELS_COMBINED_MAX,
+            &16u32.to_ne_bytes(),
+        );
+
+        let channels = Channels::from_attrs(&attrs);
+
+        assert_eq!(channels.max_rx(), 2);
+        assert_eq!(channels.max_combined(), 16);
+        assert_eq!(channels.max_queue_count(), 18);
+    }
+}
@@ -0,0 +1,167 @@
+//! Creating sockets inside another network namespace.
+
+use log::error;
+use std::{
+    borrow::Borrow,
+    fs::File,
+    io,
+    marker::PhantomData,
+    os::unix::prelude::{AsRawFd, OwnedFd},
+    path::Path,
+};
+
+use crate::{
+    config::{Interface, SocketConfig},
+    umem::{CompQueue, FillQueue, Umem},
+};
+
+use super::{RxQueue, Socket, SocketCreateError, SocketCreateErrorKind, TxQueue};
+
+/// The directory `ip netns` keeps named network namespaces in.
+const NETNS_RUN_DIR: &str = "/var/run/netns";
+
+/// A handle to a network namespace.
+#[derive(Debug)]
+pub struct NetNs {
+    fd: OwnedFd,
+}
+
+impl NetNs {
+    /// Open the network namespace at `path`, for example
+    /// `/proc/<pid>/ns/net` or a namespace created by `ip netns add`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        Ok(Self { fd: file.into() })
+    }
+
+    /// Open the network namespace created by `ip netns add <name>`.
+    pub fn named(name: &str) -> io::Result<Self> {
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid network namespace name",
+            ));
+        }
+
+        Self::open(Path::new(NETNS_RUN_DIR).join(name))
+    }
+
+    /// The network namespace the calling thread is in.
+    pub fn current() -> io::Result<Self> {
+        Self::open("/proc/thread-self/ns/net")
+    }
+
+    /// Use an already open network namespace descriptor.
+    pub fn from_fd(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+
+    /// Move the calling thread into this namespace, until the
+    /// returned guard is dropped.
+    ///
+    /// Requires `CAP_SYS_ADMIN`.
+    pub fn enter(&self) -> io::Result<NetNsGuard> {
+        let prev = Self::current()?;
+
+        setns(&self.fd)?;
+
+        Ok(NetNsGuard {
+            prev,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+impl AsRawFd for NetNs {
+    #[inline]
+    fn as_raw_fd(&self) -> libc::c_int {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Keeps the calling thread in a network namespace entered with
+/// [`NetNs::enter`], moving it back to the namespace it was in before
+/// once dropped.
+///
+/// Network namespaces are per thread, so the guard can't be sent to
+/// another thread.
+#[derive(Debug)]
+#[must_use = "the previous namespace is restored as soon as the guard is dropped"]
+pub struct NetNsGuard {
+    prev: NetNs,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for NetNsGuard {
+    fn drop(&mut self) {
+        if let Err(e) = setns(&self.prev.fd) {
+            error!("failed to restore network namespace: {}", e);
+        }
+    }
+}
+
+fn setns(fd: &OwnedFd) -> io::Result<()> {
+    if unsafe { libc::setns(fd.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+impl Socket {
+    /// Same as [`new`](Self::new), but the socket is created inside
+    /// `netns`, which `if_name` is looked up in. The calling thread
+    /// enters the namespace for the duration of the call only.
+    ///
+    /// Once created the socket stays bound to the interface in
+    /// `netns`, and its queues can be used from any namespace. Link
+    /// status queries, such as [`TxQueue::link_status`], are however
+    /// made from the namespace of the calling thread.
+    ///
+    /// Requires `CAP_SYS_ADMIN`.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](Self::new).
+    #[allow(clippy::type_complexity)]
+    pub unsafe fn new_in_netns<C>(
+        config: C,
+        umem: &Umem,
+        if_name: &Interface,
+        queue_id: u32,
+        netns: &NetNs,
+    ) -> Result<(TxQueue, RxQueue, Option<(FillQueue, CompQueue)>), SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        let _guard = netns.enter().map_err(|e| {
+            SocketCreateError::new(SocketCreateErrorKind::NetNs, e).at(if_name, queue_id)
+        })?;
+
+        // SAFETY: the unsafe contract of `new_in_netns`.
+        unsafe { Self::new(config, umem, if_name, queue_id) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        for name in ["", ".", "..", "../foo", "a/b"] {
+            assert_eq!(
+                NetNs::named(name).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+
+    #[test]
+    fn current_namespace_can_be_opened() {
+        let netns = NetNs::current().unwrap();
+
+        assert!(netns.as_raw_fd() >= 0);
+    }
+}
@@ -0,0 +1,79 @@
+//! Binding a socket along with a [`Umem`] of its own, without
+//! `unsafe`.
+
+use std::{borrow::Borrow, num::NonZeroU32};
+
+use crate::{
+    config::{Interface, SocketConfig, UmemConfig},
+    umem::{frame::Frame, CompQueue, FillQueue, Umem},
+};
+
+use super::{RxQueue, Socket, SocketCreateError, SocketCreateErrorKind, TxQueue};
+
+/// A socket bound by [`Socket::new_owned`], along with the [`Umem`]
+/// created for it, all of the `Umem`'s frames and the socket's
+/// queues.
+#[derive(Debug)]
+pub struct OwnedSocket {
+    /// The socket's `Umem`.
+    pub umem: Umem,
+    /// All of the `Umem`'s frames, none of which have been handed to
+    /// the kernel yet.
+    pub frames: Vec<Frame>,
+    /// The socket's tx queue.
+    pub tx_q: TxQueue,
+    /// The socket's rx queue.
+    pub rx_q: RxQueue,
+    /// The `Umem`'s fill queue.
+    pub fq: FillQueue,
+    /// The `Umem`'s completion queue.
+    pub cq: CompQueue,
+}
+
+impl Socket {
+    /// Create a [`Umem`] of `frame_count` frames and bind a socket
+    /// using it to `(if_name, queue_id)`.
+    ///
+    /// Unlike [`new`](Self::new) this is safe, since nothing else has
+    /// a handle to the new `Umem` to have bound a socket with it
+    /// already. The frames are returned as owned [`Frame`]s, so the
+    /// socket can be driven entirely with the safe queue methods such
+    /// as [`FillQueue::produce_frames`] and
+    /// [`RxQueue::consume_frames`].
+    ///
+    /// If creating the `Umem` fails then the error's
+    /// [`kind`](SocketCreateError::kind) is
+    /// [`SocketCreateErrorKind::Umem`].
+    pub fn new_owned<C, U>(
+        config: C,
+        umem_config: U,
+        frame_count: NonZeroU32,
+        if_name: &Interface,
+        queue_id: u32,
+    ) -> Result<OwnedSocket, SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+        U: Borrow<UmemConfig>,
+    {
+        let (umem, frames) = Umem::new_owned(umem_config, frame_count, false).map_err(|e| {
+            SocketCreateError::new(SocketCreateErrorKind::Umem(e.kind()), e.into_inner())
+                .at(if_name, queue_id)
+        })?;
+
+        // SAFETY: the UMEM was only just created, so this is the first
+        // socket bound with it.
+        let (tx_q, rx_q, fq_and_cq) = unsafe { Self::new(config, &umem, if_name, queue_id)? };
+
+        let (fq, cq) =
+            fq_and_cq.expect("first socket bound with a UMEM gets its fill and comp queue");
+
+        Ok(OwnedSocket {
+            umem,
+            frames,
+            tx_q,
+            rx_q,
+            fq,
+            cq,
+        })
+    }
+}
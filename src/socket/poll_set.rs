@@ -0,0 +1,253 @@
+//! Polling many sockets with a single syscall.
+
+use bitflags::bitflags;
+use libc::{EINTR, POLLERR, POLLHUP, POLLIN, POLLOUT};
+use std::{fmt, io, os::unix::prelude::AsRawFd};
+
+use crate::util;
+
+use super::Fd;
+
+bitflags! {
+    /// The events a [`PollSet`] waits for on a socket, and those it
+    /// reports the socket ready for.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PollEvents: i16 {
+        /// There are frames to consume off the rx ring.
+        const READABLE = POLLIN;
+        /// There is space on the tx ring.
+        const WRITABLE = POLLOUT;
+        /// The socket has an error pending. Only ever reported.
+        const ERROR = POLLERR;
+        /// The socket has been hung up, for example because its
+        /// interface went away. Only ever reported.
+        const HANGUP = POLLHUP;
+    }
+}
+
+/// A socket reported ready by [`PollSet::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ready {
+    /// The token the socket was registered with.
+    pub token: usize,
+    /// The events the socket is ready for.
+    pub events: PollEvents,
+}
+
+impl Ready {
+    /// Whether there are frames to consume off the socket's rx ring.
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.events.contains(PollEvents::READABLE)
+    }
+
+    /// Whether there is space on the socket's tx ring.
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.events.contains(PollEvents::WRITABLE)
+    }
+}
+
+/// A set of sockets, each registered with the events to wait for and
+/// a token identifying it, which are all polled at once.
+///
+/// Lets a single thread servicing many queues wait on all of them
+/// with one `poll` syscall, rather than polling each in turn with a
+/// short timeout.
+///
+/// Sockets aren't kept alive by the set, so must be
+/// [removed](Self::remove) before being dropped, otherwise their file
+/// descriptors, possibly reused by then, carry on being polled.
+#[derive(Default)]
+pub struct PollSet {
+    pollfds: Vec<libc::pollfd>,
+    tokens: Vec<usize>,
+}
+
+impl PollSet {
+    /// An empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `fd` to be polled for `events`, reporting it with
+    /// `token` when ready. Registering a token already in the set
+    /// replaces its socket and events.
+    ///
+    /// [`ERROR`](PollEvents::ERROR) and
+    /// [`HANGUP`](PollEvents::HANGUP) are always reported, whether
+    /// asked for or not.
+    pub fn insert(&mut self, fd: &Fd, events: PollEvents, token: usize) {
+        let pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: events.bits(),
+            revents: 0,
+        };
+
+        match self.index_of(token) {
+            Some(idx) => self.pollfds[idx] = pollfd,
+            None => {
+                self.pollfds.push(pollfd);
+                self.tokens.push(token);
+            }
+        }
+    }
+
+    /// Change the events the socket registered with `token` is polled
+    /// for. Returns `false` if there is no such socket.
+    pub fn modify(&mut self, token: usize, events: PollEvents) -> bool {
+        match self.index_of(token) {
+            Some(idx) => {
+                self.pollfds[idx].events = events.bits();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop polling the socket registered with `token`. Returns
+    /// `false` if there is no such socket.
+    pub fn remove(&mut self, token: usize) -> bool {
+        match self.index_of(token) {
+            Some(idx) => {
+                self.pollfds.swap_remove(idx);
+                self.tokens.swap_remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The number of sockets in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pollfds.len()
+    }
+
+    /// Whether the set is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pollfds.is_empty()
+    }
+
+    /// Wait up to `timeout_ms` milliseconds for any of the sockets to
+    /// become ready, returning how many are. A negative timeout waits
+    /// indefinitely.
+    ///
+    /// Which sockets are ready, and for what, can then be read off
+    /// [`ready`](Self::ready), until the next call. As with the
+    /// queues' `poll` methods, an interrupted poll reports none ready
+    /// rather than an error.
+    pub fn poll(&mut self, timeout_ms: i32) -> io::Result<usize> {
+        self.pollfds
+            .iter_mut()
+            .for_each(|pollfd| pollfd.revents = 0);
+
+        let ret = unsafe {
+            libc::poll(
+                self.pollfds.as_mut_ptr(),
+                self.pollfds.len() as libc::nfds_t,
+                timeout_ms,
+            )
+        };
+
+        if ret < 0 {
+            if util::get_errno() != EINTR {
+                return Err(io::Error::last_os_error());
+            } else {
+                return Ok(0);
+            }
+        }
+
+        Ok(ret as usize)
+    }
+
+    /// The sockets found ready by the last call to
+    /// [`poll`](Self::poll).
+    pub fn ready(&self) -> impl Iterator<Item = Ready> + '_ {
+        self.pollfds
+            .iter()
+            .zip(self.tokens.iter())
+            .filter(|(pollfd, _)| pollfd.revents != 0)
+            .map(|(pollfd, &token)| Ready {
+                token,
+                events: PollEvents::from_bits_truncate(pollfd.revents),
+            })
+    }
+
+    fn index_of(&self, token: usize) -> Option<usize> {
+        self.tokens.iter().position(|&t| t == token)
+    }
+}
+
+impl fmt::Debug for PollSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollSet")
+            .field("tokens", &self.tokens)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::prelude::{FromRawFd, OwnedFd};
+
+    use super::*;
+
+    fn socketpair() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+
+        let ret = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_DGRAM, 0, fds.as_mut_ptr()) };
+        assert_eq!(ret, 0);
+
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn only_ready_sockets_are_reported() {
+        let (a, b) = socketpair();
+        let (c, _d) = socketpair();
+
+        let (b_fd, c_fd) =
+            unsafe { (Fd::borrow_raw(b.as_raw_fd()), Fd::borrow_raw(c.as_raw_fd())) };
+
+        let mut set = PollSet::new();
+
+        set.insert(&b_fd, PollEvents::READABLE, 1);
+        set.insert(&c_fd, PollEvents::READABLE, 2);
+
+        assert_eq!(set.poll(0).unwrap(), 0);
+        assert_eq!(set.ready().count(), 0);
+
+        let sent = unsafe { libc::send(a.as_raw_fd(), b"x".as_ptr() as *const _, 1, 0) };
+        assert_eq!(sent, 1);
+
+        assert_eq!(set.poll(100).unwrap(), 1);
+
+        let ready = set.ready().collect::<Vec<_>>();
+
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].token, 1);
+        assert!(ready[0].is_readable());
+        assert!(!ready[0].is_writable());
+    }
+
+    #[test]
+    fn sockets_can_be_modified_and_removed() {
+        let (a, _b) = socketpair();
+        let a_fd = unsafe { Fd::borrow_raw(a.as_raw_fd()) };
+
+        let mut set = PollSet::new();
+
+        set.insert(&a_fd, PollEvents::READABLE, 7);
+        assert_eq!(set.poll(0).unwrap(), 0);
+
+        assert!(set.modify(7, PollEvents::WRITABLE));
+        assert_eq!(set.poll(0).unwrap(), 1);
+        assert!(set.ready().next().unwrap().is_writable());
+
+        assert!(set.remove(7));
+        assert!(!set.remove(7));
+        assert!(set.is_empty());
+    }
+}
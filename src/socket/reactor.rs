@@ -0,0 +1,76 @@
+//! Registration of a socket's file descriptor with the tokio reactor,
+//! shared by all of its queues.
+
+use std::os::unix::prelude::{AsRawFd, RawFd};
+
+/// The raw socket file descriptor registered with the tokio reactor.
+/// Doesn't own the descriptor, that's left to the socket.
+#[derive(Debug)]
+pub(crate) struct SocketFd(pub(super) RawFd);
+
+impl AsRawFd for SocketFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, thread, time::Duration};
+
+    use crate::{sim::SimXsk, umem::frame::FrameDesc};
+
+    #[tokio::test]
+    async fn recv_waits_for_a_packet() {
+        let SimXsk {
+            descs,
+            mut fq,
+            mut rx_q,
+            mut kernel,
+            ..
+        } = SimXsk::with_defaults(16);
+
+        unsafe { fq.produce(&descs) };
+
+        let injector = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            assert!(kernel.inject(b"hello"));
+            kernel
+        });
+
+        let mut received = [FrameDesc::default(); 4];
+
+        let cnt = tokio::time::timeout(Duration::from_secs(1), unsafe {
+            rx_q.recv_async(&mut received)
+        })
+        .await
+        .expect("timed out waiting for packet")
+        .unwrap();
+
+        assert_eq!(cnt, 1);
+        assert_eq!(received[0].lengths().data(), 5);
+
+        injector.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn send_fails_if_frames_can_never_fit() {
+        let SimXsk {
+            descs, mut tx_q, ..
+        } = SimXsk::with_defaults(16);
+
+        let size = tx_q.ring_info().size() as usize;
+
+        assert_eq!(unsafe { tx_q.send_async(&descs[..1]) }.await.unwrap(), 1);
+
+        let too_many = vec![descs[1]; size + 1];
+
+        assert_eq!(
+            unsafe { tx_q.send_async(&too_many) }
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
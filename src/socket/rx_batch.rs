@@ -0,0 +1,223 @@
+//! A batch of received frames which must be handed back before it's
+//! dropped.
+
+use std::{io, mem, thread};
+
+use crate::{
+    ring::ProducePolicy,
+    umem::{frame::FrameDesc, FillQueue},
+};
+
+use super::TxQueue;
+
+/// A batch of frames received via [`RxQueue::recv_batch`], each of
+/// which must be either forwarded to a [`TxQueue`] or recycled back
+/// on to the [`FillQueue`] before the guard is dropped.
+///
+/// Dropping a guard with frames still in it is a bug in the
+/// application. In debug builds this panics, after recycling the
+/// frames. In release builds the frames are quietly recycled, so are
+/// never leaked either way.
+///
+/// The batch is never larger than the free space on the fill ring at
+/// the time it was received, and the guard holds on to the
+/// [`FillQueue`] until it's dropped, so there's always room to
+/// recycle every frame.
+///
+/// [`RxQueue::recv_batch`]: super::RxQueue::recv_batch
+#[derive(Debug)]
+#[must_use = "received frames must be forwarded or recycled"]
+pub struct RxBatchGuard<'a> {
+    fq: &'a mut FillQueue,
+    descs: &'a mut [FrameDesc],
+}
+
+impl<'a> RxBatchGuard<'a> {
+    pub(super) fn new(fq: &'a mut FillQueue, descs: &'a mut [FrameDesc]) -> Self {
+        Self { fq, descs }
+    }
+
+    /// The descriptors of the frames received.
+    #[inline]
+    pub fn descs(&self) -> &[FrameDesc] {
+        self.descs
+    }
+
+    /// Mutable access to the descriptors of the frames received, for
+    /// example for rewriting packets in place before forwarding them.
+    #[inline]
+    pub fn descs_mut(&mut self) -> &mut [FrameDesc] {
+        self.descs
+    }
+
+    /// The number of frames in the batch.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.descs.len()
+    }
+
+    /// Whether the batch is empty, i.e. nothing was received.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.descs.is_empty()
+    }
+
+    /// Hand every frame in the batch back to the [`FillQueue`] to be
+    /// received into again. Returns the number of frames recycled.
+    pub fn recycle(mut self) -> usize {
+        self.recycle_remaining()
+    }
+
+    /// Submit the frames in the batch to `tx_q` for transmission,
+    /// waking up the kernel if required. Returns the number of frames
+    /// submitted.
+    ///
+    /// Any frames which don't fit on the tx ring are recycled back on
+    /// to the [`FillQueue`], i.e. dropped, as a bridge or reflector
+    /// would.
+    ///
+    /// # Panics
+    ///
+    /// If `tx_q` is bound to a different [`Umem`](crate::Umem) than
+    /// the [`FillQueue`].
+    pub fn forward(mut self, tx_q: &mut TxQueue) -> io::Result<usize> {
+        assert!(
+            tx_q.umem().shares_mem(self.fq.umem()),
+            "tx queue belongs to a different UMEM"
+        );
+
+        // SAFETY: the frames were taken off the rx ring of a socket
+        // sharing the fill queue's UMEM, as is `tx_q`, and nothing
+        // else holds them.
+        let cnt = unsafe { tx_q.produce_with_policy(self.descs, ProducePolicy::SplitAcrossCalls)? };
+
+        self.descs = &mut mem::take(&mut self.descs)[cnt..];
+        self.recycle_remaining();
+
+        if cnt > 0 && tx_q.needs_wakeup() {
+            tx_q.wakeup()?;
+        }
+
+        Ok(cnt)
+    }
+
+    /// Take responsibility for the frames in the batch, returning
+    /// their descriptors. The frames must then eventually be handed
+    /// back via [`FillQueue::produce`] or [`TxQueue::produce`] as
+    /// usual.
+    pub fn release(mut self) -> &'a mut [FrameDesc] {
+        mem::take(&mut self.descs)
+    }
+
+    fn recycle_remaining(&mut self) -> usize {
+        let descs = mem::take(&mut self.descs);
+
+        // SAFETY: the frames were taken off the rx ring of a socket
+        // sharing this fill queue's UMEM and nothing else holds them.
+        let cnt = unsafe { self.fq.produce(descs) };
+
+        // Can't fail to fit, since the batch was no bigger than the
+        // free space on the fill ring.
+        debug_assert_eq!(cnt, descs.len());
+
+        cnt
+    }
+}
+
+impl Drop for RxBatchGuard<'_> {
+    fn drop(&mut self) {
+        if self.descs.is_empty() {
+            return;
+        }
+
+        let cnt = self.recycle_remaining();
+
+        if cfg!(debug_assertions) && !thread::panicking() {
+            panic!(
+                "RxBatchGuard dropped with {} frames neither forwarded nor recycled",
+                cnt
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::{
+        config::{QueueSize, SocketConfig, UmemConfig},
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    fn build(fq_size: u32, tx_size: u32) -> SimXsk {
+        let umem_config = UmemConfig::builder()
+            .fill_queue_size(QueueSize::new(fq_size).unwrap())
+            .build()
+            .unwrap();
+
+        let socket_config = SocketConfig::builder()
+            .tx_queue_size(QueueSize::new(tx_size).unwrap())
+            .build();
+
+        SimXsk::new(
+            umem_config,
+            socket_config,
+            32.try_into().unwrap(),
+            SimConfig::default(),
+        )
+        .unwrap()
+    }
+
+    fn receive(xsk: &mut SimXsk, n: usize) {
+        for _ in 0..n {
+            assert!(xsk.kernel.inject(b"hello"));
+        }
+    }
+
+    #[test]
+    fn frames_which_cannot_be_forwarded_are_recycled() {
+        let mut xsk = build(16, 4);
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..8]) }, 8);
+        receive(&mut xsk, 8);
+
+        let mut buf = vec![FrameDesc::default(); 16];
+        let batch = unsafe { xsk.rx_q.recv_batch(&mut xsk.fq, &mut buf) };
+
+        assert_eq!(batch.len(), 8);
+        assert_eq!(batch.forward(&mut xsk.tx_q).unwrap(), 4);
+
+        assert_eq!(xsk.tx_q.ring_info().occupancy(), 4);
+        assert_eq!(xsk.fq.ring_info().occupancy(), 4);
+    }
+
+    #[test]
+    fn batch_is_capped_by_free_space_on_the_fill_ring() {
+        let mut xsk = build(4, 4);
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..4]) }, 4);
+        receive(&mut xsk, 4);
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[4..8]) }, 4);
+
+        let mut buf = vec![FrameDesc::default(); 4];
+        let batch = unsafe { xsk.rx_q.recv_batch(&mut xsk.fq, &mut buf) };
+
+        assert!(batch.is_empty());
+        assert_eq!(xsk.rx_q.ring_info().occupancy(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "neither forwarded nor recycled")]
+    fn dropping_an_unhandled_batch_panics_in_debug() {
+        let mut xsk = build(16, 4);
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+        receive(&mut xsk, 2);
+
+        let mut buf = vec![FrameDesc::default(); 2];
+        let _batch = unsafe { xsk.rx_q.recv_batch(&mut xsk.fq, &mut buf) };
+    }
+}
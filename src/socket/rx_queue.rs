@@ -1,22 +1,110 @@
-use std::io;
+use libxdp_sys::{xdp_desc, XDP_PGOFF_RX_RING};
+use std::{
+    io,
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd},
+};
 
-use crate::{ring::XskRingCons, umem::frame::FrameDesc};
+use crate::{
+    ring::{NoHooks, QueueHooks, RingKind, XskRingCons},
+    umem::{
+        frame::{Data, Frame, FrameDesc},
+        ledger::FrameState,
+        pool::AllocOrder,
+        FillQueue, FrameAllocator, Umem,
+    },
+    util,
+};
 
-use super::{fd::Fd, Socket};
+#[cfg(feature = "metrics")]
+use super::QueueMetrics;
+use super::{
+    fd::Fd, metrics::Counters, LinkStatus, MmapOffsets, RingInfo, RxBatchGuard, Socket,
+    SocketCreateError, SocketCreateErrorKind, SocketInfo,
+};
 
 /// The receiving side of an AF_XDP [`Socket`].
 ///
 /// More details can be found in the
 /// [docs](https://www.kernel.org/doc/html/latest/networking/af_xdp.html#rx-ring).
+///
+/// Ring operations run the queue's [`QueueHooks`], which do nothing
+/// unless set with [`with_hooks`](Self::with_hooks).
 #[derive(Debug)]
-pub struct RxQueue {
+pub struct RxQueue<H = NoHooks> {
     ring: XskRingCons,
     socket: Socket,
+    umem: Umem,
+    counters: Counters,
+    hooks: H,
 }
 
 impl RxQueue {
-    pub(super) fn new(ring: XskRingCons, socket: Socket) -> Self {
-        Self { ring, socket }
+    pub(crate) fn new(ring: XskRingCons, socket: Socket) -> Self {
+        let umem = socket.umem();
+
+        Self {
+            ring,
+            socket,
+            umem,
+            counters: Counters::default(),
+            hooks: NoHooks,
+        }
+    }
+
+    /// Rebuild the rx queue of a socket reassembled with
+    /// [`Socket::from_parts`], by mapping its rx ring.
+    ///
+    /// # Safety
+    ///
+    /// There must be at most one `RxQueue` per socket, so this must only
+    /// be called once for a given socket, and never for one created
+    /// by [`Socket::new`]. The socket must have been bound with an
+    /// rx ring of the size given in its config.
+    ///
+    /// See also [`TxQueue::from_raw`](super::TxQueue::from_raw).
+    pub unsafe fn from_raw(socket: &Socket) -> Result<Self, SocketCreateError> {
+        let size = socket.config().rx_queue_size().get();
+
+        // SAFETY: the unsafe contract of this function and
+        // `Socket::from_parts` guarantees the ring exists with this
+        // size, and the mapping lives for as long as the socket.
+        let ring = unsafe {
+            socket.map_ring::<xdp_desc, _>(
+                XDP_PGOFF_RX_RING as u64,
+                MmapOffsets::rx,
+                size,
+                |mmap| XskRingCons::from_mmap(mmap),
+            )
+        }
+        .map_err(|err| socket.create_error(SocketCreateErrorKind::MapRing(RingKind::Rx), err))?;
+
+        Ok(Self::new(ring, socket.clone()))
+    }
+}
+
+impl<H: QueueHooks> RxQueue<H> {
+    /// Replace this queue's hooks with `hooks`.
+    pub fn with_hooks<G: QueueHooks>(self, hooks: G) -> RxQueue<G> {
+        RxQueue {
+            ring: self.ring,
+            socket: self.socket,
+            umem: self.umem,
+            counters: self.counters,
+            hooks,
+        }
+    }
+
+    /// The hooks run around this queue's ring operations.
+    #[inline]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// A mutable reference to the hooks run around this queue's ring
+    /// operations.
+    #[inline]
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
     }
 
     /// Update `descs` with information on which [`Umem`] frames have
@@ -31,12 +119,49 @@ impl RxQueue {
     /// and are no longer required, the frames should eventually be
     /// added back on to either the [`FillQueue`] or the [`TxQueue`].
     ///
+    /// To hide some of the memory latency of reading a batch of
+    /// small packets, consider calling [`Umem::prefetch_data`] on the
+    /// updated descriptors before processing them.
+    ///
     /// # Safety
     ///
     /// The frames passed to this queue must belong to the same
     /// [`Umem`] that this `RxQueue` instance is tied to.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { umem, descs, mut fq, mut rx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// use xsk_rs::FrameDesc;
+    ///
+    /// // Give the kernel a frame to receive into.
+    /// unsafe { fq.produce(&descs[..1]) };
+    ///
+    /// // A packet arrives.
+    /// # kernel.inject(b"hello");
+    /// let mut received = [FrameDesc::default(); 4];
+    /// let n = unsafe { rx_q.consume(&mut received) };
+    ///
+    /// assert_eq!(n, 1);
+    /// assert_eq!(unsafe { umem.data(&received[0]) }.contents(), b"hello");
+    ///
+    /// // Once done with, return the frame to the fill queue so it can
+    /// // be received into again.
+    /// assert_eq!(unsafe { fq.produce(&received[..n]) }, 1);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`Umem`]: crate::Umem
+    /// [`Umem::prefetch_data`]: crate::Umem::prefetch_data
     /// [`FillQueue`]: crate::FillQueue
     /// [`TxQueue`]: crate::TxQueue
     #[inline]
@@ -47,6 +172,11 @@ impl RxQueue {
             return 0;
         }
 
+        let nb = util::min_usize(
+            self.hooks.pre_consume(RingKind::Rx, nb as usize),
+            nb as usize,
+        ) as u32;
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
@@ -56,19 +186,21 @@ impl RxQueue {
                 let recv_pkt_desc =
                     unsafe { libxdp_sys::xsk_ring_cons__rx_desc(self.ring.as_ref(), idx) };
 
-                unsafe {
-                    desc.addr = (*recv_pkt_desc).addr as usize;
-                    desc.lengths.data = (*recv_pkt_desc).len as usize;
-                    desc.lengths.headroom = 0;
-                    desc.options = (*recv_pkt_desc).options;
-                }
+                unsafe { desc.read_xdp_desc(&*recv_pkt_desc) }
+
+                self.umem.track_complete(desc.addr, FrameState::FillRing);
+                self.hooks.on_consume(RingKind::Rx, desc);
 
-                idx += 1;
+                idx = idx.wrapping_add(1);
             }
 
             unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
         }
 
+        self.hooks.post_consume(RingKind::Rx, cnt as usize);
+
         cnt as usize
     }
 
@@ -81,6 +213,11 @@ impl RxQueue {
     /// [`consume`]: Self::consume
     #[inline]
     pub unsafe fn consume_one(&mut self, desc: &mut FrameDesc) -> usize {
+        if self.hooks.pre_consume(RingKind::Rx, 1) == 0 {
+            self.hooks.post_consume(RingKind::Rx, 0);
+            return 0;
+        }
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), 1, &mut idx) };
@@ -89,19 +226,270 @@ impl RxQueue {
             let recv_pkt_desc =
                 unsafe { libxdp_sys::xsk_ring_cons__rx_desc(self.ring.as_ref(), idx) };
 
-            unsafe {
-                desc.addr = (*recv_pkt_desc).addr as usize;
-                desc.lengths.data = (*recv_pkt_desc).len as usize;
-                desc.lengths.headroom = 0;
-                desc.options = (*recv_pkt_desc).options;
+            unsafe { desc.read_xdp_desc(&*recv_pkt_desc) }
+
+            self.umem.track_complete(desc.addr, FrameState::FillRing);
+            self.hooks.on_consume(RingKind::Rx, desc);
+
+            unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
+        }
+
+        self.hooks.post_consume(RingKind::Rx, cnt as usize);
+
+        cnt as usize
+    }
+
+    /// Append up to `max` frames which have received packets to
+    /// `frames`, returning the number appended.
+    ///
+    /// The safe counterpart of [`consume`], for frames submitted via
+    /// [`FillQueue::produce_frames`]. Once done with, frames should be
+    /// handed back to the kernel via either the [`FillQueue`] or the
+    /// [`TxQueue`].
+    ///
+    /// [`consume`]: Self::consume
+    /// [`FillQueue::produce_frames`]: crate::FillQueue::produce_frames
+    /// [`FillQueue`]: crate::FillQueue
+    /// [`TxQueue`]: crate::TxQueue
+    pub fn consume_frames(&mut self, frames: &mut Vec<Frame>, max: usize) -> usize {
+        let nb = util::min_usize(max, u32::MAX as usize);
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_consume(RingKind::Rx, nb), nb) as u32;
+
+        let mut idx = 0;
+
+        let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
+
+        if cnt > 0 {
+            frames.reserve(cnt as usize);
+
+            for _ in 0..cnt {
+                let recv_pkt_desc =
+                    unsafe { libxdp_sys::xsk_ring_cons__rx_desc(self.ring.as_ref(), idx) };
+
+                let mut desc = FrameDesc::default();
+
+                unsafe { desc.read_xdp_desc(&*recv_pkt_desc) }
+
+                self.umem.track_complete(desc.addr, FrameState::FillRing);
+                self.hooks.on_consume(RingKind::Rx, &desc);
+
+                // SAFETY: the kernel has finished writing to the frame,
+                // which belongs to this socket's UMEM, and frames are
+                // only handed back once.
+                frames.push(unsafe { Frame::from_desc(desc, &self.umem) });
+
+                idx = idx.wrapping_add(1);
             }
 
             unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
         }
 
+        self.hooks.post_consume(RingKind::Rx, cnt as usize);
+
         cnt as usize
     }
 
+    /// Consume up to `max` frames which have received packets, calling
+    /// `f` with the packet data of each in turn before recycling the
+    /// frame back on to `fq`. Returns the number consumed.
+    ///
+    /// The safe counterpart of [`consume`] for packets which are only
+    /// read. Each frame is handed back to the kernel only once `f` has
+    /// returned, and the [`Data`] can't outlive the call, so the frame
+    /// can't be written to while it's borrowed.
+    ///
+    /// At most as many frames are consumed as there is free space for
+    /// on the fill ring.
+    ///
+    /// # Panics
+    ///
+    /// If `fq` belongs to a [`Umem`] not sharing this socket's memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { descs, mut fq, mut rx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// unsafe { fq.produce(&descs) };
+    /// # kernel.inject(b"hello");
+    ///
+    /// let mut bytes = 0;
+    /// let n = rx_q.consume_with(&mut fq, 16, |data| bytes += data.len());
+    ///
+    /// assert_eq!((n, bytes), (1, 5));
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`consume`]: Self::consume
+    /// [`Umem`]: crate::Umem
+    pub fn consume_with<F>(&mut self, fq: &mut FillQueue, max: usize, mut f: F) -> usize
+    where
+        F: FnMut(Data<'_>),
+    {
+        assert!(
+            fq.umem().shares_mem(&self.umem),
+            "fill queue belongs to a different UMEM"
+        );
+
+        let mut buf = [FrameDesc::default(); 64];
+        let mut total = 0;
+
+        while total < max {
+            let nb = util::min_usize(max - total, buf.len());
+            let nb = util::min_usize(fq.nb_free(nb), nb);
+
+            // SAFETY: the frames on the rx ring belong to this
+            // socket's UMEM.
+            let cnt = unsafe { self.consume(&mut buf[..nb]) };
+
+            if cnt == 0 {
+                break;
+            }
+
+            for desc in &buf[..cnt] {
+                // SAFETY: the kernel has finished writing to the frame,
+                // and it isn't handed back until `f` is done with it.
+                f(unsafe { self.umem.data(desc) });
+            }
+
+            // SAFETY: `fq` shares this socket's UMEM, and the frames
+            // are no longer borrowed.
+            let produced = unsafe { fq.produce(&buf[..cnt]) };
+
+            debug_assert_eq!(produced, cnt);
+
+            total += cnt;
+        }
+
+        total
+    }
+
+    /// Same as [`consume`], but each frame received is replaced on
+    /// `fq` with a free frame taken from `alloc`, so the fill ring
+    /// doesn't run dry. Returns the number of frames consumed.
+    ///
+    /// This is the usual AF_XDP receive loop in a single call. The
+    /// frames written to the start of `descs` are the caller's until
+    /// returned to `alloc` with [`FrameAllocator::free`], or sent on
+    /// via a [`TxQueue`] and reclaimed from its completion queue with
+    /// [`FrameAllocator::reclaim`].
+    ///
+    /// At most as many frames are consumed as there are both free
+    /// frames in `alloc` and free space for on the fill ring, so
+    /// every frame is replaced unless `alloc` is being drawn from
+    /// elsewhere at the same time.
+    ///
+    /// # Panics
+    ///
+    /// If `fq` or `alloc` belong to a [`Umem`] not sharing this
+    /// socket's memory.
+    ///
+    /// [`consume`]: Self::consume
+    /// [`TxQueue`]: crate::TxQueue
+    /// [`Umem`]: crate::Umem
+    pub fn consume_and_refill<O: AllocOrder>(
+        &mut self,
+        fq: &mut FillQueue,
+        alloc: &FrameAllocator<O>,
+        descs: &mut [FrameDesc],
+    ) -> usize {
+        assert!(
+            fq.umem().shares_mem(&self.umem),
+            "fill queue belongs to a different UMEM"
+        );
+        assert!(
+            alloc.umem().shares_mem(&self.umem),
+            "allocator belongs to a different UMEM"
+        );
+
+        let nb = util::min_usize(descs.len(), alloc.available());
+        let nb = util::min_usize(fq.nb_free(nb), nb);
+
+        // SAFETY: the frames on the rx ring belong to this socket's
+        // UMEM.
+        let cnt = unsafe { self.consume(&mut descs[..nb]) };
+
+        if cnt > 0 {
+            alloc.fill(fq, cnt);
+        }
+
+        cnt
+    }
+
+    /// Same as [`consume`] but the frames received are returned in a
+    /// [`RxBatchGuard`], which makes sure each one is either forwarded
+    /// or recycled back on to `fq`.
+    ///
+    /// At most as many frames are received as there is free space for
+    /// on the fill ring, so that they can always be recycled. Frames
+    /// are written to the start of `descs`.
+    ///
+    /// # Safety
+    ///
+    /// See [`consume`]. Additionally `fq` must belong to the same
+    /// [`Umem`] as this socket.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { descs, mut fq, mut rx_q, mut tx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// use xsk_rs::FrameDesc;
+    ///
+    /// unsafe { fq.produce(&descs) };
+    /// # kernel.inject(b"hello");
+    ///
+    /// let mut buf = vec![FrameDesc::default(); 16];
+    /// let batch = unsafe { rx_q.recv_batch(&mut fq, &mut buf) };
+    ///
+    /// // Reflect everything received back out. The batch can't be
+    /// // dropped without doing this or recycling it.
+    /// assert_eq!(batch.forward(&mut tx_q)?, 1);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`consume`]: Self::consume
+    /// [`Umem`]: crate::Umem
+    #[inline]
+    pub unsafe fn recv_batch<'a>(
+        &mut self,
+        fq: &'a mut FillQueue,
+        descs: &'a mut [FrameDesc],
+    ) -> RxBatchGuard<'a> {
+        let nb = fq.nb_free(descs.len());
+        let nb = util::min_usize(nb, descs.len());
+
+        // SAFETY: the unsafe contract of this function.
+        let cnt = unsafe { self.consume(&mut descs[..nb]) };
+
+        RxBatchGuard::new(fq, &mut descs[..cnt])
+    }
+
     /// Same as [`consume`] but poll first to check if there is
     /// anything to read beforehand.
     ///
@@ -109,6 +497,32 @@ impl RxQueue {
     ///
     /// See [`consume`].
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { descs, mut fq, mut rx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// use xsk_rs::FrameDesc;
+    ///
+    /// unsafe { fq.produce(&descs) };
+    /// # kernel.inject(b"hello");
+    ///
+    /// // Wait up to 100ms for packets to arrive.
+    /// let mut received = vec![FrameDesc::default(); 16];
+    /// let n = unsafe { rx_q.poll_and_consume(&mut received, 100)? };
+    ///
+    /// assert_eq!(n, 1);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`consume`]: RxQueue::consume
     #[inline]
     pub unsafe fn poll_and_consume(
@@ -122,6 +536,45 @@ impl RxQueue {
         }
     }
 
+    /// Same as [`consume`] but if there's nothing to receive then wait
+    /// until there is, rather than returning zero. Returns the number
+    /// of elements of `descs` which have been updated, which is only
+    /// zero if `descs` is empty.
+    ///
+    /// Waits for the socket to become readable via the tokio reactor,
+    /// so neither blocks the thread nor busy-polls. Must be called
+    /// from within a tokio runtime.
+    ///
+    /// Requires the `async` feature.
+    ///
+    /// # Safety
+    ///
+    /// See [`consume`].
+    ///
+    /// [`consume`]: Self::consume
+    #[cfg(feature = "async")]
+    pub async unsafe fn recv_async(&mut self, descs: &mut [FrameDesc]) -> io::Result<usize> {
+        let cnt = unsafe { self.consume(descs) };
+
+        if cnt > 0 || descs.is_empty() {
+            return Ok(cnt);
+        }
+
+        let fd = self.socket.async_fd()?;
+
+        loop {
+            // Readiness is cleared before trying again so that a
+            // packet arriving in between isn't missed.
+            fd.readable().await?.clear_ready();
+
+            let cnt = unsafe { self.consume(descs) };
+
+            if cnt > 0 {
+                return Ok(cnt);
+            }
+        }
+    }
+
     /// Same as [`poll_and_consume`] but for a single frame descriptor.
     ///
     /// # Safety
@@ -142,10 +595,36 @@ impl RxQueue {
         }
     }
 
+    /// The number of received frames waiting to be consumed, without
+    /// consuming any of them.
+    #[inline]
+    pub fn available(&mut self) -> usize {
+        unsafe { libxdp_sys::xsk_cons_nb_avail(self.ring.as_mut(), u32::MAX) as usize }
+    }
+
     /// Polls the socket, returning `true` if there is data to read.
     #[inline]
     pub fn poll(&mut self, poll_timeout: i32) -> io::Result<bool> {
-        self.socket.fd.poll_read(poll_timeout)
+        self.socket
+            .fd
+            .poll_read(poll_timeout)
+            .map(|ready| self.counters.add_poll(ready))
+    }
+
+    /// The UMEM the socket is bound to.
+    #[inline]
+    pub(crate) fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    /// The counters kept by this queue: frames consumed and polls
+    /// which timed out.
+    ///
+    /// Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub fn metrics(&self) -> QueueMetrics {
+        self.counters.snapshot()
     }
 
     /// A reference to the underlying [`Socket`]'s file descriptor.
@@ -159,4 +638,136 @@ impl RxQueue {
     pub fn fd_mut(&mut self) -> &mut Fd {
         &mut self.socket.fd
     }
+
+    /// Whether the underlying [`Socket`] has been paused after the
+    /// kernel reported that the interface is down. See
+    /// [`TxQueue::wakeup`](crate::TxQueue::wakeup).
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.socket.link.is_paused()
+    }
+
+    /// The status of the link the underlying [`Socket`] is bound to.
+    ///
+    /// Returns [`LinkStatus::Up`] without querying the interface
+    /// unless the socket has been paused, in which case the interface
+    /// is checked and the socket resumed if it's back up.
+    #[inline]
+    pub fn link_status(&self) -> io::Result<LinkStatus> {
+        self.socket.link.status()
+    }
+
+    /// Point the entry for this socket's queue id in the `XSKMAP`
+    /// `xsks_map_fd` at this socket, so that packets the XDP program
+    /// redirects through it arrive here. Any socket previously in the
+    /// entry is replaced in a single step.
+    ///
+    /// Only needed when managing the XDP program, and so the map,
+    /// yourself. See also [`Socket::migrate`].
+    pub fn update_xskmap(&self, xsks_map_fd: RawFd) -> io::Result<()> {
+        self.socket.update_xskmap(xsks_map_fd)
+    }
+
+    #[inline]
+    pub(crate) fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Details of the underlying [`Socket`], such as the interface
+    /// and queue it's bound to and the sizes of its rings.
+    #[inline]
+    pub fn socket_info(&self) -> io::Result<SocketInfo> {
+        self.socket.info()
+    }
+
+    /// Addresses and a snapshot of the indices of the rx ring, for
+    /// use by debugging tools.
+    #[inline]
+    pub fn ring_info(&self) -> RingInfo {
+        self.ring.info::<xdp_desc>()
+    }
+}
+
+impl<H> AsRawFd for RxQueue<H> {
+    /// The underlying [`Socket`]'s file descriptor, see [`fd`](Self::fd).
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.fd.as_raw_fd()
+    }
+}
+
+impl<H> AsFd for RxQueue<H> {
+    /// The underlying [`Socket`]'s file descriptor, see [`fd`](Self::fd).
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::BindFlags,
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    #[test]
+    fn borrowed_rx_frames_are_recycled_to_the_fill_ring() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+
+        assert!(xsk.kernel.inject(b"hello"));
+        assert!(xsk.kernel.inject(b"world!"));
+        assert!(!xsk.kernel.inject(b"dropped"));
+
+        let mut received = Vec::new();
+        let cnt = xsk.rx_q.consume_with(&mut xsk.fq, 4, |data| {
+            received.push(data.contents().to_vec())
+        });
+
+        assert_eq!(cnt, 2);
+        assert_eq!(received, vec![b"hello".to_vec(), b"world!".to_vec()]);
+
+        // The frames are back on the fill ring, so can be received
+        // into again.
+        assert!(xsk.kernel.inject(b"again"));
+        assert_eq!(xsk.rx_q.consume_with(&mut xsk.fq, 4, |_| ()), 1);
+    }
+
+    #[test]
+    fn consumed_rx_frames_are_replaced_from_the_allocator() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..4]) }, 4);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs[4..8].to_vec()) };
+
+        assert!(xsk.kernel.inject(b"hello"));
+        assert!(xsk.kernel.inject(b"world!"));
+
+        let mut descs = vec![FrameDesc::default(); 8];
+        assert_eq!(
+            xsk.rx_q.consume_and_refill(&mut xsk.fq, &alloc, &mut descs),
+            2
+        );
+
+        assert_eq!(unsafe { xsk.umem.data(&descs[0]) }.contents(), b"hello");
+        assert_eq!(alloc.available(), 2);
+
+        // The fill ring is back to four frames.
+        for _ in 0..4 {
+            assert!(xsk.kernel.inject(b"again"));
+        }
+        assert!(!xsk.kernel.inject(b"dropped"));
+
+        // Only as many are consumed as can be replaced.
+        assert_eq!(
+            xsk.rx_q.consume_and_refill(&mut xsk.fq, &alloc, &mut descs),
+            2
+        );
+        assert_eq!(alloc.available(), 0);
+    }
 }
@@ -0,0 +1,114 @@
+//! Binding further sockets to a `(if_name, queue_id)` pair which
+//! already has a socket bound to it with a shared UMEM.
+
+use std::{borrow::Borrow, io};
+
+use crate::{
+    config::{Interface, LibxdpFlags, SocketConfig},
+    umem::{CompQueue, FillQueue, Umem},
+};
+
+use super::{RxQueue, Socket, SocketCreateError, SocketCreateErrorKind, TxQueue};
+
+impl Socket {
+    /// Bind another socket to a `(if_name, queue_id)` pair which
+    /// already has a socket bound to it using `umem`, where `fq` and
+    /// `cq` are the [`FillQueue`] and [`CompQueue`] returned when that
+    /// first socket was created.
+    ///
+    /// All sockets bound to the pair share `fq` and `cq`, so only the
+    /// new socket's [`TxQueue`] and [`RxQueue`] are returned. Which
+    /// socket a received packet goes to is up to the XDP program.
+    ///
+    /// Unlike calling [`new`](Self::new) for the same pair, this is
+    /// safe, since the
+    /// [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`](LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD)
+    /// flag is set on `config` here rather than left to the caller.
+    ///
+    /// Fails if `fq` and `cq` don't belong to `umem`, or if the pair
+    /// turns out not to have been bound to already.
+    pub fn new_shared<C>(
+        config: C,
+        umem: &Umem,
+        fq: &FillQueue,
+        cq: &CompQueue,
+        if_name: &Interface,
+        queue_id: u32,
+    ) -> Result<(TxQueue, RxQueue), SocketCreateError>
+    where
+        C: Borrow<SocketConfig>,
+    {
+        if !fq.umem().is_same(umem) || !cq.umem().is_same(umem) {
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::ForeignQueues,
+                io::Error::from(io::ErrorKind::InvalidInput),
+            )
+            .at(if_name, queue_id));
+        }
+
+        let config = config.borrow();
+
+        let config = config
+            .with_overrides()
+            .libxdp_flags(*config.libxdp_flags() | LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD)
+            .build();
+
+        // SAFETY: the pair may already be bound to, which is fine
+        // since the program won't be loaded again, and so won't be
+        // detached twice either.
+        let (tx_q, rx_q, fq_and_cq) = unsafe { Self::new(config, umem, if_name, queue_id)? };
+
+        if fq_and_cq.is_some() {
+            // Dropping the queues closes the socket just created.
+            return Err(SocketCreateError::new(
+                SocketCreateErrorKind::NotShared,
+                io::Error::from(io::ErrorKind::InvalidInput),
+            )
+            .at(if_name, queue_id));
+        }
+
+        Ok((tx_q, rx_q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::{
+        config::UmemConfig,
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    #[test]
+    fn queues_of_another_umem_are_rejected() {
+        let sim = |umem_config| {
+            SimXsk::new(
+                umem_config,
+                SocketConfig::default(),
+                16.try_into().unwrap(),
+                SimConfig::default(),
+            )
+            .unwrap()
+        };
+
+        let first = sim(UmemConfig::default());
+        let second = sim(UmemConfig::default());
+
+        let err = Socket::new_shared(
+            SocketConfig::default(),
+            &second.umem,
+            &first.fq,
+            &first.cq,
+            &"lo".parse().unwrap(),
+            0,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.kind(), SocketCreateErrorKind::ForeignQueues);
+        assert_eq!(err.queue_id(), Some(0));
+    }
+}
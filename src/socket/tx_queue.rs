@@ -1,23 +1,147 @@
 use libc::{EAGAIN, EBUSY, ENETDOWN, ENOBUFS, MSG_DONTWAIT};
-use std::{io, os::unix::prelude::AsRawFd, ptr};
+use libxdp_sys::{xdp_desc, XDP_PGOFF_TX_RING};
+use log::warn;
+use std::{
+    io::{self, IoSlice, Write},
+    os::unix::prelude::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    ptr,
+};
 
-use crate::{ring::XskRingProd, umem::frame::FrameDesc, util};
+use crate::{
+    ring::{DropPolicy, NoHooks, OnDrop, ProducePolicy, QueueHooks, RingKind, XskRingProd},
+    umem::{
+        frame::{Frame, FrameDesc},
+        ledger::FrameState,
+        pool::AllocOrder,
+        FrameAllocator, Umem,
+    },
+    util,
+};
 
-use super::{fd::Fd, Socket};
+#[cfg(feature = "metrics")]
+use super::QueueMetrics;
+use super::{
+    fd::Fd, metrics::Counters, LinkStatus, MmapOffsets, RingInfo, Socket, SocketCreateError,
+    SocketCreateErrorKind, SocketInfo,
+};
+
+/// The outcome of a [`TxQueue::try_wakeup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeupStatus {
+    /// The kernel was woken up.
+    Completed,
+    /// The kernel couldn't take the wakeup (`EAGAIN`, `EBUSY` or
+    /// `ENOBUFS`), typically because it's still busy with the ring.
+    /// The frames stay on the ring, so the wakeup should be retried.
+    KernelBusy,
+    /// The interface is down, either reported as such by the kernel
+    /// (`ENETDOWN`) or already known to be, in which case no wakeup
+    /// was sent. See [`TxQueue::link_status`].
+    LinkDown,
+}
 
 /// The transmitting side of an AF_XDP [`Socket`].
 ///
 /// More details can be found in the
 /// [docs](https://www.kernel.org/doc/html/latest/networking/af_xdp.html#tx-ring).
+///
+/// Ring operations run the queue's [`QueueHooks`], which do nothing
+/// unless set with [`with_hooks`](Self::with_hooks).
 #[derive(Debug)]
-pub struct TxQueue {
+pub struct TxQueue<H = NoHooks> {
+    // First, so it runs while the ring is still mapped.
+    on_drop: OnDrop,
     ring: XskRingProd,
     socket: Socket,
+    umem: Umem,
+    counters: Counters,
+    hooks: H,
 }
 
 impl TxQueue {
-    pub(super) fn new(ring: XskRingProd, socket: Socket) -> Self {
-        Self { ring, socket }
+    pub(crate) fn new(ring: XskRingProd, socket: Socket) -> Self {
+        let umem = socket.umem();
+        let on_drop = OnDrop::new::<xdp_desc>(&ring, RingKind::Tx, Some(socket.fd.as_raw_fd()));
+
+        Self {
+            on_drop,
+            ring,
+            socket,
+            umem,
+            counters: Counters::default(),
+            hooks: NoHooks,
+        }
+    }
+
+    /// Rebuild the tx queue of a socket reassembled with
+    /// [`Socket::from_parts`], by mapping its tx ring.
+    ///
+    /// # Safety
+    ///
+    /// There must be at most one `TxQueue` per socket, so this must only
+    /// be called once for a given socket, and never for one created
+    /// by [`Socket::new`]. The socket must have been bound with an
+    /// tx ring of the size given in its config.
+    ///
+    /// See also [`RxQueue::from_raw`](super::RxQueue::from_raw).
+    pub unsafe fn from_raw(socket: &Socket) -> Result<Self, SocketCreateError> {
+        let size = socket.config().tx_queue_size().get();
+
+        // SAFETY: the unsafe contract of this function and
+        // `Socket::from_parts` guarantees the ring exists with this
+        // size, and the mapping lives for as long as the socket.
+        let ring = unsafe {
+            socket.map_ring::<xdp_desc, _>(
+                XDP_PGOFF_TX_RING as u64,
+                MmapOffsets::tx,
+                size,
+                |mmap| XskRingProd::from_mmap(mmap),
+            )
+        }
+        .map_err(|err| socket.create_error(SocketCreateErrorKind::MapRing(RingKind::Tx), err))?;
+
+        Ok(Self::new(ring, socket.clone()))
+    }
+}
+
+impl<H: QueueHooks> TxQueue<H> {
+    /// Replace this queue's hooks with `hooks`.
+    pub fn with_hooks<G: QueueHooks>(self, hooks: G) -> TxQueue<G> {
+        TxQueue {
+            on_drop: self.on_drop,
+            ring: self.ring,
+            socket: self.socket,
+            umem: self.umem,
+            counters: self.counters,
+            hooks,
+        }
+    }
+
+    /// The hooks run around this queue's ring operations.
+    #[inline]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// A mutable reference to the hooks run around this queue's ring
+    /// operations.
+    #[inline]
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// What happens to frames still on the tx ring when this queue is
+    /// dropped.
+    #[inline]
+    pub fn drop_policy(&self) -> &DropPolicy {
+        self.on_drop.policy()
+    }
+
+    /// Set what happens to frames still on the tx ring when this queue
+    /// is dropped. Defaults to [`DropPolicy::Leak`].
+    #[inline]
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.on_drop.set_policy(policy);
     }
 
     /// Let the kernel know that the frames described by `descs` are
@@ -27,6 +151,8 @@ impl TxQueue {
     /// Note that if the length of `descs` is greater than the number
     /// of available spaces on the underlying ring buffer then no
     /// frames at all will be submitted for transmission.
+    /// Use [`produce_with_policy`](Self::produce_with_policy) to
+    /// choose a different behaviour.
     ///
     /// Once the frames have been submitted to this queue they should
     /// not be used again until consumed via the [`CompQueue`].
@@ -45,18 +171,44 @@ impl TxQueue {
     /// [`Umem`]: crate::Umem
     #[inline]
     pub unsafe fn produce(&mut self, descs: &[FrameDesc]) -> usize {
-        let nb = descs.len() as u32;
+        let nb = descs.len();
 
         if nb == 0 {
             return 0;
         }
 
+        // Being allowed fewer frames than given looks the same as the
+        // ring not having room for them all.
+        let cnt = if self.hooks.pre_produce(RingKind::Tx, nb) < nb {
+            0
+        } else {
+            unsafe { self.produce_inner(descs) }
+        };
+
+        self.hooks.post_produce(RingKind::Tx, cnt);
+
+        cnt
+    }
+
+    /// Submit all of `descs` or, if there isn't room, none of them,
+    /// without running the `pre_` and `post_` hooks.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`](Self::produce).
+    #[inline]
+    unsafe fn produce_inner(&mut self, descs: &[FrameDesc]) -> usize {
+        let nb = descs.len() as u32;
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), nb, &mut idx) };
 
         if cnt > 0 {
             for desc in descs.iter().take(cnt as usize) {
+                self.umem.track_submit(desc.addr, FrameState::TxRing);
+                self.hooks.on_produce(RingKind::Tx, desc);
+
                 let send_pkt_desc =
                     unsafe { libxdp_sys::xsk_ring_prod__tx_desc(self.ring.as_mut(), idx) };
 
@@ -65,15 +217,130 @@ impl TxQueue {
                 // this queue.
                 unsafe { desc.write_xdp_desc(&mut *send_pkt_desc) };
 
-                idx += 1;
+                idx = idx.wrapping_add(1);
             }
 
             unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
         }
 
         cnt as usize
     }
 
+    /// Same as [`produce`] but with `policy` deciding what happens if
+    /// `descs` doesn't fit on the ring, rather than submitting
+    /// nothing. Returns the number of frames submitted to the kernel.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    #[inline]
+    pub unsafe fn produce_with_policy(
+        &mut self,
+        descs: &[FrameDesc],
+        policy: ProducePolicy,
+    ) -> io::Result<usize> {
+        let descs = self.ring.policy_prefix(descs, policy)?;
+
+        Ok(unsafe { self.produce(descs) })
+    }
+
+    /// Same as [`produce`] but, rather than submitting nothing if
+    /// `descs` doesn't fit on the ring, submits as many frames from
+    /// the front of `descs` as there are free spaces for. Returns the
+    /// number submitted, so the rest can be passed in a later call.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    #[inline]
+    pub unsafe fn produce_partial(&mut self, descs: &[FrameDesc]) -> usize {
+        let nb = util::min_usize(self.nb_free(descs.len()), descs.len());
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_produce(RingKind::Tx, nb), nb);
+
+        let cnt = unsafe { self.produce_inner(&descs[..nb]) };
+
+        self.hooks.post_produce(RingKind::Tx, cnt);
+
+        cnt
+    }
+
+    /// Same as [`produce_partial`] but taking the descriptors from an
+    /// iterator, such as that of a `VecDeque` or a chain of several,
+    /// which are written straight onto the ring. Returns the number
+    /// submitted, which are the first that many items of `descs`. Any
+    /// items past those are left unread.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    /// [`produce_partial`]: Self::produce_partial
+    #[inline]
+    pub unsafe fn produce_iter<'a, I>(&mut self, descs: I) -> usize
+    where
+        I: IntoIterator<Item = &'a FrameDesc>,
+    {
+        let free = self.nb_free(self.ring.as_ref().size as usize) as u32;
+
+        if free == 0 {
+            return 0;
+        }
+
+        let free = util::min_usize(
+            self.hooks.pre_produce(RingKind::Tx, free as usize),
+            free as usize,
+        ) as u32;
+
+        let mut idx = 0;
+
+        let reserved =
+            unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), free, &mut idx) };
+
+        let mut cnt = 0;
+
+        for desc in descs.into_iter().take(reserved as usize) {
+            self.umem.track_submit(desc.addr, FrameState::TxRing);
+            self.hooks.on_produce(RingKind::Tx, desc);
+
+            let send_pkt_desc = unsafe {
+                libxdp_sys::xsk_ring_prod__tx_desc(self.ring.as_mut(), idx.wrapping_add(cnt))
+            };
+
+            // SAFETY: unsafe contract of this function guarantees
+            // `desc` describes a frame belonging to the same UMEM as
+            // this queue.
+            unsafe { desc.write_xdp_desc(&mut *send_pkt_desc) };
+
+            cnt += 1;
+        }
+
+        // Hand back the entries the iterator ran out before filling.
+        let cached_prod = &mut self.ring.as_mut().cached_prod;
+        *cached_prod = cached_prod.wrapping_sub(reserved - cnt);
+
+        if cnt > 0 {
+            unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
+        }
+
+        self.hooks.post_produce(RingKind::Tx, cnt as usize);
+
+        cnt as usize
+    }
+
     /// Same as [`produce`] but for a single frame descriptor.
     ///
     /// # Safety
@@ -83,11 +350,19 @@ impl TxQueue {
     /// [`produce`]: Self::produce
     #[inline]
     pub unsafe fn produce_one(&mut self, desc: &FrameDesc) -> usize {
+        if self.hooks.pre_produce(RingKind::Tx, 1) == 0 {
+            self.hooks.post_produce(RingKind::Tx, 0);
+            return 0;
+        }
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), 1, &mut idx) };
 
         if cnt > 0 {
+            self.umem.track_submit(desc.addr, FrameState::TxRing);
+            self.hooks.on_produce(RingKind::Tx, desc);
+
             let send_pkt_desc =
                 unsafe { libxdp_sys::xsk_ring_prod__tx_desc(self.ring.as_mut(), idx) };
 
@@ -97,11 +372,84 @@ impl TxQueue {
             unsafe { desc.write_xdp_desc(&mut *send_pkt_desc) };
 
             unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+
+            self.counters.add_frames(cnt);
         }
 
+        self.hooks.post_produce(RingKind::Tx, cnt as usize);
+
         cnt as usize
     }
 
+    /// Submit frames from the back of `frames` for transmission, as
+    /// many as there's room for on the ring. Returns the number
+    /// submitted, which are removed from `frames`.
+    ///
+    /// The safe counterpart of [`produce`]. The frames come back via
+    /// [`CompQueue::consume_frames`] once sent. As with [`produce`],
+    /// the kernel may need waking up afterwards, see
+    /// [`needs_wakeup`](Self::needs_wakeup).
+    ///
+    /// # Panics
+    ///
+    /// If a frame to be submitted belongs to a different [`Umem`] to
+    /// this `TxQueue`.
+    ///
+    /// [`produce`]: Self::produce
+    /// [`CompQueue::consume_frames`]: crate::CompQueue::consume_frames
+    /// [`Umem`]: crate::Umem
+    pub fn produce_frames(&mut self, frames: &mut Vec<Frame>) -> usize {
+        let free = unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), frames.len() as u32) };
+
+        let nb = util::min_usize(free as usize, frames.len());
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_produce(RingKind::Tx, nb), nb);
+
+        let start = frames.len() - nb;
+
+        assert!(
+            frames[start..].iter().all(|f| f.belongs_to(&self.umem)),
+            "frame belongs to a different UMEM"
+        );
+
+        let mut idx = 0;
+
+        // Can't fail since there's at least `nb` free spaces.
+        let cnt =
+            unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), nb as u32, &mut idx) };
+
+        debug_assert_eq!(cnt as usize, nb);
+
+        for frame in &frames[start..] {
+            self.umem
+                .track_submit(frame.desc().addr, FrameState::TxRing);
+            self.hooks.on_produce(RingKind::Tx, frame.desc());
+
+            let send_pkt_desc =
+                unsafe { libxdp_sys::xsk_ring_prod__tx_desc(self.ring.as_mut(), idx) };
+
+            // SAFETY: each `Frame` owns its frame, which belongs to
+            // this queue's UMEM, and is given up to the kernel below.
+            unsafe { frame.desc().write_xdp_desc(&mut *send_pkt_desc) };
+
+            idx = idx.wrapping_add(1);
+        }
+
+        unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+
+        self.counters.add_frames(cnt);
+
+        self.hooks.post_produce(RingKind::Tx, nb);
+
+        frames.truncate(start);
+
+        nb
+    }
+
     /// Same as [`produce`] but wake up the kernel to continue
     /// processing produced frames (if required).
     ///
@@ -112,6 +460,41 @@ impl TxQueue {
     ///
     /// See [`produce`].
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { umem, descs, mut cq, mut tx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// use std::io::Write;
+    /// use xsk_rs::FrameDesc;
+    ///
+    /// // Write the packet into a free frame and hand it to the kernel.
+    /// let mut desc = descs[0];
+    ///
+    /// unsafe { umem.data_mut(&mut desc) }
+    ///     .cursor()
+    ///     .write_all(b"hello")?;
+    ///
+    /// assert_eq!(unsafe { tx_q.produce_and_wakeup(&[desc])? }, 1);
+    /// # kernel.run_until_idle(64);
+    ///
+    /// // The frame must not be touched until it comes back on the
+    /// // completion queue.
+    /// let mut completed = [FrameDesc::default(); 4];
+    ///
+    /// assert_eq!(unsafe { cq.consume(&mut completed) }, 1);
+    /// assert_eq!(completed[0].addr(), desc.addr());
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`produce`]: Self::produce
     #[inline]
     pub unsafe fn produce_and_wakeup(&mut self, descs: &[FrameDesc]) -> io::Result<usize> {
@@ -144,14 +527,191 @@ impl TxQueue {
         Ok(cnt)
     }
 
+    /// Copy the packet made up of `bufs` into a free frame taken from
+    /// `alloc` and submit it for transmission, waking up the kernel if
+    /// required. Returns the length of the packet.
+    ///
+    /// Combines allocating a frame, writing to it, setting its length
+    /// and [`produce`], so is the simplest way to send a packet. Like
+    /// any other frame it comes back on the completion queue once
+    /// sent, where it can be returned to `alloc` with
+    /// [`FrameAllocator::reclaim`].
+    ///
+    /// Fails with [`WouldBlock`] if there are no free frames or no
+    /// room on the ring, with [`InvalidInput`] if the packet is longer
+    /// than the frame's [`mtu`], and with [`Other`] if the queue's
+    /// [`QueueHooks`] refuse the frame. Nothing is sent in any of
+    /// these cases. Once the packet is on the ring it counts as sent, so a
+    /// failed wakeup is logged rather than returned.
+    ///
+    /// # Panics
+    ///
+    /// If `alloc` belongs to a [`Umem`] not sharing this queue's
+    /// memory.
+    ///
+    /// [`produce`]: Self::produce
+    /// [`WouldBlock`]: io::ErrorKind::WouldBlock
+    /// [`InvalidInput`]: io::ErrorKind::InvalidInput
+    /// [`Other`]: io::ErrorKind::Other
+    /// [`mtu`]: crate::config::UmemConfig::mtu
+    pub fn send_iovec<O: AllocOrder>(
+        &mut self,
+        alloc: &FrameAllocator<O>,
+        bufs: &[IoSlice<'_>],
+    ) -> io::Result<usize> {
+        assert!(
+            alloc.umem().shares_mem(&self.umem),
+            "allocator belongs to a different UMEM"
+        );
+
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+
+        if len > self.umem.config().mtu() as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "packet exceeds frame mtu",
+            ));
+        }
+
+        if unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), 1) } == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "no room on the tx ring",
+            ));
+        }
+
+        let mut desc = alloc
+            .alloc()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::WouldBlock, "no free frames"))?;
+
+        let written = {
+            // SAFETY: the frame was free, and belongs to this queue's
+            // UMEM.
+            let mut data = unsafe { self.umem.data_mut(&mut desc) };
+            let mut cursor = data.cursor();
+
+            cursor.set_pos(0);
+
+            bufs.iter().try_for_each(|buf| cursor.write_all(buf))
+        };
+
+        if let Err(e) = written {
+            desc.lengths = Default::default();
+
+            // SAFETY: the frame was never submitted, so is still ours.
+            unsafe { alloc.free(&[desc]) };
+
+            return Err(e);
+        }
+
+        // SAFETY: as above.
+        if unsafe { self.produce_one(&desc) } == 0 {
+            // There was room on the ring, so the hooks refused the
+            // frame.
+            desc.lengths = Default::default();
+
+            // SAFETY: the frame was never submitted, so is still ours.
+            unsafe { alloc.free(&[desc]) };
+
+            return Err(io::Error::other("refused by queue hooks"));
+        }
+
+        // The packet is on the ring, so a failed wakeup mustn't be
+        // reported as a failed send.
+        if self.needs_wakeup() {
+            if let Err(e) = self.try_wakeup() {
+                warn!("failed to wake up the kernel after sending: {}", e);
+            }
+        }
+
+        Ok(len)
+    }
+
+    /// Same as [`produce_and_wakeup`] but if there isn't room on the
+    /// ring for `descs` then wait until there is, rather than
+    /// returning zero. Returns the number of frames submitted, which
+    /// is only zero if `descs` is empty.
+    ///
+    /// Waits for the socket to become writeable via the tokio
+    /// reactor, so neither blocks the thread nor busy-polls. Must be
+    /// called from within a tokio runtime. Fails if there are more
+    /// frames in `descs` than the ring can hold, since they would
+    /// never fit.
+    ///
+    /// Requires the `async` feature.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce_and_wakeup`]: Self::produce_and_wakeup
+    /// [`produce`]: Self::produce
+    #[cfg(feature = "async")]
+    pub async unsafe fn send_async(&mut self, descs: &[FrameDesc]) -> io::Result<usize> {
+        let cnt = unsafe { self.produce_and_wakeup(descs)? };
+
+        if cnt > 0 || descs.is_empty() {
+            return Ok(cnt);
+        }
+
+        if descs.len() > self.ring_info().size() as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "more frames than fit on the tx ring",
+            ));
+        }
+
+        let fd = self.socket.async_fd()?;
+
+        loop {
+            // The kernel only frees up space on the ring as it
+            // transmits, so make sure it's awake before waiting.
+            if self.needs_wakeup() {
+                self.wakeup()?;
+            }
+
+            fd.writable().await?.clear_ready();
+
+            let cnt = unsafe { self.produce_and_wakeup(descs)? };
+
+            if cnt > 0 {
+                return Ok(cnt);
+            }
+        }
+    }
+
     /// Wake up the kernel to continue processing produced frames.
     ///
+    /// If the kernel reports that the interface is down (`ENETDOWN`)
+    /// then the queue is paused and subsequent wakeups are skipped
+    /// until the interface comes back up, at which point it resumes
+    /// automatically. See [`link_status`].
+    ///
+    /// The kernel being too busy to take the wakeup isn't an error,
+    /// use [`try_wakeup`] to find out if that happened.
+    ///
     /// See [`produce_and_wakeup`] for a link to docs with further
     /// explanation.
     ///
+    /// [`try_wakeup`]: Self::try_wakeup
     /// [`produce_and_wakeup`]: Self::produce_and_wakeup
+    /// [`link_status`]: Self::link_status
     #[inline]
     pub fn wakeup(&self) -> io::Result<()> {
+        self.try_wakeup().map(|_| ())
+    }
+
+    /// Same as [`wakeup`] but reporting whether the kernel was
+    /// actually woken up, so applications can count and react to the
+    /// times it wasn't rather than losing the wakeup. Only errors the
+    /// kernel doesn't document for a wakeup are returned as `Err`.
+    ///
+    /// [`wakeup`]: Self::wakeup
+    pub fn try_wakeup(&self) -> io::Result<WakeupStatus> {
+        if self.socket.link.is_paused() && self.link_status()? == LinkStatus::Down {
+            return Ok(WakeupStatus::LinkDown);
+        }
+
         let ret = unsafe {
             libc::sendto(
                 self.socket.fd.as_raw_fd(),
@@ -163,14 +723,20 @@ impl TxQueue {
             )
         };
 
+        self.counters.add_wakeup();
+
         if ret < 0 {
-            match util::get_errno() {
-                ENOBUFS | EAGAIN | EBUSY | ENETDOWN => (),
-                _ => return Err(io::Error::last_os_error()),
-            }
+            return match util::get_errno() {
+                ENOBUFS | EAGAIN | EBUSY => Ok(WakeupStatus::KernelBusy),
+                ENETDOWN => {
+                    self.socket.link.pause();
+                    Ok(WakeupStatus::LinkDown)
+                }
+                _ => Err(io::Error::last_os_error()),
+            };
         }
 
-        Ok(())
+        Ok(WakeupStatus::Completed)
     }
 
     /// Check if the [`XDP_USE_NEED_WAKEUP`] flag is set on the tx
@@ -180,6 +746,32 @@ impl TxQueue {
     /// See [`produce_and_wakeup`] for link to docs with further
     /// explanation.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{BindFlags, SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { descs, mut tx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::builder().bind_flags(BindFlags::XDP_USE_NEED_WAKEUP).build(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// // With the `XDP_USE_NEED_WAKEUP` bind flag set the kernel may
+    /// // stop checking the tx ring, in which case it has to be woken
+    /// // up before anything is sent.
+    /// unsafe { tx_q.produce(&descs[..1]) };
+    ///
+    /// if tx_q.needs_wakeup() {
+    ///     tx_q.wakeup()?;
+    /// }
+    /// # kernel.run_until_idle(64);
+    /// # assert_eq!(kernel.stats().tx_packets, 1);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`XDP_USE_NEED_WAKEUP`]: libxdp_sys::XDP_USE_NEED_WAKEUP
     /// [`wakeup`]: Self::wakeup
     /// [`produce_and_wakeup`]: Self::produce_and_wakeup
@@ -191,7 +783,20 @@ impl TxQueue {
     /// Polls the socket, returning `true` if it is ready to write.
     #[inline]
     pub fn poll(&mut self, poll_timeout: i32) -> io::Result<bool> {
-        self.socket.fd.poll_write(poll_timeout)
+        self.socket
+            .fd
+            .poll_write(poll_timeout)
+            .map(|ready| self.counters.add_poll(ready))
+    }
+
+    /// The counters kept by this queue: frames submitted, wakeups
+    /// sent and polls which timed out.
+    ///
+    /// Requires the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    #[inline]
+    pub fn metrics(&self) -> QueueMetrics {
+        self.counters.snapshot()
     }
 
     /// A reference to the underlying [`Socket`]'s file descriptor.
@@ -205,4 +810,217 @@ impl TxQueue {
     pub fn fd_mut(&mut self) -> &mut Fd {
         &mut self.socket.fd
     }
+
+    /// Whether the queue has been paused after the kernel reported
+    /// that the interface is down.
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.socket.link.is_paused()
+    }
+
+    /// The status of the link the underlying [`Socket`] is bound to.
+    ///
+    /// Returns [`LinkStatus::Up`] without querying the interface
+    /// unless the socket has been paused, in which case the interface
+    /// is checked and the socket resumed if it's back up.
+    #[inline]
+    pub fn link_status(&self) -> io::Result<LinkStatus> {
+        self.socket.link.status()
+    }
+
+    /// The number of free entries on the ring, checking with the
+    /// kernel if fewer than `nb` are known to be free.
+    #[inline]
+    pub(crate) fn nb_free(&mut self, nb: usize) -> usize {
+        let nb = util::min_usize(nb, u32::MAX as usize) as u32;
+
+        unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), nb) as usize }
+    }
+
+    /// The UMEM the socket is bound to.
+    #[inline]
+    pub(crate) fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    #[inline]
+    pub(crate) fn socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Details of the underlying [`Socket`], such as the interface
+    /// and queue it's bound to and the sizes of its rings.
+    #[inline]
+    pub fn socket_info(&self) -> io::Result<SocketInfo> {
+        self.socket.info()
+    }
+
+    /// Addresses and a snapshot of the indices of the tx ring, for
+    /// use by debugging tools.
+    #[inline]
+    pub fn ring_info(&self) -> RingInfo {
+        self.ring.info::<xdp_desc>()
+    }
+}
+
+impl<H> AsRawFd for TxQueue<H> {
+    /// The underlying [`Socket`]'s file descriptor, see [`fd`](Self::fd).
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.fd.as_raw_fd()
+    }
+}
+
+impl<H> AsFd for TxQueue<H> {
+    /// The underlying [`Socket`]'s file descriptor, see [`fd`](Self::fd).
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.socket.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use crate::{
+        config::BindFlags,
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    struct Refuse;
+
+    impl QueueHooks for Refuse {
+        fn pre_produce(&mut self, _ring: RingKind, _nb: usize) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn send_iovec_returns_frames_refused_by_hooks() {
+        let SimXsk {
+            umem, descs, tx_q, ..
+        } = SimXsk::with_defaults(4);
+
+        let mut tx_q = tx_q.with_hooks(Refuse);
+        let alloc = unsafe { FrameAllocator::new(&umem, descs) };
+
+        let err = tx_q
+            .send_iovec(&alloc, &[IoSlice::new(b"hello")])
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+        assert_eq!(alloc.available(), 4);
+
+        let desc = alloc.alloc().unwrap();
+        assert_eq!(unsafe { umem.data(&desc) }.contents().len(), 0);
+    }
+
+    #[test]
+    fn gathered_packets_are_copied_into_allocated_frames() {
+        let config = SimConfig {
+            loopback: true,
+            ..SimConfig::default()
+        };
+
+        let mut xsk = SimXsk::with_small_rings(config, BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.fq.produce(&xsk.descs[..2]) }, 2);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs[2..6].to_vec()) };
+
+        let sent = xsk
+            .tx_q
+            .send_iovec(&alloc, &[IoSlice::new(b"hel"), IoSlice::new(b"lo")])
+            .unwrap();
+
+        assert_eq!(sent, 5);
+        assert_eq!(alloc.available(), 3);
+
+        let too_long = vec![0; xsk.umem.config().mtu() as usize + 1];
+
+        let err = xsk
+            .tx_q
+            .send_iovec(&alloc, &[IoSlice::new(&too_long)])
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(alloc.available(), 3);
+
+        assert!(xsk.kernel.run_until_idle(16));
+
+        let mut descs = vec![FrameDesc::default(); 4];
+        assert_eq!(unsafe { xsk.rx_q.consume(&mut descs) }, 1);
+        assert_eq!(unsafe { xsk.umem.data(&descs[0]) }.contents(), b"hello");
+
+        assert_eq!(alloc.reclaim(&mut xsk.cq), 1);
+        assert_eq!(alloc.available(), 4);
+    }
+
+    #[test]
+    fn partial_produce_submits_what_fits() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..20]) }, 0);
+        assert_eq!(unsafe { xsk.tx_q.produce_partial(&xsk.descs[..20]) }, 16);
+        assert_eq!(unsafe { xsk.tx_q.produce_partial(&xsk.descs[16..20]) }, 0);
+
+        assert!(xsk.kernel.run_until_idle(16));
+
+        // Make room on the completion ring for the rest.
+        let mut completed = vec![FrameDesc::default(); 16];
+        assert_eq!(unsafe { xsk.cq.consume(&mut completed) }, 16);
+
+        assert_eq!(unsafe { xsk.tx_q.produce_partial(&xsk.descs[16..20]) }, 4);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(xsk.kernel.stats().tx_packets, 20);
+    }
+
+    #[test]
+    fn queued_descs_are_produced_without_collecting() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        let mut queued: VecDeque<FrameDesc> = xsk.descs[..20].iter().copied().collect();
+
+        // Wrap the deque's storage so it's split in two.
+        queued.rotate_left(6);
+
+        let cnt = unsafe { xsk.tx_q.produce_iter(&queued) };
+        assert_eq!(cnt, 16);
+        queued.drain(..cnt);
+
+        assert_eq!(unsafe { xsk.tx_q.produce_iter(&queued) }, 0);
+
+        assert!(xsk.kernel.run_until_idle(16));
+
+        let mut completed = vec![FrameDesc::default(); 16];
+        assert_eq!(unsafe { xsk.cq.consume(&mut completed) }, 16);
+
+        assert_eq!(completed[0].addr(), xsk.descs[6].addr());
+        assert_eq!(completed[14].addr(), xsk.descs[0].addr());
+
+        assert_eq!(unsafe { xsk.tx_q.produce_iter(&queued) }, 4);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(xsk.kernel.stats().tx_packets, 20);
+    }
+
+    #[test]
+    fn wakeups_the_kernel_can_not_take_are_reported() {
+        let mut xsk =
+            SimXsk::with_small_rings(SimConfig::default(), BindFlags::XDP_USE_NEED_WAKEUP);
+
+        // Wakeups queue up until the kernel next steps, and there's
+        // only so much room for them.
+        assert_eq!(xsk.tx_q.try_wakeup().unwrap(), WakeupStatus::Completed);
+
+        assert!((0..1 << 16).any(|_| xsk.tx_q.try_wakeup().unwrap() == WakeupStatus::KernelBusy));
+
+        xsk.kernel.step();
+
+        assert_eq!(xsk.tx_q.try_wakeup().unwrap(), WakeupStatus::Completed);
+    }
 }
@@ -0,0 +1,208 @@
+//! A transmit loop which owns its frames, recycling them as the
+//! kernel completes them.
+
+use std::{error::Error, fmt, io};
+
+use crate::umem::{frame::FrameDesc, CompQueue, Umem};
+
+use super::TxQueue;
+
+/// Returned by [`TxSession::try_send`] when a packet can't be sent
+/// yet, either because every frame is in flight or because the tx
+/// ring is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full;
+
+impl fmt::Display for Full {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no free frames or no room on the tx ring")
+    }
+}
+
+impl Error for Full {}
+
+/// A [`TxQueue`] and [`CompQueue`] along with a list of free frames,
+/// for sending packets by copying them into frames without keeping
+/// track of the frames.
+///
+/// [`try_send`](Self::try_send) copies a packet into a free frame and
+/// puts it on the tx ring, and [`reclaim`](Self::reclaim) moves
+/// frames the kernel has finished sending back onto the free list,
+/// consuming the completion ring in one go. Wakeups are left to
+/// [`flush`](Self::flush), so a burst of packets costs a single
+/// syscall.
+#[derive(Debug)]
+pub struct TxSession {
+    umem: Umem,
+    tx_q: TxQueue,
+    cq: CompQueue,
+    free: Vec<FrameDesc>,
+    total: usize,
+}
+
+impl TxSession {
+    /// Create a new `TxSession` sending on `tx_q`, whose frames come
+    /// back on `cq`, using the frames described by `descs`.
+    ///
+    /// # Safety
+    ///
+    /// `descs` must belong to the [`Umem`] of `tx_q`, as must `cq`,
+    /// and none of the frames may be in use elsewhere, either in
+    /// userspace or by the kernel. Nothing else may consume `cq`.
+    ///
+    /// # Panics
+    ///
+    /// If `cq` belongs to a [`Umem`] not sharing `tx_q`'s memory.
+    pub unsafe fn new(tx_q: TxQueue, cq: CompQueue, descs: Vec<FrameDesc>) -> Self {
+        assert!(
+            cq.umem().shares_mem(tx_q.umem()),
+            "comp queue belongs to a different UMEM"
+        );
+
+        Self {
+            umem: tx_q.umem().clone(),
+            tx_q,
+            cq,
+            total: descs.len(),
+            free: descs,
+        }
+    }
+
+    /// Copy `data` into a free frame and place it on the tx ring,
+    /// reclaiming completed frames first if none are free.
+    ///
+    /// The kernel isn't woken up, see [`flush`](Self::flush).
+    ///
+    /// # Panics
+    ///
+    /// If `data` is larger than a frame's [`mtu`].
+    ///
+    /// [`mtu`]: crate::config::UmemConfig::mtu
+    pub fn try_send(&mut self, data: &[u8]) -> Result<(), Full> {
+        assert!(
+            data.len() <= self.umem.config().mtu() as usize,
+            "packet exceeds frame mtu"
+        );
+
+        if self.free.is_empty() && self.reclaim() == 0 {
+            return Err(Full);
+        }
+
+        let mut desc = self.free.pop().expect("checked a frame is free above");
+
+        // SAFETY: the frame belongs to this session's UMEM and isn't
+        // in use by the kernel, since it's on the free list.
+        unsafe { self.umem.data_mut(&mut desc) }
+            .cursor()
+            .extend_from_slice(data)
+            .expect("checked the packet fits above");
+
+        // SAFETY: as above, and the frame isn't touched again until
+        // it's been reclaimed off the completion ring.
+        if unsafe { self.tx_q.produce_one(&desc) } == 0 {
+            desc.lengths = Default::default();
+            self.free.push(desc);
+
+            return Err(Full);
+        }
+
+        Ok(())
+    }
+
+    /// Wake the kernel up to send the packets on the tx ring, if it
+    /// needs waking.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.tx_q.needs_wakeup() {
+            self.tx_q.wakeup()?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the frames the kernel has finished sending back onto the
+    /// free list, returning how many were reclaimed.
+    pub fn reclaim(&mut self) -> usize {
+        // SAFETY: `cq` belongs to this session's UMEM, and frames
+        // come off it with their lengths reset.
+        unsafe { self.cq.consume_into(&mut self.free, usize::MAX) }
+    }
+
+    /// The number of frames free for sending, not counting any
+    /// waiting to be reclaimed.
+    #[inline]
+    pub fn free_frames(&self) -> usize {
+        self.free.len()
+    }
+
+    /// The number of frames on the tx or completion ring.
+    #[inline]
+    pub fn in_flight(&self) -> usize {
+        self.total - self.free.len()
+    }
+
+    /// The tx queue.
+    #[inline]
+    pub fn tx_q(&self) -> &TxQueue {
+        &self.tx_q
+    }
+
+    /// Take the session apart, returning its queues and its free
+    /// frames. Frames still in flight aren't included.
+    pub fn into_parts(self) -> (TxQueue, CompQueue, Vec<FrameDesc>) {
+        (self.tx_q, self.cq, self.free)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{SimKernel, SimXsk};
+
+    use super::*;
+
+    fn build(frame_count: u32) -> (TxSession, SimKernel) {
+        let SimXsk {
+            descs,
+            cq,
+            tx_q,
+            kernel,
+            ..
+        } = SimXsk::with_defaults(frame_count);
+
+        (unsafe { TxSession::new(tx_q, cq, descs) }, kernel)
+    }
+
+    #[test]
+    fn frames_are_recycled_once_completed() {
+        let (mut session, mut kernel) = build(4);
+
+        for _ in 0..4 {
+            session.try_send(b"hello").unwrap();
+        }
+
+        assert_eq!(session.try_send(b"hello"), Err(Full));
+        assert_eq!(session.in_flight(), 4);
+
+        session.flush().unwrap();
+        kernel.run_until_idle(64);
+
+        session.try_send(b"hello").unwrap();
+
+        assert_eq!(session.free_frames(), 3);
+        assert_eq!(session.in_flight(), 1);
+    }
+
+    #[test]
+    fn sent_packets_reach_the_kernel() {
+        let (mut session, mut kernel) = build(4);
+
+        session.try_send(b"hello").unwrap();
+        session.try_send(b"world!").unwrap();
+        session.flush().unwrap();
+
+        kernel.run_until_idle(64);
+
+        assert_eq!(kernel.stats().tx_packets, 2);
+        assert_eq!(session.reclaim(), 2);
+        assert_eq!(session.in_flight(), 0);
+    }
+}
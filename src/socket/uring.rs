@@ -0,0 +1,453 @@
+//! Submitting queue wakeups and polls in batches through an
+//! io_uring instance, with the `io-uring` feature.
+
+use std::{
+    fmt, io, mem,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use super::Fd;
+
+// From `linux/io_uring.h`.
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+const IORING_OFF_SQES: libc::off_t = 0x10000000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_POLL_ADD: u8 = 6;
+const IORING_OP_SEND: u8 = 26;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    // `msg_flags` for a send, `poll32_events` for a poll.
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// A shared mapping of one of an io_uring instance's regions.
+struct Mapping {
+    addr: NonNull<libc::c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(fd: &OwnedFd, offset: libc::off_t, len: usize) -> io::Result<Self> {
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd.as_raw_fd(),
+                offset,
+            )
+        };
+
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            addr: NonNull::new(addr).expect("ptr non-null since we confirmed `mmap()` succeeded"),
+            len,
+        })
+    }
+
+    /// A pointer `offset` bytes into the mapping.
+    fn ptr<T>(&self, offset: u32) -> *mut T {
+        debug_assert!(offset as usize + mem::size_of::<T>() <= self.len);
+
+        unsafe { (self.addr.as_ptr() as *mut u8).add(offset as usize) as *mut T }
+    }
+
+    fn atomic(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: the kernel's offsets point at aligned `u32`s within
+        // the mapping, which lives as long as `self`.
+        unsafe { &*self.ptr::<AtomicU32>(offset) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr.as_ptr(), self.len) };
+    }
+}
+
+/// The outcome of an operation pushed onto a [`WakeupRing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Completion {
+    /// The value the operation was pushed with.
+    pub user_data: u64,
+    res: i32,
+}
+
+impl Completion {
+    /// The operation's result, as returned by the equivalent syscall.
+    /// For a poll this is the events which became ready, and for a
+    /// wakeup the number of bytes sent, so always zero.
+    pub fn result(&self) -> io::Result<u32> {
+        if self.res < 0 {
+            Err(io::Error::from_raw_os_error(-self.res))
+        } else {
+            Ok(self.res as u32)
+        }
+    }
+}
+
+/// An io_uring instance for submitting the syscalls that wake up the
+/// kernel or poll a socket in batches, rather than one at a time.
+///
+/// In copy mode every batch of frames sent needs a wakeup, and with
+/// several sockets per thread the syscalls add up. Instead, pushing
+/// the wakeups and polls for all of a thread's queues and then
+/// [submitting](Self::submit) them costs a single syscall.
+///
+/// Operations complete asynchronously, reporting back through
+/// [`pop_completion`](Self::pop_completion). A tx wakeup completes
+/// straight away, like [`TxQueue::try_wakeup`], while a poll
+/// completes once the socket becomes ready. As with
+/// [`FillQueue::wakeup`], arming a poll for [`libc::POLLIN`] is
+/// enough to wake up the kernel to process the fill ring.
+///
+/// Requires the `io-uring` feature and a 5.6 or later kernel.
+///
+/// [`TxQueue::try_wakeup`]: crate::TxQueue::try_wakeup
+/// [`FillQueue::wakeup`]: crate::FillQueue::wakeup
+pub struct WakeupRing {
+    sq: Mapping,
+    cq: Mapping,
+    sqes: Mapping,
+    params: Params,
+    // Submission queue entries pushed but not yet submitted.
+    pending: u32,
+    fd: OwnedFd,
+}
+
+// SAFETY: the mappings are only accessed through `&mut self`, other
+// than by the kernel.
+unsafe impl Send for WakeupRing {}
+
+impl WakeupRing {
+    /// Set up an io_uring instance with room for `entries` operations
+    /// to be pushed between submissions. The kernel rounds `entries`
+    /// up to a power of two.
+    pub fn new(entries: u32) -> io::Result<Self> {
+        let mut params = Params::default();
+
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries,
+                &mut params as *mut Params,
+            )
+        };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the file descriptor was just returned by the kernel
+        // and is owned by no one else.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+
+        let sq = Mapping::new(&fd, IORING_OFF_SQ_RING, sq_len)?;
+        let cq = Mapping::new(&fd, IORING_OFF_CQ_RING, cq_len)?;
+        let sqes = Mapping::new(&fd, IORING_OFF_SQES, sqes_len)?;
+
+        // Entries are always used in order, so map each slot of the
+        // indirection array to the entry with the same index.
+        let array = sq.ptr::<u32>(params.sq_off.array);
+
+        for idx in 0..params.sq_entries {
+            unsafe { *array.add(idx as usize) = idx };
+        }
+
+        Ok(Self {
+            sq,
+            cq,
+            sqes,
+            params,
+            pending: 0,
+            fd,
+        })
+    }
+
+    /// The number of operations which can be pushed before
+    /// submitting.
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        self.params.sq_entries
+    }
+
+    /// Push a wakeup of the tx ring of the socket behind `fd`, for
+    /// example that of a [`TxQueue`](crate::TxQueue). Returns whether
+    /// there was room for it, if not then [`submit`](Self::submit)
+    /// first.
+    pub fn push_wakeup(&mut self, fd: &Fd, user_data: u64) -> bool {
+        self.push(Sqe {
+            opcode: IORING_OP_SEND,
+            fd: fd.as_raw_fd(),
+            op_flags: libc::MSG_DONTWAIT as u32,
+            user_data,
+            ..Sqe::default()
+        })
+    }
+
+    /// Push a poll of the socket behind `fd` for `events`, completing
+    /// once any of them occur. Returns whether there was room for it,
+    /// if not then [`submit`](Self::submit) first.
+    pub fn push_poll(&mut self, fd: &Fd, events: libc::c_short, user_data: u64) -> bool {
+        self.push(Sqe {
+            opcode: IORING_OP_POLL_ADD,
+            fd: fd.as_raw_fd(),
+            // Widened so it's read the same by kernels which take
+            // 16 or 32-bit poll events.
+            op_flags: events as u16 as u32,
+            user_data,
+            ..Sqe::default()
+        })
+    }
+
+    fn push(&mut self, sqe: Sqe) -> bool {
+        let head = self
+            .sq
+            .atomic(self.params.sq_off.head)
+            .load(Ordering::Acquire);
+        let tail = self
+            .sq
+            .atomic(self.params.sq_off.tail)
+            .load(Ordering::Relaxed);
+
+        if tail.wrapping_sub(head) == self.params.sq_entries {
+            return false;
+        }
+
+        let mask = unsafe { *self.sq.ptr::<u32>(self.params.sq_off.ring_mask) };
+        let idx = tail & mask;
+
+        unsafe { *self.sqes.ptr::<Sqe>(idx * mem::size_of::<Sqe>() as u32) = sqe };
+
+        self.sq
+            .atomic(self.params.sq_off.tail)
+            .store(tail.wrapping_add(1), Ordering::Release);
+
+        self.pending += 1;
+
+        true
+    }
+
+    /// Submit every operation pushed since the last submission in a
+    /// single syscall, returning how many were submitted.
+    pub fn submit(&mut self) -> io::Result<usize> {
+        self.enter(0, 0)
+    }
+
+    /// Same as [`submit`](Self::submit), but also wait for at least
+    /// `min_complete` operations to complete.
+    pub fn submit_and_wait(&mut self, min_complete: u32) -> io::Result<usize> {
+        self.enter(min_complete, IORING_ENTER_GETEVENTS)
+    }
+
+    fn enter(&mut self, min_complete: u32, flags: u32) -> io::Result<usize> {
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd.as_raw_fd(),
+                self.pending,
+                min_complete,
+                flags,
+                ptr::null::<libc::sigset_t>(),
+                0usize,
+            )
+        };
+
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        self.pending -= ret as u32;
+
+        Ok(ret as usize)
+    }
+
+    /// Take the next completed operation, if any.
+    pub fn pop_completion(&mut self) -> Option<Completion> {
+        let head_atomic = self.cq.atomic(self.params.cq_off.head);
+
+        let head = head_atomic.load(Ordering::Relaxed);
+        let tail = self
+            .cq
+            .atomic(self.params.cq_off.tail)
+            .load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        let mask = unsafe { *self.cq.ptr::<u32>(self.params.cq_off.ring_mask) };
+        let offset = self.params.cq_off.cqes + (head & mask) * mem::size_of::<Cqe>() as u32;
+
+        let completion = {
+            let cqe = unsafe { &*self.cq.ptr::<Cqe>(offset) };
+
+            Completion {
+                user_data: cqe.user_data,
+                res: cqe.res,
+            }
+        };
+
+        head_atomic.store(head.wrapping_add(1), Ordering::Release);
+
+        Some(completion)
+    }
+}
+
+impl AsRawFd for WakeupRing {
+    #[inline]
+    fn as_raw_fd(&self) -> libc::c_int {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl fmt::Debug for WakeupRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakeupRing")
+            .field("fd", &self.fd)
+            .field("capacity", &self.capacity())
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socketpair() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+
+        let ret = unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_DGRAM, 0, fds.as_mut_ptr()) };
+        assert_eq!(ret, 0);
+
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn wakeups_and_polls_are_submitted_together() {
+        // Sandboxes commonly block io_uring, in which case there's
+        // nothing to check.
+        let mut ring = match WakeupRing::new(4) {
+            Ok(ring) => ring,
+            Err(_) => return,
+        };
+
+        let (a, b) = socketpair();
+        let (a_fd, b_fd) =
+            unsafe { (Fd::borrow_raw(a.as_raw_fd()), Fd::borrow_raw(b.as_raw_fd())) };
+
+        assert!(ring.push_poll(&b_fd, libc::POLLIN, 1));
+        assert!(ring.push_wakeup(&a_fd, 2));
+
+        assert_eq!(ring.submit_and_wait(2).unwrap(), 2);
+
+        let mut completions = (0..2)
+            .map(|_| ring.pop_completion().unwrap())
+            .collect::<Vec<_>>();
+        completions.sort_by_key(|c| c.user_data);
+
+        assert!(completions[0].result().unwrap() & libc::POLLIN as u32 != 0);
+        assert_eq!(completions[1].result().unwrap(), 0);
+        assert!(ring.pop_completion().is_none());
+    }
+
+    #[test]
+    fn pushes_beyond_capacity_are_refused() {
+        let mut ring = match WakeupRing::new(2) {
+            Ok(ring) => ring,
+            Err(_) => return,
+        };
+
+        let (a, _b) = socketpair();
+        let fd = unsafe { Fd::borrow_raw(a.as_raw_fd()) };
+
+        let capacity = ring.capacity();
+
+        for user_data in 0..capacity {
+            assert!(ring.push_wakeup(&fd, user_data as u64));
+        }
+
+        assert!(!ring.push_wakeup(&fd, 0));
+
+        assert_eq!(ring.submit().unwrap(), capacity as usize);
+        assert!(ring.push_wakeup(&fd, 0));
+    }
+}
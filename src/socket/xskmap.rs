@@ -0,0 +1,104 @@
+//! Updating the entries of an `XSKMAP`, the BPF map an XDP program
+//! redirects packets to sockets through.
+
+use std::{io, mem, os::unix::prelude::RawFd};
+
+// Commands from `enum bpf_cmd` in `linux/bpf.h`.
+pub(crate) const BPF_MAP_LOOKUP_ELEM: libc::c_int = 1;
+pub(crate) const BPF_MAP_UPDATE_ELEM: libc::c_int = 2;
+pub(crate) const BPF_MAP_DELETE_ELEM: libc::c_int = 3;
+
+// `BPF_ANY` flag, create a new element or update an existing one.
+pub(crate) const BPF_ANY: u64 = 0;
+
+/// The anonymous struct of `union bpf_attr` used by the map element
+/// commands.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub(crate) struct MapElemAttr {
+    map_fd: u32,
+    // `key` is an `__aligned_u64`.
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+impl MapElemAttr {
+    pub(crate) fn new(map_fd: RawFd, key: u64, value: u64, flags: u64) -> Self {
+        Self {
+            map_fd: map_fd as u32,
+            _pad: 0,
+            key,
+            value,
+            flags,
+        }
+    }
+}
+
+pub(crate) fn map_elem_cmd(cmd: libc::c_int, attr: &MapElemAttr) -> io::Result<()> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *const MapElemAttr,
+            mem::size_of::<MapElemAttr>(),
+        )
+    };
+
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Point the entry for `queue_id` in `xsks_map_fd` at socket `fd`,
+/// replacing any socket already there in a single step.
+pub(super) fn update(xsks_map_fd: RawFd, queue_id: u32, fd: RawFd) -> io::Result<()> {
+    let key = queue_id;
+    let value = fd as u32;
+
+    let attr = MapElemAttr {
+        map_fd: xsks_map_fd as u32,
+        key: &key as *const u32 as u64,
+        value: &value as *const u32 as u64,
+        flags: BPF_ANY,
+        ..MapElemAttr::default()
+    };
+
+    map_elem_cmd(BPF_MAP_UPDATE_ELEM, &attr)
+}
+
+/// Remove the entry for `queue_id` from `xsks_map_fd`. Succeeds if
+/// there was no entry.
+pub(crate) fn delete(xsks_map_fd: RawFd, queue_id: u32) -> io::Result<()> {
+    let key = queue_id;
+
+    let attr = MapElemAttr {
+        map_fd: xsks_map_fd as u32,
+        key: &key as *const u32 as u64,
+        ..MapElemAttr::default()
+    };
+
+    match map_elem_cmd(BPF_MAP_DELETE_ELEM, &attr) {
+        Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(()),
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attr_matches_kernel_layout() {
+        assert_eq!(mem::size_of::<MapElemAttr>(), 32);
+    }
+
+    #[test]
+    fn updating_a_bad_map_fd_fails() {
+        assert!(update(-1, 0, 0).is_err());
+        assert!(delete(-1, 0).is_err());
+    }
+}
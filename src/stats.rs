@@ -0,0 +1,513 @@
+//! Aggregation of packet drop counters across sockets.
+//!
+//! The reasons a packet may be dropped are spread over the
+//! [`XdpStatistics`] of every socket bound to an interface, plus
+//! counters kept in userspace such as
+//! [`FillQueue::starved_fills`]. A [`DropAggregator`] collects these
+//! into a single [`DropBreakdown`] per interface.
+//!
+//! For exporting metrics, a [`SocketRegistry`] keeps track of a set
+//! of sockets and gathers their statistics labelled with the
+//! interface, queue and cookie of each, as [`SocketStats`].
+//!
+//! For monitoring a single socket over time, a [`StatisticsTracker`]
+//! reports how its counters have changed between polls.
+
+use libxdp_sys::xdp_statistics;
+use std::{collections::HashMap, io, time::Duration};
+
+use crate::{
+    clock::{Clock, MonotonicClock},
+    config::Interface,
+    socket::{Fd, WeakSocket, XdpStatistics},
+    FillQueue, RxQueue, TxQueue,
+};
+
+/// The statistics of a single socket, labelled with where it's bound.
+#[derive(Debug, Clone)]
+pub struct SocketStats {
+    if_name: Interface,
+    queue_id: u32,
+    cookie: u64,
+    stats: XdpStatistics,
+}
+
+impl SocketStats {
+    pub(crate) fn new(
+        if_name: Interface,
+        queue_id: u32,
+        cookie: u64,
+        stats: XdpStatistics,
+    ) -> Self {
+        Self {
+            if_name,
+            queue_id,
+            cookie,
+            stats,
+        }
+    }
+
+    /// The interface the socket is bound to.
+    #[inline]
+    pub fn if_name(&self) -> &Interface {
+        &self.if_name
+    }
+
+    /// The queue id the socket is bound to.
+    #[inline]
+    pub fn queue_id(&self) -> u32 {
+        self.queue_id
+    }
+
+    /// The socket's cookie. See [`Fd::cookie`](crate::socket::Fd::cookie).
+    #[inline]
+    pub fn cookie(&self) -> u64 {
+        self.cookie
+    }
+
+    /// The socket's statistics.
+    #[inline]
+    pub fn stats(&self) -> &XdpStatistics {
+        &self.stats
+    }
+}
+
+/// A set of sockets whose statistics can be gathered in one go.
+///
+/// The registry doesn't keep sockets open. Once all of a socket's
+/// queues have been dropped it's removed on the next call to
+/// [`gather_all`](Self::gather_all).
+#[derive(Debug, Default)]
+pub struct SocketRegistry {
+    sockets: Vec<WeakSocket>,
+}
+
+impl SocketRegistry {
+    /// Create a new, empty `SocketRegistry`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(&mut self, socket: WeakSocket) {
+        if !self.sockets.iter().any(|s| s.ptr_eq(&socket)) {
+            self.sockets.push(socket);
+        }
+    }
+
+    /// Add the socket `rx_q` belongs to. Does nothing if it's already
+    /// been added, whether through its [`RxQueue`] or [`TxQueue`].
+    pub fn register_rx_queue(&mut self, rx_q: &RxQueue) {
+        self.register(WeakSocket::new(rx_q.socket()))
+    }
+
+    /// Add the socket `tx_q` belongs to. Does nothing if it's already
+    /// been added, whether through its [`RxQueue`] or [`TxQueue`].
+    pub fn register_tx_queue(&mut self, tx_q: &TxQueue) {
+        self.register(WeakSocket::new(tx_q.socket()))
+    }
+
+    /// The number of sockets registered, including any closed since
+    /// the last call to [`gather_all`](Self::gather_all).
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sockets.len()
+    }
+
+    /// Whether no sockets are registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sockets.is_empty()
+    }
+
+    /// Gather the labelled statistics of every registered socket
+    /// still open, in the order they were registered, removing any
+    /// that have been closed.
+    pub fn gather_all(&mut self) -> io::Result<Vec<SocketStats>> {
+        self.sockets.retain(WeakSocket::is_alive);
+
+        self.sockets.iter().filter_map(WeakSocket::stats).collect()
+    }
+}
+
+/// Drop counters for an interface, summed over all the sockets added
+/// to a [`DropAggregator`] for it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DropBreakdown {
+    sockets: usize,
+    rx_dropped: u64,
+    rx_ring_full: u64,
+    rx_fill_ring_empty_descs: u64,
+    rx_invalid_descs: u64,
+    tx_invalid_descs: u64,
+    tx_ring_empty_descs: u64,
+    starved_fills: u64,
+}
+
+impl DropBreakdown {
+    fn add_statistics(&mut self, stats: &XdpStatistics) {
+        self.sockets += 1;
+        self.rx_dropped += stats.rx_dropped();
+        self.rx_ring_full += stats.rx_ring_full();
+        self.rx_fill_ring_empty_descs += stats.rx_fill_ring_empty_descs();
+        self.rx_invalid_descs += stats.rx_invalid_descs();
+        self.tx_invalid_descs += stats.tx_invalid_descs();
+        self.tx_ring_empty_descs += stats.tx_ring_empty_descs();
+    }
+
+    /// The number of sockets whose statistics were included.
+    #[inline]
+    pub fn sockets(&self) -> usize {
+        self.sockets
+    }
+
+    /// See [`XdpStatistics::rx_dropped`].
+    #[inline]
+    pub fn rx_dropped(&self) -> u64 {
+        self.rx_dropped
+    }
+
+    /// See [`XdpStatistics::rx_ring_full`].
+    #[inline]
+    pub fn rx_ring_full(&self) -> u64 {
+        self.rx_ring_full
+    }
+
+    /// See [`XdpStatistics::rx_fill_ring_empty_descs`].
+    #[inline]
+    pub fn rx_fill_ring_empty_descs(&self) -> u64 {
+        self.rx_fill_ring_empty_descs
+    }
+
+    /// See [`XdpStatistics::rx_invalid_descs`].
+    #[inline]
+    pub fn rx_invalid_descs(&self) -> u64 {
+        self.rx_invalid_descs
+    }
+
+    /// See [`XdpStatistics::tx_invalid_descs`].
+    #[inline]
+    pub fn tx_invalid_descs(&self) -> u64 {
+        self.tx_invalid_descs
+    }
+
+    /// See [`XdpStatistics::tx_ring_empty_descs`].
+    #[inline]
+    pub fn tx_ring_empty_descs(&self) -> u64 {
+        self.tx_ring_empty_descs
+    }
+
+    /// See [`FillQueue::starved_fills`].
+    #[inline]
+    pub fn starved_fills(&self) -> u64 {
+        self.starved_fills
+    }
+
+    /// Total received packets dropped by the kernel, for any reason.
+    ///
+    /// Note that `rx_fill_ring_empty_descs` isn't included, since it
+    /// counts failed attempts to take a frame from the fill ring
+    /// rather than dropped packets.
+    #[inline]
+    pub fn rx_total(&self) -> u64 {
+        self.rx_dropped + self.rx_ring_full + self.rx_invalid_descs
+    }
+}
+
+/// Collects drop counters from many sockets into a [`DropBreakdown`]
+/// per interface.
+///
+/// The counters of each socket are cumulative, so a snapshot is built
+/// by adding every socket once, reading the breakdowns, then calling
+/// [`clear`](Self::clear) before the next round.
+#[derive(Debug, Default, Clone)]
+pub struct DropAggregator {
+    interfaces: HashMap<Interface, DropBreakdown>,
+}
+
+impl DropAggregator {
+    /// Create a new, empty `DropAggregator`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, if_name: &Interface) -> &mut DropBreakdown {
+        self.interfaces.entry(if_name.clone()).or_default()
+    }
+
+    /// Add the statistics of a socket bound to `if_name`.
+    pub fn add_statistics(&mut self, if_name: &Interface, stats: &XdpStatistics) {
+        self.entry(if_name).add_statistics(stats)
+    }
+
+    /// Add statistics gathered by a [`SocketRegistry`].
+    pub fn add_socket_stats(&mut self, stats: &SocketStats) {
+        self.add_statistics(stats.if_name(), stats.stats())
+    }
+
+    /// Add the starvation count of a [`FillQueue`] feeding sockets
+    /// bound to `if_name`.
+    pub fn add_fill_queue(&mut self, if_name: &Interface, fq: &FillQueue) {
+        self.entry(if_name).starved_fills += fq.starved_fills();
+    }
+
+    /// Add the statistics of the socket `rx_q` belongs to, looking up
+    /// the interface it's bound to.
+    pub fn add_rx_queue(&mut self, rx_q: &RxQueue) -> io::Result<()> {
+        let info = rx_q.socket_info()?;
+        let stats = rx_q.fd().xdp_statistics()?;
+
+        self.add_statistics(info.if_name(), &stats);
+
+        Ok(())
+    }
+
+    /// Add the statistics of the socket `tx_q` belongs to, looking up
+    /// the interface it's bound to.
+    ///
+    /// Only one of each socket's [`RxQueue`] and [`TxQueue`] should
+    /// be added, since they share the same statistics.
+    pub fn add_tx_queue(&mut self, tx_q: &TxQueue) -> io::Result<()> {
+        let info = tx_q.socket_info()?;
+        let stats = tx_q.fd().xdp_statistics()?;
+
+        self.add_statistics(info.if_name(), &stats);
+
+        Ok(())
+    }
+
+    /// The breakdown for `if_name`, if anything has been added for
+    /// it.
+    pub fn get(&self, if_name: &Interface) -> Option<&DropBreakdown> {
+        self.interfaces.get(if_name)
+    }
+
+    /// The breakdown for every interface added so far, in no
+    /// particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Interface, &DropBreakdown)> {
+        self.interfaces.iter()
+    }
+
+    /// Remove everything added so far.
+    pub fn clear(&mut self) {
+        self.interfaces.clear()
+    }
+}
+
+/// How a socket's statistics changed between two snapshots taken by
+/// a [`StatisticsTracker`].
+#[derive(Debug, Clone, Copy)]
+pub struct StatisticsDelta {
+    counts: XdpStatistics,
+    elapsed: Duration,
+}
+
+impl StatisticsDelta {
+    /// How much each counter increased by.
+    #[inline]
+    pub fn counts(&self) -> &XdpStatistics {
+        &self.counts
+    }
+
+    /// The time between the two snapshots.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The rate at which `count`, one of the counters of
+    /// [`counts`](Self::counts), increased per second. Zero if no time
+    /// elapsed.
+    pub fn per_sec(&self, count: u64) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+
+        if secs > 0.0 {
+            count as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Snapshots a socket's [`XdpStatistics`] and reports how its counters
+/// have changed since the last snapshot, for use in monitoring loops.
+///
+/// The kernel's counters only ever increase over the life of a
+/// socket. Should one go backwards, for example because the tracker
+/// was fed another socket's statistics, its change is reported as
+/// zero.
+#[derive(Debug, Clone)]
+pub struct StatisticsTracker<C = MonotonicClock> {
+    last: XdpStatistics,
+    last_at: Duration,
+    clock: C,
+}
+
+impl StatisticsTracker<MonotonicClock> {
+    /// Start tracking from the snapshot `initial`, taken now.
+    pub fn new(initial: XdpStatistics) -> Self {
+        Self::with_clock(initial, MonotonicClock)
+    }
+
+    /// Start tracking from the current statistics of the socket `fd`
+    /// belongs to.
+    pub fn from_fd(fd: &Fd) -> io::Result<Self> {
+        Ok(Self::new(fd.xdp_statistics()?))
+    }
+}
+
+impl<C: Clock> StatisticsTracker<C> {
+    /// Same as [`new`](StatisticsTracker::new), but reading the time
+    /// from `clock`.
+    pub fn with_clock(initial: XdpStatistics, clock: C) -> Self {
+        Self {
+            last: initial,
+            last_at: clock.now(),
+            clock,
+        }
+    }
+
+    /// Record the snapshot `stats`, taken now, returning how it
+    /// differs from the last.
+    pub fn update(&mut self, stats: XdpStatistics) -> StatisticsDelta {
+        let now = self.clock.now();
+
+        let (a, b) = (&stats.0, &self.last.0);
+
+        let counts = XdpStatistics(xdp_statistics {
+            rx_dropped: a.rx_dropped.saturating_sub(b.rx_dropped),
+            rx_invalid_descs: a.rx_invalid_descs.saturating_sub(b.rx_invalid_descs),
+            tx_invalid_descs: a.tx_invalid_descs.saturating_sub(b.tx_invalid_descs),
+            rx_ring_full: a.rx_ring_full.saturating_sub(b.rx_ring_full),
+            rx_fill_ring_empty_descs: a
+                .rx_fill_ring_empty_descs
+                .saturating_sub(b.rx_fill_ring_empty_descs),
+            tx_ring_empty_descs: a.tx_ring_empty_descs.saturating_sub(b.tx_ring_empty_descs),
+        });
+
+        let delta = StatisticsDelta {
+            counts,
+            elapsed: now.saturating_sub(self.last_at),
+        };
+
+        self.last = stats;
+        self.last_at = now;
+
+        delta
+    }
+
+    /// Read the current statistics of the socket `fd` belongs to and
+    /// record them, see [`update`](Self::update).
+    pub fn poll(&mut self, fd: &Fd) -> io::Result<StatisticsDelta> {
+        Ok(self.update(fd.xdp_statistics()?))
+    }
+
+    /// The last snapshot recorded.
+    #[inline]
+    pub fn last(&self) -> &XdpStatistics {
+        &self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{clock::MockClock, sim::SimXsk};
+
+    use super::*;
+
+    fn stats(rx_dropped: u64, rx_ring_full: u64, rx_fill_ring_empty_descs: u64) -> XdpStatistics {
+        XdpStatistics(xdp_statistics {
+            rx_dropped,
+            rx_invalid_descs: 0,
+            tx_invalid_descs: 1,
+            rx_ring_full,
+            rx_fill_ring_empty_descs,
+            tx_ring_empty_descs: 0,
+        })
+    }
+
+    #[test]
+    fn sockets_on_the_same_interface_are_merged() {
+        let eth0: Interface = "eth0".parse().unwrap();
+        let eth1: Interface = "eth1".parse().unwrap();
+
+        let mut agg = DropAggregator::new();
+
+        agg.add_statistics(&eth0, &stats(1, 2, 3));
+        agg.add_statistics(&eth0, &stats(10, 20, 30));
+        agg.add_statistics(&eth1, &stats(5, 0, 0));
+
+        let b = agg.get(&eth0).unwrap();
+
+        assert_eq!(b.sockets(), 2);
+        assert_eq!(b.rx_dropped(), 11);
+        assert_eq!(b.rx_ring_full(), 22);
+        assert_eq!(b.rx_fill_ring_empty_descs(), 33);
+        assert_eq!(b.tx_invalid_descs(), 2);
+        assert_eq!(b.rx_total(), 33);
+
+        assert_eq!(agg.get(&eth1).unwrap().rx_dropped(), 5);
+        assert_eq!(agg.iter().count(), 2);
+
+        agg.clear();
+
+        assert!(agg.get(&eth0).is_none());
+    }
+
+    #[test]
+    fn registry_dedups_sockets_and_forgets_closed_ones() {
+        let xsk = SimXsk::with_defaults(16);
+
+        let mut registry = SocketRegistry::new();
+
+        registry.register_rx_queue(&xsk.rx_q);
+        registry.register_tx_queue(&xsk.tx_q);
+
+        assert_eq!(registry.len(), 1);
+
+        drop(xsk);
+
+        assert!(registry.gather_all().unwrap().is_empty());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn tracker_reports_counter_increases() {
+        let clock = MockClock::new();
+        let mut tracker = StatisticsTracker::with_clock(stats(1, 2, 3), clock.clone());
+
+        clock.advance(Duration::from_secs(2));
+
+        let delta = tracker.update(stats(4, 2, 10));
+
+        assert_eq!(delta.elapsed(), Duration::from_secs(2));
+
+        assert_eq!(delta.counts().rx_dropped(), 3);
+        assert_eq!(delta.counts().rx_ring_full(), 0);
+        assert_eq!(delta.counts().rx_fill_ring_empty_descs(), 7);
+        assert_eq!(delta.counts().tx_invalid_descs(), 0);
+        assert_eq!(tracker.last().rx_dropped(), 4);
+
+        // A counter going backwards is reported as unchanged.
+        let delta = tracker.update(stats(0, 5, 10));
+
+        assert_eq!(delta.counts().rx_dropped(), 0);
+        assert_eq!(delta.counts().rx_ring_full(), 3);
+    }
+
+    #[test]
+    fn rates_are_per_second_of_elapsed_time() {
+        let delta = StatisticsDelta {
+            counts: stats(10, 0, 0),
+            elapsed: Duration::from_millis(500),
+        };
+
+        assert_eq!(delta.per_sec(delta.counts().rx_dropped()), 20.0);
+
+        let delta = StatisticsDelta {
+            elapsed: Duration::ZERO,
+            ..delta
+        };
+
+        assert_eq!(delta.per_sec(10), 0.0);
+    }
+}
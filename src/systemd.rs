@@ -0,0 +1,284 @@
+//! Keeping sockets and UMEM memory across restarts with systemd's
+//! file descriptor store.
+//!
+//! A service run with `FileDescriptorStoreMax=` set can hand file
+//! descriptors to systemd with [`store_fd`]. They're passed back when
+//! the service next starts, where they're picked up with
+//! [`listen_fds`]. Storing a socket's descriptor keeps it open, and
+//! so bound, while the service restarts, and storing the descriptor
+//! of a file backed [`Umem`](crate::Umem) keeps its memory, and the
+//! frames in it, around with it.
+//!
+//! On restart the pieces are put back together with
+//! [`Umem::from_parts`](crate::Umem::from_parts),
+//! [`Socket::from_parts`](crate::Socket::from_parts) and the
+//! `from_raw` constructors of the queues. See
+//! `examples/fd_store.rs`.
+//!
+//! Both functions do nothing outside of systemd, where the
+//! environment variables it sets are missing.
+
+use std::{
+    env,
+    ffi::OsStr,
+    io, mem,
+    os::unix::prelude::{AsRawFd, FromRawFd, OsStrExt, OwnedFd, RawFd},
+    process, ptr,
+};
+
+/// The first file descriptor passed by systemd, from
+/// `sd-daemon.h`.
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+/// The longest name systemd accepts for a stored file descriptor.
+const FDNAME_MAX: usize = 255;
+
+/// Take the file descriptors systemd passed to this process, along
+/// with the names they were stored under. Descriptors passed via
+/// socket activation rather than the store are named after their
+/// socket unit, or `"unknown"`.
+///
+/// The environment variables describing them are removed, so they
+/// aren't inherited by child processes and later calls return
+/// nothing. Returns nothing if they weren't passed to this process.
+pub fn listen_fds() -> io::Result<Vec<(String, OwnedFd)>> {
+    let pid = match env::var("LISTEN_PID") {
+        Ok(pid) => pid,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let pid: u32 = pid
+        .parse()
+        .map_err(|_| invalid_data("LISTEN_PID is not a pid"))?;
+
+    if pid != process::id() {
+        return Ok(Vec::new());
+    }
+
+    let count: RawFd = env::var("LISTEN_FDS")
+        .map_err(|_| invalid_data("LISTEN_PID set without LISTEN_FDS"))?
+        .parse()
+        .map_err(|_| invalid_data("LISTEN_FDS is not a count"))?;
+
+    let names = env::var("LISTEN_FDNAMES").unwrap_or_default();
+
+    for var in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(var);
+    }
+
+    let mut names = names.split(':').filter(|name| !name.is_empty());
+
+    (SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count)
+        .map(|fd| {
+            if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let name = names.next().unwrap_or("unknown").to_owned();
+
+            // SAFETY: systemd passed `fd` to this process for it to
+            // own, and removing the environment variables above
+            // stops it being taken twice.
+            Ok((name, unsafe { OwnedFd::from_raw_fd(fd) }))
+        })
+        .collect()
+}
+
+/// Hand a duplicate of `fd` to systemd to keep under `name`, so it's
+/// passed back on the service's next start. Returns `false` if not
+/// running under systemd.
+///
+/// The service must have `FileDescriptorStoreMax=` set high enough
+/// for systemd to accept it, and storing the same descriptor twice
+/// has no effect. Names can't contain `:` or control characters.
+pub fn store_fd(name: &str, fd: RawFd) -> io::Result<bool> {
+    check_name(name)?;
+
+    notify(&format!("FDSTORE=1\nFDNAME={}", name), Some(fd))
+}
+
+/// Have systemd close every descriptor stored under `name`. Returns
+/// `false` if not running under systemd.
+pub fn remove_fd(name: &str) -> io::Result<bool> {
+    check_name(name)?;
+
+    notify(&format!("FDSTOREREMOVE=1\nFDNAME={}", name), None)
+}
+
+fn check_name(name: &str) -> io::Result<()> {
+    if name.is_empty()
+        || name.len() > FDNAME_MAX
+        || name.chars().any(|c| c == ':' || c.is_control())
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid file descriptor name",
+        ));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Send `state` to the socket in `NOTIFY_SOCKET`, along with `fd` if
+/// given.
+fn notify(state: &str, fd: Option<RawFd>) -> io::Result<bool> {
+    match env::var_os("NOTIFY_SOCKET") {
+        Some(path) => send_to(&path, state, fd).map(|()| true),
+        None => Ok(false),
+    }
+}
+
+/// The address of the unix socket at `path`, where a leading `@`
+/// denotes the abstract namespace.
+fn unix_addr(path: &OsStr) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let mut path = path.as_bytes().to_vec();
+
+    let abstract_ns = path.first() == Some(&b'@');
+
+    if abstract_ns {
+        path[0] = 0;
+    }
+
+    // Leave room for the nul terminator of pathname sockets.
+    if path.is_empty() || path.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid notify socket path",
+        ));
+    }
+
+    for (dst, src) in addr.sun_path.iter_mut().zip(&path) {
+        *dst = *src as libc::c_char;
+    }
+
+    let path_offset = mem::size_of::<libc::sa_family_t>();
+
+    let len = if abstract_ns {
+        path_offset + path.len()
+    } else {
+        path_offset + path.len() + 1
+    };
+
+    Ok((addr, len as libc::socklen_t))
+}
+
+fn send_to(path: &OsStr, state: &str, fd: Option<RawFd>) -> io::Result<()> {
+    let (addr, addr_len) = unix_addr(path)?;
+
+    let sock = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+
+    if sock < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `sock` was just opened and nothing else owns it.
+    let sock = unsafe { OwnedFd::from_raw_fd(sock) };
+
+    let mut iov = libc::iovec {
+        iov_base: state.as_ptr() as *mut libc::c_void,
+        iov_len: state.len(),
+    };
+
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_name = &addr as *const libc::sockaddr_un as *mut libc::c_void;
+    msg.msg_namelen = addr_len;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    // Room for a single descriptor, as a `u64` slice so it's aligned
+    // for `cmsghdr`.
+    let space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize;
+    let mut cmsg_buf = vec![0u64; space.div_ceil(8)];
+
+    if let Some(fd) = fd {
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+
+        // SAFETY: the control buffer is large enough and aligned for
+        // one header carrying a single descriptor.
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+        }
+    }
+
+    let ret = unsafe { libc::sendmsg(sock.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) };
+
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        os::unix::{net::UnixDatagram, prelude::OsStringExt},
+    };
+
+    use super::*;
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        assert!(check_name("xsk-0").is_ok());
+
+        for name in ["", "a:b", "a\nb", &"x".repeat(FDNAME_MAX + 1)] {
+            assert_eq!(
+                check_name(name).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+
+    #[test]
+    fn abstract_addrs_have_no_terminator() {
+        let (addr, len) = unix_addr(OsStr::new("@notify")).unwrap();
+
+        assert_eq!(addr.sun_path[0], 0);
+        assert_eq!(len as usize, mem::size_of::<libc::sa_family_t>() + 7);
+
+        let (_, len) = unix_addr(OsStr::new("/run/notify")).unwrap();
+
+        assert_eq!(len as usize, mem::size_of::<libc::sa_family_t>() + 12);
+    }
+
+    #[test]
+    fn state_and_fd_are_sent_to_the_notify_socket() {
+        let path =
+            OsString::from_vec(format!("@xsk-rs-notify-test-{}", process::id()).into_bytes());
+
+        let (addr, addr_len) = unix_addr(&path).unwrap();
+
+        let recv = UnixDatagram::unbound().unwrap();
+
+        let ret = unsafe {
+            libc::bind(
+                recv.as_raw_fd(),
+                &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+                addr_len,
+            )
+        };
+        assert_eq!(ret, 0);
+
+        send_to(&path, "FDSTORE=1\nFDNAME=xsk", Some(recv.as_raw_fd())).unwrap();
+
+        let mut buf = [0; 64];
+        let len = recv.recv(&mut buf).unwrap();
+
+        assert_eq!(&buf[..len], b"FDSTORE=1\nFDNAME=xsk");
+    }
+}
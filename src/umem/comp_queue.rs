@@ -1,6 +1,16 @@
-use crate::ring::XskRingCons;
+use libxdp_sys::XDP_UMEM_PGOFF_COMPLETION_RING;
 
-use super::{frame::FrameDesc, Umem};
+use crate::{
+    ring::{NoHooks, QueueHooks, RingKind, XskRingCons},
+    socket::{MmapOffsets, RingInfo, Socket, SocketCreateError, SocketCreateErrorKind},
+    util,
+};
+
+use super::{
+    frame::{Frame, FrameDesc},
+    ledger::FrameState,
+    Umem,
+};
 
 /// Used to transfer ownership of [`Umem`](super::Umem) frames from
 /// kernel-space to user-space.
@@ -10,15 +20,79 @@ use super::{frame::FrameDesc, Umem};
 ///
 /// For more information see the
 /// [docs](https://www.kernel.org/doc/html/latest/networking/af_xdp.html#umem-completion-ring).
+///
+/// Ring operations run the queue's [`QueueHooks`], which do nothing
+/// unless set with [`with_hooks`](Self::with_hooks).
 #[derive(Debug)]
-pub struct CompQueue {
+pub struct CompQueue<H = NoHooks> {
     ring: XskRingCons,
-    _umem: Umem,
+    umem: Umem,
+    hooks: H,
 }
 
 impl CompQueue {
     pub(crate) fn new(ring: XskRingCons, umem: Umem) -> Self {
-        Self { ring, _umem: umem }
+        Self {
+            ring,
+            umem,
+            hooks: NoHooks,
+        }
+    }
+
+    /// Rebuild the completion queue of a UMEM reassembled with
+    /// [`Umem::from_parts`], by mapping the completion ring of
+    /// `socket`. See [`FillQueue::from_raw`](super::FillQueue::from_raw).
+    ///
+    /// # Safety
+    ///
+    /// See [`FillQueue::from_raw`], which applies to the completion
+    /// ring in the same way.
+    ///
+    /// [`FillQueue::from_raw`]: super::FillQueue::from_raw
+    pub unsafe fn from_raw(socket: &Socket) -> Result<Self, SocketCreateError> {
+        let umem = socket.umem();
+        let size = umem.config().comp_queue_size().get();
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // ring exists with this size, and the mapping lives for as
+        // long as the UMEM.
+        let ring = unsafe {
+            socket.map_umem_ring::<u64, _>(
+                XDP_UMEM_PGOFF_COMPLETION_RING,
+                MmapOffsets::comp,
+                size,
+                |mmap| XskRingCons::from_mmap(mmap),
+            )
+        }
+        .map_err(|err| {
+            socket.create_error(SocketCreateErrorKind::MapRing(RingKind::Completion), err)
+        })?;
+
+        Ok(Self::new(ring, umem))
+    }
+}
+
+impl<H: QueueHooks> CompQueue<H> {
+    /// Replace this queue's hooks with `hooks`.
+    pub fn with_hooks<G: QueueHooks>(self, hooks: G) -> CompQueue<G> {
+        CompQueue {
+            ring: self.ring,
+            umem: self.umem,
+            hooks,
+        }
+    }
+
+    /// The hooks run around this queue's ring operations.
+    #[inline]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// A mutable reference to the hooks run around this queue's ring
+    /// operations.
+    #[inline]
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
     }
 
     /// Update `descs` with details of frames whose contents have been
@@ -38,6 +112,38 @@ impl CompQueue {
     /// The frames passed to this queue must belong to the same
     /// [`Umem`] that this `CompQueue` instance is tied to.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let SimXsk { descs, mut cq, mut tx_q, mut kernel, .. } = SimXsk::new(
+    /// #     UmemConfig::default(), SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// use xsk_rs::FrameDesc;
+    ///
+    /// let mut free = descs;
+    /// let desc = free.pop().unwrap();
+    ///
+    /// unsafe { tx_q.produce_and_wakeup(&[desc])? };
+    /// # kernel.run_until_idle(64);
+    ///
+    /// // Once the kernel has sent the packet the frame is handed back
+    /// // and may be used again.
+    /// let mut completed = vec![FrameDesc::default(); 16];
+    /// let n = unsafe { cq.consume(&mut completed) };
+    ///
+    /// free.extend_from_slice(&completed[..n]);
+    ///
+    /// assert_eq!(free.len(), 16);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`TxQueue`]: crate::socket::TxQueue
     /// [`FillQueue`]: crate::FillQueue
     #[inline]
@@ -48,6 +154,11 @@ impl CompQueue {
             return 0;
         }
 
+        let nb = util::min_usize(
+            self.hooks.pre_consume(RingKind::Completion, nb as usize),
+            nb as usize,
+        ) as u32;
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
@@ -62,12 +173,123 @@ impl CompQueue {
                 desc.lengths.headroom = 0;
                 desc.options = 0;
 
-                idx += 1;
+                self.umem.track_complete(desc.addr, FrameState::TxRing);
+                self.hooks.on_consume(RingKind::Completion, desc);
+
+                idx = idx.wrapping_add(1);
+            }
+
+            unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
+        }
+
+        self.hooks.post_consume(RingKind::Completion, cnt as usize);
+
+        cnt as usize
+    }
+
+    /// Same as [`consume`] but appending up to `max` descriptors to
+    /// `descs`, growing it as needed, rather than filling a slice
+    /// sized up front. Returns the number appended.
+    ///
+    /// Passing the same `Vec`, cleared, on each call avoids
+    /// allocating once it has grown to the usual completion count.
+    ///
+    /// # Safety
+    ///
+    /// See [`consume`].
+    ///
+    /// [`consume`]: Self::consume
+    #[inline]
+    pub unsafe fn consume_into(&mut self, descs: &mut Vec<FrameDesc>, max: usize) -> usize {
+        let nb = util::min_usize(max, u32::MAX as usize);
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_consume(RingKind::Completion, nb), nb) as u32;
+
+        let mut idx = 0;
+
+        let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
+
+        if cnt > 0 {
+            descs.reserve(cnt as usize);
+
+            for _ in 0..cnt {
+                let addr =
+                    unsafe { *libxdp_sys::xsk_ring_cons__comp_addr(self.ring.as_ref(), idx) };
+
+                let desc = FrameDesc::new(addr as usize);
+
+                self.umem.track_complete(desc.addr, FrameState::TxRing);
+                self.hooks.on_consume(RingKind::Completion, &desc);
+
+                descs.push(desc);
+
+                idx = idx.wrapping_add(1);
             }
 
             unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
         }
 
+        self.hooks.post_consume(RingKind::Completion, cnt as usize);
+
+        cnt as usize
+    }
+
+    /// The number of sent frames waiting to be consumed, without
+    /// consuming any of them.
+    ///
+    /// Useful for deciding whether a batch is worth consuming yet.
+    /// Frames may still be added after this returns, so a following
+    /// [`consume`](Self::consume) may return more.
+    #[inline]
+    pub fn available(&mut self) -> usize {
+        unsafe { libxdp_sys::xsk_cons_nb_avail(self.ring.as_mut(), u32::MAX) as usize }
+    }
+
+    /// Same as [`consume`] but the frames are left on the ring, so
+    /// are returned again by the next call to [`consume`]. Returns
+    /// the number of elements of `descs` which have been updated.
+    ///
+    /// # Safety
+    ///
+    /// The frames described by `descs` are not handed back until
+    /// they're consumed, so mustn't be used before then. See also
+    /// [`consume`].
+    ///
+    /// [`consume`]: Self::consume
+    #[inline]
+    pub unsafe fn peek(&mut self, descs: &mut [FrameDesc]) -> usize {
+        let nb = util::min_usize(descs.len(), u32::MAX as usize) as u32;
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let mut idx = 0;
+
+        let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
+
+        if cnt > 0 {
+            for desc in descs.iter_mut().take(cnt as usize) {
+                let addr =
+                    unsafe { *libxdp_sys::xsk_ring_cons__comp_addr(self.ring.as_ref(), idx) };
+
+                desc.addr = addr as usize;
+                desc.lengths.data = 0;
+                desc.lengths.headroom = 0;
+                desc.options = 0;
+
+                idx = idx.wrapping_add(1);
+            }
+
+            // Rewind rather than release, leaving the entries to be
+            // consumed later.
+            unsafe { libxdp_sys::xsk_ring_cons__cancel(self.ring.as_mut(), cnt) };
+        }
+
         cnt as usize
     }
 
@@ -80,6 +302,11 @@ impl CompQueue {
     /// [`consume`]: Self::consume
     #[inline]
     pub unsafe fn consume_one(&mut self, desc: &mut FrameDesc) -> usize {
+        if self.hooks.pre_consume(RingKind::Completion, 1) == 0 {
+            self.hooks.post_consume(RingKind::Completion, 0);
+            return 0;
+        }
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), 1, &mut idx) };
@@ -92,9 +319,128 @@ impl CompQueue {
             desc.lengths.headroom = 0;
             desc.options = 0;
 
+            self.umem.track_complete(desc.addr, FrameState::TxRing);
+            self.hooks.on_consume(RingKind::Completion, desc);
+
             unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
         }
 
+        self.hooks.post_consume(RingKind::Completion, cnt as usize);
+
         cnt as usize
     }
+
+    /// Append up to `max` frames which have been sent to `frames`,
+    /// returning the number appended.
+    ///
+    /// The safe counterpart of [`consume`], for frames submitted via
+    /// [`TxQueue::produce_frames`].
+    ///
+    /// [`consume`]: Self::consume
+    /// [`TxQueue::produce_frames`]: crate::TxQueue::produce_frames
+    pub fn consume_frames(&mut self, frames: &mut Vec<Frame>, max: usize) -> usize {
+        let nb = util::min_usize(max, u32::MAX as usize);
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_consume(RingKind::Completion, nb), nb) as u32;
+
+        let mut idx = 0;
+
+        let cnt = unsafe { libxdp_sys::xsk_ring_cons__peek(self.ring.as_mut(), nb, &mut idx) };
+
+        if cnt > 0 {
+            frames.reserve(cnt as usize);
+
+            for _ in 0..cnt {
+                let addr =
+                    unsafe { *libxdp_sys::xsk_ring_cons__comp_addr(self.ring.as_ref(), idx) };
+
+                let desc = FrameDesc::new(addr as usize);
+
+                self.umem.track_complete(desc.addr, FrameState::TxRing);
+                self.hooks.on_consume(RingKind::Completion, &desc);
+
+                // SAFETY: the kernel is done with the frame, which
+                // belongs to this queue's UMEM, and frames are only
+                // handed back once.
+                frames.push(unsafe { Frame::from_desc(desc, &self.umem) });
+
+                idx = idx.wrapping_add(1);
+            }
+
+            unsafe { libxdp_sys::xsk_ring_cons__release(self.ring.as_mut(), cnt) };
+        }
+
+        self.hooks.post_consume(RingKind::Completion, cnt as usize);
+
+        cnt as usize
+    }
+
+    /// The UMEM the queue belongs to.
+    #[inline]
+    pub(crate) fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    /// Addresses and a snapshot of the indices of the completion ring,
+    /// for use by debugging tools.
+    #[inline]
+    pub fn ring_info(&self) -> RingInfo {
+        self.ring.info::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::BindFlags,
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    #[test]
+    fn peeked_completions_are_consumed_later() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..3]) }, 3);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        assert_eq!(xsk.cq.available(), 3);
+
+        let mut peeked = vec![FrameDesc::default(); 2];
+        assert_eq!(unsafe { xsk.cq.peek(&mut peeked) }, 2);
+        assert_eq!(xsk.cq.available(), 3);
+
+        let mut consumed = vec![FrameDesc::default(); 4];
+        assert_eq!(unsafe { xsk.cq.consume(&mut consumed) }, 3);
+        assert_eq!(xsk.cq.available(), 0);
+
+        for (peeked, consumed) in peeked.iter().zip(&consumed) {
+            assert_eq!(peeked.addr(), consumed.addr());
+        }
+    }
+
+    #[test]
+    fn completions_are_appended_to_a_reused_vec() {
+        let mut xsk = SimXsk::with_small_rings(SimConfig::default(), BindFlags::empty());
+
+        assert_eq!(unsafe { xsk.tx_q.produce(&xsk.descs[..5]) }, 5);
+        assert!(xsk.kernel.run_until_idle(16));
+
+        let mut completed = Vec::new();
+
+        assert_eq!(unsafe { xsk.cq.consume_into(&mut completed, 3) }, 3);
+        assert_eq!(unsafe { xsk.cq.consume_into(&mut completed, 16) }, 2);
+        assert_eq!(unsafe { xsk.cq.consume_into(&mut completed, 16) }, 0);
+
+        assert_eq!(completed.len(), 5);
+
+        for (sent, completed) in xsk.descs[..5].iter().zip(&completed) {
+            assert_eq!(sent.addr(), completed.addr());
+        }
+    }
 }
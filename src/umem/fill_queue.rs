@@ -1,8 +1,32 @@
+use libxdp_sys::XDP_UMEM_PGOFF_FILL_RING;
 use std::io;
 
-use crate::{ring::XskRingProd, socket::Fd};
+use crate::{
+    ring::{DropPolicy, NoHooks, OnDrop, ProducePolicy, QueueHooks, RingKind, XskRingProd},
+    socket::{
+        Fd, MmapOffsets, RingInfo, Socket, SocketCreateError, SocketCreateErrorKind, XdpStatistics,
+    },
+    util,
+};
 
-use super::{frame::FrameDesc, Umem};
+use super::{
+    frame::{Frame, FrameDesc},
+    ledger::FrameState,
+    Umem,
+};
+
+/// How many frames a [`FillQueue`] needs, returned by
+/// [`FillQueue::needed`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FillNeed {
+    /// Free slots on the ring, and so the number of frames to
+    /// produce to fill it.
+    pub frames: usize,
+    /// Received packets the kernel dropped for want of a frame on the
+    /// ring since the statistics were last passed in. Always zero if
+    /// they weren't.
+    pub missed: u64,
+}
 
 /// Used to transfer ownership of [`Umem`](super::Umem) frames from
 /// user-space to kernel-space.
@@ -12,15 +36,107 @@ use super::{frame::FrameDesc, Umem};
 ///
 /// For more information see the
 /// [docs](https://www.kernel.org/doc/html/latest/networking/af_xdp.html#umem-fill-ring).
+///
+/// Ring operations run the queue's [`QueueHooks`], which do nothing
+/// unless set with [`with_hooks`](Self::with_hooks).
 #[derive(Debug)]
-pub struct FillQueue {
+pub struct FillQueue<H = NoHooks> {
+    // First, so it runs while the ring is still mapped.
+    on_drop: OnDrop,
     ring: XskRingProd,
-    _umem: Umem,
+    starved_fills: u64,
+    // The kernel's `rx_fill_ring_empty_descs` count as of the last
+    // call to `needed`.
+    empty_descs_seen: u64,
+    umem: Umem,
+    hooks: H,
 }
 
 impl FillQueue {
     pub(crate) fn new(ring: XskRingProd, umem: Umem) -> Self {
-        Self { ring, _umem: umem }
+        Self {
+            on_drop: OnDrop::new::<u64>(&ring, RingKind::Fill, None),
+            ring,
+            starved_fills: 0,
+            empty_descs_seen: 0,
+            umem,
+            hooks: NoHooks,
+        }
+    }
+
+    /// Rebuild the fill queue of a UMEM reassembled with
+    /// [`Umem::from_parts`], by mapping the fill ring of `socket`, one
+    /// reassembled with [`Socket::from_parts`] from the socket through
+    /// which the UMEM was registered.
+    ///
+    /// # Safety
+    ///
+    /// There must be at most one `FillQueue` per fill ring, so this
+    /// must only be called once for a given socket, and never for one
+    /// created by [`Socket::new`]. The UMEM must have been registered
+    /// through `socket` with a fill ring of the size given in its
+    /// config.
+    ///
+    /// See also [`CompQueue::from_raw`](super::CompQueue::from_raw).
+    pub unsafe fn from_raw(socket: &Socket) -> Result<Self, SocketCreateError> {
+        let umem = socket.umem();
+        let size = umem.config().fill_queue_size().get();
+
+        // SAFETY: the unsafe contract of this function guarantees the
+        // ring exists with this size, and the mapping lives for as
+        // long as the UMEM.
+        let ring = unsafe {
+            socket.map_umem_ring::<u64, _>(
+                XDP_UMEM_PGOFF_FILL_RING,
+                MmapOffsets::fill,
+                size,
+                |mmap| XskRingProd::from_mmap(mmap),
+            )
+        }
+        .map_err(|err| socket.create_error(SocketCreateErrorKind::MapRing(RingKind::Fill), err))?;
+
+        Ok(Self::new(ring, umem))
+    }
+}
+
+impl<H: QueueHooks> FillQueue<H> {
+    /// Replace this queue's hooks with `hooks`.
+    pub fn with_hooks<G: QueueHooks>(self, hooks: G) -> FillQueue<G> {
+        FillQueue {
+            on_drop: self.on_drop,
+            ring: self.ring,
+            starved_fills: self.starved_fills,
+            empty_descs_seen: self.empty_descs_seen,
+            umem: self.umem,
+            hooks,
+        }
+    }
+
+    /// The hooks run around this queue's ring operations.
+    #[inline]
+    pub fn hooks(&self) -> &H {
+        &self.hooks
+    }
+
+    /// A mutable reference to the hooks run around this queue's ring
+    /// operations.
+    #[inline]
+    pub fn hooks_mut(&mut self) -> &mut H {
+        &mut self.hooks
+    }
+
+    /// What happens to frames still on the fill ring when this queue
+    /// is dropped.
+    #[inline]
+    pub fn drop_policy(&self) -> &DropPolicy {
+        self.on_drop.policy()
+    }
+
+    /// Set what happens to frames still on the fill ring when this
+    /// queue is dropped. Defaults to [`DropPolicy::Leak`].
+    #[inline]
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) {
+        self.on_drop.set_policy(policy);
     }
 
     /// Let the kernel know that the [`Umem`] frames described by
@@ -30,6 +146,8 @@ impl FillQueue {
     /// Note that if the length of `descs` is greater than the number
     /// of available spaces on the underlying ring buffer then no
     /// frames at all will be handed over to the kernel.
+    /// Use [`produce_with_policy`](Self::produce_with_policy) to
+    /// choose a different behaviour.
     ///
     /// Once the frames have been submitted to this queue they should
     /// not be used again until consumed via the [`RxQueue`].
@@ -44,28 +162,80 @@ impl FillQueue {
     /// Furthermore, the frames passed to this queue must belong to
     /// the same [`Umem`] that this `FillQueue` instance is tied to.
     ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let umem_config = UmemConfig::builder()
+    /// #     .fill_queue_size(4.try_into().unwrap())
+    /// #     .build()
+    /// #     .unwrap();
+    /// # let SimXsk { descs, mut fq, .. } = SimXsk::new(
+    /// #     umem_config, SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// // The fill ring has four entries. Eight frames won't fit, so
+    /// // none are submitted.
+    /// assert_eq!(unsafe { fq.produce(&descs[..8]) }, 0);
+    ///
+    /// // Four will.
+    /// assert_eq!(unsafe { fq.produce(&descs[..4]) }, 4);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
     /// [`TxQueue`]: crate::TxQueue
     /// [`RxQueue`]: crate::RxQueue
     #[inline]
     pub unsafe fn produce(&mut self, descs: &[FrameDesc]) -> usize {
-        let nb = descs.len() as u32;
+        let nb = descs.len();
 
         if nb == 0 {
             return 0;
         }
 
+        // Being allowed fewer frames than given looks the same as the
+        // ring not having room for them all.
+        let cnt = if self.hooks.pre_produce(RingKind::Fill, nb) < nb {
+            0
+        } else {
+            unsafe { self.produce_inner(descs) }
+        };
+
+        self.hooks.post_produce(RingKind::Fill, cnt);
+
+        cnt
+    }
+
+    /// Submit all of `descs` or, if there isn't room, none of them,
+    /// without running the `pre_` and `post_` hooks.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`](Self::produce).
+    #[inline]
+    unsafe fn produce_inner(&mut self, descs: &[FrameDesc]) -> usize {
+        let nb = descs.len() as u32;
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), nb, &mut idx) };
 
         if cnt > 0 {
             for desc in descs.iter().take(cnt as usize) {
+                self.umem.track_submit(desc.addr, FrameState::FillRing);
+                self.hooks.on_produce(RingKind::Fill, desc);
+
                 unsafe {
                     *libxdp_sys::xsk_ring_prod__fill_addr(self.ring.as_mut(), idx) =
                         desc.addr as u64
                 };
 
-                idx += 1;
+                idx = idx.wrapping_add(1);
             }
 
             unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
@@ -74,6 +244,332 @@ impl FillQueue {
         cnt as usize
     }
 
+    /// Same as [`produce`] but with `policy` deciding what happens if
+    /// `descs` doesn't fit on the ring, rather than submitting
+    /// nothing. Returns the number of frames submitted to the kernel.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    #[inline]
+    pub unsafe fn produce_with_policy(
+        &mut self,
+        descs: &[FrameDesc],
+        policy: ProducePolicy,
+    ) -> io::Result<usize> {
+        let descs = self.ring.policy_prefix(descs, policy)?;
+
+        Ok(unsafe { self.produce(descs) })
+    }
+
+    /// Same as [`produce`] but, rather than submitting nothing if
+    /// `descs` doesn't fit on the ring, submits as many frames from
+    /// the front of `descs` as there are free slots for. Returns the
+    /// number submitted, so the rest can be passed in a later call.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    #[inline]
+    pub unsafe fn produce_partial(&mut self, descs: &[FrameDesc]) -> usize {
+        let nb = util::min_usize(self.nb_free(descs.len()), descs.len());
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_produce(RingKind::Fill, nb), nb);
+
+        let cnt = unsafe { self.produce_inner(&descs[..nb]) };
+
+        self.hooks.post_produce(RingKind::Fill, cnt);
+
+        cnt
+    }
+
+    /// Submit frames taken from an iterator, such as that of a
+    /// `VecDeque` or a chain of several, as many as there's room for
+    /// on the ring. The addresses are written straight onto the ring.
+    /// Returns the number submitted, which are the first that many
+    /// items of `descs`. Any items past those are left unread.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// [`produce`]: Self::produce
+    #[inline]
+    pub unsafe fn produce_iter<'a, I>(&mut self, descs: I) -> usize
+    where
+        I: IntoIterator<Item = &'a FrameDesc>,
+    {
+        let free = self.nb_free(self.ring.as_ref().size as usize) as u32;
+
+        if free == 0 {
+            return 0;
+        }
+
+        let free = util::min_usize(
+            self.hooks.pre_produce(RingKind::Fill, free as usize),
+            free as usize,
+        ) as u32;
+
+        let mut idx = 0;
+
+        let reserved =
+            unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), free, &mut idx) };
+
+        let mut cnt = 0;
+
+        for desc in descs.into_iter().take(reserved as usize) {
+            self.umem.track_submit(desc.addr, FrameState::FillRing);
+            self.hooks.on_produce(RingKind::Fill, desc);
+
+            unsafe {
+                *libxdp_sys::xsk_ring_prod__fill_addr(self.ring.as_mut(), idx.wrapping_add(cnt)) =
+                    desc.addr as u64
+            };
+
+            cnt += 1;
+        }
+
+        // Hand back the entries the iterator ran out before filling.
+        let cached_prod = &mut self.ring.as_mut().cached_prod;
+        *cached_prod = cached_prod.wrapping_sub(reserved - cnt);
+
+        if cnt > 0 {
+            unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+        }
+
+        self.hooks.post_produce(RingKind::Fill, cnt as usize);
+
+        cnt as usize
+    }
+
+    /// Fill the ring to capacity with frames taken from the back of
+    /// `pool`, returning the number of frames submitted to the
+    /// kernel. Submitted frames are removed from `pool`.
+    ///
+    /// Unlike [`produce`], this never fails outright because there
+    /// are more frames than free spaces, so it can be used to prime
+    /// the ring regardless of how the number of frames compares to
+    /// the ring size.
+    ///
+    /// If `pool` runs out before the ring is full then the kernel is
+    /// being starved of frames to receive into, and the count
+    /// returned by [`starved_fills`](Self::starved_fills) is
+    /// incremented.
+    ///
+    /// # Safety
+    ///
+    /// See [`produce`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "testing")]
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::convert::TryInto;
+    /// # use xsk_rs::{config::{SocketConfig, UmemConfig}, sim::{SimConfig, SimXsk}};
+    /// # let umem_config = UmemConfig::builder()
+    /// #     .fill_queue_size(4.try_into().unwrap())
+    /// #     .build()
+    /// #     .unwrap();
+    /// # let SimXsk { descs, mut fq, .. } = SimXsk::new(
+    /// #     umem_config, SocketConfig::default(), 16.try_into().unwrap(), SimConfig::default(),
+    /// # )?;
+    /// // The fill ring has four entries, so only four of the sixteen
+    /// // frames in the pool are handed over.
+    /// let mut pool = descs;
+    ///
+    /// assert_eq!(unsafe { fq.fill_from(&mut pool) }, 4);
+    /// assert_eq!(pool.len(), 12);
+    ///
+    /// // The ring is now full.
+    /// assert_eq!(unsafe { fq.fill_from(&mut pool) }, 0);
+    /// # Ok(())
+    /// # }
+    /// # #[cfg(not(feature = "testing"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// [`produce`]: Self::produce
+    pub unsafe fn fill_from(&mut self, pool: &mut Vec<FrameDesc>) -> usize {
+        let mut total = 0;
+
+        while !pool.is_empty() {
+            let free =
+                unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), pool.len() as u32) };
+
+            let nb = util::min_usize(free as usize, pool.len());
+
+            if nb == 0 {
+                break;
+            }
+
+            let start = pool.len() - nb;
+
+            // Can't fail since there's at least `nb` free spaces,
+            // unless the hooks make it look like there aren't.
+            let cnt = unsafe { self.produce(&pool[start..]) };
+
+            if cnt == 0 {
+                break;
+            }
+
+            debug_assert_eq!(cnt, nb);
+
+            pool.truncate(pool.len() - cnt);
+
+            total += cnt;
+        }
+
+        if pool.is_empty() && unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), 1) } > 0 {
+            self.starved_fills += 1;
+        }
+
+        total
+    }
+
+    /// Hand frames from the back of `frames` to the kernel to receive
+    /// packets into, as many as there's room for on the ring. Returns
+    /// the number submitted, which are removed from `frames`.
+    ///
+    /// The safe counterpart of [`produce`]. The frames come back via
+    /// [`RxQueue::consume_frames`].
+    ///
+    /// # Panics
+    ///
+    /// If a frame to be submitted belongs to a different [`Umem`] to
+    /// this `FillQueue`.
+    ///
+    /// [`produce`]: Self::produce
+    /// [`RxQueue::consume_frames`]: crate::RxQueue::consume_frames
+    pub fn produce_frames(&mut self, frames: &mut Vec<Frame>) -> usize {
+        let free = unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), frames.len() as u32) };
+
+        let nb = util::min_usize(free as usize, frames.len());
+
+        if nb == 0 {
+            return 0;
+        }
+
+        let nb = util::min_usize(self.hooks.pre_produce(RingKind::Fill, nb), nb);
+
+        let start = frames.len() - nb;
+
+        assert!(
+            frames[start..].iter().all(|f| f.belongs_to(&self.umem)),
+            "frame belongs to a different UMEM"
+        );
+
+        let mut idx = 0;
+
+        // Can't fail since there's at least `nb` free spaces.
+        let cnt =
+            unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), nb as u32, &mut idx) };
+
+        debug_assert_eq!(cnt as usize, nb);
+
+        for frame in &frames[start..] {
+            self.umem
+                .track_submit(frame.desc().addr, FrameState::FillRing);
+            self.hooks.on_produce(RingKind::Fill, frame.desc());
+
+            // SAFETY: each `Frame` owns its frame, which belongs to
+            // this queue's UMEM, and is given up to the kernel below.
+            unsafe {
+                *libxdp_sys::xsk_ring_prod__fill_addr(self.ring.as_mut(), idx) =
+                    frame.desc().addr as u64
+            };
+
+            idx = idx.wrapping_add(1);
+        }
+
+        unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
+
+        self.hooks.post_produce(RingKind::Fill, nb);
+
+        frames.truncate(start);
+
+        nb
+    }
+
+    /// The number of free entries on the ring, checking with the
+    /// kernel if fewer than `nb` are known to be free.
+    #[inline]
+    pub(crate) fn nb_free(&mut self, nb: usize) -> usize {
+        let nb = util::min_usize(nb, u32::MAX as usize) as u32;
+
+        unsafe { libxdp_sys::xsk_prod_nb_free(self.ring.as_mut(), nb) as usize }
+    }
+
+    /// The number of free slots on the ring, and so the most frames
+    /// a following [`produce`](Self::produce) can submit. The kernel
+    /// may free up more in the meantime, so there could be more by
+    /// then but never fewer.
+    #[inline]
+    pub fn free_slots(&mut self) -> usize {
+        self.nb_free(self.ring.as_ref().size as usize)
+    }
+
+    /// The number of frames needed to fill the ring and, if the
+    /// socket's `stats` are given, how many packets the kernel has
+    /// missed since the last call for want of a frame.
+    ///
+    /// Topping up by exactly [`frames`](FillNeed::frames) keeps the
+    /// ring full without taking more frames out of the free pool than
+    /// it has room for. A non-zero [`missed`](FillNeed::missed) means
+    /// the ring ran dry in the meantime, so frames are either being
+    /// returned too slowly or the pool is too small.
+    ///
+    /// `stats` should come from [`Fd::xdp_statistics`] on the socket
+    /// the ring was created with. Pass [`None`] when they can't be
+    /// retrieved, or on kernels older than 5.9, which don't count
+    /// empty fill ring descriptors.
+    pub fn needed(&mut self, stats: Option<&XdpStatistics>) -> FillNeed {
+        let missed = match stats {
+            Some(stats) => {
+                let empty_descs = stats.rx_fill_ring_empty_descs();
+                let missed = empty_descs.saturating_sub(self.empty_descs_seen);
+
+                self.empty_descs_seen = empty_descs;
+
+                missed
+            }
+            None => 0,
+        };
+
+        FillNeed {
+            frames: self.free_slots(),
+            missed,
+        }
+    }
+
+    /// The UMEM the queue belongs to.
+    #[inline]
+    pub(crate) fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    /// The number of calls to [`fill_from`](Self::fill_from) which
+    /// ran out of frames before the ring was full.
+    ///
+    /// Frames the kernel can't find on the fill ring show up as
+    /// [`rx_fill_ring_empty_descs`] drops, so a rise in both points
+    /// at the application not returning frames quickly enough, or not
+    /// having enough of them.
+    ///
+    /// [`rx_fill_ring_empty_descs`]: crate::socket::XdpStatistics::rx_fill_ring_empty_descs
+    #[inline]
+    pub fn starved_fills(&self) -> u64 {
+        self.starved_fills
+    }
+
     /// Same as [`produce`] but for a single frame descriptor.
     ///
     /// # Safety
@@ -83,11 +579,19 @@ impl FillQueue {
     /// [`produce`]: Self::produce
     #[inline]
     pub unsafe fn produce_one(&mut self, desc: &FrameDesc) -> usize {
+        if self.hooks.pre_produce(RingKind::Fill, 1) == 0 {
+            self.hooks.post_produce(RingKind::Fill, 0);
+            return 0;
+        }
+
         let mut idx = 0;
 
         let cnt = unsafe { libxdp_sys::xsk_ring_prod__reserve(self.ring.as_mut(), 1, &mut idx) };
 
         if cnt > 0 {
+            self.umem.track_submit(desc.addr, FrameState::FillRing);
+            self.hooks.on_produce(RingKind::Fill, desc);
+
             unsafe {
                 *libxdp_sys::xsk_ring_prod__fill_addr(self.ring.as_mut(), idx) = desc.addr as u64
             };
@@ -95,6 +599,8 @@ impl FillQueue {
             unsafe { libxdp_sys::xsk_ring_prod__submit(self.ring.as_mut(), cnt) };
         }
 
+        self.hooks.post_produce(RingKind::Fill, cnt as usize);
+
         cnt as usize
     }
 
@@ -178,4 +684,240 @@ impl FillQueue {
     pub fn needs_wakeup(&self) -> bool {
         unsafe { libxdp_sys::xsk_ring_prod__needs_wakeup(self.ring.as_ref()) != 0 }
     }
+
+    /// Addresses and a snapshot of the indices of the fill ring,
+    /// for use by debugging tools.
+    #[inline]
+    pub fn ring_info(&self) -> RingInfo {
+        self.ring.info::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, convert::TryInto};
+
+    use crate::{
+        config::{QueueSize, SocketConfig, UmemConfig},
+        sim::{SimConfig, SimXsk},
+    };
+
+    use super::*;
+
+    fn build(fq_size: u32, frame_count: u32) -> SimXsk {
+        let umem_config = UmemConfig::builder()
+            .fill_queue_size(QueueSize::new(fq_size).unwrap())
+            .build()
+            .unwrap();
+
+        SimXsk::new(
+            umem_config,
+            SocketConfig::default(),
+            frame_count.try_into().unwrap(),
+            SimConfig::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn unaligned_frames_are_filled_at_their_chunk_address() {
+        let umem_config = UmemConfig::builder()
+            .frame_size(3000.try_into().unwrap())
+            .unaligned_chunks(true)
+            .build()
+            .unwrap();
+
+        let SimXsk {
+            mut fq,
+            mut rx_q,
+            mut kernel,
+            umem,
+            descs,
+            ..
+        } = SimXsk::new(
+            umem_config,
+            SocketConfig::default(),
+            4.try_into().unwrap(),
+            SimConfig::default(),
+        )
+        .unwrap();
+
+        // Off the aligned frame grid.
+        let chunk = umem.chunk_desc(4500).unwrap();
+
+        assert_eq!(unsafe { fq.produce(&[chunk]) }, 1);
+        assert!(kernel.inject(b"hello"));
+
+        let mut desc = FrameDesc::default();
+
+        assert_eq!(unsafe { rx_q.consume_one(&mut desc) }, 1);
+        assert_eq!(desc.addr(), 4500 + (descs[0].addr() % 3000));
+        assert_eq!(unsafe { umem.data(&desc) }.contents(), b"hello");
+
+        assert!(umem.chunk_desc(4 * 3000 - 2999).is_none());
+    }
+
+    #[test]
+    fn fill_from_stops_once_ring_is_full() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        let mut pool = descs.clone();
+
+        assert_eq!(unsafe { fq.fill_from(&mut pool) }, 16);
+        let addrs = |descs: &[FrameDesc]| descs.iter().map(|d| d.addr()).collect::<Vec<_>>();
+
+        assert_eq!(addrs(&pool), addrs(&descs[..16]));
+
+        assert_eq!(unsafe { fq.fill_from(&mut pool) }, 0);
+        assert_eq!(pool.len(), 16);
+    }
+
+    #[test]
+    fn fill_from_handles_fewer_frames_than_ring_size() {
+        let SimXsk { mut fq, descs, .. } = build(16, 4);
+
+        let mut pool = descs;
+
+        assert_eq!(unsafe { fq.fill_from(&mut pool) }, 4);
+        assert!(pool.is_empty());
+        assert_eq!(fq.starved_fills(), 1);
+    }
+
+    #[test]
+    fn fill_from_only_counts_starvation_if_ring_has_room() {
+        let SimXsk { mut fq, descs, .. } = build(16, 16);
+
+        let mut pool = descs;
+
+        assert_eq!(unsafe { fq.fill_from(&mut pool) }, 16);
+        assert_eq!(fq.starved_fills(), 0);
+    }
+
+    #[test]
+    fn fill_from_tops_up_a_partially_filled_ring() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        assert_eq!(unsafe { fq.produce(&descs[..10]) }, 10);
+
+        let mut pool = descs[10..].to_vec();
+
+        assert_eq!(unsafe { fq.fill_from(&mut pool) }, 6);
+        assert_eq!(pool.len(), 16);
+    }
+
+    #[test]
+    fn needed_counts_free_slots_and_missed_packets() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        assert_eq!(unsafe { fq.produce(&descs[..10]) }, 10);
+
+        assert_eq!(
+            fq.needed(None),
+            FillNeed {
+                frames: 6,
+                missed: 0
+            }
+        );
+
+        let mut stats = XdpStatistics::default();
+        stats.0.rx_fill_ring_empty_descs = 3;
+
+        assert_eq!(fq.needed(Some(&stats)).missed, 3);
+        assert_eq!(fq.needed(Some(&stats)).missed, 0);
+
+        stats.0.rx_fill_ring_empty_descs = 5;
+
+        assert_eq!(fq.needed(Some(&stats)).missed, 2);
+    }
+
+    #[test]
+    fn produce_iter_submits_as_many_as_fit() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        let pending: VecDeque<FrameDesc> = descs[..3].iter().copied().collect();
+
+        // Fewer than the free spaces, so the unused reservation is
+        // handed back for the next call.
+        assert_eq!(unsafe { fq.produce_iter(&pending) }, 3);
+
+        let mut iter = descs[3..8].iter().chain(&descs[8..24]);
+
+        assert_eq!(unsafe { fq.produce_iter(&mut iter) }, 13);
+        assert_eq!(iter.next().map(|d| d.addr), Some(descs[16].addr));
+
+        assert_eq!(unsafe { fq.produce_iter(&descs[17..]) }, 0);
+    }
+
+    #[test]
+    fn produce_partial_tops_up_the_free_slots() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        assert_eq!(fq.free_slots(), 16);
+        assert_eq!(unsafe { fq.produce(&descs[..10]) }, 10);
+        assert_eq!(fq.free_slots(), 6);
+
+        assert_eq!(unsafe { fq.produce(&descs[10..20]) }, 0);
+        assert_eq!(unsafe { fq.produce_partial(&descs[10..20]) }, 6);
+
+        assert_eq!(fq.free_slots(), 0);
+        assert_eq!(unsafe { fq.produce_partial(&descs[16..20]) }, 0);
+    }
+
+    #[test]
+    fn produce_with_policy_handles_overflow() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        assert_eq!(unsafe { fq.produce(&descs[..20]) }, 0);
+
+        assert_eq!(
+            unsafe { fq.produce_with_policy(&descs[..20], ProducePolicy::ErrorOut) }
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        assert_eq!(
+            unsafe { fq.produce_with_policy(&descs[..4], ProducePolicy::ErrorOut) }.unwrap(),
+            4
+        );
+
+        // Only 12 spaces left, so truncating to the ring size of 16
+        // still doesn't fit.
+        assert_eq!(
+            unsafe { fq.produce_with_policy(&descs[4..24], ProducePolicy::TruncateToCapacity) }
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            unsafe { fq.produce_with_policy(&descs[4..24], ProducePolicy::SplitAcrossCalls) }
+                .unwrap(),
+            12
+        );
+
+        assert_eq!(
+            unsafe { fq.produce_with_policy(&descs[16..24], ProducePolicy::SplitAcrossCalls) }
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn ring_info_reflects_ring_state() {
+        let SimXsk { mut fq, descs, .. } = build(16, 32);
+
+        let info = fq.ring_info();
+
+        assert_eq!(info.size(), 16);
+        assert_eq!(info.entry_size(), 8);
+        assert_eq!(info.occupancy(), 0);
+
+        assert_eq!(unsafe { fq.produce(&descs[..5]) }, 5);
+
+        let info = fq.ring_info();
+
+        assert_eq!(info.producer(), 5);
+        assert_eq!(info.occupancy(), 5);
+        assert_ne!(info.desc_addr(), 0);
+    }
 }
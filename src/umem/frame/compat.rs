@@ -0,0 +1,134 @@
+//! Zero-copy views of frame data as the packet types of other crates,
+//! so that their protocol parsers can be used directly on a frame.
+//!
+//! - With the `etherparse` feature, [`Data::sliced`] and
+//!   [`DataMut::sliced`] parse the contents into an
+//!   [`etherparse::SlicedPacket`].
+//! - With the `pnet_packet` feature, [`Data`] implements
+//!   [`pnet_packet::Packet`], and [`DataMut`] also implements
+//!   [`pnet_packet::MutablePacket`], with the whole of the contents as
+//!   both the packet and its payload. [`Data::ethernet`] and
+//!   [`DataMut::ethernet_mut`] view the contents as an Ethernet
+//!   frame.
+//!
+//! All of these borrow the frame's contents, up to its current
+//! length, rather than copying them.
+
+#[cfg(feature = "etherparse")]
+mod etherparse_impl {
+    use etherparse::{err::packet::SliceError, SlicedPacket};
+
+    use super::super::{Data, DataMut};
+
+    impl<'umem> Data<'umem> {
+        /// Parse the contents as an Ethernet II frame and the headers
+        /// within it, borrowing rather than copying them.
+        #[inline]
+        pub fn sliced(&self) -> Result<SlicedPacket<'umem>, SliceError> {
+            SlicedPacket::from_ethernet(self.contents)
+        }
+    }
+
+    impl DataMut<'_> {
+        /// Same as [`Data::sliced`]. The returned slices borrow the
+        /// segment, so it can't be written to while they're held.
+        #[inline]
+        pub fn sliced(&self) -> Result<SlicedPacket<'_>, SliceError> {
+            SlicedPacket::from_ethernet(self.contents())
+        }
+    }
+}
+
+#[cfg(feature = "pnet_packet")]
+mod pnet_impl {
+    use pnet_packet::{
+        ethernet::{EthernetPacket, MutableEthernetPacket},
+        MutablePacket, Packet,
+    };
+
+    use super::super::{Data, DataMut};
+
+    impl<'umem> Data<'umem> {
+        /// View the contents as an Ethernet frame. Returns [`None`] if
+        /// they're too short for an Ethernet header.
+        #[inline]
+        pub fn ethernet(&self) -> Option<EthernetPacket<'umem>> {
+            EthernetPacket::new(self.contents)
+        }
+    }
+
+    impl Packet for Data<'_> {
+        #[inline]
+        fn packet(&self) -> &[u8] {
+            self.contents
+        }
+
+        #[inline]
+        fn payload(&self) -> &[u8] {
+            self.contents
+        }
+    }
+
+    impl DataMut<'_> {
+        /// View the contents as a mutable Ethernet frame. Returns
+        /// [`None`] if they're too short for an Ethernet header.
+        ///
+        /// Writes through the returned packet can't change the length
+        /// of the segment, use a [`cursor`](DataMut::cursor) for that.
+        #[inline]
+        pub fn ethernet_mut(&mut self) -> Option<MutableEthernetPacket<'_>> {
+            MutableEthernetPacket::new(self.contents_mut())
+        }
+    }
+
+    impl Packet for DataMut<'_> {
+        #[inline]
+        fn packet(&self) -> &[u8] {
+            self.contents()
+        }
+
+        #[inline]
+        fn payload(&self) -> &[u8] {
+            self.contents()
+        }
+    }
+
+    impl MutablePacket for DataMut<'_> {
+        #[inline]
+        fn packet_mut(&mut self) -> &mut [u8] {
+            self.contents_mut()
+        }
+
+        #[inline]
+        fn payload_mut(&mut self) -> &mut [u8] {
+            self.contents_mut()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use pnet_packet::ethernet::EtherTypes;
+        use std::io::Write;
+
+        use super::*;
+
+        #[test]
+        fn ethernet_headers_are_written_in_place() {
+            let mut len = 0;
+            let mut buf = [0u8; 64];
+
+            let mut addr = 0;
+            let mut data = DataMut::new(&mut addr, &mut len, 0, &mut buf);
+            assert!(data.ethernet_mut().is_none());
+
+            data.cursor().write_all(&[0; 20]).unwrap();
+
+            data.ethernet_mut().unwrap().set_ethertype(EtherTypes::Ipv6);
+
+            assert_eq!(data.packet()[12..14], [0x86, 0xdd]);
+
+            let data = Data::new(&buf[..20]);
+            assert_eq!(data.ethernet().unwrap().get_ethertype(), EtherTypes::Ipv6);
+        }
+    }
+}
@@ -47,6 +47,83 @@ impl<'a> Cursor<'a> {
         self.buf.fill(0);
         self.set_pos(0);
     }
+
+    /// The space left in the buffer after the cursor's write
+    /// position.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - util::min_usize(*self.pos, self.buf.len())
+    }
+
+    /// Write all of `bytes` at the cursor's write position and
+    /// advance it past them.
+    ///
+    /// Unlike [`write`](Write::write), nothing is written if there
+    /// isn't space for all of `bytes`, in which case an error is
+    /// returned.
+    #[inline]
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_at(*self.pos, bytes)
+    }
+
+    /// Write all of `bytes` starting at `offset` in the buffer,
+    /// regardless of the cursor's write position. Useful for filling
+    /// in a header once the length of what follows it is known.
+    ///
+    /// If the write ends past the write position then the position is
+    /// moved to its end, with any gap between the two zeroed first.
+    /// Otherwise the position is left as is.
+    ///
+    /// Nothing is written if `bytes` would extend past the end of the
+    /// buffer, in which case an error is returned.
+    pub fn write_at(&mut self, offset: usize, bytes: &[u8]) -> io::Result<()> {
+        let end = match offset.checked_add(bytes.len()) {
+            Some(end) if end <= self.buf.len() => end,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "write exceeds end of buffer",
+                ))
+            }
+        };
+
+        let pos = util::min_usize(*self.pos, self.buf.len());
+
+        if offset > pos {
+            self.buf[pos..offset].fill(0);
+        }
+
+        self.buf[offset..end].copy_from_slice(bytes);
+
+        if end > pos {
+            *self.pos = end;
+        }
+
+        Ok(())
+    }
+
+    /// Write all of `bufs`, one after the other, at the cursor's write
+    /// position and advance it past them.
+    ///
+    /// Unlike [`write_vectored`](Write::write_vectored), nothing is
+    /// written if there isn't space for all of `bufs`, in which case
+    /// an error is returned.
+    pub fn write_all_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<()> {
+        let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+
+        if len > self.remaining() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "write exceeds end of buffer",
+            ));
+        }
+
+        for buf in bufs {
+            self.extend_from_slice(buf)?;
+        }
+
+        Ok(())
+    }
 }
 
 // Taken almost verbatim from
@@ -149,4 +226,45 @@ mod tests {
         cursor.set_pos(33);
         assert_eq!(cursor.pos(), 32);
     }
+
+    #[test]
+    fn write_at_fills_in_headers_and_zeroes_gaps() {
+        let mut pos = 0;
+        let mut buf = [0xff; 16];
+
+        let mut cursor = Cursor::new(&mut pos, &mut buf[..]);
+
+        cursor.write_at(4, b"body").unwrap();
+        assert_eq!(cursor.pos(), 8);
+
+        cursor.write_at(0, b"hd").unwrap();
+        assert_eq!(cursor.pos(), 8);
+
+        cursor.extend_from_slice(b"!").unwrap();
+        assert_eq!(cursor.pos(), 9);
+
+        assert!(cursor.write_at(14, b"abc").is_err());
+        assert!(cursor.write_at(usize::MAX, b"a").is_err());
+        assert_eq!(cursor.pos(), 9);
+
+        assert_eq!(&buf[..pos], b"hd\0\0body!");
+    }
+
+    #[test]
+    fn write_all_vectored_writes_nothing_unless_all_fit() {
+        let mut pos = 0;
+        let mut buf = [0; 8];
+
+        let mut cursor = Cursor::new(&mut pos, &mut buf[..]);
+
+        let bufs = [IoSlice::new(b"head"), IoSlice::new(b"pay")];
+        cursor.write_all_vectored(&bufs).unwrap();
+        assert_eq!(cursor.remaining(), 1);
+
+        assert!(cursor.write_all_vectored(&bufs[1..]).is_err());
+        assert!(cursor.extend_from_slice(b"ld").is_err());
+        assert_eq!(cursor.pos(), 7);
+
+        assert_eq!(&buf[..pos], b"headpay");
+    }
 }
@@ -1,14 +1,30 @@
 //! Types for representing and working with a [`Umem`](super::Umem)
 //! frame.
 
+mod compat;
+
 mod cursor;
 pub use cursor::Cursor;
 
+mod owned;
+pub use owned::Frame;
+
+use libxdp_sys::xdp_desc;
 use std::{
     borrow::{Borrow, BorrowMut},
+    convert::TryFrom,
+    io,
     ops::{Deref, DerefMut},
 };
 
+use crate::{config::UmemConfig, net::VlanTag};
+
+use super::unaligned;
+
+/// The length of the destination and source addresses which start an
+/// Ethernet header.
+const ETH_ADDRS_LEN: usize = 12;
+
 /// The length (in bytes) of data in a frame's packet data and
 /// headroom segments.
 ///
@@ -55,6 +71,9 @@ impl SegmentLengths {
 pub struct FrameDesc {
     pub(crate) addr: usize,
     pub(crate) options: u32,
+    // The `UmemSet` region the frame belongs to. Never written to a
+    // ring.
+    pub(crate) region: u8,
     pub(crate) lengths: SegmentLengths,
 }
 
@@ -67,6 +86,7 @@ impl FrameDesc {
         Self {
             addr,
             options: 0,
+            region: 0,
             lengths: SegmentLengths::default(),
         }
     }
@@ -97,12 +117,74 @@ impl FrameDesc {
         self.options = options
     }
 
+    /// The `xdp_desc` describing this frame, as it would be written
+    /// to the tx ring. Only the packet data segment is described, so
+    /// the headroom length is dropped.
     #[inline]
-    pub(crate) fn write_xdp_desc(&self, desc: &mut libxdp_sys::xdp_desc) {
+    pub fn to_xdp_desc(&self) -> xdp_desc {
+        let mut desc = xdp_desc {
+            addr: 0,
+            len: 0,
+            options: 0,
+        };
+        self.write_xdp_desc(&mut desc);
+        desc
+    }
+
+    /// Creates a frame descriptor from an `xdp_desc`, as read from
+    /// the rx ring of a socket bound to a [`Umem`](super::Umem)
+    /// created with `config`.
+    ///
+    /// The headroom length is set to zero, since the kernel doesn't
+    /// write to it. Returns `None` if `desc` doesn't point into the
+    /// packet data segment of a frame, if its data would run past
+    /// the end of that frame, or if its address can't be addressed on
+    /// this target.
+    ///
+    /// In unaligned chunk mode the address is decoded first, and the
+    /// packet's offset is that encoded by the kernel, relative to
+    /// wherever its frame starts.
+    pub fn from_xdp_desc(desc: &xdp_desc, config: &UmemConfig) -> Option<Self> {
+        if usize::try_from(unaligned::add_offset_to_addr(desc.addr)).is_err() {
+            return None;
+        }
+
+        let frame_size = config.frame_size().get() as u64;
+        let headroom = (config.xdp_headroom() + config.frame_headroom()) as u64;
+
+        let offset = if config.unaligned_chunks() {
+            unaligned::extract_offset(desc.addr)
+        } else {
+            desc.addr % frame_size
+        };
+
+        if offset < headroom || offset + desc.len as u64 > frame_size {
+            return None;
+        }
+
+        let mut frame_desc = Self::default();
+        frame_desc.read_xdp_desc(desc);
+        Some(frame_desc)
+    }
+
+    #[inline]
+    pub(crate) fn write_xdp_desc(&self, desc: &mut xdp_desc) {
         desc.addr = self.addr as u64;
         desc.options = self.options;
         desc.len = self.lengths.data as u32;
     }
+
+    #[inline]
+    pub(crate) fn read_xdp_desc(&mut self, desc: &xdp_desc) {
+        // A no-op unless the UMEM is in unaligned chunk mode.
+        self.addr = unaligned::add_offset_to_addr(desc.addr) as usize;
+        self.options = desc.options;
+        // The kernel knows nothing of regions, so the frame has to be
+        // tagged again by whoever knows which ring it came off.
+        self.region = 0;
+        self.lengths.data = desc.len as usize;
+        self.lengths.headroom = 0;
+    }
 }
 
 impl Default for FrameDesc {
@@ -122,6 +204,7 @@ impl Default for FrameDesc {
         Self {
             addr: 0,
             options: 0,
+            region: 0,
             lengths: Default::default(),
         }
     }
@@ -298,13 +381,34 @@ impl Deref for Data<'_> {
 /// frame.
 #[derive(Debug)]
 pub struct DataMut<'umem> {
+    addr: &'umem mut usize,
     len: &'umem mut usize,
+    // Where the segment starts in `buf`, which also covers the space
+    // in front of it that the segment may be grown into.
+    head: usize,
     buf: &'umem mut [u8],
 }
 
 impl<'umem> DataMut<'umem> {
-    pub(super) fn new(len: &'umem mut usize, buf: &'umem mut [u8]) -> Self {
-        Self { len, buf }
+    /// `buf` runs from `head` bytes before the segment's start at
+    /// `addr` to the end of the frame.
+    pub(super) fn new(
+        addr: &'umem mut usize,
+        len: &'umem mut usize,
+        head: usize,
+        buf: &'umem mut [u8],
+    ) -> Self {
+        Self {
+            addr,
+            len,
+            head,
+            buf,
+        }
+    }
+
+    #[inline]
+    fn segment_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.head..]
     }
 
     /// Returns this segment's contents, up to its current length.
@@ -312,7 +416,7 @@ impl<'umem> DataMut<'umem> {
     /// Will change as packets are sent or received using this frame.
     #[inline]
     pub fn contents(&self) -> &[u8] {
-        &self.buf[..*self.len]
+        &self.buf[self.head..self.head + *self.len]
     }
 
     /// Returns a mutable view of this segment's contents, up to its
@@ -321,7 +425,7 @@ impl<'umem> DataMut<'umem> {
     /// Will change as packets are sent or received using this frame.
     #[inline]
     pub fn contents_mut(&mut self) -> &mut [u8] {
-        &mut self.buf[..*self.len]
+        &mut self.buf[self.head..self.head + *self.len]
     }
 
     /// A cursor for writing to this segment.
@@ -335,7 +439,181 @@ impl<'umem> DataMut<'umem> {
     /// [`contents_mut`]: Self::contents_mut
     #[inline]
     pub fn cursor(&mut self) -> Cursor<'_> {
-        Cursor::new(self.len, self.buf)
+        Cursor::new(self.len, &mut self.buf[self.head..])
+    }
+
+    /// The number of bytes that can still be appended to this
+    /// segment before it reaches the end of the frame. For a segment
+    /// starting where the kernel places received packets this is the
+    /// [`mtu`] less its length.
+    ///
+    /// [`mtu`]: crate::config::UmemConfig::mtu
+    #[inline]
+    pub fn tail_room(&self) -> usize {
+        self.buf.len() - self.head - *self.len
+    }
+
+    /// Append `trailer` after the segment's current contents,
+    /// extending its length. Useful for adding a trailer to a
+    /// received packet before sending it on.
+    ///
+    /// Nothing is written if there isn't [`tail_room`] for all of
+    /// `trailer`, in which case an error is returned.
+    ///
+    /// [`tail_room`]: Self::tail_room
+    #[inline]
+    pub fn extend_tail(&mut self, trailer: &[u8]) -> io::Result<()> {
+        if trailer.len() > self.tail_room() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "trailer exceeds space remaining in frame",
+            ));
+        }
+
+        let len = *self.len;
+        let end = len + trailer.len();
+
+        self.segment_mut()[len..end].copy_from_slice(trailer);
+        *self.len = end;
+
+        Ok(())
+    }
+
+    /// The number of bytes this segment can be grown by at the front
+    /// with [`push_front`](Self::push_front).
+    ///
+    /// This is the part of the frame's XDP headroom not taken up by
+    /// the headroom segment, which always sits immediately in front
+    /// of this one and so moves back with it. For a segment starting
+    /// where the kernel places received packets this is the
+    /// [`xdp_headroom`]. A segment borrowed alongside the headroom
+    /// segment through [`Umem::frame_mut`] has none unless the
+    /// [`frame_headroom`] is zero.
+    ///
+    /// [`xdp_headroom`]: crate::config::UmemConfig::xdp_headroom
+    /// [`frame_headroom`]: crate::config::UmemConfig::frame_headroom
+    /// [`Umem::frame_mut`]: crate::Umem::frame_mut
+    #[inline]
+    pub fn head_room(&self) -> usize {
+        self.head
+    }
+
+    /// Grow this segment backwards by `len` bytes and return them, now
+    /// at its start, to be written to. Like `skb_push` in the kernel,
+    /// this prepends an encapsulation header such as a VXLAN header
+    /// or an MPLS label in place, without moving the packet.
+    ///
+    /// The descriptor's address is moved back and its data length
+    /// extended by `len`, so it describes the grown segment. The
+    /// returned bytes are left as they were, and any
+    /// [metadata](crate::meta) written by the XDP program is
+    /// overwritten, so should be read beforehand.
+    ///
+    /// Nothing is changed if there isn't [`head_room`] for `len`
+    /// bytes, in which case an error is returned.
+    ///
+    /// [`head_room`]: Self::head_room
+    pub fn push_front(&mut self, len: usize) -> io::Result<&mut [u8]> {
+        if len > self.head {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "header exceeds space in front of frame data",
+            ));
+        }
+
+        self.head -= len;
+        *self.addr -= len;
+        *self.len += len;
+
+        Ok(&mut self.buf[self.head..self.head + len])
+    }
+
+    /// Shrink this segment by `len` bytes at the front and return
+    /// them, the reverse of [`push_front`](Self::push_front). Useful
+    /// for stripping an encapsulation header before passing a packet
+    /// on.
+    ///
+    /// The descriptor's address is moved forward and its data length
+    /// reduced by `len`. Returns [`None`], leaving the segment
+    /// untouched, if it's shorter than `len`.
+    pub fn pull_front(&mut self, len: usize) -> Option<&[u8]> {
+        if len > *self.len {
+            return None;
+        }
+
+        let start = self.head;
+
+        self.head += len;
+        *self.addr += len;
+        *self.len -= len;
+
+        Some(&self.buf[start..start + len])
+    }
+
+    /// Insert `tag` after the source address of the Ethernet frame in
+    /// this segment, extending its length.
+    ///
+    /// The frame's address is left as is, with the rest of the frame
+    /// moved back to make room, so the descriptor still refers to the
+    /// same frame as far as the [`Umem`](crate::Umem) and any frame
+    /// tracking is concerned.
+    ///
+    /// Nothing is written if the segment is too short to hold an
+    /// Ethernet header or there isn't [`tail_room`] for the tag, in
+    /// which case an error is returned.
+    ///
+    /// [`tail_room`]: Self::tail_room
+    pub fn push_vlan(&mut self, tag: VlanTag) -> io::Result<()> {
+        if *self.len < ETH_ADDRS_LEN + 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame too short for an Ethernet header",
+            ));
+        }
+
+        if VlanTag::LEN > self.tail_room() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "VLAN tag exceeds space remaining in frame",
+            ));
+        }
+
+        let len = *self.len;
+
+        let buf = self.segment_mut();
+
+        buf.copy_within(ETH_ADDRS_LEN..len, ETH_ADDRS_LEN + VlanTag::LEN);
+        buf[ETH_ADDRS_LEN..ETH_ADDRS_LEN + VlanTag::LEN].copy_from_slice(&tag.to_bytes());
+
+        *self.len = len + VlanTag::LEN;
+
+        Ok(())
+    }
+
+    /// Remove and return the outermost VLAN tag of the Ethernet frame
+    /// in this segment, shortening it. Returns [`None`], leaving the
+    /// frame untouched, if it isn't tagged.
+    ///
+    /// As with [`push_vlan`](Self::push_vlan), the frame's address is
+    /// left as is.
+    pub fn pop_vlan(&mut self) -> Option<VlanTag> {
+        let len = *self.len;
+
+        if len < ETH_ADDRS_LEN + VlanTag::LEN + 2 {
+            return None;
+        }
+
+        let mut bytes = [0; VlanTag::LEN];
+        bytes.copy_from_slice(&self.contents()[ETH_ADDRS_LEN..ETH_ADDRS_LEN + VlanTag::LEN]);
+
+        let tag = VlanTag::from_bytes(bytes)?;
+
+        self.segment_mut()
+            .copy_within(ETH_ADDRS_LEN + VlanTag::LEN..len, ETH_ADDRS_LEN);
+
+        *self.len = len - VlanTag::LEN;
+
+        Some(tag)
     }
 }
 
@@ -389,11 +667,15 @@ mod tests {
     use std::{
         convert::TryInto,
         io::{self, Write},
+        num::NonZeroU32,
     };
 
     use libxdp_sys::xdp_desc;
 
-    use crate::umem::{FrameDesc, FrameLayout, UmemRegion};
+    use crate::{
+        config::PrefaultMode,
+        umem::{FrameDesc, FrameLayout, UmemBacking, UmemRegion},
+    };
 
     #[test]
     fn writes_persist() {
@@ -403,10 +685,17 @@ mod tests {
             mtu: 2048,
         };
 
-        let frame_count = 16.try_into().unwrap();
+        let frame_count = 16;
         let frame_size = layout.frame_size();
 
-        let umem_region = UmemRegion::new(frame_count, layout, false).unwrap();
+        let umem_region = UmemRegion::new(
+            frame_count * frame_size,
+            layout,
+            false,
+            PrefaultMode::default(),
+            UmemBacking::Anonymous,
+        )
+        .unwrap();
 
         let mut desc_0 = FrameDesc::new(0 * frame_size + layout.frame_headroom);
 
@@ -473,6 +762,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn extend_tail_appends_within_mtu() {
+        let layout = FrameLayout {
+            xdp_headroom: 0,
+            frame_headroom: 0,
+            mtu: 8,
+        };
+
+        let umem_region = UmemRegion::new(
+            layout.frame_size(),
+            layout,
+            false,
+            PrefaultMode::default(),
+            UmemBacking::Anonymous,
+        )
+        .unwrap();
+
+        let mut desc = FrameDesc::new(0);
+
+        let mut data = unsafe { umem_region.data_mut(&mut desc) };
+
+        data.cursor().write_all(b"hello").unwrap();
+
+        assert_eq!(data.tail_room(), 3);
+
+        assert_eq!(
+            data.extend_tail(b"1234").unwrap_err().kind(),
+            io::ErrorKind::WriteZero
+        );
+        assert_eq!(data.contents(), b"hello");
+
+        data.extend_tail(b"123").unwrap();
+
+        assert_eq!(data.contents(), b"hello123");
+        assert_eq!(data.tail_room(), 0);
+
+        assert_eq!(desc.lengths().data(), 8);
+    }
+
     #[test]
     fn writes_are_contiguous() {
         let layout = FrameLayout {
@@ -481,8 +809,15 @@ mod tests {
             mtu: 12,
         };
 
-        let frame_count = 4.try_into().unwrap();
-        let umem_region = UmemRegion::new(frame_count, layout, false).unwrap();
+        let frame_count: NonZeroU32 = 4.try_into().unwrap();
+        let umem_region = UmemRegion::new(
+            frame_count.get() as usize * layout.frame_size(),
+            layout,
+            false,
+            PrefaultMode::default(),
+            UmemBacking::Anonymous,
+        )
+        .unwrap();
 
         // An arbitrary layout
         let xdp_headroom_segment = [0, 0, 0, 0];
@@ -541,4 +876,184 @@ mod tests {
 
         assert_eq!(mmap_region, expected_layout)
     }
+
+    #[test]
+    fn vlan_tags_are_pushed_and_popped_in_place() {
+        use super::DataMut;
+        use crate::net::VlanTag;
+
+        let untagged: Vec<u8> = (0..20).collect();
+
+        let mut buf = [0u8; 24];
+        buf[..20].copy_from_slice(&untagged);
+
+        let mut len = 20;
+        let mut addr = 0;
+        let mut data = DataMut::new(&mut addr, &mut len, 0, &mut buf);
+
+        assert_eq!(data.pop_vlan(), None);
+
+        data.push_vlan(VlanTag::new(7, 3)).unwrap();
+
+        assert_eq!(&data.contents()[..12], &untagged[..12]);
+        assert_eq!(&data.contents()[12..16], &VlanTag::new(7, 3).to_bytes());
+        assert_eq!(&data.contents()[16..], &untagged[12..]);
+
+        // No room for a second tag.
+        assert_eq!(
+            data.push_vlan(VlanTag::new(8, 0)).unwrap_err().kind(),
+            io::ErrorKind::WriteZero
+        );
+
+        assert_eq!(data.pop_vlan(), Some(VlanTag::new(7, 3)));
+        assert_eq!(data.contents(), &untagged[..]);
+    }
+
+    #[test]
+    fn push_front_grows_data_into_xdp_headroom() {
+        let layout = FrameLayout {
+            xdp_headroom: 256,
+            frame_headroom: 64,
+            mtu: 1728,
+        };
+
+        let frame_size = layout.frame_size();
+
+        let umem_region = UmemRegion::new(
+            2 * frame_size,
+            layout,
+            false,
+            PrefaultMode::default(),
+            UmemBacking::Anonymous,
+        )
+        .unwrap();
+
+        let canonical = frame_size + layout.xdp_headroom + layout.frame_headroom;
+        let mut desc = FrameDesc::new(canonical);
+
+        {
+            let mut data = unsafe { umem_region.data_mut(&mut desc) };
+
+            data.cursor().write_all(b"payload").unwrap();
+            assert_eq!(data.head_room(), 256);
+            assert_eq!(data.tail_room(), 1728 - 7);
+
+            data.push_front(4).unwrap().copy_from_slice(b"encp");
+            assert_eq!(data.contents(), b"encppayload");
+            assert_eq!(data.head_room(), 252);
+            assert_eq!(data.tail_room(), 1728 - 7);
+
+            assert_eq!(
+                data.push_front(253).unwrap_err().kind(),
+                io::ErrorKind::WriteZero
+            );
+        }
+
+        assert_eq!(desc.addr(), canonical - 4);
+        assert_eq!(desc.lengths().data(), 11);
+
+        // The headroom segment moves back with the data, staying
+        // within the frame.
+        {
+            let (mut headroom, data) = unsafe { umem_region.frame_mut(&mut desc) };
+
+            assert_eq!(data.head_room(), 0);
+            assert_eq!(data.contents(), b"encppayload");
+
+            headroom.cursor().write_all(&[1; 64]).unwrap();
+        }
+
+        {
+            let mut data = unsafe { umem_region.data_mut(&mut desc) };
+
+            assert_eq!(data.contents(), b"encppayload");
+            assert_eq!(data.pull_front(4), Some(&b"encp"[..]));
+            assert_eq!(data.pull_front(8), None);
+            assert_eq!(data.contents(), b"payload");
+            assert_eq!(data.head_room(), 256);
+        }
+
+        assert_eq!(desc.addr(), canonical);
+    }
+
+    #[test]
+    fn xdp_desc_round_trip() {
+        let config = crate::config::UmemConfig::builder()
+            .frame_headroom(32)
+            .build()
+            .unwrap();
+
+        let frame_size = config.frame_size().get() as u64;
+        let headroom = (config.xdp_headroom() + config.frame_headroom()) as u64;
+        let data_start = frame_size + headroom;
+
+        let desc = xdp_desc {
+            addr: data_start,
+            len: 64,
+            options: 1,
+        };
+
+        let frame_desc = FrameDesc::from_xdp_desc(&desc, &config).unwrap();
+
+        assert_eq!(frame_desc.addr(), data_start as usize);
+        assert_eq!(frame_desc.lengths().data(), 64);
+        assert_eq!(frame_desc.lengths().headroom(), 0);
+        assert_eq!(frame_desc.options(), 1);
+
+        let out = frame_desc.to_xdp_desc();
+
+        assert_eq!(out.addr, desc.addr);
+        assert_eq!(out.len, desc.len);
+        assert_eq!(out.options, desc.options);
+
+        // Points into the headroom.
+        let in_headroom = xdp_desc {
+            addr: frame_size,
+            ..desc
+        };
+        assert!(FrameDesc::from_xdp_desc(&in_headroom, &config).is_none());
+
+        // Runs past the end of the frame.
+        let too_long = xdp_desc {
+            len: (frame_size - headroom + 1) as u32,
+            ..desc
+        };
+        assert!(FrameDesc::from_xdp_desc(&too_long, &config).is_none());
+    }
+
+    #[test]
+    fn unaligned_xdp_desc_is_decoded_before_checking() {
+        use libxdp_sys::XSK_UNALIGNED_BUF_OFFSET_SHIFT;
+
+        let config = crate::config::UmemConfig::builder()
+            .frame_size(3000.try_into().unwrap())
+            .unaligned_chunks(true)
+            .build()
+            .unwrap();
+
+        let headroom = (config.xdp_headroom() + config.frame_headroom()) as u64;
+        let encoded = |chunk: u64, offset: u64| chunk | (offset << XSK_UNALIGNED_BUF_OFFSET_SHIFT);
+
+        // A chunk starting off the aligned frame grid.
+        let desc = xdp_desc {
+            addr: encoded(4500, headroom),
+            len: 64,
+            options: 0,
+        };
+
+        let frame_desc = FrameDesc::from_xdp_desc(&desc, &config).unwrap();
+        assert_eq!(frame_desc.addr(), (4500 + headroom) as usize);
+
+        let in_headroom = xdp_desc {
+            addr: encoded(4500, headroom - 1),
+            ..desc
+        };
+        assert!(FrameDesc::from_xdp_desc(&in_headroom, &config).is_none());
+
+        let too_long = xdp_desc {
+            len: (3000 - headroom + 1) as u32,
+            ..desc
+        };
+        assert!(FrameDesc::from_xdp_desc(&too_long, &config).is_none());
+    }
 }
@@ -0,0 +1,245 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    meta::XdpMetadata,
+    umem::{mem::UmemRegion, Umem},
+};
+
+use super::{Data, DataMut, FrameDesc, Headroom, HeadroomMut};
+
+/// An owned [`Umem`] frame.
+///
+/// Pairs a [`FrameDesc`] with a handle to the memory it points into,
+/// so its contents can be read and written without `unsafe` and
+/// without holding on to the [`Umem`]. A `Frame` keeps the memory
+/// alive, so it can be stored in user structs or sent to another
+/// thread, and is handed to the kernel by value via
+/// [`FillQueue::produce_frames`] or [`TxQueue::produce_frames`],
+/// coming back via [`RxQueue::consume_frames`] or
+/// [`CompQueue::consume_frames`].
+///
+/// Dereferences to the contents of the frame's packet data segment.
+///
+/// Each frame should be owned either by a single `Frame` or by a
+/// [`FrameDesc`] used with the `unsafe` queue methods, never both.
+/// Mixing the two for the same [`Umem`] is fine so long as a given
+/// frame is only ever handled one way.
+///
+/// [`FillQueue::produce_frames`]: crate::FillQueue::produce_frames
+/// [`TxQueue::produce_frames`]: crate::TxQueue::produce_frames
+/// [`RxQueue::consume_frames`]: crate::RxQueue::consume_frames
+/// [`CompQueue::consume_frames`]: crate::CompQueue::consume_frames
+#[derive(Debug)]
+pub struct Frame {
+    desc: FrameDesc,
+    mem: UmemRegion,
+}
+
+impl Frame {
+    /// Take ownership of the frame described by `desc`.
+    ///
+    /// # Safety
+    ///
+    /// `desc` must describe a frame belonging to `umem`, and no other
+    /// `Frame` or [`FrameDesc`] for that frame may be used while the
+    /// returned `Frame` exists, by either userspace or the kernel.
+    #[inline]
+    pub unsafe fn from_desc(desc: FrameDesc, umem: &Umem) -> Self {
+        Self {
+            desc,
+            mem: umem.mem.clone(),
+        }
+    }
+
+    /// Give up ownership of the frame, returning its descriptor.
+    /// Accessing or submitting the frame from then on requires the
+    /// `unsafe` [`Umem`] and queue methods.
+    #[inline]
+    pub fn into_desc(self) -> FrameDesc {
+        self.desc
+    }
+
+    /// The frame's descriptor.
+    #[inline]
+    pub fn desc(&self) -> &FrameDesc {
+        &self.desc
+    }
+
+    /// Set the frame options.
+    #[inline]
+    pub fn set_options(&mut self, options: u32) {
+        self.desc.options = options
+    }
+
+    /// The headroom segment of the frame. Contents are read-only.
+    #[inline]
+    pub fn headroom(&self) -> Headroom<'_> {
+        // SAFETY: this `Frame` owns the frame it describes, which
+        // belongs to `mem`.
+        unsafe { self.mem.headroom(&self.desc) }
+    }
+
+    /// The packet data segment of the frame. Contents are read-only.
+    #[inline]
+    pub fn data(&self) -> Data<'_> {
+        // SAFETY: see `headroom`.
+        unsafe { self.mem.data(&self.desc) }
+    }
+
+    /// The metadata written by the XDP program immediately before the
+    /// packet data. See [`Umem::metadata`].
+    #[inline]
+    pub fn metadata<T: XdpMetadata>(&self) -> T {
+        // SAFETY: see `headroom`.
+        unsafe { self.mem.metadata(&self.desc) }
+    }
+
+    /// The headroom and packet data segments of the frame. Contents
+    /// are writeable.
+    #[inline]
+    pub fn frame_mut(&mut self) -> (HeadroomMut<'_>, DataMut<'_>) {
+        // SAFETY: see `headroom`, and we hold the only reference to
+        // this `Frame`.
+        unsafe { self.mem.frame_mut(&mut self.desc) }
+    }
+
+    /// The headroom segment of the frame. Contents are writeable.
+    #[inline]
+    pub fn headroom_mut(&mut self) -> HeadroomMut<'_> {
+        // SAFETY: see `frame_mut`.
+        unsafe { self.mem.headroom_mut(&mut self.desc) }
+    }
+
+    /// The packet data segment of the frame. Contents are writeable.
+    #[inline]
+    pub fn data_mut(&mut self) -> DataMut<'_> {
+        // SAFETY: see `frame_mut`.
+        unsafe { self.mem.data_mut(&mut self.desc) }
+    }
+
+    /// Whether this frame belongs to the memory of `umem`. True for
+    /// any [`Umem`] sharing its memory, as created by
+    /// [`Umem::reregister_with_queue_sizes`].
+    #[inline]
+    pub(crate) fn belongs_to(&self, umem: &Umem) -> bool {
+        self.mem.as_ptr() == umem.mem.as_ptr()
+    }
+}
+
+impl Deref for Frame {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: see `Frame::headroom`.
+        unsafe { self.mem.data_contents(&self.desc) }
+    }
+}
+
+impl DerefMut for Frame {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: see `Frame::frame_mut`.
+        unsafe { self.mem.data_contents_mut(&self.desc) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, ptr};
+
+    use crate::{meta::RxMetadata, sim::SimXsk};
+
+    use super::*;
+
+    fn build() -> (SimXsk, Vec<Frame>) {
+        let mut xsk = SimXsk::with_defaults(16);
+
+        let frames = std::mem::take(&mut xsk.descs)
+            .into_iter()
+            .map(|desc| unsafe { Frame::from_desc(desc, &xsk.umem) })
+            .collect();
+
+        (xsk, frames)
+    }
+
+    #[test]
+    fn frames_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Frame>();
+    }
+
+    #[test]
+    fn frames_round_trip_through_the_queues() {
+        let (mut xsk, mut frames) = build();
+
+        assert_eq!(xsk.fq.produce_frames(&mut frames), 16);
+        assert!(frames.is_empty());
+
+        assert!(xsk.kernel.inject(b"hello"));
+
+        let mut received = Vec::new();
+        assert_eq!(xsk.rx_q.consume_frames(&mut received, 4), 1);
+        assert_eq!(&received[0][..], b"hello");
+
+        // Reply from another thread, to show frames don't borrow the
+        // UMEM.
+        let mut reply = std::thread::spawn(move || {
+            let mut frame = received.pop().unwrap();
+
+            frame.data_mut().cursor().write_all(b" world!").unwrap();
+            frame
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(&reply[..], b"hello world!");
+
+        reply[0] = b'H';
+
+        let addr = reply.desc().addr();
+        let mut to_send = vec![reply];
+
+        assert_eq!(xsk.tx_q.produce_frames(&mut to_send), 1);
+
+        if xsk.tx_q.needs_wakeup() {
+            xsk.tx_q.wakeup().unwrap();
+        }
+
+        xsk.kernel.run_until_idle(64);
+
+        let mut completed = Vec::new();
+        assert_eq!(xsk.cq.consume_frames(&mut completed, 4), 1);
+        assert_eq!(completed[0].desc().addr(), addr);
+    }
+
+    #[test]
+    fn metadata_is_read_from_in_front_of_the_packet() {
+        let (xsk, frames) = build();
+        let frame = &frames[3];
+
+        let mut written = [0u8; RxMetadata::LEN];
+        written[RxMetadata::RX_HASH_OFFSET..][..4].copy_from_slice(&0xdead_beefu32.to_ne_bytes());
+        written[RxMetadata::FLAGS_OFFSET..].copy_from_slice(&RxMetadata::RX_HASH.to_ne_bytes());
+
+        // Write it as the XDP program would.
+        unsafe {
+            let data = (xsk.umem.as_ptr() as *mut u8).add(frame.desc().addr());
+            ptr::copy_nonoverlapping(written.as_ptr(), data.sub(written.len()), written.len());
+        }
+
+        let meta: RxMetadata = frame.metadata();
+
+        assert_eq!(meta.rx_hash(), Some((0xdead_beef, 0)));
+        assert_eq!(meta.rx_timestamp(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "different UMEM")]
+    fn producing_a_frame_from_another_umem_panics() {
+        let (mut xsk, _) = build();
+        let (_, mut other) = build();
+
+        xsk.fq.produce_frames(&mut other);
+    }
+}
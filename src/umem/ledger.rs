@@ -0,0 +1,311 @@
+//! A record of who owns each frame of a [`Umem`].
+//!
+//! Every frame starts out owned by the user. Submitting it to the
+//! fill or tx ring hands it over, and it's handed back once read off
+//! the rx or completion ring respectively. A [`Ledger`] records these
+//! transitions, refusing any that don't make sense, such as
+//! submitting a frame which is already on a ring.
+//!
+//! With the `debug-tracking` feature enabled each [`Umem`] keeps one,
+//! updated by its queues and checked by its frame accessors, see
+//! `Umem::ledger`. A `Ledger` can also be kept alongside an
+//! application's own frame accounting, for example a free list, to
+//! check the two agree.
+//!
+//! From user space a frame sitting on the fill ring can't be told
+//! apart from one the kernel has since taken off it to receive into,
+//! so both count as being on the fill ring. Likewise for the tx ring.
+//!
+//! Frames are assumed to lie back to back, each starting at a
+//! multiple of the frame size, so a `Ledger` can't track a [`Umem`]
+//! in unaligned chunk mode. The `debug-tracking` feature doesn't keep
+//! one for such a `Umem`.
+//!
+//! ```
+//! use xsk_rs::umem::ledger::{FrameState, Ledger};
+//!
+//! let ledger = Ledger::new(4, 2048);
+//!
+//! ledger.submit(2048, FrameState::FillRing).unwrap();
+//!
+//! // Already on the fill ring.
+//! assert!(ledger.submit(2048, FrameState::TxRing).is_err());
+//! assert_eq!(ledger.count(FrameState::User), 3);
+//!
+//! ledger.complete(2048 + 256, FrameState::FillRing).unwrap();
+//! assert!(ledger.is_user_owned(2048));
+//! ```
+
+use std::{
+    error::Error,
+    fmt,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use super::Umem;
+
+/// Who a frame currently belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum FrameState {
+    /// Owned by the user, so free to be accessed or submitted.
+    User = 0,
+    /// Submitted to the fill ring, and not yet read off the rx ring.
+    FillRing = 1,
+    /// Submitted to the tx ring, and not yet read off the completion
+    /// ring.
+    TxRing = 2,
+}
+
+impl FrameState {
+    fn from_u8(state: u8) -> Self {
+        match state {
+            0 => FrameState::User,
+            1 => FrameState::FillRing,
+            _ => FrameState::TxRing,
+        }
+    }
+}
+
+impl fmt::Display for FrameState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            FrameState::User => "owned by the user",
+            FrameState::FillRing => "on the fill ring",
+            FrameState::TxRing => "on the tx ring",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// A transition refused by a [`Ledger`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedgerError {
+    /// The address lies past the last frame.
+    OutOfBounds {
+        /// The address.
+        addr: usize,
+    },
+    /// The frame was expected to be in one state but is in another.
+    WrongState {
+        /// The address the frame was looked up by.
+        addr: usize,
+        /// The state the frame needed to be in.
+        expected: FrameState,
+        /// The state the frame is actually in.
+        found: FrameState,
+    },
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::OutOfBounds { addr } => {
+                write!(f, "frame address {} lies outside of the UMEM", addr)
+            }
+            LedgerError::WrongState {
+                addr,
+                expected,
+                found,
+            } => write!(
+                f,
+                "frame at address {} is {} rather than {}",
+                addr, found, expected
+            ),
+        }
+    }
+}
+
+impl Error for LedgerError {}
+
+/// The [`FrameState`] of each of a [`Umem`]'s frames. Frames are
+/// looked up by any address within them, so descriptors can be
+/// passed as they are whether they point at the start of the frame
+/// or at its packet data.
+///
+/// Updates are atomic, so a `Ledger` can be shared between threads,
+/// for example by putting it in an [`Arc`](std::sync::Arc).
+pub struct Ledger {
+    states: Box<[AtomicU8]>,
+    frame_size: usize,
+}
+
+impl Ledger {
+    /// A ledger of `frame_count` frames of `frame_size` bytes, all
+    /// owned by the user.
+    ///
+    /// # Panics
+    ///
+    /// If `frame_size` is zero.
+    pub fn new(frame_count: usize, frame_size: usize) -> Self {
+        assert!(frame_size > 0, "frame size must be non-zero");
+
+        Self {
+            states: (0..frame_count)
+                .map(|_| AtomicU8::new(FrameState::User as u8))
+                .collect(),
+            frame_size,
+        }
+    }
+
+    /// A ledger covering every frame of `umem`, all owned by the
+    /// user.
+    pub fn for_umem(umem: &Umem) -> Self {
+        Self::new(
+            umem.frame_count(),
+            umem.config().frame_size().get() as usize,
+        )
+    }
+
+    /// The number of frames covered.
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.states.len()
+    }
+
+    fn entry(&self, addr: usize) -> Result<&AtomicU8, LedgerError> {
+        self.states
+            .get(addr / self.frame_size)
+            .ok_or(LedgerError::OutOfBounds { addr })
+    }
+
+    /// The state of the frame containing `addr`, or [`None`] if it
+    /// lies past the last frame.
+    #[inline]
+    pub fn state(&self, addr: usize) -> Option<FrameState> {
+        self.entry(addr)
+            .ok()
+            .map(|s| FrameState::from_u8(s.load(Ordering::Acquire)))
+    }
+
+    /// Whether the frame containing `addr` is owned by the user.
+    #[inline]
+    pub fn is_user_owned(&self, addr: usize) -> bool {
+        self.state(addr) == Some(FrameState::User)
+    }
+
+    /// The number of frames in `state`.
+    pub fn count(&self, state: FrameState) -> usize {
+        self.frames(state).count()
+    }
+
+    /// The start addresses of the frames in `state`, in ascending
+    /// order.
+    pub fn frames(&self, state: FrameState) -> impl Iterator<Item = usize> + '_ {
+        self.states
+            .iter()
+            .enumerate()
+            .filter(move |(_, s)| s.load(Ordering::Acquire) == state as u8)
+            .map(move |(idx, _)| idx * self.frame_size)
+    }
+
+    /// Record the frame containing `addr` being submitted to the ring
+    /// given by `to`. Fails, leaving the frame as it is, if it isn't
+    /// owned by the user.
+    pub fn submit(&self, addr: usize, to: FrameState) -> Result<(), LedgerError> {
+        self.transition(addr, FrameState::User, to)
+    }
+
+    /// Record the frame containing `addr` being read back off the
+    /// ring given by `from`. Fails, leaving the frame as it is, if it
+    /// isn't on that ring.
+    pub fn complete(&self, addr: usize, from: FrameState) -> Result<(), LedgerError> {
+        self.transition(addr, from, FrameState::User)
+    }
+
+    /// Check the frame containing `addr` is owned by the user, so may
+    /// be accessed.
+    pub fn check_owned(&self, addr: usize) -> Result<(), LedgerError> {
+        match self.state(addr) {
+            None => Err(LedgerError::OutOfBounds { addr }),
+            Some(FrameState::User) => Ok(()),
+            Some(found) => Err(LedgerError::WrongState {
+                addr,
+                expected: FrameState::User,
+                found,
+            }),
+        }
+    }
+
+    fn transition(&self, addr: usize, from: FrameState, to: FrameState) -> Result<(), LedgerError> {
+        self.entry(addr)?
+            .compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire)
+            .map(|_| ())
+            .map_err(|found| LedgerError::WrongState {
+                addr,
+                expected: from,
+                found: FrameState::from_u8(found),
+            })
+    }
+}
+
+impl fmt::Debug for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ledger")
+            .field("frame_count", &self.frame_count())
+            .field("frame_size", &self.frame_size)
+            .field("fill_ring", &self.count(FrameState::FillRing))
+            .field("tx_ring", &self.count(FrameState::TxRing))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_move_between_user_and_rings() {
+        let ledger = Ledger::new(4, 2048);
+
+        ledger.submit(0, FrameState::FillRing).unwrap();
+        ledger.submit(2048 + 256, FrameState::TxRing).unwrap();
+
+        assert!(ledger.is_user_owned(4096));
+        assert_eq!(
+            ledger.frames(FrameState::TxRing).collect::<Vec<_>>(),
+            [2048]
+        );
+        assert_eq!(ledger.count(FrameState::User), 2);
+
+        ledger.complete(100, FrameState::FillRing).unwrap();
+        ledger.complete(2048, FrameState::TxRing).unwrap();
+
+        assert_eq!(ledger.count(FrameState::User), 4);
+    }
+
+    #[test]
+    fn refused_transitions_leave_the_frame_as_it_is() {
+        let ledger = Ledger::new(4, 2048);
+
+        ledger.submit(0, FrameState::FillRing).unwrap();
+
+        assert_eq!(
+            ledger.submit(0, FrameState::TxRing),
+            Err(LedgerError::WrongState {
+                addr: 0,
+                expected: FrameState::User,
+                found: FrameState::FillRing,
+            })
+        );
+
+        assert!(ledger.complete(0, FrameState::TxRing).is_err());
+        assert!(ledger.check_owned(512).is_err());
+        assert_eq!(ledger.state(0), Some(FrameState::FillRing));
+
+        assert!(ledger.complete(4096, FrameState::FillRing).is_err());
+        assert!(ledger.is_user_owned(4096));
+    }
+
+    #[test]
+    fn addresses_past_the_last_frame_are_out_of_bounds() {
+        let ledger = Ledger::new(4, 2048);
+
+        assert_eq!(ledger.state(8192), None);
+        assert_eq!(
+            ledger.submit(8192, FrameState::FillRing),
+            Err(LedgerError::OutOfBounds { addr: 8192 })
+        );
+    }
+}
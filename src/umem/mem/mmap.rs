@@ -1,37 +1,115 @@
 pub use inner::Mmap;
 
-use std::{io, ptr::NonNull};
+use std::{
+    io,
+    ops::Range,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd},
+    ptr::NonNull,
+};
+
+use crate::{config::PrefaultMode, umem::UmemBacking};
+
+/// Open the file backing a mapping of `len` bytes, if there is one,
+/// making sure it's at least `len` bytes long.
+fn open_backing(
+    len: usize,
+    use_huge_pages: bool,
+    backing: UmemBacking,
+) -> io::Result<Option<OwnedFd>> {
+    let fd = match backing {
+        UmemBacking::Anonymous => return Ok(None),
+        UmemBacking::Memfd => {
+            let mut flags = libc::MFD_CLOEXEC;
+
+            if use_huge_pages {
+                flags |= libc::MFD_HUGETLB;
+            }
+
+            let fd = unsafe { libc::memfd_create(b"xsk-umem\0".as_ptr() as *const _, flags) };
+
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // SAFETY: `fd` was just created and nothing else owns it.
+            unsafe { OwnedFd::from_raw_fd(fd) }
+        }
+        UmemBacking::Fd(fd) => fd,
+    };
+
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Only ever grow the file, since it may already hold frames
+    // written by another process.
+    if (stat.st_size as u64) < len as u64
+        && unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Some(fd))
+}
 
 #[cfg(not(test))]
 mod inner {
     use libc::{
-        MAP_ANONYMOUS, MAP_FAILED, MAP_HUGETLB, MAP_POPULATE, MAP_SHARED, PROT_READ, PROT_WRITE,
+        MADV_DONTNEED, MADV_WILLNEED, MAP_ANONYMOUS, MAP_FAILED, MAP_HUGETLB, MAP_POPULATE,
+        MAP_SHARED, PROT_READ, PROT_WRITE,
     };
     use log::error;
     use std::ptr;
 
     use super::*;
+    use crate::util;
 
-    /// An anonymous memory mapped region.
+    // Not defined by older versions of `libc`. Added in linux 5.14.
+    const MADV_POPULATE_WRITE: libc::c_int = 23;
+
+    /// A memory mapped region, either anonymous or backed by a file.
     #[derive(Debug)]
     pub struct Mmap {
         addr: NonNull<libc::c_void>,
         len: usize,
+        fd: Option<OwnedFd>,
     }
 
     unsafe impl Send for Mmap {}
 
     impl Mmap {
-        pub fn new(len: usize, use_huge_pages: bool) -> io::Result<Self> {
-            // MAP_ANONYMOUS: mapping not backed by a file.
+        pub fn new(
+            len: usize,
+            use_huge_pages: bool,
+            prefault: PrefaultMode,
+            backing: UmemBacking,
+        ) -> io::Result<Self> {
+            let fd = open_backing(len, use_huge_pages, backing)?;
+
             // MAP_SHARED: shares this mapping, so changes are visible
-            // to other processes mapping the same file.
+            // to other processes mapping the same file. In particular
+            // a child process forked after the UMEM is created sees
+            // the same frames as the parent, rather than a
+            // copy-on-write snapshot as it would with MAP_PRIVATE.
+            let mut flags = MAP_SHARED;
+
+            // MAP_ANONYMOUS: mapping not backed by a file, so
+            // zeroed. Whether a file mapping uses huge pages is down
+            // to the file.
+            if fd.is_none() {
+                flags |= MAP_ANONYMOUS;
+
+                if use_huge_pages {
+                    flags |= MAP_HUGETLB;
+                }
+            }
+
             // MAP_POPULATE: pre-populate page tables, reduces
             // blocking on page faults later.
-            let mut flags = MAP_ANONYMOUS | MAP_SHARED | MAP_POPULATE;
-
-            if use_huge_pages {
-                flags |= MAP_HUGETLB;
+            if prefault == PrefaultMode::Populate {
+                flags |= MAP_POPULATE;
             }
 
             let addr = unsafe {
@@ -40,8 +118,8 @@ mod inner {
                     len,
                     PROT_READ | PROT_WRITE, // prot
                     flags,
-                    -1, // file
-                    0,  // offset
+                    fd.as_ref().map_or(-1, |fd| fd.as_raw_fd()), // file
+                    0,                                           // offset
                 )
             };
 
@@ -51,7 +129,64 @@ mod inner {
                 let addr =
                     NonNull::new(addr).expect("ptr non-null since we confirmed `mmap()` succeeded");
 
-                Ok(Mmap { addr, len })
+                let mmap = Mmap { addr, len, fd };
+
+                if prefault == PrefaultMode::WillNeed {
+                    mmap.advise(MADV_WILLNEED)?;
+                }
+
+                Ok(mmap)
+            }
+        }
+
+        /// Fault in every page of the region for writing, without
+        /// modifying its contents.
+        pub fn populate(&self) -> io::Result<()> {
+            self.advise(MADV_POPULATE_WRITE)
+        }
+
+        /// Drop the pages in `range`, an offset from the start of the
+        /// region, from this process's page tables. The range must be
+        /// page aligned.
+        pub fn dont_need(&self, range: Range<usize>) -> io::Result<()> {
+            self.advise_range(range, MADV_DONTNEED)
+        }
+
+        /// Fault in every page of the region for writing and lock them
+        /// into memory.
+        pub fn lock(&self) -> io::Result<()> {
+            // `mlock` faults pages in too, but only for reading in the
+            // case of a shared mapping, so populate them for writing
+            // first where the kernel supports it.
+            match self.populate() {
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => (),
+                res => res?,
+            }
+
+            let addr = self.addr.as_ptr() as usize;
+
+            util::mlock(addr..addr + self.len)
+        }
+
+        fn advise(&self, advice: libc::c_int) -> io::Result<()> {
+            self.advise_range(0..self.len, advice)
+        }
+
+        fn advise_range(&self, range: Range<usize>, advice: libc::c_int) -> io::Result<()> {
+            debug_assert!(range.end <= self.len);
+
+            let err = unsafe {
+                libc::madvise(
+                    self.addr.as_ptr().add(range.start),
+                    range.end - range.start,
+                    advice,
+                )
+            };
+
+            if err != 0 {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(())
             }
         }
 
@@ -60,6 +195,12 @@ mod inner {
         pub fn addr(&self) -> NonNull<libc::c_void> {
             self.addr
         }
+
+        /// The file backing the region, if not anonymous.
+        #[inline]
+        pub fn fd(&self) -> Option<&OwnedFd> {
+            self.fd.as_ref()
+        }
     }
 
     impl Drop for Mmap {
@@ -109,13 +250,33 @@ mod inner {
         }
     }
 
-    /// A mocked [`Mmap`] that uses the heap for memory.
+    /// A mocked [`Mmap`] that uses the heap for memory. Any backing
+    /// file is still opened and sized, but not mapped.
     #[derive(Debug)]
-    pub struct Mmap(VecParts<u8>);
+    pub struct Mmap(VecParts<u8>, Option<OwnedFd>);
 
     impl Mmap {
-        pub fn new(len: usize, _use_huge_pages: bool) -> io::Result<Self> {
-            Ok(Self(VecParts::new(vec![0; len])))
+        pub fn new(
+            len: usize,
+            use_huge_pages: bool,
+            _prefault: PrefaultMode,
+            backing: UmemBacking,
+        ) -> io::Result<Self> {
+            let fd = open_backing(len, use_huge_pages, backing)?;
+
+            Ok(Self(VecParts::new(vec![0; len]), fd))
+        }
+
+        pub fn populate(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        pub fn dont_need(&self, _range: Range<usize>) -> io::Result<()> {
+            Ok(())
+        }
+
+        pub fn lock(&self) -> io::Result<()> {
+            Ok(())
         }
 
         /// Returns a pointer to the start of the mmap'd region.
@@ -123,6 +284,11 @@ mod inner {
         pub fn addr(&self) -> NonNull<libc::c_void> {
             NonNull::new(self.0.ptr.as_ptr() as *mut libc::c_void).unwrap()
         }
+
+        #[inline]
+        pub fn fd(&self) -> Option<&OwnedFd> {
+            self.1.as_ref()
+        }
     }
 }
 
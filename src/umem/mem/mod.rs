@@ -3,15 +3,24 @@ use mmap::Mmap;
 
 use std::{
     io,
-    num::NonZeroU32,
-    ptr::NonNull,
+    ops::Range,
+    os::unix::prelude::{AsRawFd, RawFd},
+    ptr::{self, NonNull},
     slice,
     sync::{Arc, Mutex},
 };
 
+use crate::{
+    config::PrefaultMode,
+    meta::{XdpMetadata, META_MAX_LEN},
+    util,
+};
+
+#[cfg(feature = "debug-tracking")]
+use super::ledger::Ledger;
 use super::{
     frame::{Data, DataMut, FrameDesc, Headroom, HeadroomMut},
-    FrameLayout,
+    FrameLayout, UmemBacking,
 };
 
 /// A framed, memory mapped region which functions as the working
@@ -25,7 +34,14 @@ pub struct UmemRegion {
     // region.
     addr: NonNull<libc::c_void>,
     len: usize,
-    _mmap: Arc<Mutex<Mmap>>,
+    // Likewise for the backing file's descriptor, which stays open
+    // for as long as the mmap region does.
+    fd: Option<RawFd>,
+    mmap: Arc<Mutex<Mmap>>,
+    // Shared by every clone, so frame states are kept in step across
+    // any `Umem`s registered with this region.
+    #[cfg(feature = "debug-tracking")]
+    ledger: Option<Arc<Ledger>>,
 }
 
 unsafe impl Send for UmemRegion {}
@@ -37,29 +53,103 @@ unsafe impl Send for UmemRegion {}
 unsafe impl Sync for UmemRegion {}
 
 impl UmemRegion {
+    /// Create a new region of `len` bytes, which should have been
+    /// checked with [`UmemConfig::umem_len`](crate::config::UmemConfig::umem_len).
     pub(super) fn new(
-        frame_count: NonZeroU32,
+        len: usize,
         frame_layout: FrameLayout,
         use_huge_pages: bool,
+        prefault: PrefaultMode,
+        backing: UmemBacking,
     ) -> io::Result<Self> {
-        let len = (frame_count.get() as usize) * frame_layout.frame_size();
-
-        let mmap = Mmap::new(len, use_huge_pages)?;
+        let mmap = Mmap::new(len, use_huge_pages, prefault, backing)?;
 
         Ok(Self {
             layout: frame_layout,
             addr: mmap.addr(),
             len,
-            _mmap: Arc::new(Mutex::new(mmap)),
+            fd: mmap.fd().map(|fd| fd.as_raw_fd()),
+            mmap: Arc::new(Mutex::new(mmap)),
+            #[cfg(feature = "debug-tracking")]
+            ledger: Some(Arc::new(Ledger::new(
+                len / frame_layout.frame_size(),
+                frame_layout.frame_size(),
+            ))),
         })
     }
 
+    /// Stop tracking the ownership of this region's frames, for when
+    /// it's unknown which the kernel has.
+    #[cfg(feature = "debug-tracking")]
+    pub fn without_ledger(mut self) -> Self {
+        self.ledger = None;
+        self
+    }
+
+    /// See docs for [`super::Umem::ledger`].
+    #[cfg(feature = "debug-tracking")]
+    #[inline]
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.ledger.as_deref()
+    }
+
     /// The size of the underlying memory region.
     #[inline]
     pub fn len(&self) -> usize {
         self.len
     }
 
+    /// See docs for [`super::Umem::backing_fd`].
+    #[inline]
+    pub fn fd(&self) -> Option<RawFd> {
+        self.fd
+    }
+
+    /// Fault in every page of the memory region.
+    pub fn prefault(&self) -> io::Result<()> {
+        self.mmap.lock().unwrap().populate()
+    }
+
+    /// Fault in every page of the memory region and lock them into
+    /// memory.
+    pub fn lock(&self) -> io::Result<()> {
+        self.mmap.lock().unwrap().lock()
+    }
+
+    /// Read a byte from every page of the memory region, returning the
+    /// number of major page faults the calling thread took doing so.
+    pub fn warm_up(&self) -> io::Result<u64> {
+        let page_size = util::page_size();
+        let before = util::major_faults()?;
+
+        for offset in (0..self.len).step_by(page_size) {
+            // SAFETY: the offset lies within the region, and a read
+            // racing with the kernel is harmless since the value is
+            // discarded.
+            unsafe { ptr::read_volatile((self.addr.as_ptr() as *const u8).add(offset)) };
+        }
+
+        Ok(util::major_faults()?.saturating_sub(before))
+    }
+
+    /// See docs for [`super::Umem::release_unused`].
+    pub fn release_unused(&self, range: Range<usize>) -> io::Result<()> {
+        if range.start > range.end || range.end > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "range lies outside of the memory region",
+            ));
+        }
+
+        let range = util::page_aligned_within(range, util::page_size());
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.mmap.lock().unwrap().dont_need(range)
+    }
+
     /// Get a pointer to the start of the memory region.
     #[inline]
     pub fn as_ptr(&self) -> *mut libc::c_void {
@@ -114,6 +204,50 @@ impl UmemRegion {
         Data::new(unsafe { slice::from_raw_parts(data_ptr, desc.lengths.data) })
     }
 
+    /// The contents of the packet data segment of the frame described
+    /// by `desc`, up to its current length.
+    ///
+    /// # Safety
+    ///
+    /// See [`super::Umem::data`].
+    #[inline]
+    pub unsafe fn data_contents(&self, desc: &FrameDesc) -> &[u8] {
+        // SAFETY: see `frame`.
+        let data_ptr = unsafe { self.data_ptr(desc) };
+
+        unsafe { slice::from_raw_parts(data_ptr, desc.lengths.data) }
+    }
+
+    /// A mutable view of the contents of the packet data segment of
+    /// the frame described by `desc`, up to its current length.
+    ///
+    /// # Safety
+    ///
+    /// See [`super::Umem::data_mut`].
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn data_contents_mut(&self, desc: &FrameDesc) -> &mut [u8] {
+        // SAFETY: see `frame_mut`.
+        let data_ptr = unsafe { self.data_ptr(desc) };
+
+        unsafe { slice::from_raw_parts_mut(data_ptr, desc.lengths.data) }
+    }
+
+    /// See docs for [`super::Umem::metadata`].
+    #[inline]
+    pub unsafe fn metadata<T: XdpMetadata>(&self, desc: &FrameDesc) -> T {
+        // The XDP headroom is always larger than the metadata, so this
+        // stays within the frame.
+        assert!(T::LEN <= META_MAX_LEN, "metadata too long");
+
+        // SAFETY: see `frame`, and `T` is valid for any bit pattern.
+        unsafe {
+            let meta_ptr = self.data_ptr(desc).sub(T::LEN);
+
+            ptr::read_unaligned(meta_ptr as *const T)
+        }
+    }
+
     /// See docs for [`super::Umem::frame_mut`].
     #[inline]
     pub unsafe fn frame_mut<'a>(
@@ -127,11 +261,20 @@ impl UmemRegion {
         let headroom =
             unsafe { slice::from_raw_parts_mut(headroom_ptr, self.layout.frame_headroom) };
 
-        let data = unsafe { slice::from_raw_parts_mut(data_ptr, self.layout.mtu) };
+        // The headroom segment occupies the space in front of the data
+        // segment, unless it's empty.
+        let (offset, tail) = self.data_bounds(desc);
+        let head = if self.layout.frame_headroom == 0 {
+            offset
+        } else {
+            0
+        };
+
+        let data = unsafe { slice::from_raw_parts_mut(data_ptr.sub(head), head + tail) };
 
         (
             HeadroomMut::new(&mut desc.lengths.headroom, headroom),
-            DataMut::new(&mut desc.lengths.data, data),
+            DataMut::new(&mut desc.addr, &mut desc.lengths.data, head, data),
         )
     }
 
@@ -153,8 +296,34 @@ impl UmemRegion {
         // SAFETY: see `frame_mut`.
         let data_ptr = unsafe { self.data_ptr(desc) };
 
-        let data = unsafe { slice::from_raw_parts_mut(data_ptr, self.layout.mtu) };
+        // Leave room in front for the headroom segment, so that it
+        // stays within the frame if the data segment is pushed back.
+        let (offset, tail) = self.data_bounds(desc);
+        let head = offset.saturating_sub(self.layout.frame_headroom);
+
+        let data = unsafe { slice::from_raw_parts_mut(data_ptr.sub(head), head + tail) };
 
-        DataMut::new(&mut desc.lengths.data, data)
+        DataMut::new(&mut desc.addr, &mut desc.lengths.data, head, data)
+    }
+
+    /// The offset of the start of the data segment described by
+    /// `desc` within its frame, and the number of bytes from there to
+    /// the end of the frame.
+    #[inline]
+    fn data_bounds(&self, desc: &FrameDesc) -> (usize, usize) {
+        let frame_size = self.layout.frame_size();
+        let offset = desc.addr % frame_size;
+
+        (offset, frame_size - offset)
+    }
+
+    /// Prefetch the first cache line of the packet data segment of the
+    /// frame described by `desc`. Does nothing if `desc` lies outside
+    /// the memory region.
+    #[inline]
+    pub fn prefetch_data(&self, desc: &FrameDesc) {
+        if desc.addr < self.len {
+            util::prefetch_read((self.as_ptr() as *const u8).wrapping_add(desc.addr));
+        }
     }
 }
@@ -4,28 +4,50 @@ mod mem;
 use mem::UmemRegion;
 
 pub mod frame;
-use frame::{Data, DataMut, FrameDesc, Headroom, HeadroomMut};
+use frame::{Data, DataMut, Frame, FrameDesc, Headroom, HeadroomMut};
 
 mod fill_queue;
-pub use fill_queue::FillQueue;
+pub use fill_queue::{FillNeed, FillQueue};
 
 mod comp_queue;
 pub use comp_queue::CompQueue;
 
+pub mod pool;
+pub use pool::{FrameAllocator, FramePool, SocketFrames};
+
+pub mod set;
+pub use set::UmemSet;
+
+pub mod split;
+pub use split::SplitAllocator;
+
+pub mod unaligned;
+
+pub mod ledger;
+use ledger::FrameState;
+pub use ledger::Ledger;
+
 use libxdp_sys::xsk_umem;
 use log::error;
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
 use std::{
     borrow::Borrow,
     error::Error,
     fmt, io,
+    iter::FusedIterator,
     num::NonZeroU32,
+    ops::Range,
+    os::unix::prelude::{OwnedFd, RawFd},
     ptr::{self, NonNull},
     sync::{Arc, Mutex},
 };
 
 use crate::{
-    config::UmemConfig,
-    ring::{XskRingCons, XskRingProd},
+    config::{QueueSize, UmemConfig},
+    meta::XdpMetadata,
+    ring::{self, RingKind, RingMmap, RingSetupError, XskRingCons, XskRingProd},
+    util,
 };
 
 /// Wrapper around a pointer to some [`Umem`].
@@ -76,29 +98,64 @@ impl Drop for XskUmem {
 /// socket for the first time with this [`Umem`]. Hence we store them
 /// here so we don't prematurely clear up the rings' memory between
 /// creating the [`Umem`] and creating the socket.
+///
+/// `ptr` is [`None`] if the memory wasn't registered with the kernel
+/// through this [`Umem`], as is the case for a simulated [`Umem`] or
+/// one reassembled with [`Umem::from_parts`].
 #[derive(Debug)]
 struct UmemInner {
-    ptr: XskUmem,
+    ptr: Option<XskUmem>,
     saved_fq_and_cq: Option<(Box<XskRingProd>, Box<XskRingCons>)>,
+    // Fill and completion rings mapped by this crate rather than
+    // libxdp, see `FillQueue::from_raw` and `CompQueue::from_raw`.
+    rings: Vec<RingMmap>,
 }
 
 impl UmemInner {
-    fn new(ptr: XskUmem, saved_fq_and_cq: Option<(Box<XskRingProd>, Box<XskRingCons>)>) -> Self {
+    fn new(
+        ptr: Option<XskUmem>,
+        saved_fq_and_cq: Option<(Box<XskRingProd>, Box<XskRingCons>)>,
+    ) -> Self {
         Self {
             ptr,
             saved_fq_and_cq,
+            rings: Vec::new(),
         }
     }
 }
 
+/// The memory backing a [`Umem`], see [`Umem::new_with_backing`].
+#[derive(Debug)]
+pub enum UmemBacking {
+    /// An anonymous mapping, as used by [`Umem::new`].
+    Anonymous,
+    /// A new anonymous file created with `memfd_create`. Its
+    /// descriptor is available from [`Umem::backing_fd`], to be
+    /// passed to other processes.
+    Memfd,
+    /// The file referred to by the descriptor, mapped from offset
+    /// zero. It's extended to the length of the [`Umem`] if shorter,
+    /// but its contents are otherwise left as they are.
+    Fd(OwnedFd),
+}
+
 /// A region of virtual contiguous memory divided into equal-sized
 /// frames. It provides the underlying working memory for an AF_XDP
 /// [`Socket`](crate::socket::Socket).
+///
+/// The memory is a `MAP_SHARED` mapping, by default anonymous, so
+/// starts out zeroed and remains shared with any child processes
+/// forked after its creation. It can also be backed by a file to
+/// share it with unrelated processes, see
+/// [`new_with_backing`](Self::new_with_backing). When its pages are
+/// faulted in is controlled by the config's
+/// [`PrefaultMode`](crate::config::PrefaultMode).
 #[derive(Debug, Clone)]
 pub struct Umem {
     // `inner` must appear before `mem` to ensure correct drop order.
     inner: Arc<Mutex<UmemInner>>,
     mem: UmemRegion,
+    config: UmemConfig,
 }
 
 impl Umem {
@@ -110,20 +167,277 @@ impl Umem {
     /// getting errors as a result of this, check that the
     /// `HugePages_Total` setting is non-zero when you run `cat
     /// /proc/meminfo`.
-    pub fn new(
+    ///
+    /// `config` may be passed by reference, as may that of the other
+    /// constructors.
+    pub fn new<C>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+    ) -> Result<(Self, Vec<FrameDesc>), UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+    {
+        let mut descs = Vec::with_capacity(frame_count.get() as usize);
+
+        let umem = Self::new_with_descs(config, frame_count, use_huge_pages, &mut descs)?;
+
+        Ok((umem, descs))
+    }
+
+    /// Same as [`new`](Self::new) but the frames are returned as
+    /// owned [`Frame`]s, for use with the safe queue methods such as
+    /// [`FillQueue::produce_frames`] and [`RxQueue::consume_frames`].
+    ///
+    /// [`RxQueue::consume_frames`]: crate::RxQueue::consume_frames
+    pub fn new_owned<C>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+    ) -> Result<(Self, Vec<Frame>), UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+    {
+        let (umem, descs) = Self::new(config, frame_count, use_huge_pages)?;
+
+        let frames = descs
+            .into_iter()
+            // SAFETY: the descriptors were just created for this
+            // UMEM and nothing else holds them.
+            .map(|desc| unsafe { Frame::from_desc(desc, &umem) })
+            .collect();
+
+        Ok((umem, frames))
+    }
+
+    /// Same as [`new`](Self::new) but rather than allocating a new
+    /// [`Vec`] for the frame descriptors, they are appended to
+    /// `descs`.
+    ///
+    /// Useful if the descriptors should live in memory managed by the
+    /// caller, for example a pre-allocated [`Vec`], one using a custom
+    /// allocator or some other arena-backed collection.
+    pub fn new_with_descs<C, D>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+        descs: &mut D,
+    ) -> Result<Self, UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+        D: Extend<FrameDesc>,
+    {
+        let umem = Self::create(
+            *config.borrow(),
+            frame_count,
+            use_huge_pages,
+            UmemBacking::Anonymous,
+        )?;
+
+        descs.extend(umem.desc_iter());
+
+        Ok(umem)
+    }
+
+    /// Same as [`new`](Self::new) but no frame descriptors are
+    /// created. They can be generated as needed with
+    /// [`desc_iter`](Self::desc_iter) instead.
+    ///
+    /// For a `Umem` of tens of millions of frames, most of which are
+    /// left to a [`FramePool`] or handed out a batch at a time, this
+    /// avoids allocating and filling a [`Vec`] of them all up front.
+    pub fn new_without_descs<C>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+    ) -> Result<Self, UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+    {
+        Self::create(
+            *config.borrow(),
+            frame_count,
+            use_huge_pages,
+            UmemBacking::Anonymous,
+        )
+    }
+
+    /// Same as [`new`](Self::new) but the memory is backed by
+    /// `backing`, allowing it to be shared between processes.
+    ///
+    /// For example, one process might create a `Umem` backed by a
+    /// [`Memfd`](UmemBacking::Memfd) and bind sockets with it, then
+    /// pass [`backing_fd`](Self::backing_fd) to another process over
+    /// a unix socket. That process maps the same memory with
+    /// [`Fd`](UmemBacking::Fd) and the same config, and the two can
+    /// hand frames to one another by descriptor, a common way of
+    /// splitting packet I/O and processing across processes. The
+    /// processes must agree between themselves which of them owns
+    /// each frame at any point.
+    ///
+    /// With a [`Memfd`](UmemBacking::Memfd), `use_huge_pages` creates
+    /// it with `MFD_HUGETLB`. With an [`Fd`](UmemBacking::Fd), it's
+    /// ignored and whether huge pages are used depends on the file,
+    /// e.g. whether it lives on a `hugetlbfs` mount.
+    pub fn new_with_backing<C>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+        backing: UmemBacking,
+    ) -> Result<(Self, Vec<FrameDesc>), UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+    {
+        let umem = Self::create(*config.borrow(), frame_count, use_huge_pages, backing)?;
+        let descs = umem.desc_iter().collect();
+
+        Ok((umem, descs))
+    }
+
+    /// Reassemble a `Umem` from the file backing the memory of a UMEM
+    /// registered elsewhere, for example by an earlier run of this
+    /// process which kept it and its sockets in systemd's file
+    /// descriptor store, see [`systemd`](crate::systemd).
+    ///
+    /// The memory is mapped but not registered again, so the returned
+    /// `Umem` can only be used with sockets reassembled with
+    /// [`Socket::from_parts`], whose fill and completion queues are
+    /// rebuilt with [`FillQueue::from_raw`] and
+    /// [`CompQueue::from_raw`]. Frame contents are left as they are.
+    /// Which frames are still with the kernel is up to the caller to
+    /// work out, for example from the rings' indices.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be the file backing the memory registered as the UMEM
+    /// of those sockets, and `config` and `frame_count` must be the
+    /// same as when it was created.
+    ///
+    /// [`Socket::from_parts`]: crate::Socket::from_parts
+    pub unsafe fn from_parts<C>(
+        config: C,
+        frame_count: NonZeroU32,
+        fd: OwnedFd,
+    ) -> Result<(Self, Vec<FrameDesc>), UmemCreateError>
+    where
+        C: Borrow<UmemConfig>,
+    {
+        let config = *config.borrow();
+        let frame_layout = config.into();
+
+        let mem = UmemRegion::new(
+            umem_len(&config, frame_count)?,
+            frame_layout,
+            false,
+            config.prefault_mode(),
+            UmemBacking::Fd(fd),
+        )
+        .map_err(|e| UmemCreateError {
+            kind: UmemCreateErrorKind::MapBacking,
+            err: e,
+        })?;
+
+        // Which frames are with the kernel isn't known, so their
+        // ownership can't be tracked.
+        #[cfg(feature = "debug-tracking")]
+        let mem = mem.without_ledger();
+
+        lock_if_strict(&mem, &config)?;
+
+        let umem = Umem {
+            inner: Arc::new(Mutex::new(UmemInner::new(None, None))),
+            mem,
+            config,
+        };
+
+        Ok((umem, frame_descs(frame_count, frame_layout).collect()))
+    }
+
+    fn create(
         config: UmemConfig,
         frame_count: NonZeroU32,
         use_huge_pages: bool,
-    ) -> Result<(Self, Vec<FrameDesc>), UmemCreateError> {
+        backing: UmemBacking,
+    ) -> Result<Self, UmemCreateError> {
         let frame_layout = config.into();
 
-        let mem = UmemRegion::new(frame_count, frame_layout, use_huge_pages).map_err(|e| {
-            UmemCreateError {
-                reason: "failed to create mmap'd UMEM region",
-                err: e,
-            }
+        let mem = UmemRegion::new(
+            umem_len(&config, frame_count)?,
+            frame_layout,
+            use_huge_pages,
+            config.prefault_mode(),
+            backing,
+        )
+        .map_err(|e| UmemCreateError {
+            kind: UmemCreateErrorKind::Mmap,
+            err: e,
         })?;
 
+        // Frames needn't lie on a grid in unaligned chunk mode, so
+        // there's no fixed set of them to track.
+        #[cfg(feature = "debug-tracking")]
+        let mem = if config.unaligned_chunks() {
+            mem.without_ledger()
+        } else {
+            mem
+        };
+
+        lock_if_strict(&mem, &config)?;
+
+        Self::register(mem, config)
+    }
+
+    /// Same as [`new`](Self::new) but the frame descriptors are
+    /// allocated using `alloc`.
+    ///
+    /// Requires the `allocator_api` feature, and a nightly compiler.
+    #[cfg(feature = "allocator_api")]
+    #[allow(clippy::type_complexity)]
+    pub fn new_in<C: Borrow<UmemConfig>, A: Allocator>(
+        config: C,
+        frame_count: NonZeroU32,
+        use_huge_pages: bool,
+        alloc: A,
+    ) -> Result<(Self, Vec<FrameDesc, A>), UmemCreateError> {
+        let mut descs = Vec::with_capacity_in(frame_count.get() as usize, alloc);
+
+        let umem = Self::new_with_descs(config, frame_count, use_huge_pages, &mut descs)?;
+
+        Ok((umem, descs))
+    }
+
+    /// Register a second UMEM with the kernel over this `Umem`'s
+    /// memory region, whose [`FillQueue`] and [`CompQueue`] have the
+    /// given sizes rather than those set in its original config.
+    ///
+    /// The returned `Umem` shares both memory and frame layout with
+    /// this one, so frame descriptors are valid for either. It is not
+    /// a shared UMEM in the kernel's sense however: the kernel sees
+    /// two separate UMEMs which happen to cover the same memory, so
+    /// sockets bound with one can't share an `(if_name, queue_id)`
+    /// pair with sockets bound with the other.
+    ///
+    /// A frame must still only be in use by one queue at a time,
+    /// whichever `Umem` that queue belongs to.
+    pub fn reregister_with_queue_sizes(
+        &self,
+        fill_queue_size: QueueSize,
+        comp_queue_size: QueueSize,
+    ) -> Result<Self, UmemCreateError> {
+        let config = self
+            .config
+            .to_builder()
+            .fill_queue_size(fill_queue_size)
+            .comp_queue_size(comp_queue_size)
+            .build()
+            .expect("frame size and headroom taken from a valid config");
+
+        Self::register(self.mem.clone(), config)
+    }
+
+    /// Register `mem` with the kernel as a UMEM with the given
+    /// config.
+    fn register(mem: UmemRegion, config: UmemConfig) -> Result<Self, UmemCreateError> {
         let mut umem_ptr = ptr::null_mut();
         let mut fq: Box<XskRingProd> = Box::default();
         let mut cq: Box<XskRingCons> = Box::default();
@@ -141,7 +455,7 @@ impl Umem {
 
         if err != 0 {
             return Err(UmemCreateError {
-                reason: "non-zero error code returned when creating UMEM",
+                kind: UmemCreateErrorKind::Create,
                 err: io::Error::from_raw_os_error(-err),
             });
         }
@@ -155,7 +469,7 @@ impl Umem {
             }
             None => {
                 return Err(UmemCreateError {
-                    reason: "UMEM is null",
+                    kind: UmemCreateErrorKind::UmemNull,
                     err: io::Error::from_raw_os_error(-err),
                 });
             }
@@ -163,38 +477,182 @@ impl Umem {
 
         if fq.is_ring_null() {
             return Err(UmemCreateError {
-                reason: "fill queue ring is null",
-                err: io::Error::from_raw_os_error(-err),
+                kind: UmemCreateErrorKind::RingNull(RingKind::Fill),
+                err: RingSetupError::new(RingKind::Fill, RingSetupError::VERSION_HINT).into(),
             });
         };
 
         if cq.is_ring_null() {
             return Err(UmemCreateError {
-                reason: "comp queue ring is null",
-                err: io::Error::from_raw_os_error(-err),
+                kind: UmemCreateErrorKind::RingNull(RingKind::Completion),
+                err: RingSetupError::new(RingKind::Completion, RingSetupError::VERSION_HINT).into(),
             });
         }
 
-        let inner = UmemInner::new(umem_ptr, Some((fq, cq)));
+        if config.lock_memory() {
+            for span in [fq.info::<u64>().span(), cq.info::<u64>().span()] {
+                util::mlock(span).map_err(|e| UmemCreateError {
+                    kind: UmemCreateErrorKind::LockRings,
+                    err: e,
+                })?;
+            }
+        }
 
-        let frame_count = frame_count.get() as usize;
+        let inner = UmemInner::new(Some(umem_ptr), Some((fq, cq)));
 
-        let mut frame_descs: Vec<FrameDesc> = Vec::with_capacity(frame_count);
+        Ok(Umem {
+            inner: Arc::new(Mutex::new(inner)),
+            mem,
+            config,
+        })
+    }
 
-        for i in 0..frame_count {
-            let addr = (i * frame_layout.frame_size())
-                + frame_layout.xdp_headroom
-                + frame_layout.frame_headroom;
+    /// Create a `Umem` whose memory is not registered with the
+    /// kernel. Used by the simulated backend, which plays the part of
+    /// the kernel itself.
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn new_unregistered(
+        config: UmemConfig,
+        frame_count: NonZeroU32,
+    ) -> Result<(Self, Vec<FrameDesc>), UmemCreateError> {
+        let frame_layout = config.into();
 
-            frame_descs.push(FrameDesc::new(addr));
-        }
+        let len = umem_len(&config, frame_count)?;
+
+        let mem = UmemRegion::new(
+            len,
+            frame_layout,
+            false,
+            config.prefault_mode(),
+            UmemBacking::Anonymous,
+        )
+        .map_err(|e| UmemCreateError {
+            kind: UmemCreateErrorKind::Mmap,
+            err: e,
+        })?;
+
+        // Frames needn't lie on a grid in unaligned chunk mode, so
+        // there's no fixed set of them to track.
+        #[cfg(feature = "debug-tracking")]
+        let mem = if config.unaligned_chunks() {
+            mem.without_ledger()
+        } else {
+            mem
+        };
 
         let umem = Umem {
-            inner: Arc::new(Mutex::new(inner)),
+            inner: Arc::new(Mutex::new(UmemInner::new(None, None))),
             mem,
+            config,
         };
 
-        Ok((umem, frame_descs))
+        Ok((umem, frame_descs(frame_count, frame_layout).collect()))
+    }
+
+    /// The config the `Umem` was created with.
+    #[inline]
+    pub fn config(&self) -> &UmemConfig {
+        &self.config
+    }
+
+    /// The number of frames the `Umem`'s memory is divided into.
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.mem.len() / FrameLayout::from(self.config).frame_size()
+    }
+
+    /// The descriptors of all of the `Umem`'s frames, in order of
+    /// address, as returned by [`new`](Self::new).
+    ///
+    /// Each call starts from the first frame again, so it's up to the
+    /// caller not to hand out a frame that's already in use.
+    #[inline]
+    pub fn desc_iter(&self) -> DescIter {
+        DescIter {
+            frames: 0..self.frame_count(),
+            frame_layout: self.config.into(),
+        }
+    }
+
+    /// The descriptor of the file backing the `Umem`'s memory, or
+    /// [`None`] if it's an anonymous mapping. Remains open for as
+    /// long as the `Umem`, or any [`Frame`] or queue using its
+    /// memory, does.
+    #[inline]
+    pub fn backing_fd(&self) -> Option<RawFd> {
+        self.mem.fd()
+    }
+
+    /// Fault in every page of the `Umem`'s memory region, without
+    /// modifying its contents.
+    ///
+    /// Intended for use with [`PrefaultMode::Lazy`], so the cost of
+    /// faulting in a large `Umem` can be paid at a time of the user's
+    /// choosing rather than on creation or when the first packets
+    /// arrive. Uses `MADV_POPULATE_WRITE`, so requires linux 5.14 or
+    /// later.
+    ///
+    /// [`PrefaultMode::Lazy`]: crate::config::PrefaultMode::Lazy
+    pub fn prefault(&self) -> io::Result<()> {
+        self.mem.prefault()
+    }
+
+    /// Read every page of the `Umem`'s memory region, failing if any
+    /// of them had to be read back in from disk, that is if a major
+    /// page fault occurred.
+    ///
+    /// Intended as a check before traffic starts that the memory is
+    /// resident, typically with
+    /// [`lock_memory`](crate::config::UmemConfigBuilder::lock_memory)
+    /// set so it stays that way. Any page not yet faulted in is
+    /// faulted in, though pages never touched since creation are only
+    /// minor faults and so don't fail the check.
+    pub fn warm_up(&self) -> io::Result<()> {
+        let faults = self.mem.warm_up()?;
+
+        if faults > 0 {
+            return Err(io::Error::other(format!(
+                "{} major page faults while warming up UMEM",
+                faults
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Drop the pages covering `range`, a range of byte offsets into
+    /// the `Umem`'s memory region, from this process's page tables
+    /// with `MADV_DONTNEED`. They're faulted back in on next access.
+    ///
+    /// Useful for returning the cold parts of a `Umem` sized for peak
+    /// traffic during idle periods. The range is shrunk to the pages
+    /// lying entirely inside it, so frames either side of it are left
+    /// untouched.
+    ///
+    /// Since the memory is a shared mapping, its contents are
+    /// preserved. Note that this also means the pages stay allocated
+    /// as shared memory, and the kernel keeps every page of a
+    /// registered `Umem` pinned regardless. So while this reduces the
+    /// process's resident set and page table overhead, the memory
+    /// itself is only returned to the OS once the `Umem` is dropped.
+    /// With huge pages the range must be aligned to the huge page
+    /// size.
+    pub fn release_unused(&self, range: Range<usize>) -> io::Result<()> {
+        self.mem.release_unused(range)
+    }
+
+    /// A pointer to the start of the `Umem`'s memory region.
+    #[cfg(any(test, feature = "testing"))]
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *mut libc::c_void {
+        self.mem.as_ptr()
+    }
+
+    /// The length of the `Umem`'s memory region in bytes.
+    #[cfg(any(test, feature = "testing"))]
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.mem.len()
     }
 
     /// The headroom and packet data segments of the `Umem` frame
@@ -224,6 +682,8 @@ impl Umem {
         // `desc` is valid, since it describes a frame in this UMEM.
         // b. This access is sound since there are no mutable
         // references to the headroom and data segments.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.frame(desc) }
     }
 
@@ -236,6 +696,8 @@ impl Umem {
     #[inline]
     pub unsafe fn headroom(&self, desc: &FrameDesc) -> Headroom {
         // SAFETY: see `frame`.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.headroom(desc) }
     }
 
@@ -248,9 +710,31 @@ impl Umem {
     #[inline]
     pub unsafe fn data(&self, desc: &FrameDesc) -> Data {
         // SAFETY: see `frame`.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.data(desc) }
     }
 
+    /// The metadata written by the XDP program immediately before the
+    /// packet data of the `Umem` frame pointed at by `desc`. See the
+    /// [`meta`](crate::meta) module.
+    ///
+    /// If the program didn't write any metadata of this layout then
+    /// the contents are unspecified, though still valid for `T`. Note
+    /// that the metadata shares space with the end of the frame's
+    /// headroom.
+    ///
+    /// # Safety
+    ///
+    /// See [`frame`](Self::frame).
+    #[inline]
+    pub unsafe fn metadata<T: XdpMetadata>(&self, desc: &FrameDesc) -> T {
+        // SAFETY: see `frame`.
+        self.track_access(desc.addr);
+
+        unsafe { self.mem.metadata(desc) }
+    }
+
     /// The headroom and packet data segments of the `Umem` frame
     /// pointed at by `desc`. Contents are writeable.
     ///
@@ -281,6 +765,8 @@ impl Umem {
         // `desc` is valid, since it describes a frame in this UMEM.
         // b. This access is sound since there are no other mutable or
         // immutable references to the headroom and data segments.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.frame_mut(desc) }
     }
 
@@ -293,6 +779,8 @@ impl Umem {
     #[inline]
     pub unsafe fn headroom_mut<'a>(&'a self, desc: &'a mut FrameDesc) -> HeadroomMut<'a> {
         // SAFETY: see `frame_mut`.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.headroom_mut(desc) }
     }
 
@@ -305,9 +793,196 @@ impl Umem {
     #[inline]
     pub unsafe fn data_mut<'a>(&'a self, desc: &'a mut FrameDesc) -> DataMut<'a> {
         // SAFETY: see `frame_mut`.
+        self.track_access(desc.addr);
+
         unsafe { self.mem.data_mut(desc) }
     }
 
+    /// A descriptor for the frame starting at `addr`, for handing to
+    /// the [`FillQueue`] in unaligned chunk mode. The fill queue puts
+    /// a descriptor's address on the ring as is, and in unaligned mode
+    /// the kernel takes it as the start of the frame to receive into,
+    /// rather than rounding it down.
+    ///
+    /// Returns `None` if the `Umem` is in aligned mode, or if a whole
+    /// frame doesn't fit between `addr` and the end of the `Umem`.
+    /// Note that unless the `Umem` is backed by huge pages the kernel
+    /// also rejects frames which cross a page boundary.
+    ///
+    /// The descriptor is only meant for filling: its address is the
+    /// start of the frame, so there's no room in front of it for the
+    /// headroom segment.
+    pub fn chunk_desc(&self, addr: usize) -> Option<FrameDesc> {
+        let frame_size = self.config.frame_size().get() as usize;
+
+        let fits = matches!(addr.checked_add(frame_size), Some(end) if end <= self.mem.len());
+
+        if self.config.unaligned_chunks() && fits {
+            Some(FrameDesc::new(addr))
+        } else {
+            None
+        }
+    }
+
+    /// Whether `desc`'s address points into one of this `Umem`'s
+    /// frames, with room in front of it for the headroom segment. Its
+    /// lengths aren't checked, see [`validate`](Self::validate) for
+    /// that.
+    ///
+    /// In unaligned chunk mode a frame may start at any address, so
+    /// only the bounds of the `Umem` as a whole are checked.
+    #[inline]
+    pub fn contains(&self, desc: &FrameDesc) -> bool {
+        desc.addr < self.mem.len()
+            && self.frame_offset(desc.addr).0 >= self.config.frame_headroom() as usize
+    }
+
+    /// Check that `desc` describes a frame of this `Umem`: that its
+    /// address is [contained](Self::contains) in it, and that its
+    /// headroom and packet data lengths fit within the frame.
+    ///
+    /// A descriptor which passes can be given to the accessors such
+    /// as [`data`](Self::data) without them reaching outside of its
+    /// frame, leaving only the question of who owns the frame to the
+    /// caller.
+    pub fn validate(&self, desc: &FrameDesc) -> Result<(), DescError> {
+        let layout = FrameLayout::from(self.config);
+        let addr = desc.addr;
+
+        if addr >= self.mem.len() {
+            return Err(DescError::OutOfBounds {
+                addr,
+                umem_len: self.mem.len(),
+            });
+        }
+
+        let (offset, room) = self.frame_offset(addr);
+
+        if offset < layout.frame_headroom {
+            return Err(DescError::NoHeadroom { addr, offset });
+        }
+
+        if desc.lengths.data > room {
+            return Err(DescError::DataOverrun {
+                addr,
+                len: desc.lengths.data,
+                room,
+            });
+        }
+
+        if desc.lengths.headroom > layout.frame_headroom {
+            return Err(DescError::HeadroomOverrun {
+                len: desc.lengths.headroom,
+                frame_headroom: layout.frame_headroom,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The offset of the in-bounds `addr` from the start of its
+    /// frame, and the room left in the frame after it. In unaligned
+    /// chunk mode there's no frame grid, so this is measured against
+    /// the whole `Umem` instead.
+    #[inline]
+    fn frame_offset(&self, addr: usize) -> (usize, usize) {
+        if self.config.unaligned_chunks() {
+            (addr, self.mem.len() - addr)
+        } else {
+            let frame_size = FrameLayout::from(self.config).frame_size();
+            let offset = addr % frame_size;
+
+            (offset, frame_size - offset)
+        }
+    }
+
+    /// Check that the frame at `addr` is still owned by userspace. A
+    /// no-op without the `debug-tracking` feature.
+    #[inline]
+    #[cfg_attr(not(feature = "debug-tracking"), allow(unused_variables))]
+    fn track_access(&self, addr: usize) {
+        #[cfg(feature = "debug-tracking")]
+        if let Some(Err(e)) = self.ledger().map(|l| l.check_owned(addr)) {
+            panic!("frame accessed after submission: {}", e);
+        }
+    }
+
+    /// Record the frame at `addr` being submitted to a ring. A no-op
+    /// without the `debug-tracking` feature.
+    #[inline]
+    #[cfg_attr(not(feature = "debug-tracking"), allow(unused_variables))]
+    pub(crate) fn track_submit(&self, addr: usize, to: FrameState) {
+        #[cfg(feature = "debug-tracking")]
+        if let Some(Err(e)) = self.ledger().map(|l| l.submit(addr, to)) {
+            panic!("frame submitted twice: {}", e);
+        }
+    }
+
+    /// Record the frame at `addr` being read back off the ring it was
+    /// submitted to. A no-op without the `debug-tracking` feature.
+    #[inline]
+    #[cfg_attr(not(feature = "debug-tracking"), allow(unused_variables))]
+    pub(crate) fn track_complete(&self, addr: usize, from: FrameState) {
+        #[cfg(feature = "debug-tracking")]
+        if let Some(Err(e)) = self.ledger().map(|l| l.complete(addr, from)) {
+            panic!("frame handed back from a ring it wasn't on: {}", e);
+        }
+    }
+
+    /// The ledger of which of this `Umem`'s frames are owned by the
+    /// user and which are on a ring, kept up to date by its queues.
+    /// Shared with any `Umem` created from it with
+    /// [`reregister_with_queue_sizes`](Self::reregister_with_queue_sizes).
+    ///
+    /// [`None`] for a `Umem` reassembled with
+    /// [`from_parts`](Self::from_parts), since which frames the
+    /// kernel has isn't known, and for one in unaligned chunk mode.
+    ///
+    /// Requires the `debug-tracking` feature.
+    #[cfg(feature = "debug-tracking")]
+    #[inline]
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.mem.ledger()
+    }
+
+    /// Issue a software prefetch for the first cache line of each
+    /// frame's packet data segment, so that it is hopefully in cache
+    /// by the time it's read. Best called straight after
+    /// [`RxQueue::consume`] and before iterating over the received
+    /// frames.
+    ///
+    /// This is only a hint, and is a no-op on targets without
+    /// prefetch support. Descriptors not belonging to this `Umem` are
+    /// ignored or, at worst, prefetch an unrelated part of it.
+    ///
+    /// [`RxQueue::consume`]: crate::RxQueue::consume
+    #[inline]
+    pub fn prefetch_data(&self, descs: &[FrameDesc]) {
+        for desc in descs {
+            self.mem.prefetch_data(desc);
+        }
+    }
+
+    /// Whether `other` shares this `Umem`'s memory, i.e. is the same
+    /// `Umem` or one created from it with
+    /// [`reregister_with_queue_sizes`](Self::reregister_with_queue_sizes).
+    #[inline]
+    pub(crate) fn shares_mem(&self, other: &Umem) -> bool {
+        self.mem.as_ptr() == other.mem.as_ptr()
+    }
+
+    /// Whether `other` is this `Umem` or a clone of it, so registered
+    /// with the kernel as the same UMEM.
+    #[inline]
+    pub(crate) fn is_same(&self, other: &Umem) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Keep `mmap` mapped for as long as the `Umem`.
+    pub(crate) fn keep_ring(&self, mmap: RingMmap) {
+        self.inner.lock().unwrap().rings.push(mmap);
+    }
+
     /// Intended to be called on socket creation, this passes the
     /// create function a pointer to the UMEM and any saved fill queue
     /// or completion queue.
@@ -315,6 +990,9 @@ impl Umem {
     /// Regarding the saved queues, this is a byproduct of how the
     /// UMEM is created in the C code and we save them here to avoid
     /// leaking memory.
+    ///
+    /// The pointer passed will be null if the UMEM was never
+    /// registered with the kernel.
     #[inline]
     pub(crate) fn with_ptr_and_saved_queues<F, T>(&self, mut f: F) -> T
     where
@@ -322,20 +1000,247 @@ impl Umem {
     {
         let mut inner = self.inner.lock().unwrap();
 
-        f(inner.ptr.as_mut_ptr(), &mut inner.saved_fq_and_cq)
+        let ptr = inner
+            .ptr
+            .as_ref()
+            .map_or(ptr::null_mut(), |ptr| ptr.as_mut_ptr());
+
+        f(ptr, &mut inner.saved_fq_and_cq)
+    }
+}
+
+/// Lock `mem` into memory if `config` asks for it.
+fn lock_if_strict(mem: &UmemRegion, config: &UmemConfig) -> Result<(), UmemCreateError> {
+    if !config.lock_memory() {
+        return Ok(());
+    }
+
+    mem.lock().map_err(|e| UmemCreateError {
+        kind: UmemCreateErrorKind::LockMemory,
+        err: e,
+    })
+}
+
+/// The length of the memory region of a UMEM with `frame_count`
+/// frames, failing if it can't be addressed on this target.
+fn umem_len(config: &UmemConfig, frame_count: NonZeroU32) -> Result<usize, UmemCreateError> {
+    config.umem_len(frame_count).map_err(|e| UmemCreateError {
+        kind: UmemCreateErrorKind::TooLarge,
+        err: io::Error::new(io::ErrorKind::InvalidInput, e),
+    })
+}
+
+/// Generates a descriptor for each of the `frame_count` frames in a
+/// UMEM laid out as per `frame_layout`.
+///
+/// The UMEM's length must have been checked with [`umem_len`], so
+/// that no address overflows.
+fn frame_descs(frame_count: NonZeroU32, frame_layout: FrameLayout) -> DescIter {
+    DescIter {
+        frames: 0..frame_count.get() as usize,
+        frame_layout,
+    }
+}
+
+/// The address of the packet data segment of the `idx`th frame.
+fn frame_addr(idx: usize, frame_layout: FrameLayout) -> usize {
+    (idx * frame_layout.frame_size()) + frame_layout.xdp_headroom + frame_layout.frame_headroom
+}
+
+/// An iterator over the descriptors of a [`Umem`]'s frames, created
+/// with [`Umem::desc_iter`].
+///
+/// Descriptors are generated as the iterator is advanced rather than
+/// stored, and it can be split with [`nth`](Iterator::nth) or
+/// [`skip`](Iterator::skip) without generating those passed over.
+#[derive(Debug, Clone)]
+pub struct DescIter {
+    frames: Range<usize>,
+    frame_layout: FrameLayout,
+}
+
+impl Iterator for DescIter {
+    type Item = FrameDesc;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.frames
+            .next()
+            .map(|idx| FrameDesc::new(frame_addr(idx, self.frame_layout)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+
+    #[inline]
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.frames
+            .nth(n)
+            .map(|idx| FrameDesc::new(frame_addr(idx, self.frame_layout)))
+    }
+}
+
+impl DoubleEndedIterator for DescIter {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.frames
+            .next_back()
+            .map(|idx| FrameDesc::new(frame_addr(idx, self.frame_layout)))
+    }
+}
+
+impl ExactSizeIterator for DescIter {}
+
+impl FusedIterator for DescIter {}
+
+/// Why a [`FrameDesc`] doesn't describe a frame of some [`Umem`], as
+/// returned by [`Umem::validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescError {
+    /// The address lies past the end of the `Umem`.
+    OutOfBounds {
+        /// The descriptor's address.
+        addr: usize,
+        /// The length of the `Umem`'s memory.
+        umem_len: usize,
+    },
+    /// The address lies too close to the start of its frame for the
+    /// headroom segment to fit in front of it.
+    NoHeadroom {
+        /// The descriptor's address.
+        addr: usize,
+        /// The address's offset from the start of its frame.
+        offset: usize,
+    },
+    /// The packet data runs past the end of the frame.
+    DataOverrun {
+        /// The descriptor's address.
+        addr: usize,
+        /// The packet data length.
+        len: usize,
+        /// The number of bytes from the address to the end of its
+        /// frame.
+        room: usize,
+    },
+    /// The headroom length exceeds the
+    /// [`frame_headroom`](UmemConfig::frame_headroom).
+    HeadroomOverrun {
+        /// The headroom length.
+        len: usize,
+        /// The configured frame headroom.
+        frame_headroom: usize,
+    },
+}
+
+impl fmt::Display for DescError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DescError::OutOfBounds { addr, umem_len } => write!(
+                f,
+                "address {} lies past the end of the UMEM of length {}",
+                addr, umem_len
+            ),
+            DescError::NoHeadroom { addr, offset } => write!(
+                f,
+                "address {} lies {} bytes into its frame, leaving no room for the headroom",
+                addr, offset
+            ),
+            DescError::DataOverrun { addr, len, room } => write!(
+                f,
+                "data length {} at address {} exceeds the {} bytes left in the frame",
+                len, addr, room
+            ),
+            DescError::HeadroomOverrun {
+                len,
+                frame_headroom,
+            } => write!(
+                f,
+                "headroom length {} exceeds the frame headroom {}",
+                len, frame_headroom
+            ),
+        }
+    }
+}
+
+impl Error for DescError {}
+
+/// What went wrong when creating a [`Umem`], see
+/// [`UmemCreateError::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UmemCreateErrorKind {
+    /// The UMEM is too large to address on this target.
+    TooLarge,
+    /// The UMEM's memory couldn't be mapped.
+    Mmap,
+    /// The file backing the UMEM couldn't be mapped.
+    MapBacking,
+    /// The UMEM's memory couldn't be locked.
+    LockMemory,
+    /// libxdp failed to register the UMEM. See
+    /// [`UmemCreateError::errno`], for example `EPERM` if lacking the
+    /// required capabilities, or `ENOMEM` if the locked memory limit
+    /// was hit.
+    Create,
+    /// libxdp reported success but returned no UMEM.
+    UmemNull,
+    /// libxdp reported success but didn't set up the fill or
+    /// completion ring. See [`UmemCreateError::ring_setup_error`].
+    RingNull(RingKind),
+    /// The fill and completion rings couldn't be locked into memory.
+    LockRings,
+}
+
+impl fmt::Display for UmemCreateErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UmemCreateErrorKind::TooLarge => write!(f, "UMEM too large to address on this target"),
+            UmemCreateErrorKind::Mmap => write!(f, "failed to create mmap'd UMEM region"),
+            UmemCreateErrorKind::MapBacking => write!(f, "failed to map UMEM backing file"),
+            UmemCreateErrorKind::LockMemory => write!(f, "failed to lock UMEM memory"),
+            UmemCreateErrorKind::Create => write!(f, "failed to create UMEM"),
+            UmemCreateErrorKind::UmemNull => write!(f, "UMEM is null"),
+            UmemCreateErrorKind::RingNull(ring) => write!(f, "{} ring is null", ring),
+            UmemCreateErrorKind::LockRings => {
+                write!(f, "failed to lock fill and completion rings")
+            }
+        }
     }
 }
 
 /// Error detailing why [`Umem`] creation failed.
 #[derive(Debug)]
 pub struct UmemCreateError {
-    reason: &'static str,
+    kind: UmemCreateErrorKind,
     err: io::Error,
 }
 
+impl UmemCreateError {
+    /// What went wrong.
+    pub fn kind(&self) -> UmemCreateErrorKind {
+        self.kind
+    }
+
+    /// The OS error code behind the failure, if there is one.
+    pub fn errno(&self) -> Option<i32> {
+        self.err.raw_os_error()
+    }
+
+    /// The details of the ring which wasn't set up, if that's why
+    /// creation failed.
+    pub fn ring_setup_error(&self) -> Option<&RingSetupError> {
+        ring::ring_setup_error(&self.err)
+    }
+
+    pub(crate) fn into_inner(self) -> io::Error {
+        self.err
+    }
+}
+
 impl fmt::Display for UmemCreateError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.reason)
+        write!(f, "{}", self.kind)
     }
 }
 
@@ -373,7 +1278,9 @@ impl From<UmemConfig> for FrameLayout {
 mod tests {
     use std::convert::TryInto;
 
-    use crate::config::{UmemConfigBuilder, XDP_UMEM_MIN_CHUNK_SIZE};
+    use std::os::unix::prelude::{AsRawFd, FromRawFd};
+
+    use crate::config::{PrefaultMode, UmemConfigBuilder, XDP_UMEM_MIN_CHUNK_SIZE};
 
     use super::*;
 
@@ -389,4 +1296,218 @@ mod tests {
 
         assert_eq!(config.frame_size().get() as usize, layout.frame_size())
     }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn frame_addrs_beyond_4gib_do_not_wrap() {
+        let config = UmemConfigBuilder::new()
+            .frame_size(4096.try_into().unwrap())
+            .build()
+            .unwrap();
+
+        let layout: FrameLayout = config.into();
+        let headroom = layout.xdp_headroom + layout.frame_headroom;
+
+        assert_eq!(frame_addr(1 << 20, layout), (1 << 32) + headroom);
+
+        // The last frame of the largest possible UMEM.
+        let last = u32::MAX as usize - 1;
+
+        assert_eq!(
+            frame_addr(last, layout) as u64,
+            last as u64 * 4096 + headroom as u64
+        );
+
+        let len = umem_len(&config, u32::MAX.try_into().unwrap()).unwrap();
+
+        assert_eq!(frame_addr(last, layout) + layout.mtu, len);
+    }
+
+    #[test]
+    fn prefetching_any_desc_is_harmless() {
+        let (umem, mut descs) =
+            Umem::new_unregistered(UmemConfig::default(), 4.try_into().unwrap()).unwrap();
+
+        descs.push(FrameDesc::new(usize::MAX));
+
+        umem.prefetch_data(&descs);
+    }
+
+    #[test]
+    fn backing_file_is_sized_to_fit_and_never_shrunk() {
+        fn file_len(fd: RawFd) -> i64 {
+            let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+            assert_eq!(unsafe { libc::fstat(fd, &mut stat) }, 0);
+            stat.st_size
+        }
+
+        let layout: FrameLayout = UmemConfig::default().into();
+        let len = 4 * layout.frame_size();
+
+        let region = |len, backing| {
+            UmemRegion::new(len, layout, false, PrefaultMode::default(), backing).unwrap()
+        };
+
+        assert_eq!(region(len, UmemBacking::Anonymous).fd(), None);
+
+        let memfd = region(len, UmemBacking::Memfd);
+        let fd = memfd.fd().unwrap();
+
+        assert_eq!(file_len(fd), len as i64);
+
+        // Map a smaller region over the same file, as another process
+        // might.
+        let dup = unsafe { OwnedFd::from_raw_fd(libc::dup(fd)) };
+        let shared = region(len / 2, UmemBacking::Fd(dup));
+
+        assert_ne!(shared.fd(), Some(fd));
+        assert_eq!(file_len(fd), len as i64);
+    }
+
+    #[test]
+    fn reassembled_umem_maps_the_given_file() {
+        let fd = unsafe { libc::memfd_create(b"umem\0".as_ptr() as *const _, 0) };
+        assert!(fd >= 0);
+
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw_fd = fd.as_raw_fd();
+
+        let config = UmemConfig::default();
+        let layout: FrameLayout = config.into();
+
+        let (umem, descs) = unsafe { Umem::from_parts(config, 4.try_into().unwrap(), fd) }.unwrap();
+
+        assert_eq!(umem.backing_fd(), Some(raw_fd));
+
+        assert_eq!(descs.len(), 4);
+        assert_eq!(descs[1].addr(), frame_addr(1, layout));
+
+        // Not registered through this `Umem`.
+        assert!(umem.with_ptr_and_saved_queues(|ptr, _| ptr.is_null()));
+    }
+
+    #[test]
+    fn warming_up_resident_memory_succeeds() {
+        let config = UmemConfigBuilder::new().lock_memory(true).build().unwrap();
+
+        let (umem, _descs) = unsafe {
+            Umem::from_parts(
+                config,
+                4.try_into().unwrap(),
+                OwnedFd::from_raw_fd(libc::memfd_create(b"umem\0".as_ptr() as *const _, 0)),
+            )
+        }
+        .unwrap();
+
+        assert!(umem.config().lock_memory());
+
+        umem.warm_up().unwrap();
+    }
+
+    #[test]
+    fn descs_are_validated_against_the_frame_layout() {
+        let config = UmemConfigBuilder::new().frame_headroom(32).build().unwrap();
+
+        let layout = FrameLayout::from(config);
+        let frame_size = layout.frame_size();
+
+        let (umem, descs) = Umem::new_unregistered(config, 4.try_into().unwrap()).unwrap();
+
+        let mut desc = descs[3];
+        assert!(umem.contains(&desc));
+        assert_eq!(umem.validate(&desc), Ok(()));
+
+        desc.lengths.data = layout.mtu;
+        desc.lengths.headroom = layout.frame_headroom;
+        assert_eq!(umem.validate(&desc), Ok(()));
+
+        desc.lengths.data += 1;
+        assert_eq!(
+            umem.validate(&desc),
+            Err(DescError::DataOverrun {
+                addr: desc.addr,
+                len: layout.mtu + 1,
+                room: layout.mtu,
+            })
+        );
+
+        desc.lengths.data = 0;
+        desc.lengths.headroom += 1;
+        assert!(matches!(
+            umem.validate(&desc),
+            Err(DescError::HeadroomOverrun { .. })
+        ));
+
+        // Pushed back into the XDP headroom, but no further.
+        let pushed = FrameDesc::new(frame_size + layout.frame_headroom);
+        assert_eq!(umem.validate(&pushed), Ok(()));
+
+        let too_far = FrameDesc::new(frame_size + layout.frame_headroom - 1);
+        assert!(!umem.contains(&too_far));
+        assert!(matches!(
+            umem.validate(&too_far),
+            Err(DescError::NoHeadroom { .. })
+        ));
+
+        let past_end = FrameDesc::new(4 * frame_size + descs[0].addr);
+        assert!(!umem.contains(&past_end));
+        assert!(matches!(
+            umem.validate(&past_end),
+            Err(DescError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn unaligned_descs_are_validated_against_the_whole_umem() {
+        let config = UmemConfigBuilder::new()
+            .frame_headroom(32)
+            .unaligned_chunks(true)
+            .build()
+            .unwrap();
+
+        let layout = FrameLayout::from(config);
+        let frame_size = layout.frame_size();
+
+        let (umem, _descs) = Umem::new_unregistered(config, 4.try_into().unwrap()).unwrap();
+
+        // Off the frame grid, with the packet data spanning what would
+        // be two aligned frames.
+        let mut desc = FrameDesc::new(frame_size / 2 + layout.frame_headroom);
+        desc.lengths.data = frame_size;
+        assert!(umem.contains(&desc));
+        assert_eq!(umem.validate(&desc), Ok(()));
+
+        let too_early = FrameDesc::new(layout.frame_headroom - 1);
+        assert!(!umem.contains(&too_early));
+
+        desc.lengths.data = umem.len();
+        assert!(matches!(
+            umem.validate(&desc),
+            Err(DescError::DataOverrun { .. })
+        ));
+    }
+
+    #[test]
+    fn desc_iter_generates_the_same_descs_lazily() {
+        let config = UmemConfigBuilder::new().frame_headroom(32).build().unwrap();
+
+        let (umem, descs) = Umem::new_unregistered(config, 8.try_into().unwrap()).unwrap();
+
+        assert_eq!(umem.frame_count(), 8);
+
+        assert!(umem
+            .desc_iter()
+            .zip(descs.iter())
+            .all(|(a, b)| a.addr() == b.addr()));
+
+        let mut iter = umem.desc_iter();
+        assert_eq!(iter.len(), 8);
+
+        assert_eq!(iter.nth(2).unwrap().addr(), descs[2].addr());
+        assert_eq!(iter.next_back().unwrap().addr(), descs[7].addr());
+        assert_eq!(iter.len(), 4);
+
+        assert_eq!(iter.nth(3).unwrap().addr(), descs[6].addr());
+        assert!(iter.next().is_none());
+    }
 }
@@ -0,0 +1,796 @@
+//! A pool of free [`Umem`](super::Umem) frames.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    socket::{RxQueue, TxQueue, WeakSocket},
+    util,
+};
+
+use super::{frame::FrameDesc, CompQueue, FillQueue, Umem};
+
+/// The number of descriptors moved between a queue and a
+/// [`FrameAllocator`] at a time.
+pub(super) const BATCH_SIZE: usize = 64;
+
+/// Decides which free frame a [`FramePool`] hands out next.
+///
+/// The pool keeps its free frames in the order they were returned,
+/// oldest first. Implementors pick the index of the frame to take
+/// from the `len` available.
+pub trait AllocOrder {
+    /// The index of the next frame to hand out, which must be less
+    /// than `len`. Only called when `len` is non-zero.
+    fn next_index(&mut self, len: usize) -> usize;
+}
+
+/// Hand out the most recently returned frame first. Its data is the
+/// most likely to still be in cache, so this is the default.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Lifo;
+
+impl AllocOrder for Lifo {
+    #[inline]
+    fn next_index(&mut self, len: usize) -> usize {
+        len - 1
+    }
+}
+
+/// Hand out the least recently returned frame first, cycling through
+/// the whole pool.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Fifo;
+
+impl AllocOrder for Fifo {
+    #[inline]
+    fn next_index(&mut self, _len: usize) -> usize {
+        0
+    }
+}
+
+/// Hand out frames in a pseudo-random order.
+///
+/// Useful in testing, since frames that are used after being
+/// returned to the pool are much more likely to be caught than when
+/// the same few frames are recycled over and over. The sequence is
+/// determined by the seed, so failures can be reproduced.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Create a new `Random` order from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // SplitMix64.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl AllocOrder for Random {
+    #[inline]
+    fn next_index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+/// A pool of free frames, handed out in the order decided by its
+/// [`AllocOrder`].
+///
+/// The pool does no checking of the frames given to it, it's up to
+/// the user to ensure that a frame isn't returned while it's still in
+/// use.
+#[derive(Debug, Clone)]
+pub struct FramePool<O = Lifo> {
+    free: VecDeque<FrameDesc>,
+    order: O,
+}
+
+impl FramePool<Lifo> {
+    /// Create a new pool containing `descs`, handing out the most
+    /// recently returned frames first.
+    pub fn new(descs: Vec<FrameDesc>) -> Self {
+        Self::with_order(descs, Lifo)
+    }
+}
+
+impl<O: AllocOrder> FramePool<O> {
+    /// Create a new pool containing `descs`, handing them out in the
+    /// order decided by `order`.
+    pub fn with_order(descs: Vec<FrameDesc>, order: O) -> Self {
+        Self {
+            free: descs.into(),
+            order,
+        }
+    }
+
+    /// Take a free frame from the pool, if there are any left.
+    #[inline]
+    pub fn alloc(&mut self) -> Option<FrameDesc> {
+        if self.free.is_empty() {
+            return None;
+        }
+
+        let idx = self.order.next_index(self.free.len());
+
+        if idx == 0 {
+            self.free.pop_front()
+        } else {
+            self.free.swap_remove_back(idx)
+        }
+    }
+
+    /// Fill `descs` with free frames, returning how many were taken.
+    #[inline]
+    pub fn alloc_into(&mut self, descs: &mut [FrameDesc]) -> usize {
+        let mut cnt = 0;
+
+        for desc in descs.iter_mut() {
+            match self.alloc() {
+                Some(d) => *desc = d,
+                None => break,
+            }
+            cnt += 1;
+        }
+
+        cnt
+    }
+
+    /// Return a frame to the pool.
+    #[inline]
+    pub fn free(&mut self, desc: FrameDesc) {
+        self.free.push_back(desc);
+    }
+
+    /// The number of free frames in the pool.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// Whether there are no free frames left.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    /// A reference to the allocation order.
+    #[inline]
+    pub fn order(&self) -> &O {
+        &self.order
+    }
+}
+
+impl<O> Extend<FrameDesc> for FramePool<O> {
+    fn extend<I: IntoIterator<Item = FrameDesc>>(&mut self, iter: I) {
+        self.free.extend(iter)
+    }
+}
+
+/// A [`FramePool`] shared between the sockets bound to a [`Umem`].
+///
+/// Cloning a `FrameAllocator` returns another handle to the same
+/// pool, so each socket sharing the [`Umem`] can be given its own.
+/// Frames are taken from the pool to be placed on a [`FillQueue`]
+/// with [`fill`](Self::fill), and those the kernel has finished
+/// transmitting are moved back into it from a [`CompQueue`] with
+/// [`reclaim`](Self::reclaim), so there's no need to keep track of
+/// which socket is using which frames.
+#[derive(Debug)]
+pub struct FrameAllocator<O = Lifo> {
+    pool: Arc<Mutex<FramePool<O>>>,
+    umem: Umem,
+    total: usize,
+}
+
+impl<O> Clone for FrameAllocator<O> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: Arc::clone(&self.pool),
+            umem: self.umem.clone(),
+            total: self.total,
+        }
+    }
+}
+
+impl FrameAllocator<Lifo> {
+    /// Create a new allocator for the frames of `umem` described by
+    /// `descs`, handing out the most recently returned frames first.
+    ///
+    /// # Safety
+    ///
+    /// `descs` must belong to `umem`, and none of the frames may be
+    /// in use elsewhere, either in userspace or by the kernel.
+    pub unsafe fn new(umem: &Umem, descs: Vec<FrameDesc>) -> Self {
+        // SAFETY: see above.
+        unsafe { Self::with_order(umem, descs, Lifo) }
+    }
+}
+
+impl<O: AllocOrder> FrameAllocator<O> {
+    /// Create a new allocator for the frames of `umem` described by
+    /// `descs`, handing them out in the order decided by `order`.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`](FrameAllocator::new).
+    pub unsafe fn with_order(umem: &Umem, descs: Vec<FrameDesc>, order: O) -> Self {
+        Self {
+            total: descs.len(),
+            pool: Arc::new(Mutex::new(FramePool::with_order(descs, order))),
+            umem: umem.clone(),
+        }
+    }
+
+    /// Take a free frame, if there are any left.
+    #[inline]
+    pub fn alloc(&self) -> Option<FrameDesc> {
+        self.pool.lock().unwrap().alloc()
+    }
+
+    /// Fill `descs` with free frames, returning how many were taken.
+    #[inline]
+    pub fn alloc_into(&self, descs: &mut [FrameDesc]) -> usize {
+        self.pool.lock().unwrap().alloc_into(descs)
+    }
+
+    /// Return frames to the pool.
+    ///
+    /// # Safety
+    ///
+    /// The frames must have been taken from this allocator, and must
+    /// no longer be in use, either in userspace or by the kernel.
+    #[inline]
+    pub unsafe fn free(&self, descs: &[FrameDesc]) {
+        self.pool.lock().unwrap().extend(descs.iter().copied())
+    }
+
+    /// Place free frames on `fq` for receiving into, as many as there
+    /// are free spaces for on the ring, up to `max`. Returns the
+    /// number placed.
+    ///
+    /// # Panics
+    ///
+    /// If `fq` belongs to a [`Umem`] not sharing this allocator's
+    /// memory.
+    pub fn fill(&self, fq: &mut FillQueue, max: usize) -> usize {
+        self.fill_inner(fq, max, |_| ())
+    }
+
+    /// Same as [`fill`](Self::fill), but also records the frames
+    /// placed in `frames`.
+    ///
+    /// # Panics
+    ///
+    /// See [`fill`](Self::fill).
+    pub fn fill_for(&self, fq: &mut FillQueue, max: usize, frames: &mut SocketFrames) -> usize {
+        self.fill_inner(fq, max, |descs| frames.submitted(descs))
+    }
+
+    fn fill_inner<F>(&self, fq: &mut FillQueue, max: usize, mut on_fill: F) -> usize
+    where
+        F: FnMut(&[FrameDesc]),
+    {
+        assert!(
+            fq.umem().shares_mem(&self.umem),
+            "fill queue belongs to a different UMEM"
+        );
+
+        let mut pool = self.pool.lock().unwrap();
+        let mut buf = [FrameDesc::default(); BATCH_SIZE];
+
+        let mut remaining = util::min_usize(fq.nb_free(max), max);
+        let mut total = 0;
+
+        while remaining > 0 {
+            let nb = util::min_usize(remaining, BATCH_SIZE);
+            let cnt = pool.alloc_into(&mut buf[..nb]);
+
+            if cnt == 0 {
+                break;
+            }
+
+            // SAFETY: the frames belong to the fill queue's UMEM and
+            // were free. There's room for all of them since we hold
+            // the only reference to `fq`.
+            let produced = unsafe { fq.produce(&buf[..cnt]) };
+
+            debug_assert_eq!(produced, cnt);
+
+            on_fill(&buf[..cnt]);
+
+            remaining -= cnt;
+            total += cnt;
+        }
+
+        total
+    }
+
+    /// Move frames the kernel has finished transmitting from `cq` back
+    /// into the pool. Returns the number reclaimed.
+    ///
+    /// # Panics
+    ///
+    /// If `cq` belongs to a [`Umem`] not sharing this allocator's
+    /// memory.
+    pub fn reclaim(&self, cq: &mut CompQueue) -> usize {
+        self.reclaim_inner(cq, |_| ())
+    }
+
+    /// Same as [`reclaim`](Self::reclaim), but also records the frames
+    /// reclaimed in `frames`.
+    ///
+    /// # Panics
+    ///
+    /// See [`reclaim`](Self::reclaim).
+    pub fn reclaim_for(&self, cq: &mut CompQueue, frames: &mut SocketFrames) -> usize {
+        self.reclaim_inner(cq, |descs| frames.returned(descs))
+    }
+
+    fn reclaim_inner<F>(&self, cq: &mut CompQueue, mut on_reclaim: F) -> usize
+    where
+        F: FnMut(&[FrameDesc]),
+    {
+        assert!(
+            cq.umem().shares_mem(&self.umem),
+            "comp queue belongs to a different UMEM"
+        );
+
+        let mut buf = [FrameDesc::default(); BATCH_SIZE];
+        let mut total = 0;
+
+        loop {
+            // SAFETY: `cq` belongs to this allocator's UMEM.
+            let cnt = unsafe { cq.consume(&mut buf) };
+
+            if cnt == 0 {
+                break;
+            }
+
+            self.pool.lock().unwrap().extend(buf[..cnt].iter().copied());
+
+            on_reclaim(&buf[..cnt]);
+
+            total += cnt;
+        }
+
+        total
+    }
+
+    /// Return the frames recorded in `frames` as still being on its
+    /// socket's rings, once the socket has been torn down, or `timeout`
+    /// after it was [marked dead](SocketFrames::mark_dead). Returns
+    /// the number reclaimed, which is zero if neither has happened
+    /// yet.
+    ///
+    /// A socket is torn down once its [`RxQueue`] and [`TxQueue`] have
+    /// both been dropped, at which point the kernel has stopped using
+    /// any frames left on its rings.
+    ///
+    /// # Safety
+    ///
+    /// The frames must have been taken from this allocator, and
+    /// `frames` must have been kept up to date with every frame placed
+    /// on or taken off the socket's rings.
+    ///
+    /// The socket's [`FillQueue`] and [`CompQueue`] must not be used
+    /// once the frames are reclaimed, since any left on them will be
+    /// handed out again. If reclaiming on timeout, the kernel must also
+    /// have finished with the frames, for example because the
+    /// socket's interface has been removed.
+    pub unsafe fn reclaim_stranded(&self, frames: &mut SocketFrames, timeout: Duration) -> usize {
+        if !frames.is_stranded(timeout) {
+            return 0;
+        }
+
+        let mut pool = self.pool.lock().unwrap();
+        let cnt = frames.frames.len();
+
+        pool.extend(frames.frames.drain().map(|(_, desc)| desc));
+
+        if let Some(submitted_at) = &mut frames.submitted_at {
+            submitted_at.clear();
+        }
+
+        cnt
+    }
+
+    /// The UMEM the allocator's frames belong to.
+    #[inline]
+    pub(crate) fn umem(&self) -> &Umem {
+        &self.umem
+    }
+
+    /// The number of free frames in the pool.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.pool.lock().unwrap().len()
+    }
+
+    /// The number of frames taken from the pool and not yet returned.
+    #[inline]
+    pub fn in_use(&self) -> usize {
+        self.total.saturating_sub(self.available())
+    }
+}
+
+/// A record of which frames are on a socket's rings, so that they can
+/// be returned to a [`FrameAllocator`] if the socket dies with frames
+/// still on them, for example once its interface has been removed.
+///
+/// Frames placed on the socket's fill and tx rings are recorded with
+/// [`submitted`](Self::submitted), and those taken off its rx and
+/// completion rings with [`returned`](Self::returned).
+/// [`FrameAllocator::fill_for`] and [`FrameAllocator::reclaim_for`]
+/// do both as they go. Whatever remains can then be handed back with
+/// [`FrameAllocator::reclaim_stranded`].
+///
+/// If created with [`track_submission_times`](Self::track_submission_times),
+/// the time each frame was submitted is kept too, so that frames the
+/// kernel has held on to for too long can be found with
+/// [`expired_in_flight`](Self::expired_in_flight).
+///
+/// Frames are identified by the frame-sized slot of the [`Umem`] they
+/// lie in, so a `SocketFrames` doesn't support a `Umem` in unaligned
+/// chunk mode, where frames may start at any address.
+#[derive(Debug)]
+pub struct SocketFrames {
+    socket: WeakSocket,
+    // Frames are keyed by the address of their start, since a
+    // descriptor's address moves within its frame as headroom is used
+    // or given back, by the application or by the XDP program.
+    frame_size: usize,
+    frames: HashMap<usize, FrameDesc>,
+    submitted_at: Option<HashMap<usize, Instant>>,
+    dead_since: Option<Instant>,
+}
+
+impl SocketFrames {
+    fn new(socket: WeakSocket, umem: &Umem) -> Self {
+        Self {
+            socket,
+            frame_size: umem.config().frame_size().get() as usize,
+            frames: HashMap::new(),
+            submitted_at: None,
+            dead_since: None,
+        }
+    }
+
+    /// Create a new, empty record for the socket `rx_q` belongs to.
+    pub fn for_rx_queue(rx_q: &RxQueue) -> Self {
+        Self::new(WeakSocket::new(rx_q.socket()), rx_q.umem())
+    }
+
+    /// Create a new, empty record for the socket `tx_q` belongs to.
+    pub fn for_tx_queue(tx_q: &TxQueue) -> Self {
+        Self::new(WeakSocket::new(tx_q.socket()), tx_q.umem())
+    }
+
+    /// Also record the time each frame is submitted, so that frames
+    /// which have been on the socket's rings for too long, a sign of a
+    /// stuck ring or lost completions, can be found with
+    /// [`expired_in_flight`](Self::expired_in_flight).
+    pub fn track_submission_times(mut self) -> Self {
+        self.submitted_at.get_or_insert_with(HashMap::new);
+        self
+    }
+
+    /// Record frames placed on the socket's fill or tx ring.
+    pub fn submitted(&mut self, descs: &[FrameDesc]) {
+        let frame_size = self.frame_size;
+        let start = |desc: &FrameDesc| desc.addr - desc.addr % frame_size;
+
+        self.frames
+            .extend(descs.iter().map(|desc| (start(desc), *desc)));
+
+        if let Some(submitted_at) = &mut self.submitted_at {
+            let now = Instant::now();
+            submitted_at.extend(descs.iter().map(|desc| (start(desc), now)));
+        }
+    }
+
+    /// Record frames taken off the socket's rx or completion ring.
+    pub fn returned(&mut self, descs: &[FrameDesc]) {
+        for desc in descs {
+            let start = desc.addr - desc.addr % self.frame_size;
+
+            self.frames.remove(&start);
+
+            if let Some(submitted_at) = &mut self.submitted_at {
+                submitted_at.remove(&start);
+            }
+        }
+    }
+
+    /// The frames which were submitted at least `older_than` ago and
+    /// haven't been returned since. Always empty unless submission
+    /// times are [tracked](Self::track_submission_times).
+    ///
+    /// Frames on the fill ring are only returned once a packet is
+    /// received into them, so on a quiet socket they're expected to
+    /// show up here.
+    pub fn expired_in_flight(&self, older_than: Duration) -> Vec<FrameDesc> {
+        let submitted_at = match &self.submitted_at {
+            Some(submitted_at) => submitted_at,
+            None => return Vec::new(),
+        };
+
+        submitted_at
+            .iter()
+            .filter(|(_, at)| at.elapsed() >= older_than)
+            .filter_map(|(addr, _)| self.frames.get(addr).copied())
+            .collect()
+    }
+
+    /// Mark the socket as dead, starting the timeout after which
+    /// [`FrameAllocator::reclaim_stranded`] returns its frames even if
+    /// it hasn't been torn down. Has no effect if already marked.
+    pub fn mark_dead(&mut self) {
+        self.dead_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Whether the socket has been torn down, or was marked dead at
+    /// least `timeout` ago.
+    pub fn is_stranded(&self, timeout: Duration) -> bool {
+        !self.socket.is_alive()
+            || matches!(self.dead_since, Some(since) if since.elapsed() >= timeout)
+    }
+
+    /// The number of frames recorded as on the socket's rings.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frames are recorded as on the socket's rings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::SimXsk;
+
+    use super::*;
+
+    fn descs(n: usize) -> Vec<FrameDesc> {
+        (0..n).map(|i| FrameDesc::new(i * 2048)).collect()
+    }
+
+    fn drain<O: AllocOrder>(pool: &mut FramePool<O>) -> Vec<usize> {
+        std::iter::from_fn(|| pool.alloc())
+            .map(|d| d.addr)
+            .collect()
+    }
+
+    #[test]
+    fn lifo_hands_out_most_recently_freed_first() {
+        let mut pool = FramePool::new(descs(4));
+
+        let d = pool.alloc().unwrap();
+        assert_eq!(d.addr, 3 * 2048);
+
+        pool.free(d);
+
+        assert_eq!(drain(&mut pool), vec![6144, 4096, 2048, 0]);
+    }
+
+    #[test]
+    fn fifo_hands_out_least_recently_freed_first() {
+        let mut pool = FramePool::with_order(descs(4), Fifo);
+
+        let d = pool.alloc().unwrap();
+        assert_eq!(d.addr, 0);
+
+        pool.free(d);
+
+        assert_eq!(drain(&mut pool), vec![2048, 4096, 6144, 0]);
+    }
+
+    #[test]
+    fn random_hands_out_every_frame_once_and_is_reproducible() {
+        let mut a = FramePool::with_order(descs(64), Random::new(1));
+        let mut b = FramePool::with_order(descs(64), Random::new(1));
+
+        let order = drain(&mut a);
+
+        assert_eq!(order, drain(&mut b));
+
+        let mut sorted = order.clone();
+        sorted.sort_unstable();
+
+        assert_eq!(sorted, (0..64).map(|i| i * 2048).collect::<Vec<_>>());
+        assert_ne!(order, sorted);
+    }
+
+    #[test]
+    fn alloc_into_stops_when_pool_is_empty() {
+        let mut pool = FramePool::new(descs(3));
+        let mut out = vec![FrameDesc::default(); 4];
+
+        assert_eq!(pool.alloc_into(&mut out), 3);
+        assert!(pool.is_empty());
+        assert_eq!(pool.alloc_into(&mut out), 0);
+    }
+
+    #[test]
+    fn frames_are_tracked_across_clones() {
+        let mut xsk = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs.clone()) };
+        let other = alloc.clone();
+
+        assert_eq!(alloc.fill(&mut xsk.fq, 8), 8);
+        assert_eq!(other.in_use(), 8);
+
+        let tx_desc = other.alloc().unwrap();
+
+        unsafe { xsk.tx_q.produce_and_wakeup(&[tx_desc]).unwrap() };
+        xsk.kernel.run_until_idle(64);
+
+        assert_eq!(alloc.in_use(), 9);
+        assert_eq!(alloc.reclaim(&mut xsk.cq), 1);
+        assert_eq!(other.in_use(), 8);
+        assert_eq!(other.available(), 24);
+    }
+
+    #[test]
+    #[should_panic(expected = "different UMEM")]
+    fn filling_another_umems_queue_panics() {
+        let xsk = SimXsk::with_defaults(32);
+        let mut other = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs.clone()) };
+
+        alloc.fill(&mut other.fq, 1);
+    }
+
+    #[test]
+    fn frames_of_a_dead_socket_are_reclaimed_after_timeout() {
+        let mut xsk = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs.clone()) };
+        let mut frames = SocketFrames::for_rx_queue(&xsk.rx_q);
+
+        assert_eq!(alloc.fill_for(&mut xsk.fq, 4, &mut frames), 4);
+        assert_eq!(frames.len(), 4);
+
+        assert!(xsk.kernel.inject(b"hello"));
+
+        let mut received = [FrameDesc::default(); 4];
+        let cnt = unsafe { xsk.rx_q.consume(&mut received) };
+        frames.returned(&received[..cnt]);
+
+        assert_eq!(frames.len(), 3);
+
+        let timeout = Duration::from_secs(3600);
+
+        assert_eq!(unsafe { alloc.reclaim_stranded(&mut frames, timeout) }, 0);
+
+        frames.mark_dead();
+
+        assert_eq!(unsafe { alloc.reclaim_stranded(&mut frames, timeout) }, 0);
+        assert_eq!(
+            unsafe { alloc.reclaim_stranded(&mut frames, Duration::ZERO) },
+            3
+        );
+
+        assert!(frames.is_empty());
+        assert_eq!(alloc.in_use(), 1);
+    }
+
+    #[test]
+    fn frames_are_matched_up_after_moving_their_address() {
+        let mut xsk = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs.clone()) };
+        let mut frames = SocketFrames::for_rx_queue(&xsk.rx_q);
+
+        assert_eq!(alloc.fill_for(&mut xsk.fq, 1, &mut frames), 1);
+        assert!(xsk.kernel.inject(b"hello"));
+
+        let mut received = [FrameDesc::default(); 1];
+        assert_eq!(unsafe { xsk.rx_q.consume(&mut received) }, 1);
+
+        let mut desc = received[0];
+        let addr = desc.addr();
+
+        unsafe { xsk.umem.data_mut(&mut desc) }
+            .push_front(14)
+            .unwrap();
+
+        assert_eq!(desc.addr(), addr - 14);
+
+        frames.submitted(&[desc]);
+        assert_eq!(frames.len(), 1);
+
+        unsafe { xsk.umem.data_mut(&mut desc) }
+            .pull_front(14)
+            .unwrap();
+
+        frames.returned(&[desc]);
+        assert!(frames.is_empty());
+
+        frames.mark_dead();
+
+        assert_eq!(
+            unsafe { alloc.reclaim_stranded(&mut frames, Duration::ZERO) },
+            0
+        );
+    }
+
+    #[test]
+    fn frames_held_by_the_kernel_too_long_are_listed() {
+        let mut xsk = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&xsk.umem, xsk.descs.clone()) };
+        let mut frames = SocketFrames::for_rx_queue(&xsk.rx_q).track_submission_times();
+
+        assert_eq!(alloc.fill_for(&mut xsk.fq, 4, &mut frames), 4);
+
+        assert!(frames
+            .expired_in_flight(Duration::from_secs(3600))
+            .is_empty());
+
+        assert!(xsk.kernel.inject(b"hello"));
+
+        let mut received = [FrameDesc::default(); 4];
+        let cnt = unsafe { xsk.rx_q.consume(&mut received) };
+        frames.returned(&received[..cnt]);
+
+        let mut expired: Vec<_> = frames
+            .expired_in_flight(Duration::ZERO)
+            .iter()
+            .map(|desc| desc.addr())
+            .collect();
+        expired.sort_unstable();
+
+        let mut still_on_ring = xsk.kernel.outstanding();
+        still_on_ring.sort_unstable();
+
+        assert_eq!(expired, still_on_ring);
+        assert_eq!(expired.len(), 3);
+    }
+
+    #[test]
+    fn frames_are_reclaimed_once_the_socket_is_torn_down() {
+        let SimXsk {
+            umem,
+            descs,
+            mut fq,
+            tx_q,
+            rx_q,
+            ..
+        } = SimXsk::with_defaults(32);
+
+        let alloc = unsafe { FrameAllocator::new(&umem, descs) };
+        let mut frames = SocketFrames::for_tx_queue(&tx_q);
+
+        assert_eq!(alloc.fill_for(&mut fq, 8, &mut frames), 8);
+
+        let timeout = Duration::from_secs(3600);
+
+        drop(rx_q);
+        assert_eq!(unsafe { alloc.reclaim_stranded(&mut frames, timeout) }, 0);
+
+        drop(tx_q);
+        assert_eq!(unsafe { alloc.reclaim_stranded(&mut frames, timeout) }, 8);
+
+        assert_eq!(alloc.in_use(), 0);
+    }
+}
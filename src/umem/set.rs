@@ -0,0 +1,173 @@
+//! A frame pool spread over several [`Umem`]s, so it can be grown
+//! at runtime.
+//!
+//! A socket's UMEM is fixed when it's bound, so a pool can't be grown
+//! by registering more memory with an existing socket. Instead a
+//! [`UmemSet`] holds a number of regions, each its own [`Umem`], and
+//! a new region is added by creating another `Umem` and binding
+//! sockets to it as needed, while sockets bound to the existing
+//! regions carry on undisturbed.
+//!
+//! The region a descriptor's frame belongs to is recorded alongside
+//! it, so frames from all regions can be handled by the same code and
+//! passed back to the right region's queues. The region is kept apart
+//! from the descriptor's [options](FrameDesc::options) and is never
+//! written to a ring. Nor does the kernel hand it back, so
+//! descriptors read off a ring need [tagging](UmemSet::tag) again
+//! with the region of the queue they were read from.
+//!
+//! ```
+//! use xsk_rs::umem::{frame::FrameDesc, set::UmemSet};
+//!
+//! let mut desc = FrameDesc::default();
+//!
+//! UmemSet::tag(&mut desc, 3);
+//! assert_eq!(UmemSet::region_of(&desc), 3);
+//!
+//! UmemSet::untag(&mut desc);
+//! assert_eq!(UmemSet::region_of(&desc), 0);
+//! ```
+
+use super::{frame::FrameDesc, Umem};
+
+/// The most regions a [`UmemSet`] can hold.
+pub const MAX_REGIONS: usize = u8::MAX as usize + 1;
+
+/// A number of [`Umem`]s treated as one frame pool, each identified
+/// by a region id. See the [module docs](self).
+#[derive(Debug, Default, Clone)]
+pub struct UmemSet {
+    regions: Vec<Umem>,
+}
+
+impl UmemSet {
+    /// An empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `umem` as a new region, tagging `descs`, its frames, with
+    /// the region's id, which is returned. Region ids are handed out
+    /// in order starting from zero.
+    ///
+    /// Returns [`None`], leaving `descs` as they are, if the set
+    /// already holds [`MAX_REGIONS`] regions.
+    pub fn add(&mut self, umem: Umem, descs: &mut [FrameDesc]) -> Option<u8> {
+        if self.regions.len() == MAX_REGIONS {
+            return None;
+        }
+
+        let region = self.regions.len() as u8;
+
+        descs.iter_mut().for_each(|desc| Self::tag(desc, region));
+
+        self.regions.push(umem);
+
+        Some(region)
+    }
+
+    /// The UMEM of `region`, if the set holds it.
+    #[inline]
+    pub fn get(&self, region: u8) -> Option<&Umem> {
+        self.regions.get(region as usize)
+    }
+
+    /// The UMEM of the region `desc` is tagged with, if the set holds
+    /// it.
+    #[inline]
+    pub fn umem_of(&self, desc: &FrameDesc) -> Option<&Umem> {
+        self.get(Self::region_of(desc))
+    }
+
+    /// The number of regions.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Whether the set holds no regions.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// The regions along with their ids, in order.
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &Umem)> + '_ {
+        self.regions
+            .iter()
+            .enumerate()
+            .map(|(region, umem)| (region as u8, umem))
+    }
+
+    /// The total number of frames over all regions.
+    pub fn frame_count(&self) -> usize {
+        self.regions.iter().map(Umem::frame_count).sum()
+    }
+
+    /// Tag `desc` with `region`, replacing any region it was tagged
+    /// with before.
+    #[inline]
+    pub fn tag(desc: &mut FrameDesc, region: u8) {
+        desc.region = region;
+    }
+
+    /// The region `desc` is tagged with. Untagged descriptors belong
+    /// to region zero.
+    #[inline]
+    pub fn region_of(desc: &FrameDesc) -> u8 {
+        desc.region
+    }
+
+    /// Clear the region `desc` is tagged with.
+    #[inline]
+    pub fn untag(desc: &mut FrameDesc) {
+        desc.region = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::config::UmemConfig;
+
+    use super::*;
+
+    #[test]
+    fn regions_are_looked_up_by_the_tag_on_their_frames() {
+        let mut set = UmemSet::new();
+
+        let mut regions = (0..2)
+            .map(|_| Umem::new_unregistered(UmemConfig::default(), 4.try_into().unwrap()).unwrap())
+            .collect::<Vec<_>>();
+
+        for (expected, (umem, descs)) in regions.iter_mut().enumerate() {
+            assert_eq!(set.add(umem.clone(), descs), Some(expected as u8));
+        }
+
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.frame_count(), 8);
+
+        let (second, descs) = &regions[1];
+
+        assert!(set.umem_of(&descs[3]).unwrap().is_same(second));
+        assert!(set.get(2).is_none());
+    }
+
+    #[test]
+    fn tags_are_kept_off_the_ring() {
+        let mut desc = FrameDesc::default();
+        desc.set_options(0xff00_0001);
+
+        UmemSet::tag(&mut desc, 0xab);
+
+        assert_eq!(UmemSet::region_of(&desc), 0xab);
+        assert_eq!(desc.options(), 0xff00_0001);
+        assert_eq!(desc.to_xdp_desc().options, 0xff00_0001);
+
+        UmemSet::untag(&mut desc);
+
+        assert_eq!(UmemSet::region_of(&desc), 0);
+        assert_eq!(desc.options(), 0xff00_0001);
+    }
+}
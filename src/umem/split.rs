@@ -0,0 +1,333 @@
+//! Dividing a [`Umem`]'s frames into a region for receiving and one
+//! for transmitting, each with its own pool.
+//!
+//! Frames in the rx region are placed on the fill ring as they are,
+//! so received packets start after the XDP headroom as usual. Frames
+//! in the tx region can instead carry a header written to them once
+//! up front, for example the Ethernet and IP headers of a fixed flow,
+//! and are handed out with their data length already covering it so
+//! only the payload needs writing. Since the kernel never writes to a
+//! frame it transmits, the header is still there each time the frame
+//! comes back off the completion ring.
+//!
+//! Frames are split by address, so which region a frame belongs to
+//! can always be told from its descriptor, and frames returned to a
+//! [`SplitAllocator`] always go back to the right pool. This holds
+//! even when a received frame is forwarded and comes back off the
+//! completion ring.
+
+use super::{
+    frame::FrameDesc,
+    pool::{AllocOrder, FrameAllocator, Lifo, BATCH_SIZE},
+    CompQueue, FillQueue, Umem,
+};
+
+/// The half of a split [`Umem`] a frame belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// Frames for the fill ring, to receive into.
+    Rx,
+    /// Frames for the tx ring.
+    Tx,
+}
+
+/// A pair of [`FrameAllocator`]s over the rx and tx regions of a
+/// [`Umem`]. See the [module docs](self).
+///
+/// Cloning returns another handle to the same pools.
+#[derive(Debug)]
+pub struct SplitAllocator<O = Lifo> {
+    rx: FrameAllocator<O>,
+    tx: FrameAllocator<O>,
+    // Frames at or past this address are in the tx region.
+    tx_start: usize,
+    tx_header_len: usize,
+}
+
+impl<O> Clone for SplitAllocator<O> {
+    fn clone(&self) -> Self {
+        Self {
+            rx: self.rx.clone(),
+            tx: self.tx.clone(),
+            tx_start: self.tx_start,
+            tx_header_len: self.tx_header_len,
+        }
+    }
+}
+
+impl SplitAllocator<Lifo> {
+    /// Split the frames of `umem` described by `descs`, putting the
+    /// `rx_count` with the lowest addresses in the rx region and the
+    /// rest in the tx region. Tx frames carry no header.
+    ///
+    /// # Panics
+    ///
+    /// If `rx_count` is greater than the number of frames.
+    ///
+    /// # Safety
+    ///
+    /// See [`FrameAllocator::new`].
+    pub unsafe fn new(umem: &Umem, descs: Vec<FrameDesc>, rx_count: usize) -> Self {
+        // SAFETY: see above.
+        unsafe { Self::with_tx_header(umem, descs, rx_count, &[], Lifo) }
+    }
+}
+
+impl<O: AllocOrder + Clone> SplitAllocator<O> {
+    /// Same as [`new`](SplitAllocator::new), but writes `header` to
+    /// the start of the data segment of every tx frame, and hands
+    /// frames out of both pools in the order decided by `order`.
+    ///
+    /// # Panics
+    ///
+    /// If `rx_count` is greater than the number of frames, or
+    /// `header` doesn't fit in a frame's data segment.
+    ///
+    /// # Safety
+    ///
+    /// See [`FrameAllocator::new`].
+    pub unsafe fn with_tx_header(
+        umem: &Umem,
+        mut descs: Vec<FrameDesc>,
+        rx_count: usize,
+        header: &[u8],
+        order: O,
+    ) -> Self {
+        assert!(rx_count <= descs.len(), "more rx frames than in the UMEM");
+
+        descs.sort_unstable_by_key(|desc| desc.addr());
+
+        let tx_descs = descs.split_off(rx_count);
+
+        let tx_start = tx_descs
+            .first()
+            .map(|desc| desc.addr())
+            .unwrap_or(usize::MAX);
+
+        for desc in tx_descs.iter() {
+            let mut desc = *desc;
+
+            // SAFETY: the frame belongs to `umem` and isn't in use, as
+            // per the unsafe contract of this function.
+            let mut data = unsafe { umem.data_mut(&mut desc) };
+
+            data.cursor()
+                .write_at(0, header)
+                .expect("tx header larger than a frame's data segment");
+        }
+
+        Self {
+            // SAFETY: see above.
+            rx: unsafe { FrameAllocator::with_order(umem, descs, order.clone()) },
+            tx: unsafe { FrameAllocator::with_order(umem, tx_descs, order) },
+            tx_start,
+            tx_header_len: header.len(),
+        }
+    }
+}
+
+impl<O: AllocOrder> SplitAllocator<O> {
+    /// The region the frame pointed at by `desc` belongs to.
+    #[inline]
+    pub fn region_of(&self, desc: &FrameDesc) -> Region {
+        if desc.addr() < self.tx_start {
+            Region::Rx
+        } else {
+            Region::Tx
+        }
+    }
+
+    /// The allocator of the rx region.
+    #[inline]
+    pub fn rx(&self) -> &FrameAllocator<O> {
+        &self.rx
+    }
+
+    /// The allocator of the tx region. Frames taken from it directly
+    /// aren't given the header's length, see
+    /// [`alloc_tx`](Self::alloc_tx).
+    #[inline]
+    pub fn tx(&self) -> &FrameAllocator<O> {
+        &self.tx
+    }
+
+    /// The length of the header carried by tx frames.
+    #[inline]
+    pub fn tx_header_len(&self) -> usize {
+        self.tx_header_len
+    }
+
+    /// Take a free tx frame, with its data length covering the header
+    /// so that writing to it with a [`cursor`] appends the payload.
+    ///
+    /// [`cursor`]: crate::umem::frame::DataMut::cursor
+    #[inline]
+    pub fn alloc_tx(&self) -> Option<FrameDesc> {
+        self.tx.alloc().map(|mut desc| {
+            desc.lengths.data = self.tx_header_len;
+            desc
+        })
+    }
+
+    /// Fill `descs` with free tx frames as per
+    /// [`alloc_tx`](Self::alloc_tx), returning how many were taken.
+    pub fn alloc_tx_into(&self, descs: &mut [FrameDesc]) -> usize {
+        let cnt = self.tx.alloc_into(descs);
+
+        for desc in descs[..cnt].iter_mut() {
+            desc.lengths.data = self.tx_header_len;
+        }
+
+        cnt
+    }
+
+    /// Place free rx frames on `fq`, see [`FrameAllocator::fill`].
+    ///
+    /// # Panics
+    ///
+    /// See [`FrameAllocator::fill`].
+    #[inline]
+    pub fn fill(&self, fq: &mut FillQueue, max: usize) -> usize {
+        self.rx.fill(fq, max)
+    }
+
+    /// Move frames the kernel has finished transmitting from `cq`
+    /// back into the pool of the region each belongs to. Returns the
+    /// number reclaimed.
+    ///
+    /// # Panics
+    ///
+    /// See [`FrameAllocator::reclaim`].
+    pub fn reclaim(&self, cq: &mut CompQueue) -> usize {
+        assert!(
+            cq.umem().shares_mem(self.tx.umem()),
+            "comp queue belongs to a different UMEM"
+        );
+
+        let mut buf = [FrameDesc::default(); BATCH_SIZE];
+        let mut total = 0;
+
+        loop {
+            // SAFETY: `cq` belongs to this allocator's UMEM.
+            let cnt = unsafe { cq.consume(&mut buf) };
+
+            if cnt == 0 {
+                break;
+            }
+
+            // SAFETY: the kernel has finished with the frames, which
+            // were taken from this allocator.
+            unsafe { self.free(&buf[..cnt]) };
+
+            total += cnt;
+        }
+
+        total
+    }
+
+    /// Return frames to the pool of the region each belongs to.
+    ///
+    /// # Safety
+    ///
+    /// See [`FrameAllocator::free`].
+    pub unsafe fn free(&self, mut descs: &[FrameDesc]) {
+        // Frames tend to come back in runs from the same region, so
+        // hand each run over in one go rather than frame by frame.
+        while let Some(first) = descs.first() {
+            let region = self.region_of(first);
+
+            let run = descs
+                .iter()
+                .position(|desc| self.region_of(desc) != region)
+                .unwrap_or(descs.len());
+
+            let pool = match region {
+                Region::Rx => &self.rx,
+                Region::Tx => &self.tx,
+            };
+
+            // SAFETY: see above.
+            unsafe { pool.free(&descs[..run]) };
+
+            descs = &descs[run..];
+        }
+    }
+
+    /// The number of free frames in each region.
+    pub fn available(&self) -> (usize, usize) {
+        (self.rx.available(), self.tx.available())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::sim::SimXsk;
+
+    use super::*;
+
+    #[test]
+    fn fill_ring_only_gets_rx_frames() {
+        let mut xsk = SimXsk::with_defaults(16);
+
+        let alloc = unsafe { SplitAllocator::new(&xsk.umem, xsk.descs.clone(), 4) };
+
+        assert_eq!(alloc.fill(&mut xsk.fq, 16), 4);
+        assert_eq!(alloc.available(), (0, 12));
+
+        let tx_desc = alloc.alloc_tx().unwrap();
+
+        assert_eq!(alloc.region_of(&tx_desc), Region::Tx);
+        assert_eq!(alloc.region_of(&xsk.descs[0]), Region::Rx);
+    }
+
+    #[test]
+    fn tx_frames_keep_their_header_across_transmits() {
+        let mut xsk = SimXsk::with_defaults(16);
+
+        let alloc = unsafe {
+            SplitAllocator::with_tx_header(&xsk.umem, xsk.descs.clone(), 8, b"hdr:", Lifo)
+        };
+
+        let mut desc = alloc.alloc_tx().unwrap();
+        assert_eq!(desc.lengths().data(), 4);
+
+        unsafe { xsk.umem.data_mut(&mut desc) }
+            .cursor()
+            .write_all(b"ping")
+            .unwrap();
+
+        assert_eq!(unsafe { xsk.umem.data(&desc) }.contents(), b"hdr:ping");
+
+        unsafe { xsk.tx_q.produce_and_wakeup(&[desc]).unwrap() };
+        xsk.kernel.run_until_idle(64);
+
+        assert_eq!(alloc.reclaim(&mut xsk.cq), 1);
+        assert_eq!(alloc.available(), (8, 8));
+
+        let desc = alloc.alloc_tx().unwrap();
+
+        assert_eq!(unsafe { xsk.umem.data(&desc) }.contents(), b"hdr:");
+    }
+
+    #[test]
+    fn freed_frames_go_back_to_their_own_region() {
+        let xsk = SimXsk::with_defaults(16);
+
+        let alloc = unsafe { SplitAllocator::new(&xsk.umem, xsk.descs.clone(), 8) };
+
+        let mut rx_descs = [FrameDesc::default(); 2];
+        let mut tx_descs = [FrameDesc::default(); 2];
+
+        assert_eq!(alloc.rx().alloc_into(&mut rx_descs), 2);
+        assert_eq!(alloc.alloc_tx_into(&mut tx_descs), 2);
+        assert_eq!(alloc.available(), (6, 6));
+
+        let mixed = [rx_descs[0], tx_descs[0], tx_descs[1], rx_descs[1]];
+
+        unsafe { alloc.free(&mixed) };
+
+        assert_eq!(alloc.available(), (8, 8));
+    }
+}
@@ -0,0 +1,53 @@
+//! Address helpers for a [`Umem`](super::Umem) in unaligned chunk
+//! mode, see
+//! [`UmemConfigBuilder::unaligned_chunks`](crate::config::UmemConfigBuilder::unaligned_chunks).
+//!
+//! In unaligned mode the kernel places the offset of a received
+//! packet from the start of its frame in the upper bits of the rx
+//! descriptor's address, rather than adding it to the address. The
+//! queues decode these addresses when reading them, so these are
+//! only needed when handling raw addresses, for example those read
+//! directly from a ring.
+//!
+//! In aligned mode the upper bits are always zero, so each of these
+//! is a no-op.
+
+use libxdp_sys::{XSK_UNALIGNED_BUF_ADDR_MASK, XSK_UNALIGNED_BUF_OFFSET_SHIFT};
+
+/// The address of the start of the frame. Equivalent to
+/// `xsk_umem__extract_addr`.
+#[inline]
+pub fn extract_addr(addr: u64) -> u64 {
+    addr & XSK_UNALIGNED_BUF_ADDR_MASK
+}
+
+/// The offset of the packet from the start of the frame. Equivalent
+/// to `xsk_umem__extract_offset`.
+#[inline]
+pub fn extract_offset(addr: u64) -> u64 {
+    addr >> XSK_UNALIGNED_BUF_OFFSET_SHIFT
+}
+
+/// The address of the packet itself, that is the start of the frame
+/// plus the offset. Equivalent to `xsk_umem__add_offset_to_addr`.
+#[inline]
+pub fn add_offset_to_addr(addr: u64) -> u64 {
+    extract_addr(addr) + extract_offset(addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_is_split_from_addr() {
+        let addr = 0x1000 | (256 << XSK_UNALIGNED_BUF_OFFSET_SHIFT);
+
+        assert_eq!(extract_addr(addr), 0x1000);
+        assert_eq!(extract_offset(addr), 256);
+        assert_eq!(add_offset_to_addr(addr), 0x1100);
+
+        // Aligned addresses are left alone.
+        assert_eq!(add_offset_to_addr(0x1100), 0x1100);
+    }
+}
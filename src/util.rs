@@ -1,6 +1,10 @@
+use std::{io, ops::Range};
+
+/// The calling thread's `errno`. Read through std rather than with
+/// `__errno_location`, which isn't part of every libc's ABI.
 #[inline]
 pub fn get_errno() -> i32 {
-    unsafe { *libc::__errno_location() }
+    io::Error::last_os_error().raw_os_error().unwrap_or(0)
 }
 
 #[inline]
@@ -26,6 +30,77 @@ pub fn min_usize(fst: usize, snd: usize) -> usize {
     }
 }
 
+/// The largest sub-range of `range` whose bounds are multiples of
+/// `page_size`, which must be a power of two. Empty if `range` doesn't
+/// cover a whole page.
+#[inline]
+pub fn page_aligned_within(range: Range<usize>, page_size: usize) -> Range<usize> {
+    let start = (range.start + page_size - 1) & !(page_size - 1);
+    let end = range.end & !(page_size - 1);
+
+    if start < end {
+        start..end
+    } else {
+        start..start
+    }
+}
+
+/// The size of a page of memory, in bytes.
+#[inline]
+pub fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Lock the pages covering `range`, a range of addresses in this
+/// process, into memory with `mlock`.
+pub fn mlock(range: Range<usize>) -> io::Result<()> {
+    let page_size = page_size();
+
+    let start = range.start & !(page_size - 1);
+    let end = (range.end + page_size - 1) & !(page_size - 1);
+
+    if unsafe { libc::mlock(start as *const libc::c_void, end - start) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// The number of major page faults taken by the calling thread.
+pub fn major_faults() -> io::Result<u64> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::getrusage(libc::RUSAGE_THREAD, &mut usage) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(usage.ru_majflt as u64)
+}
+
+/// Hint to the CPU that the cache line containing `ptr` is about to
+/// be read. Prefetches never fault, so `ptr` need not be valid. A
+/// no-op on targets other than x86_64 and aarch64.
+#[inline(always)]
+pub fn prefetch_read(ptr: *const u8) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(ptr as *const i8)
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!(
+            "prfm pldl1keep, [{}]",
+            in(reg) ptr,
+            options(nostack, preserves_flags, readonly)
+        )
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = ptr;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,4 +112,12 @@ mod tests {
         assert_eq!(is_pow_of_two(2), true);
         assert_eq!(is_pow_of_two(13), false);
     }
+
+    #[test]
+    fn page_aligned_within_rounds_inwards() {
+        assert_eq!(page_aligned_within(0..8192, 4096), 0..8192);
+        assert_eq!(page_aligned_within(1..12287, 4096), 4096..8192);
+        assert!(page_aligned_within(1..4096, 4096).is_empty());
+        assert!(page_aligned_within(4097..4098, 4096).is_empty());
+    }
 }
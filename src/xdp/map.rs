@@ -0,0 +1,148 @@
+//! Typed handles to the maps of a loaded XDP program.
+
+use std::{io, marker::PhantomData, mem::MaybeUninit, os::unix::prelude::RawFd};
+
+use crate::socket::{
+    xskmap::{
+        self, MapElemAttr, BPF_ANY, BPF_MAP_DELETE_ELEM, BPF_MAP_LOOKUP_ELEM, BPF_MAP_UPDATE_ELEM,
+    },
+    RxQueue,
+};
+
+use super::XdpProgram;
+
+/// Plain data which can be used as a map key or value.
+///
+/// # Safety
+///
+/// Implementors must be valid for any bit pattern and, if structs,
+/// `#[repr(C)]` with the same layout as the type used by the XDP
+/// program.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A map of an [`XdpProgram`], with keys of type `K` and values of
+/// type `V`. See [`XdpProgram::map`].
+#[derive(Debug)]
+pub struct Map<'a, K, V> {
+    fd: RawFd,
+    _marker: PhantomData<(&'a XdpProgram, K, V)>,
+}
+
+impl<'a, K: Pod, V: Pod> Map<'a, K, V> {
+    pub(super) fn new(fd: RawFd) -> Self {
+        Self {
+            fd,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The map's file descriptor.
+    #[inline]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Set the value for `key`, creating the entry if it doesn't
+    /// exist.
+    pub fn update(&self, key: &K, value: &V) -> io::Result<()> {
+        let attr = MapElemAttr::new(
+            self.fd,
+            key as *const K as u64,
+            value as *const V as u64,
+            BPF_ANY,
+        );
+
+        xskmap::map_elem_cmd(BPF_MAP_UPDATE_ELEM, &attr)
+    }
+
+    /// The value for `key`, or [`None`] if there's no entry.
+    pub fn lookup(&self, key: &K) -> io::Result<Option<V>> {
+        let mut value = MaybeUninit::<V>::uninit();
+
+        let attr = MapElemAttr::new(
+            self.fd,
+            key as *const K as u64,
+            value.as_mut_ptr() as u64,
+            0,
+        );
+
+        match xskmap::map_elem_cmd(BPF_MAP_LOOKUP_ELEM, &attr) {
+            // SAFETY: the kernel wrote a value of the map's value
+            // size, which was checked to match `V`, and `V` is valid
+            // for any bit pattern.
+            Ok(()) => Ok(Some(unsafe { value.assume_init() })),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Remove the entry for `key`, returning whether there was one.
+    pub fn delete(&self, key: &K) -> io::Result<bool> {
+        let attr = MapElemAttr::new(self.fd, key as *const K as u64, 0, 0);
+
+        match xskmap::map_elem_cmd(BPF_MAP_DELETE_ELEM, &attr) {
+            Ok(()) => Ok(true),
+            Err(e) if e.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The `XSKMAP` of an [`XdpProgram`], through which it redirects
+/// packets to sockets. See [`XdpProgram::xsk_map`].
+#[derive(Debug)]
+pub struct XskMap<'a> {
+    fd: RawFd,
+    _marker: PhantomData<&'a XdpProgram>,
+}
+
+impl<'a> XskMap<'a> {
+    pub(super) fn new(fd: RawFd) -> Self {
+        Self {
+            fd,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The map's file descriptor.
+    #[inline]
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Point the entry for `rx_q`'s queue id at its socket. See
+    /// [`RxQueue::update_xskmap`].
+    pub fn insert(&self, rx_q: &RxQueue) -> io::Result<()> {
+        rx_q.update_xskmap(self.fd)
+    }
+
+    /// Remove the entry for `queue_id`, so packets redirected to it
+    /// are dropped. Succeeds if there was no entry.
+    pub fn remove(&self, queue_id: u32) -> io::Result<()> {
+        xskmap::delete(self.fd, queue_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_on_a_bad_map_fd_fail() {
+        let map = Map::<u32, [u8; 6]>::new(-1);
+
+        assert!(map.update(&0, &[0; 6]).is_err());
+        assert!(map.lookup(&0).is_err());
+        assert!(map.delete(&0).is_err());
+    }
+}
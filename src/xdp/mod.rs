@@ -0,0 +1,311 @@
+//! Loading user XDP programs with libxdp.
+//!
+//! By default libxdp loads its own program when a socket is created,
+//! redirecting everything arriving on the socket's queue to it. To
+//! filter packets first, load your own program with [`XdpProgram`]
+//! and create sockets with the
+//! [`XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD`](crate::config::LibxdpFlags::XSK_LIBXDP_FLAGS_INHIBIT_PROG_LOAD)
+//! flag set, registering them in the program's `XSKMAP` with
+//! [`XskMap::insert`].
+//!
+//! Object files often bundle several programs, so which one is loaded
+//! can be picked by section or by name with [`ProgramSelector`]. The
+//! object's maps are available by name once the program is attached,
+//! either as an [`XskMap`] or as a [`Map`] with typed keys and values.
+
+mod map;
+pub use map::{Map, Pod, XskMap};
+
+use libxdp_sys::{bpf_map, xdp_attach_mode, xdp_program, xdp_program_opts};
+use log::error;
+use std::{
+    ffi::CString,
+    io, mem,
+    os::unix::prelude::OsStrExt,
+    path::Path,
+    ptr::{self, NonNull},
+};
+
+use crate::config::{Interface, XdpFlags};
+
+// From `enum xdp_attach_mode` in `xdp/libxdp.h`.
+const XDP_MODE_UNSPEC: xdp_attach_mode = 0;
+const XDP_MODE_NATIVE: xdp_attach_mode = 1;
+const XDP_MODE_SKB: xdp_attach_mode = 2;
+const XDP_MODE_HW: xdp_attach_mode = 3;
+
+// From `enum bpf_map_type` in `linux/bpf.h`.
+const BPF_MAP_TYPE_PERCPU_HASH: u32 = 5;
+const BPF_MAP_TYPE_PERCPU_ARRAY: u32 = 6;
+const BPF_MAP_TYPE_LRU_PERCPU_HASH: u32 = 10;
+const BPF_MAP_TYPE_XSKMAP: u32 = 17;
+const BPF_MAP_TYPE_PERCPU_CGROUP_STORAGE: u32 = 21;
+
+/// Which program in an object file to load.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramSelector<'a> {
+    /// The first program in the file.
+    First,
+    /// The first program in the given ELF section, for example
+    /// `"xdp"` or `"xdp.frags"`.
+    Section(&'a str),
+    /// The program with the given function name.
+    Name(&'a str),
+}
+
+/// An XDP program loaded from an object file, and the object's maps.
+///
+/// The program is detached, if attached, and closed on drop.
+#[derive(Debug)]
+pub struct XdpProgram {
+    prog: NonNull<xdp_program>,
+    attached: Option<(libc::c_int, xdp_attach_mode)>,
+}
+
+unsafe impl Send for XdpProgram {}
+
+impl XdpProgram {
+    /// Open the program in the object file at `path` picked by
+    /// `select`.
+    ///
+    /// The program and its maps are only loaded into the kernel when
+    /// it's [attached](Self::attach).
+    pub fn open<P: AsRef<Path>>(path: P, select: ProgramSelector<'_>) -> io::Result<Self> {
+        let path = cstring(path.as_ref().as_os_str().as_bytes())?;
+
+        let prog = match select {
+            ProgramSelector::First => open_with_opts(&path, None)?,
+            ProgramSelector::Section(section) => {
+                let section = cstring(section.as_bytes())?;
+
+                let prog = unsafe {
+                    libxdp_sys::xdp_program__open_file(
+                        path.as_ptr(),
+                        section.as_ptr(),
+                        ptr::null_mut(),
+                    )
+                };
+
+                check_ptr(prog)?
+            }
+            ProgramSelector::Name(name) => {
+                let name = cstring(name.as_bytes())?;
+
+                open_with_opts(&path, Some(&name))?
+            }
+        };
+
+        Ok(Self {
+            prog,
+            attached: None,
+        })
+    }
+
+    /// Attach the program to `if_name`, loading it and creating its
+    /// maps if not already loaded.
+    ///
+    /// The attach mode is taken from the mode flags in `xdp_flags`,
+    /// that is [`XDP_FLAGS_SKB_MODE`](XdpFlags::XDP_FLAGS_SKB_MODE),
+    /// [`XDP_FLAGS_DRV_MODE`](XdpFlags::XDP_FLAGS_DRV_MODE) or
+    /// [`XDP_FLAGS_HW_MODE`](XdpFlags::XDP_FLAGS_HW_MODE), leaving it
+    /// up to libxdp if none are set. Other flags are ignored.
+    pub fn attach(&mut self, if_name: &Interface, xdp_flags: XdpFlags) -> io::Result<()> {
+        if self.attached.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "program is already attached",
+            ));
+        }
+
+        let ifindex = if_name.index()? as libc::c_int;
+        let mode = attach_mode(xdp_flags);
+
+        let err = unsafe { libxdp_sys::xdp_program__attach(self.prog.as_ptr(), ifindex, mode, 0) };
+
+        if err != 0 {
+            return Err(io::Error::from_raw_os_error(-err));
+        }
+
+        self.attached = Some((ifindex, mode));
+
+        Ok(())
+    }
+
+    /// Detach the program from the interface it was attached to. Does
+    /// nothing if it isn't attached.
+    pub fn detach(&mut self) -> io::Result<()> {
+        if let Some((ifindex, mode)) = self.attached {
+            let err =
+                unsafe { libxdp_sys::xdp_program__detach(self.prog.as_ptr(), ifindex, mode, 0) };
+
+            if err != 0 {
+                return Err(io::Error::from_raw_os_error(-err));
+            }
+
+            self.attached = None;
+        }
+
+        Ok(())
+    }
+
+    /// The `XSKMAP` called `name`, through which the program
+    /// redirects packets to sockets.
+    ///
+    /// Fails if the map doesn't exist, is of a different type, or the
+    /// program hasn't been attached yet.
+    pub fn xsk_map(&self, name: &str) -> io::Result<XskMap<'_>> {
+        let map = self.find_map(name)?;
+
+        if unsafe { libxdp_sys::bpf_map__type(map.as_ptr()) } as u32 != BPF_MAP_TYPE_XSKMAP {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "map is not an XSKMAP",
+            ));
+        }
+
+        Ok(XskMap::new(map_fd(map)?))
+    }
+
+    /// The map called `name`, with keys of type `K` and values of
+    /// type `V`.
+    ///
+    /// Fails if the map doesn't exist, the sizes of `K` and `V` don't
+    /// match its key and value sizes, it's a per-CPU map, or the
+    /// program hasn't been attached yet.
+    pub fn map<K: Pod, V: Pod>(&self, name: &str) -> io::Result<Map<'_, K, V>> {
+        let map = self.find_map(name)?;
+
+        let (map_type, key_size, value_size) = unsafe {
+            (
+                libxdp_sys::bpf_map__type(map.as_ptr()) as u32,
+                libxdp_sys::bpf_map__key_size(map.as_ptr()) as usize,
+                libxdp_sys::bpf_map__value_size(map.as_ptr()) as usize,
+            )
+        };
+
+        // Lookups on per-CPU maps return a value for every CPU.
+        if matches!(
+            map_type,
+            BPF_MAP_TYPE_PERCPU_HASH
+                | BPF_MAP_TYPE_PERCPU_ARRAY
+                | BPF_MAP_TYPE_LRU_PERCPU_HASH
+                | BPF_MAP_TYPE_PERCPU_CGROUP_STORAGE
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "per-CPU maps are not supported",
+            ));
+        }
+
+        if key_size != mem::size_of::<K>() || value_size != mem::size_of::<V>() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "key or value size does not match the map's",
+            ));
+        }
+
+        Ok(Map::new(map_fd(map)?))
+    }
+
+    fn find_map(&self, name: &str) -> io::Result<NonNull<bpf_map>> {
+        let name = cstring(name.as_bytes())?;
+
+        let obj = unsafe { libxdp_sys::xdp_program__bpf_obj(self.prog.as_ptr()) };
+
+        if obj.is_null() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "program has no associated object",
+            ));
+        }
+
+        let map = unsafe { libxdp_sys::bpf_object__find_map_by_name(obj, name.as_ptr()) };
+
+        NonNull::new(map).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such map"))
+    }
+}
+
+impl Drop for XdpProgram {
+    fn drop(&mut self) {
+        if let Err(e) = self.detach() {
+            error!("failed to detach XDP program: {}", e);
+        }
+
+        unsafe { libxdp_sys::xdp_program__close(self.prog.as_ptr()) };
+    }
+}
+
+fn cstring(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn open_with_opts(path: &CString, prog_name: Option<&CString>) -> io::Result<NonNull<xdp_program>> {
+    // Zero every field not set, as `DECLARE_LIBXDP_OPTS` does.
+    let mut opts: xdp_program_opts = unsafe { mem::zeroed() };
+
+    opts.sz = mem::size_of::<xdp_program_opts>();
+    opts.open_filename = path.as_ptr();
+    opts.prog_name = prog_name.map_or(ptr::null(), |name| name.as_ptr());
+
+    check_ptr(unsafe { libxdp_sys::xdp_program__create(&mut opts) })
+}
+
+/// libxdp returns either a valid pointer, null or an error encoded in
+/// the pointer, depending on version.
+fn check_ptr(prog: *mut xdp_program) -> io::Result<NonNull<xdp_program>> {
+    let err = unsafe { libxdp_sys::libxdp_get_error(prog as *const libc::c_void) };
+
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(-err as i32));
+    }
+
+    NonNull::new(prog).ok_or_else(io::Error::last_os_error)
+}
+
+fn map_fd(map: NonNull<bpf_map>) -> io::Result<libc::c_int> {
+    let fd = unsafe { libxdp_sys::bpf_map__fd(map.as_ptr()) };
+
+    if fd < 0 {
+        return Err(io::Error::other(
+            "map has not been created, the program must be attached first",
+        ));
+    }
+
+    Ok(fd)
+}
+
+fn attach_mode(xdp_flags: XdpFlags) -> xdp_attach_mode {
+    if xdp_flags.contains(XdpFlags::XDP_FLAGS_DRV_MODE) {
+        XDP_MODE_NATIVE
+    } else if xdp_flags.contains(XdpFlags::XDP_FLAGS_SKB_MODE) {
+        XDP_MODE_SKB
+    } else if xdp_flags.contains(XdpFlags::XDP_FLAGS_HW_MODE) {
+        XDP_MODE_HW
+    } else {
+        XDP_MODE_UNSPEC
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attach_mode_follows_xdp_flags() {
+        assert_eq!(attach_mode(XdpFlags::empty()), XDP_MODE_UNSPEC);
+        assert_eq!(
+            attach_mode(XdpFlags::XDP_FLAGS_UPDATE_IF_NOEXIST),
+            XDP_MODE_UNSPEC
+        );
+        assert_eq!(attach_mode(XdpFlags::XDP_FLAGS_DRV_MODE), XDP_MODE_NATIVE);
+        assert_eq!(attach_mode(XdpFlags::XDP_FLAGS_SKB_MODE), XDP_MODE_SKB);
+        assert_eq!(attach_mode(XdpFlags::XDP_FLAGS_HW_MODE), XDP_MODE_HW);
+    }
+
+    #[test]
+    fn names_with_nul_bytes_are_rejected() {
+        let err = XdpProgram::open("prog\0.o", ProgramSelector::First).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
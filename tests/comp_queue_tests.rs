@@ -1,6 +1,4 @@
-#[allow(dead_code)]
-mod setup;
-use setup::{PacketGenerator, Xsk, XskConfig, ETHERNET_PACKET};
+use xsk_rs_helpers::{PacketGenerator, Xsk, XskConfig, ETHERNET_PACKET};
 
 use serial_test::serial;
 use std::{convert::TryInto, io::Write, thread, time::Duration};
@@ -192,7 +190,7 @@ where
     let (dev1_umem_config, dev1_socket_config) = build_configs();
     let (dev2_umem_config, dev2_socket_config) = build_configs();
 
-    setup::run_test(
+    xsk_rs_helpers::run_test(
         XskConfig {
             frame_count: FRAME_COUNT.try_into().unwrap(),
             umem_config: dev1_umem_config,
@@ -1,11 +1,7 @@
-#[allow(dead_code)]
-mod setup;
-use std::convert::TryInto;
-
-use setup::{PacketGenerator, Xsk, XskConfig};
-
 use serial_test::serial;
+use std::convert::TryInto;
 use xsk_rs::config::{QueueSize, SocketConfig, UmemConfig};
+use xsk_rs_helpers::{PacketGenerator, Xsk, XskConfig};
 
 const FQ_SIZE: u32 = 4;
 const FRAME_COUNT: u32 = 32;
@@ -79,7 +75,7 @@ where
     let (dev1_umem_config, dev1_socket_config) = build_configs();
     let (dev2_umem_config, dev2_socket_config) = build_configs();
 
-    setup::run_test(
+    xsk_rs_helpers::run_test(
         XskConfig {
             frame_count: FRAME_COUNT.try_into().unwrap(),
             umem_config: dev1_umem_config,
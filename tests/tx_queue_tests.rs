@@ -1,13 +1,7 @@
-#[allow(dead_code)]
-mod setup;
-use std::convert::TryInto;
-
-use setup::Xsk;
-
 use serial_test::serial;
+use std::convert::TryInto;
 use xsk_rs::config::{QueueSize, SocketConfig, UmemConfig};
-
-use crate::setup::{PacketGenerator, XskConfig};
+use xsk_rs_helpers::{PacketGenerator, Xsk, XskConfig};
 
 const TX_Q_SIZE: u32 = 4;
 const FRAME_COUNT: u32 = 8;
@@ -75,6 +69,25 @@ async fn produce_one_is_ok() {
     build_configs_and_run_test(test).await
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[serial]
+async fn socket_info_reports_configured_queue_sizes() {
+    fn test(dev1: (Xsk, PacketGenerator), _dev2: (Xsk, PacketGenerator)) {
+        let xsk1 = dev1.0;
+
+        let info = xsk1.tx_q.socket_info().unwrap();
+
+        assert_eq!(info.queue_id(), 0);
+        assert_eq!(info.tx_queue_size().get(), TX_Q_SIZE);
+        assert_eq!(
+            info.fill_queue_size().get(),
+            UmemConfig::default().fill_queue_size().get()
+        );
+    }
+
+    build_configs_and_run_test(test).await
+}
+
 async fn build_configs_and_run_test<F>(test: F)
 where
     F: Fn((Xsk, PacketGenerator), (Xsk, PacketGenerator)) + Send + 'static,
@@ -82,7 +95,7 @@ where
     let (dev1_umem_config, dev1_socket_config) = build_configs();
     let (dev2_umem_config, dev2_socket_config) = build_configs();
 
-    setup::run_test(
+    xsk_rs_helpers::run_test(
         XskConfig {
             frame_count: FRAME_COUNT.try_into().unwrap(),
             umem_config: dev1_umem_config,
@@ -1,12 +1,10 @@
-#[allow(dead_code)]
-mod setup;
-use setup::{veth_setup, VethDevConfig, Xsk, ETHERNET_PACKET};
+use xsk_rs_helpers::{veth_setup, VethDevConfig, Xsk, ETHERNET_PACKET};
 
 use serial_test::serial;
 use std::{convert::TryInto, io::Write};
 use xsk_rs::{
-    config::{LibxdpFlags, SocketConfig, UmemConfig},
-    Socket, Umem,
+    config::{LibxdpFlags, QueueSize, SocketConfig, UmemConfig},
+    FrameDesc, Socket, Umem,
 };
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -70,7 +68,7 @@ async fn shared_umem_returns_new_fq_and_cq_when_sockets_are_bound_to_different_d
         send_and_receive_pkt(&mut sender, &mut receiver, &ETHERNET_PACKET[..]);
     };
 
-    let (dev1_config, dev2_config) = setup::default_veth_dev_configs();
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
 
     veth_setup::run_with_veth_pair(inner, dev1_config, dev2_config)
         .await
@@ -113,7 +111,84 @@ async fn shared_umem_does_not_return_new_fq_and_cq_when_sockets_are_bound_to_sam
         assert!(receiver_fq_and_cq.is_none());
     };
 
-    let (dev1_config, dev2_config) = setup::default_veth_dev_configs();
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
+
+    veth_setup::run_with_veth_pair(inner, dev1_config, dev2_config)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[serial]
+async fn umem_registered_with_different_queue_sizes_shares_frames() {
+    let inner = move |dev1_config: VethDevConfig, dev2_config: VethDevConfig| {
+        let frame_count = 64;
+
+        let (umem, descs) = Umem::new(
+            UmemConfig::default(),
+            frame_count.try_into().unwrap(),
+            false,
+        )
+        .unwrap();
+
+        let receiver_umem = umem
+            .with_queue_sizes(QueueSize::new(4096).unwrap(), QueueSize::new(512).unwrap())
+            .unwrap();
+
+        let mut sender_descs = descs;
+        let receiver_descs = sender_descs.drain((frame_count / 2) as usize..).collect();
+
+        let (sender_tx_q, sender_rx_q, sender_fq_and_cq) = unsafe {
+            Socket::new(
+                SocketConfig::default(),
+                &umem,
+                &dev1_config.if_name().parse().unwrap(),
+                0,
+            )
+        }
+        .unwrap();
+
+        let (sender_fq, sender_cq) = sender_fq_and_cq.unwrap();
+
+        let mut sender = Xsk {
+            umem,
+            fq: sender_fq,
+            cq: sender_cq,
+            tx_q: sender_tx_q,
+            rx_q: sender_rx_q,
+            descs: sender_descs,
+        };
+
+        let (receiver_tx_q, receiver_rx_q, receiver_fq_and_cq) = unsafe {
+            Socket::new(
+                SocketConfig::default(),
+                &receiver_umem,
+                &dev2_config.if_name().parse().unwrap(),
+                0,
+            )
+        }
+        .unwrap();
+
+        let info = receiver_rx_q.socket_info().unwrap();
+
+        assert_eq!(info.fill_queue_size().get(), 4096);
+        assert_eq!(info.comp_queue_size().get(), 512);
+
+        let (receiver_fq, receiver_cq) = receiver_fq_and_cq.unwrap();
+
+        let mut receiver = Xsk {
+            umem: receiver_umem,
+            fq: receiver_fq,
+            cq: receiver_cq,
+            tx_q: receiver_tx_q,
+            rx_q: receiver_rx_q,
+            descs: receiver_descs,
+        };
+
+        send_and_receive_pkt(&mut sender, &mut receiver, &ETHERNET_PACKET[..]);
+    };
+
+    let (dev1_config, dev2_config) = xsk_rs_helpers::default_veth_dev_configs();
 
     veth_setup::run_with_veth_pair(inner, dev1_config, dev2_config)
         .await
@@ -144,6 +219,31 @@ async fn writing_to_frame_and_reading_works_as_expected() {
     }
 }
 
+#[tokio::test]
+#[serial]
+async fn descs_are_appended_to_caller_provided_collection() {
+    let (_umem, expected) =
+        Umem::new(UmemConfig::default(), 16.try_into().unwrap(), false).unwrap();
+
+    let mut descs = Vec::with_capacity(32);
+    descs.push(FrameDesc::default());
+
+    let _umem = Umem::new_with_descs(
+        UmemConfig::default(),
+        16.try_into().unwrap(),
+        false,
+        &mut descs,
+    )
+    .unwrap();
+
+    assert_eq!(descs.len(), 17);
+    assert_eq!(descs.capacity(), 32);
+
+    for (desc, expected) in descs[1..].iter().zip(expected.iter()) {
+        assert_eq!(desc.addr(), expected.addr());
+    }
+}
+
 fn send_and_receive_pkt(sender: &mut Xsk, receiver: &mut Xsk, pkt: &[u8]) {
     unsafe {
         assert_eq!(